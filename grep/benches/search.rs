@@ -0,0 +1,186 @@
+//! Timings for the search core: `cargo bench`, or `cargo bench -- literal` for the benchmarks
+//! whose names contain `literal`.
+//!
+//! Each benchmark runs once to warm up, then repeatedly for about a second, and reports the
+//! fastest and median run. The inputs are generated under Cargo's scratch directory on first use.
+
+use grep::{Config, MatchSink, ParseOutcome, Searcher, SinkMatch};
+use std::env;
+use std::fs;
+use std::hint::black_box;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Roughly how long to spend timing each benchmark.
+const BUDGET: Duration = Duration::from_secs(1);
+
+fn config(args: &[&str]) -> Config {
+    match Config::parse(args.iter().map(|arg| arg.to_string()).collect()) {
+        Ok(ParseOutcome::Run(config)) => config,
+        _ => panic!("arguments should produce a runnable config"),
+    }
+}
+
+/// Counts selected lines, so the search cannot be optimised away.
+#[derive(Default)]
+struct Count(usize);
+
+impl MatchSink for Count {
+    fn matched(&mut self, _found: &SinkMatch<'_>) -> io::Result<bool> {
+        self.0 += 1;
+        Ok(true)
+    }
+}
+
+/// Lines of prose-like text, with `needle` on every thousandth.
+fn corpus(lines: usize) -> String {
+    let words = [
+        "search", "utility", "pattern", "line", "file", "match", "buffer", "walk", "regex",
+        "literal", "offset", "context",
+    ];
+    let mut text = String::new();
+    for line in 0..lines {
+        for word in 0..10 {
+            text.push_str(words[(line * 7 + word * 3) % words.len()]);
+            text.push(' ');
+        }
+        if line % 1000 == 999 {
+            text.push_str("needle42");
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// A scratch directory for generated inputs, created once and reused across runs.
+fn scratch(name: &str, create: impl FnOnce(&Path)) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("bench")
+        .join(name);
+    if !dir.exists() {
+        let building = dir.with_extension("partial");
+        let _ = fs::remove_dir_all(&building);
+        fs::create_dir_all(&building).expect("create bench input");
+        create(&building);
+        fs::rename(&building, &dir).expect("finish bench input");
+    }
+    dir
+}
+
+/// One large file of 40 MB or so.
+fn large_file() -> PathBuf {
+    scratch("large", |dir| {
+        fs::write(dir.join("large.txt"), corpus(500_000)).expect("write large file")
+    })
+    .join("large.txt")
+}
+
+/// 2,000 small files spread over nested directories.
+fn tree() -> PathBuf {
+    scratch("tree", |dir| {
+        let text = corpus(40);
+        for index in 0..2_000 {
+            let sub = dir
+                .join(format!("d{}", index % 20))
+                .join(format!("e{}", index % 7));
+            fs::create_dir_all(&sub).expect("create bench tree");
+            fs::write(sub.join(format!("f{}.txt", index)), &text).expect("write bench file");
+        }
+    })
+}
+
+/// Times `run` and prints the fastest and median of its runs as `name`, unless the command
+/// line names benchmarks and this is not one of them.
+fn bench(name: &str, mut run: impl FnMut() -> usize) {
+    let filters: Vec<String> = env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+        return;
+    }
+    black_box(run());
+    let mut times = Vec::new();
+    let started = Instant::now();
+    while started.elapsed() < BUDGET || times.len() < 3 {
+        let start = Instant::now();
+        black_box(run());
+        times.push(start.elapsed());
+    }
+    times.sort();
+    println!(
+        "{:<32} fastest {:>10.3?}   median {:>10.3?}   ({} runs)",
+        name,
+        times[0],
+        times[times.len() / 2],
+        times.len()
+    );
+}
+
+fn search_text(config: &Config, text: &str) -> usize {
+    let mut count = Count::default();
+    Searcher::new(config)
+        .search_reader(text.as_bytes(), &mut count)
+        .expect("search text");
+    count.0
+}
+
+fn search_file(config: &Config, path: &Path) -> usize {
+    let mut count = Count::default();
+    Searcher::new(config)
+        .search_path(path, &mut count)
+        .expect("search file");
+    count.0
+}
+
+fn main() {
+    let text = corpus(100_000);
+    let literal = config(&["needle42", "unused"]);
+    let ignore_case = config(&["-i", "NEEDLE42", "unused"]);
+    let regex = config(&["-E", r"ne+dle\d+", "unused"]);
+    let alternation = config(&["-E", "needle|haystack|pin", "unused"]);
+    bench("literal/in-memory", || search_text(&literal, &text));
+    bench("literal-ignore-case/in-memory", || {
+        search_text(&ignore_case, &text)
+    });
+    bench("regex/in-memory", || search_text(&regex, &text));
+    bench("alternation/in-memory", || search_text(&alternation, &text));
+
+    let large = large_file();
+    let mapped = config(&["--mmap", "needle42", "unused"]);
+    let read = config(&["--no-mmap", "needle42", "unused"]);
+    bench("literal/large-file-mmap", || search_file(&mapped, &large));
+    bench("literal/large-file-read", || search_file(&read, &large));
+    bench("regex/large-file", || search_file(&regex, &large));
+
+    let tree = tree();
+    let files: Vec<PathBuf> = walk_files(&tree);
+    bench("literal/small-files", || {
+        files.iter().map(|path| search_file(&literal, path)).sum()
+    });
+    // A pattern that never matches prints nothing, leaving the walk and the search.
+    let root = tree.to_string_lossy();
+    for threads in ["1", "0"] {
+        let walk = config(&["-r", "--threads", threads, "absent-word", &root]);
+        let name = format!("walk-and-search/threads={}", threads);
+        bench(&name, || grep::run(&walk).expect("run").files_searched);
+    }
+}
+
+/// Every file under `dir`, for searching the tree without the walker.
+fn walk_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).expect("read bench tree") {
+            let path = entry.expect("read bench entry").path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
@@ -0,0 +1,294 @@
+use grep::{Config, MatchSink, ParseOutcome, SearchError, SearchResult, Searcher, SinkMatch};
+use std::io::{self, Cursor};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+fn config(args: &[&str]) -> Config {
+    match Config::parse(args.iter().map(|arg| arg.to_string()).collect()) {
+        Ok(ParseOutcome::Run(config)) => config,
+        _ => panic!("arguments should produce a runnable config"),
+    }
+}
+
+#[derive(Default)]
+struct Collect {
+    matches: Vec<(usize, String, Vec<std::ops::Range<usize>>)>,
+}
+
+impl MatchSink for Collect {
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+        self.matches.push((
+            found.line_number,
+            found.line.to_string(),
+            found.spans.clone(),
+        ));
+        Ok(true)
+    }
+}
+
+#[test]
+fn search_reader_reports_line_numbers_and_spans() {
+    let config = config(&["-i", "cat", "unused"]);
+    let mut sink = Collect::default();
+    Searcher::new(&config)
+        .search_reader(Cursor::new("dog\nCat and cat\n"), &mut sink)
+        .unwrap();
+
+    assert_eq!(
+        sink.matches,
+        vec![(2, "Cat and cat".to_string(), vec![0..3, 8..11])]
+    );
+}
+
+#[test]
+fn sink_can_stop_the_search_early() {
+    struct First(Vec<usize>);
+    impl MatchSink for First {
+        fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+            self.0.push(found.line_number);
+            Ok(false)
+        }
+    }
+
+    let config = config(&["a", "unused"]);
+    let mut sink = First(Vec::new());
+    let searcher = Searcher::new(&config);
+    searcher
+        .search_reader(Cursor::new("a\na\na\n"), &mut sink)
+        .unwrap();
+
+    assert_eq!(sink.0, vec![1]);
+    // Only the first line was read before the sink stopped the search.
+    assert_eq!(searcher.bytes_searched(), 2);
+}
+
+#[test]
+fn collect_path_returns_owned_results() {
+    let config = config(&["-n", "Utility", "unused"]);
+    let results = Searcher::new(&config)
+        .collect_path(Path::new("tests/grep.md"))
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![SearchResult {
+            path: Some(PathBuf::from("tests/grep.md")),
+            line_number: 1,
+            byte_offset: 0,
+            line: "## Search Utility".to_string(),
+            spans: vec![Range { start: 10, end: 17 }],
+            char_spans: vec![Range { start: 10, end: 17 }],
+        }]
+    );
+}
+
+#[test]
+fn results_carry_char_spans_alongside_byte_spans() {
+    let config = config(&["-E", "l[eé]", "unused"]);
+    let mut results: Vec<SearchResult> = Vec::new();
+    Searcher::new(&config)
+        .search_reader(Cursor::new("café, olé, ole\nplain\n"), &mut results)
+        .unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].line, "café, olé, ole");
+    assert_eq!(results[0].spans, [8..11, 14..16]);
+    assert_eq!(results[0].char_spans, [7..9, 12..14]);
+}
+
+#[test]
+fn sink_receives_context_and_breaks() {
+    #[derive(Default)]
+    struct Events(Vec<String>);
+    impl MatchSink for Events {
+        fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+            self.0.push(format!("match {}", found.line_number));
+            Ok(true)
+        }
+        fn context(&mut self, line: &SinkMatch<'_>) -> io::Result<bool> {
+            self.0.push(format!("context {}", line.line_number));
+            Ok(true)
+        }
+        fn context_break(&mut self) -> io::Result<bool> {
+            self.0.push("break".to_string());
+            Ok(true)
+        }
+    }
+
+    let config = config(&["-A", "1", "x", "unused"]);
+    let mut sink = Events::default();
+    Searcher::new(&config)
+        .search_reader(Cursor::new("x\na\nb\nx\n"), &mut sink)
+        .unwrap();
+
+    assert_eq!(sink.0, ["match 1", "context 2", "break", "match 4"]);
+}
+
+#[test]
+fn sink_sees_each_input_begin_and_end_or_fail() {
+    #[derive(Default)]
+    struct Events(Vec<String>);
+    impl MatchSink for Events {
+        fn begin(&mut self, path: Option<&Path>) -> io::Result<()> {
+            self.0.push(format!("begin {:?}", path));
+            Ok(())
+        }
+        fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+            self.0.push(format!("match {}", found.line_number));
+            Ok(false)
+        }
+        fn end(&mut self, path: Option<&Path>) -> io::Result<()> {
+            self.0.push(format!("end {:?}", path));
+            Ok(())
+        }
+        fn error(&mut self, path: Option<&Path>, error: &io::Error) {
+            self.0.push(format!("error {:?} {:?}", path, error.kind()));
+        }
+    }
+
+    let config = config(&["x", "unused"]);
+    let searcher = Searcher::new(&config);
+    let mut sink = Events::default();
+    searcher
+        .search_reader(Cursor::new("x\nx\n"), &mut sink)
+        .unwrap();
+    let missing = Path::new("tests/missing.md");
+    assert!(searcher.search_path(missing, &mut sink).is_err());
+
+    assert_eq!(
+        sink.0,
+        [
+            "begin None",
+            "match 1",
+            "end None",
+            "begin Some(\"tests/missing.md\")",
+            "error Some(\"tests/missing.md\") NotFound",
+        ]
+    );
+}
+
+#[test]
+fn parse_errors_say_what_went_wrong() {
+    let parse = |args: &[&str]| Config::parse(args.iter().map(|arg| arg.to_string()).collect());
+    match parse(&["-E", "(unclosed", "unused"]) {
+        Err(error @ SearchError::InvalidPattern(_)) => assert_eq!(error.exit_code(), 3),
+        _ => panic!("an unclosed group should be an invalid pattern"),
+    }
+    match parse(&["--max-count", "many", "x"]) {
+        Err(error @ SearchError::InvalidFlag(_)) => assert_eq!(error.exit_code(), 4),
+        _ => panic!("a non-numeric count should be an invalid flag"),
+    }
+    match parse(&["--file", "missing-patterns.txt", "unused"]) {
+        Err(SearchError::Io { what, path, source }) => {
+            assert_eq!(what, "pattern file");
+            assert_eq!(path, Path::new("missing-patterns.txt"));
+            assert_eq!(source.kind(), io::ErrorKind::NotFound);
+        }
+        _ => panic!("a missing pattern file should be an I/O error"),
+    }
+}
+
+#[test]
+fn validate_refuses_conflicts_and_notes_options_with_no_effect() {
+    let mut config = config(&["x", "tests/grep.md"]);
+    assert!(config.validate().unwrap().is_empty());
+
+    config.total = true;
+    config.recursive = true;
+    let notes = config.validate().unwrap();
+    assert_eq!(notes.len(), 2, "{:?}", notes);
+    assert!(notes[0].starts_with("--total has no effect without --count"));
+    assert!(notes[1].starts_with("-r has no effect when only files are given"));
+
+    config.total = false;
+    config.recursive = false;
+    config.count = true;
+    config.json = true;
+    let notes = config.validate().unwrap();
+    assert_eq!(notes.len(), 1, "{:?}", notes);
+    assert!(notes[0].starts_with("--json has no effect with --count, -l, or -L"));
+
+    config.list_files = true;
+    match config.validate() {
+        Err(error @ SearchError::InvalidFlag(_)) => {
+            assert!(
+                error
+                    .to_string()
+                    .contains("--count cannot be combined with -l or -L")
+            );
+        }
+        _ => panic!("--count with -l should be refused"),
+    }
+
+    config.list_files = false;
+    config.invert_match = true;
+    config.only_matching = true;
+    match config.validate() {
+        Err(error @ SearchError::InvalidFlag(_)) => {
+            assert_eq!(error.exit_code(), 4);
+            assert!(
+                error
+                    .to_string()
+                    .contains("-o prints matches, but -v selects lines")
+            );
+        }
+        _ => panic!("-o with -v should be refused"),
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_search_streams_results_without_a_runtime() {
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+
+    struct Unpark(Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+    // The least an executor needs: poll, and park until woken.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    let config = Arc::new(config(&["-n", "hit", "unused"]));
+    let text: String = (1..=1000).map(|n| format!("{} hit\n", n)).collect();
+    let mut stream = grep::search_async(Arc::clone(&config), Cursor::new(text));
+    let mut numbers = Vec::new();
+    while let Some(result) = block_on(stream.recv()) {
+        numbers.push(result.unwrap().line_number);
+    }
+    assert_eq!(numbers, (1..=1000).collect::<Vec<_>>());
+
+    let mut missing = grep::search_path_async(config, PathBuf::from("tests/missing.md"));
+    let failure = block_on(missing.recv()).unwrap().unwrap_err();
+    assert_eq!(failure.kind(), io::ErrorKind::NotFound);
+    assert!(block_on(missing.recv()).is_none());
+
+    struct Broken;
+    impl io::Read for Broken {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            panic!("the reader broke");
+        }
+    }
+    let mut broken = grep::search_async(Arc::new(crate::config(&["hit", "unused"])), Broken);
+    let failure = block_on(broken.recv()).unwrap().unwrap_err();
+    assert!(
+        failure.to_string().contains("the reader broke"),
+        "{}",
+        failure
+    );
+    assert!(block_on(broken.recv()).is_none());
+}
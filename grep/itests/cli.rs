@@ -1,9 +1,39 @@
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
+use std::fs;
+use std::path::PathBuf;
 use std::process::Command;
 
+/// The binary, kept away from any configuration file of the user running the tests.
 fn binary() -> Command {
-    Command::cargo_bin("grep").expect("binary exists")
+    let mut command = Command::cargo_bin("grep").expect("binary exists");
+    command
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("GREP_COLORS")
+        .env_remove("SEARCH_UTILITY_OPTS")
+        .env_remove("GREP_OPTIONS");
+    command
+}
+
+/// Like [`binary`], but able to feed the process standard input.
+fn piped_binary() -> assert_cmd::Command {
+    let mut command = assert_cmd::Command::cargo_bin("grep").expect("binary exists");
+    command
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("GREP_COLORS")
+        .env_remove("SEARCH_UTILITY_OPTS")
+        .env_remove("GREP_OPTIONS");
+    command
+}
+
+/// A fresh, empty directory under Cargo's per-test scratch space.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("create scratch dir");
+    dir
 }
 
 #[test]
@@ -73,7 +103,9 @@ fn searches_directories_recursively() {
         .args(["Utility", "tests", "-r"])
         .assert()
         .success()
-        .stdout(predicate::eq("## Search Utility\n## Search Utility\n"));
+        .stdout(predicate::eq(
+            "tests/grep.md: ## Search Utility\ntests/recursive/grep.md: ## Search Utility\n",
+        ));
 }
 
 #[test]
@@ -83,8 +115,8 @@ fn prints_filenames_when_requested() {
         .assert()
         .success()
         .stdout(predicate::eq(
-            "tests/recursive/grep.md: ## Search Utility\n\
-tests/grep.md: ## Search Utility\n",
+            "tests/grep.md: ## Search Utility\n\
+tests/recursive/grep.md: ## Search Utility\n",
         ));
 }
 
@@ -134,7 +166,7 @@ fn directory_without_recursive_flag_prints_nothing() {
     binary()
         .args(["Utility", "tests"])
         .assert()
-        .success()
+        .code(1)
         .stdout(predicate::eq(""));
 }
 
@@ -143,7 +175,7 @@ fn reports_empty_output_when_no_lines_match() {
     binary()
         .args(["NonexistentPattern", "tests/grep.md"])
         .assert()
-        .success()
+        .code(1)
         .stdout(predicate::eq(""));
 }
 
@@ -152,6 +184,3908 @@ fn treats_arguments_after_double_dash_as_literals() {
     binary()
         .args(["--", "-n", "tests/grep.md"])
         .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn uses_custom_field_separator_for_prefixes() {
+    binary()
+        .args([
+            "Utility",
+            "tests/grep.md",
+            "-f",
+            "-n",
+            "--field-separator",
+            "|",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md|1| ## Search Utility\n"));
+}
+
+#[test]
+fn errors_when_field_separator_value_is_missing() {
+    binary()
+        .args(["Utility", "tests/grep.md", "--field-separator"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Missing value for --field-separator",
+        ));
+}
+
+#[test]
+fn multiline_matches_span_line_boundaries() {
+    binary()
+        .args(["Utility\nIn this", "tests/grep.md", "--multiline", "-n"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "1: ## Search Utility\nIn this programming assignment, you are expected to implement a command-line utility that\n",
+        ));
+}
+
+#[test]
+fn line_end_anchors_match_before_crlf_endings() {
+    for args in [
+        &["-E", "two$"][..],
+        &["-x", "two"],
+        &["-E", "-x", "t.o"],
+        &["--multiline", "-E", "^two$"],
+        &["--multiline", "-P", "two$(?!x)"],
+    ] {
+        piped_binary()
+            .args(args)
+            .write_stdin("one\r\ntwo\r\nthree\r\n")
+            .assert()
+            .success()
+            .stdout("two\r\n");
+    }
+}
+
+#[test]
+fn printed_lines_keep_their_own_line_endings() {
+    let mixed = "alpha\r\nbeta\ngamma\r\n";
+    piped_binary()
+        .args(["-E", "-x", "alpha|gamma"])
+        .write_stdin(mixed)
+        .assert()
+        .success()
+        .stdout("alpha\r\ngamma\r\n");
+    piped_binary()
+        .args(["-C", "1", "beta"])
+        .write_stdin(mixed)
+        .assert()
+        .success()
+        .stdout(mixed);
+    // Only whole lines carry the ending; -o prints matches alone.
+    piped_binary()
+        .args(["-o", "alpha"])
+        .write_stdin(mixed)
+        .assert()
+        .success()
+        .stdout("alpha\n");
+}
+
+#[test]
+fn patterns_with_newlines_do_not_match_without_multiline() {
+    binary()
+        .args(["Utility\nIn this", "tests/grep.md"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn multiline_rejects_files_larger_than_max_buffer() {
+    binary()
+        .args([
+            "Utility",
+            "tests/grep.md",
+            "--multiline",
+            "--max-buffer",
+            "10",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::eq(
+            "Error: tests/grep.md: the input exceeds --max-buffer of 10 bytes\n",
+        ));
+}
+
+#[test]
+fn lists_only_matching_filenames() {
+    binary()
+        .args(["Utility", "tests", "-r", "-l"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md\ntests/recursive/grep.md\n"));
+}
+
+#[test]
+fn list_files_emits_nul_terminated_paths_with_null_flag() {
+    binary()
+        .args(["Utility", "tests", "-r", "-l", "-Z"])
+        .assert()
         .success()
+        .stdout(predicate::eq("tests/grep.md\0tests/recursive/grep.md\0"));
+}
+
+#[test]
+fn list_files_prints_nothing_without_matches() {
+    binary()
+        .args(["NonexistentPattern", "tests", "-r", "-l", "-Z"])
+        .assert()
+        .code(1)
         .stdout(predicate::eq(""));
 }
+
+#[test]
+fn max_columns_truncates_around_first_match() {
+    binary()
+        .args(["expected", "tests/grep.md", "--max-columns", "20"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "[... omitted] u are expected to im [... omitted]\n",
+        ));
+}
+
+#[test]
+fn max_columns_preview_keeps_the_start_of_long_lines() {
+    binary()
+        .args(["expected", "tests/grep.md", "--max-columns", "20"])
+        .arg("--max-columns-preview")
+        .assert()
+        .success()
+        .stdout(predicate::eq("In this programming  [... omitted]\n"));
+    // Highlighting covers only what is still visible.
+    binary()
+        .args([
+            "--color",
+            "always",
+            "-e",
+            "this",
+            "-e",
+            "expected",
+            "tests/grep.md",
+        ])
+        .args(["--max-columns", "10", "--max-columns-preview"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "In \u{1b}[31mthis\u{1b}[0m pr [... omitted]\n",
+        ));
+}
+
+#[test]
+fn max_columns_leaves_short_lines_untouched() {
+    binary()
+        .args(["Utility", "tests/grep.md", "--max-columns", "40"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+}
+
+#[test]
+fn continues_past_unreadable_files_and_exits_with_two() {
+    binary()
+        .args(["Utility", "tests/missing.md", "tests/grep.md"])
+        .assert()
+        .code(2)
+        .stdout(predicate::eq("tests/grep.md: ## Search Utility\n"))
+        .stderr(predicate::str::contains("tests/missing.md"));
+}
+
+#[test]
+fn no_messages_suppresses_file_errors_but_keeps_exit_code() {
+    binary()
+        .args(["Utility", "tests/missing.md", "tests/grep.md", "-s"])
+        .assert()
+        .code(2)
+        .stdout(predicate::eq("tests/grep.md: ## Search Utility\n"))
+        .stderr(predicate::eq(""));
+}
+
+#[test]
+fn no_messages_does_not_hide_argument_errors() {
+    binary()
+        .args([
+            "Utility",
+            "tests/grep.md",
+            "--max-columns",
+            "x",
+            "--no-messages",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid value for --max-columns: x",
+        ));
+}
+
+#[test]
+fn invert_match_rejects_only_matching() {
+    binary()
+        .args(["-v", "-o", "Utility", "tests/grep.md"])
+        .assert()
+        .code(4)
+        .stdout(predicate::eq(""))
+        .stderr(predicate::str::contains(
+            "-o prints matches, but -v selects lines without any",
+        ));
+}
+
+#[test]
+fn invert_match_colors_prefixes_and_matches_in_context() {
+    let dir = scratch_dir("invert_color");
+    let input = dir.join("input.txt");
+    fs::write(&input, "keep\ndrop me\n").unwrap();
+    binary()
+        .args(["--color", "always", "-v", "-n", "-A1", "drop"])
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "\u{1b}[32m1\u{1b}[0m\u{1b}[36m:\u{1b}[0m keep\n\
+             \u{1b}[32m2\u{1b}[0m\u{1b}[36m-\u{1b}[0m \u{1b}[31mdrop\u{1b}[0m me\n",
+        ));
+    // Inverted counts are uncoloured, like any count.
+    binary()
+        .args(["--color", "always", "-v", "--count", "drop"])
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\n"));
+}
+
+#[test]
+fn count_with_invert_match_counts_non_matching_lines() {
+    binary()
+        .args(["Utility", "tests/grep.md", "-v", "--count"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("3\n"));
+}
+
+#[test]
+fn counts_matching_lines_in_a_single_file() {
+    binary()
+        .args(["Utility", "tests/grep.md", "-i", "--count"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("2\n"));
+}
+
+#[test]
+fn total_sums_counts_across_files() {
+    binary()
+        .args(["Utility", "tests", "-r", "--count", "--total"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "tests/grep.md:1\ntests/recursive/grep.md:1\ntotal:2\n",
+        ));
+}
+
+#[test]
+fn total_with_single_file_prints_both_lines() {
+    binary()
+        .args(["Utility", "tests/grep.md", "--count", "--total"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\ntotal:1\n"));
+}
+
+#[test]
+fn count_matches_counts_every_match_on_a_line() {
+    let dir = scratch_dir("count_matches");
+    fs::write(
+        dir.join("a.txt"),
+        "cat cat cat
+dog
+cat
+",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("b.txt"),
+        "dog
+",
+    )
+    .unwrap();
+    binary()
+        .args(["--count-matches", "--total", "cat"])
+        .arg(dir.join("a.txt"))
+        .arg(dir.join("b.txt"))
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}:4\n{}:0\ntotal:4\n",
+            dir.join("a.txt").display(),
+            dir.join("b.txt").display()
+        )));
+    binary()
+        .args(["--count-matches", "-v", "--stats", "cat"])
+        .arg(dir.join("a.txt"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\n"))
+        .stderr(predicate::str::starts_with("1 match\n1 line matched\n"));
+}
+
+#[test]
+fn pcre_supports_lookbehind_and_lookahead() {
+    binary()
+        .args(["-P", "(?<=## Search )Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+    binary()
+        .args(["-P", "command(?=-line)", "tests/grep.md", "-n"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "2: In this programming assignment, you are expected to implement a command-line utility that\n",
+        ));
+}
+
+#[test]
+fn pcre2_is_another_name_for_pcre() {
+    binary()
+        .args(["--pcre2", "command(?=-line)", "tests/grep.md", "-n"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("2: "));
+}
+
+#[test]
+fn pcre_negative_lookbehind_excludes_prefixed_matches() {
+    binary()
+        .args(["-P", "(?<!Search )utility", "tests/grep.md", "-i", "-n"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("2: "));
+}
+
+#[test]
+fn pcre_lookahead_backtracks_to_shorter_matches() {
+    binary()
+        .args(["-P", "search.*(?=in)", "tests/grep.md", "-n"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("3: searches"));
+}
+
+#[test]
+fn pcre_rejects_lookaround_inside_the_pattern() {
+    binary()
+        .args(["-P", "a(?=b)c", "tests/grep.md"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid -P pattern"));
+}
+
+#[test]
+fn pcre_names_an_unbalanced_group() {
+    binary()
+        .args(["-P", "(?<=a)(b", "tests/grep.md"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "Invalid -P pattern: unclosed group: the ( at byte 6 has no )",
+        ));
+    binary()
+        .args(["-P", "a)(?=b)", "tests/grep.md"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("the ) at byte 1 has no ("));
+}
+
+#[test]
+fn byte_offsets_count_crlf_and_multibyte_content() {
+    binary()
+        .args(["gamma", "itests/fixtures/offsets.txt", "-b"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("14: gamma\n"));
+    binary()
+        .args(["béta", "itests/fixtures/offsets.txt", "-b"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("7: béta\r\n"));
+}
+
+#[test]
+fn byte_offsets_are_reported_for_context_lines_too() {
+    binary()
+        .args(["gamma", "itests/fixtures/offsets.txt", "-b", "-B", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("7- béta\r\n14: gamma\n"));
+}
+
+#[test]
+fn byte_offset_follows_filename_and_line_number() {
+    binary()
+        .args([
+            "gamma",
+            "itests/fixtures/offsets.txt",
+            "-f",
+            "-n",
+            "--byte-offset",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("itests/fixtures/offsets.txt:3:14: gamma\n"));
+}
+
+#[test]
+fn context_dim_dims_text_around_colored_matches() {
+    binary()
+        .args(["Search", "tests/grep.md", "-c", "--context-dim"])
+        .env("CLICOLOR_FORCE", "1")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "\u{1b}[2m## \u{1b}[0m\u{1b}[31mSearch\u{1b}[0m\u{1b}[2m Utility\u{1b}[0m\n",
+        ));
+}
+
+#[test]
+fn context_dim_has_no_effect_without_color() {
+    binary()
+        .args(["Search", "tests/grep.md", "--context-dim"])
+        .env("CLICOLOR_FORCE", "1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+}
+
+#[test]
+fn color_always_colors_piped_output_and_never_overrides_force() {
+    binary()
+        .args(["--color=always", "Utility", "tests/grep.md"])
+        .env("NO_COLOR", "1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search \u{1b}[31mUtility\u{1b}[0m\n"));
+    binary()
+        .args(["--color", "never", "Utility", "tests/grep.md"])
+        .env("CLICOLOR_FORCE", "1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+    binary()
+        .args(["--color", "sometimes", "Utility", "tests/grep.md"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "Invalid value for --color: sometimes (expected auto, always, never)",
+        ));
+}
+
+#[test]
+fn hyperlink_wraps_printed_paths_in_osc_8_links() {
+    let dir = scratch_dir("hyperlink");
+    fs::write(dir.join("a.txt"), "hay\nthe needle\n").unwrap();
+    let link = |target: &str, text: &str| format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", target, text);
+    let file = dir.join("a.txt");
+
+    binary()
+        .args(["--hyperlink", "always", "--hyperlink-format", "vscode"])
+        .args(["-H", "-n", "needle", "a.txt"])
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}:2: the needle\n",
+            link(&format!("vscode://file{}:2:5", file.display()), "a.txt")
+        )));
+    // A file named on its own, as by -l, is linked at its first line.
+    binary()
+        .args([
+            "--hyperlink",
+            "always",
+            "--hyperlink-format",
+            "edit:{path}?{line}",
+        ])
+        .args(["-l", "needle", "a.txt"])
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n",
+            link(&format!("edit:{}?1", file.display()), "a.txt")
+        )));
+    // Only a terminal gets links from auto.
+    binary()
+        .args(["--hyperlink", "auto", "-H", "needle", "a.txt"])
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq("a.txt: the needle\n"));
+}
+
+#[test]
+fn colors_customize_matches_filenames_and_line_numbers() {
+    binary()
+        .args(["--color", "always", "-H", "-n", "Utility", "tests/grep.md"])
+        .env("GREP_COLORS", "ms=01;32:fn=35:ln=33:sl=1")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "\u{1b}[35mtests/grep.md\u{1b}[0m\u{1b}[36m:\u{1b}[0m\u{1b}[33m1\u{1b}[0m\u{1b}[36m:\u{1b}[0m ## Search \u{1b}[01;32mUtility\u{1b}[0m\n",
+        ));
+    // --colors applies over GREP_COLORS.
+    binary()
+        .args([
+            "--color",
+            "always",
+            "--colors",
+            "ms=4",
+            "Utility",
+            "tests/grep.md",
+        ])
+        .env("GREP_COLORS", "ms=01;32")
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search \u{1b}[4mUtility\u{1b}[0m\n"));
+    binary()
+        .args(["--colors", "ms=purple", "Utility", "tests/grep.md"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "Invalid value for --colors: Invalid colour for ms: purple",
+        ));
+}
+
+#[test]
+fn colors_give_capture_groups_their_own_colours() {
+    piped_binary()
+        .args(["-E", "--color", "always", "--colors", "g1=yellow,group2=36"])
+        .arg(r"id=([0-9]+) user=(\w+)")
+        .write_stdin("at id=42 user=bob\n")
+        .assert()
+        .success()
+        .stdout(
+            "at \u{1b}[31mid=\u{1b}[0m\u{1b}[33m42\u{1b}[0m\u{1b}[31m user=\u{1b}[0m\u{1b}[36mbob\u{1b}[0m\n",
+        );
+}
+
+#[test]
+fn sort_path_orders_recursive_results_lexicographically() {
+    binary()
+        .args(["Utility", "tests", "-r", "-l", "--sort", "path"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md\ntests/recursive/grep.md\n"));
+}
+
+#[test]
+fn sort_size_and_mtime_order_recursive_results() {
+    let dir = scratch_dir("sort_keys");
+    fs::write(dir.join("small.txt"), "needle\n").unwrap();
+    fs::write(dir.join("large.txt"), "needle\nplus some padding\n").unwrap();
+    let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+    fs::File::options()
+        .write(true)
+        .open(dir.join("large.txt"))
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+
+    let listing = |key: &str| {
+        let output = binary()
+            .args(["needle", dir.to_str().unwrap(), "-r", "-l", "--sort", key])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let names = |text: String| {
+        text.lines()
+            .map(|line| line.rsplit('/').next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(listing("size")), ["large.txt", "small.txt"]);
+    assert_eq!(names(listing("mtime")), ["small.txt", "large.txt"]);
+    assert_eq!(names(listing("modified")), ["small.txt", "large.txt"]);
+}
+
+#[test]
+fn sort_does_not_reorder_explicit_files() {
+    binary()
+        .args([
+            "Utility",
+            "tests/recursive/grep.md",
+            "tests/grep.md",
+            "-l",
+            "--sort",
+            "path",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/recursive/grep.md\ntests/grep.md\n"));
+}
+
+#[test]
+fn recursive_walks_default_to_name_order_depth_first() {
+    let dir = scratch_dir("walk_order");
+    for name in ["c.txt", "b/z.txt", "a.txt", "b/y.txt", "b.txt"] {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "needle\n").unwrap();
+    }
+    let listing = |sort: &[&str]| {
+        let output = binary()
+            .args(["-rl", "needle", "."])
+            .args(sort)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap().replace("./", "")
+    };
+    assert_eq!(listing(&[]), "a.txt\nb/y.txt\nb/z.txt\nb.txt\nc.txt\n");
+    assert_eq!(listing(&["--sort", "path"]), listing(&[]));
+    let mut unsorted: Vec<String> = listing(&["--sort=none"])
+        .lines()
+        .map(String::from)
+        .collect();
+    unsorted.sort();
+    assert_eq!(unsorted, ["a.txt", "b.txt", "b/y.txt", "b/z.txt", "c.txt"]);
+}
+
+#[test]
+fn parallel_walk_yields_the_same_order_with_any_number_of_workers() {
+    let dir = scratch_dir("parallel_walk");
+    let mut expected = String::new();
+    for a in ["a", "b", "c", "d"] {
+        for b in ["a", "b", "c", "d"] {
+            for c in ["x.txt", "y.txt"] {
+                let name = format!("{}/{}/{}", a, b, c);
+                fs::create_dir_all(dir.join(a).join(b)).unwrap();
+                fs::write(dir.join(&name), "needle\n").unwrap();
+                // Ignore files deep in the tree apply while their directory is read.
+                if b == "c" && c == "y.txt" {
+                    fs::write(dir.join(a).join(b).join(".gitignore"), "y.txt\n").unwrap();
+                    continue;
+                }
+                expected.push_str(&name);
+                expected.push('\n');
+            }
+        }
+        fs::write(dir.join(format!("{}.txt", a)), "needle\n").unwrap();
+        expected.push_str(&format!("{}.txt\n", a));
+    }
+    for threads in ["1", "8"] {
+        let output = binary()
+            .args(["-rl", "--threads", threads, "needle", "."])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        let listed = String::from_utf8(output.stdout).unwrap().replace("./", "");
+        assert_eq!(listed, expected, "with {} threads", threads);
+    }
+}
+
+#[test]
+fn streamed_walk_reports_unreadable_entries_and_searches_the_rest() {
+    use std::os::unix::fs::symlink;
+    let dir = scratch_dir("streamed_walk");
+    fs::create_dir_all(dir.join("a")).unwrap();
+    fs::create_dir_all(dir.join("c")).unwrap();
+    fs::write(dir.join("a/x.txt"), "needle\n").unwrap();
+    fs::write(dir.join("c/z.txt"), "needle\n").unwrap();
+    symlink(dir.join("missing"), dir.join("b")).unwrap();
+    for threads in ["1", "8"] {
+        binary()
+            .args(["-r", "--follow", "--threads", threads, "needle", "."])
+            .current_dir(&dir)
+            .assert()
+            .code(2)
+            .stdout("./a/x.txt: needle\n./c/z.txt: needle\n")
+            .stderr(predicate::str::contains("Error: ./b: No such file"));
+    }
+}
+
+#[test]
+fn rejects_unknown_sort_keys() {
+    binary()
+        .args(["Utility", "tests", "-r", "--sort", "color"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid value for --sort"));
+}
+
+#[test]
+fn timeout_skips_slow_files_and_continues() {
+    let dir = scratch_dir("timeout");
+    let big = dir.join("big.txt");
+    fs::write(&big, "filler line without the word\n".repeat(400_000)).unwrap();
+
+    binary()
+        .args([
+            "Utility",
+            big.to_str().unwrap(),
+            "tests/grep.md",
+            "--timeout",
+            "1",
+            // Mapped, a file with no match is scanned in one step too quick to time.
+            "--engine",
+            "stream",
+        ])
+        .assert()
+        .code(2)
+        .stdout(predicate::eq("tests/grep.md: ## Search Utility\n"))
+        .stderr(predicate::str::contains("search timed out after 1 ms"));
+}
+
+#[test]
+fn deadline_stops_the_whole_run() {
+    let dir = scratch_dir("deadline");
+    let filler = "filler line without the word\n".repeat(400_000);
+    for name in ["one.txt", "two.txt"] {
+        fs::write(dir.join(name), &filler).unwrap();
+    }
+
+    binary()
+        .args([
+            "--threads",
+            "1",
+            "--deadline",
+            "1",
+            "--engine",
+            "stream",
+            "Utility",
+        ])
+        .arg(dir.join("one.txt"))
+        .arg(dir.join("two.txt"))
+        .assert()
+        .code(2)
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::eq(
+            "Error: the --deadline of 1 ms was reached; 2 files were cut short or not searched\n",
+        ));
+}
+
+#[test]
+fn max_memory_skips_what_it_cannot_stream() {
+    let dir = scratch_dir("max_memory");
+    fs::write(dir.join("long"), format!("{}\nneedle\n", "x".repeat(5000))).unwrap();
+    fs::write(dir.join("short"), "needle\n").unwrap();
+
+    binary()
+        .args([
+            "-r",
+            "--sort",
+            "path",
+            "--max-memory",
+            "1K",
+            "--multiline",
+            "needle",
+        ])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}: needle\n",
+            dir.join("short").display()
+        )))
+        .stderr(predicate::str::contains(format!(
+            "Warning: {}: skipped: the file is over --max-memory of 1024 bytes",
+            dir.join("long").display()
+        )));
+    // Line by line, even with --mmap, a file over the cap is streamed, long line and all.
+    binary()
+        .args(["--mmap", "--max-memory", "2K", "-n", "needle"])
+        .arg(dir.join("long"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("2: needle\n"));
+}
+
+#[test]
+fn long_lines_are_searched_in_windows_that_overlap() {
+    let dir = scratch_dir("long_lines");
+    let path = dir.join("line");
+    // Windows of 1 MiB overlap by 64 KiB, so `needle` straddles the first boundary.
+    let first = (1 << 20) - 3;
+    let line = format!(
+        "{}needle{}é{}\r\n",
+        "a".repeat(first),
+        "b".repeat(2_000_000),
+        "c".repeat(3000)
+    );
+    fs::write(&path, format!("{}short\n", line)).unwrap();
+
+    let search = |args: &[&str]| {
+        let output = binary().args(args).arg(&path).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        search(&["-o", "-b", "needle"]),
+        format!("{}: needle\n", first)
+    );
+    assert_eq!(
+        search(&["-E", "-o", "-b", "bé"]),
+        format!("{}: bé\n", first + 6 + 1_999_999)
+    );
+    // `^` and `$` hold only at the ends of the whole line, not of each window.
+    assert_eq!(search(&["-E", "--count", "^aaa"]), "1\n");
+    assert_eq!(search(&["-E", "--count", "^b|^c|a$|b$|c$"]), "1\n");
+    assert_eq!(search(&["-E", "--count", "^b|^c|a$|b$"]), "0\n");
+    assert_eq!(search(&["--count", "needle", "--all-of", "é"]), "1\n");
+    assert_eq!(search(&["--count", "needle", "--none-of", "é"]), "0\n");
+    // A printed long line is printed whole, however it was searched.
+    assert_eq!(search(&["-n", "needle"]), format!("1: {}", line));
+    assert_eq!(search(&["-v", "short"]), line);
+    assert_eq!(
+        search(&["-A", "1", "--engine", "stream", "needle"]),
+        format!("{}short\n", line)
+    );
+
+    assert_eq!(
+        search(&["-n", "-B", "1", "short"]),
+        format!("1- {}2: short\n", line)
+    );
+    assert_eq!(
+        search(&["--column", "needle"]),
+        format!("{}: {}", first + 1, line)
+    );
+
+    // Under --max-memory the line is still searched in windows of that size, and printed a
+    // piece at a time; only output that needs it whole, such as --json, skips it with a warning
+    // rather than cutting it short.
+    assert_eq!(
+        search(&["--max-memory", "1K", "--count", "needle", "--all-of", "é"]),
+        "1\n"
+    );
+    assert_eq!(search(&["--max-memory", "1K", "needle"]), line);
+    binary()
+        .args(["--max-memory", "1K", "--json", "needle"])
+        .arg(&path)
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "skipped: line 1 is over --max-memory of 1024 bytes",
+        ));
+}
+
+#[test]
+fn printing_a_long_line_holds_only_a_piece_of_it_at_a_time() {
+    let dir = scratch_dir("long_line_memory");
+    let path = dir.join("line");
+    let line = format!("{}needle{}\n", "a".repeat(40 << 20), "b".repeat(40 << 20));
+    fs::write(&path, &line).unwrap();
+
+    // Capped at 64 MiB of address space, the process could not hold the 80 MiB line whole.
+    let grep = binary();
+    let mut limited = Command::new("sh");
+    limited
+        .args(["-c", "ulimit -v 65536 && exec \"$@\"", "sh"])
+        .arg(grep.get_program())
+        .args(["--engine", "stream", "needle"])
+        .arg(&path);
+    for (key, value) in grep.get_envs() {
+        match value {
+            Some(value) => limited.env(key, value),
+            None => limited.env_remove(key),
+        };
+    }
+    let output = limited.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stdout == line.as_bytes());
+}
+
+#[test]
+fn options_with_no_effect_draw_a_warning() {
+    binary()
+        .args(["-r", "-C", "2", "--count", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\n"))
+        .stderr(predicate::eq(
+            "Warning: -A, -B, and -C have no effect with -o, --count, -l, -L, or -q, which print no context lines.\n\
+             Warning: -r has no effect when only files are given; name a directory to search it recursively.\n",
+        ));
+    // -s silences them along with other messages.
+    binary()
+        .args(["-s", "--total", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn zero_timeout_disables_the_limit() {
+    binary()
+        .args(["Utility", "tests/grep.md", "--timeout", "0"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+}
+
+#[test]
+fn combined_short_flags_expand_to_individual_flags() {
+    binary()
+        .args(["-inr", "utility", "tests"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "tests/grep.md:1: ## Search Utility\n\
+tests/grep.md:2: In this programming assignment, you are expected to implement a command-line utility that\n\
+tests/recursive/grep.md:1: ## Search Utility\n\
+tests/recursive/grep.md:2: In this programming assignment, you are expected to implement a command-line utility that\n",
+        ));
+}
+
+#[test]
+fn combined_invert_and_filename_flags() {
+    binary()
+        .args(["Utility", "tests/grep.md", "-vf"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "tests/grep.md: In this programming assignment, you are expected to implement a command-line utility that\n\
+tests/grep.md: searches for a specific pattern in one or multiple files, similar in spirit to the UNIX\n\
+tests/grep.md: `grep` command.\n",
+        ));
+}
+
+#[test]
+fn combined_short_flags_reject_unknown_letters() {
+    binary()
+        .args(["-iy", "Utility", "tests/grep.md"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown option -y in -iy."));
+}
+
+#[test]
+fn long_names_and_unknown_options() {
+    binary()
+        .args([
+            "--ignore-case",
+            "--line-number",
+            "--invert-match",
+            "utility",
+        ])
+        .args(["--context=0", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "3: searches for a specific pattern",
+        ));
+    binary()
+        .args(["--bogus", "Utility", "tests/grep.md"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("Unknown option --bogus."));
+    binary()
+        .args(["--count=2", "Utility", "tests/grep.md"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "Option --count does not take a value.",
+        ));
+    binary()
+        .args(["--", "-x", "tests/grep.md"])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn files_without_match_lists_only_files_with_no_hit() {
+    let dir = scratch_dir("files_without_match");
+    fs::write(dir.join("hit.txt"), "needle\n").unwrap();
+    fs::write(dir.join("miss.txt"), "hay\n").unwrap();
+
+    binary()
+        .args(["-L", "--sort", "path", "-r", "needle"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n",
+            dir.join("miss.txt").display()
+        )));
+    binary()
+        .args(["--files-with-matches", "needle"])
+        .arg(dir.join("hit.txt"))
+        .arg(dir.join("miss.txt"))
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n",
+            dir.join("hit.txt").display()
+        )));
+}
+
+#[test]
+fn list_files_with_bundled_null_flag() {
+    binary()
+        .args(["-rlZ", "Utility", "tests"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md\0tests/recursive/grep.md\0"));
+}
+
+#[test]
+fn count_reports_zero_for_files_without_matches() {
+    binary()
+        .args([
+            "Utility",
+            "tests/grep.md",
+            "itests/fixtures/offsets.txt",
+            "--count",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "tests/grep.md:1\nitests/fixtures/offsets.txt:0\n",
+        ));
+}
+
+#[test]
+fn count_for_single_file_prints_bare_number_even_when_zero() {
+    binary()
+        .args(["NonexistentPattern", "tests/grep.md", "--count"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq("0\n"));
+    binary()
+        .args(["NonexistentPattern", "tests/grep.md", "--count", "-f"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq("tests/grep.md:0\n"));
+}
+
+#[test]
+fn recursive_count_lists_every_scanned_file() {
+    let dir = scratch_dir("recursive_count");
+    fs::write(dir.join("only.txt"), "nothing here\n").unwrap();
+    let expected = format!("{}:0\n", dir.join("only.txt").display());
+
+    binary()
+        .args(["needle", dir.to_str().unwrap(), "-r", "--count"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(expected));
+}
+
+#[test]
+fn unique_suppresses_repeated_lines_within_a_file() {
+    binary()
+        .args(["retry", "itests/fixtures/repeated.log", "--unique", "-n"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: retry failed\n4: retry succeeded\n"));
+}
+
+#[test]
+fn unique_counts_distinct_matching_lines() {
+    binary()
+        .args([
+            "retry",
+            "itests/fixtures/repeated.log",
+            "--unique",
+            "--count",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("2\n"));
+}
+
+#[test]
+fn group_separator_options_leave_plain_output_unchanged() {
+    binary()
+        .args(["Utility", "tests/grep.md", "--context-separator", "=="])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+    binary()
+        .args(["Utility", "tests/grep.md", "--no-group-separator"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+}
+
+#[test]
+fn summary_reports_matched_and_searched_files_on_stderr() {
+    binary()
+        .args([
+            "Utility",
+            "tests",
+            "itests/fixtures/offsets.txt",
+            "-r",
+            "--summary",
+            "--no-filename",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n## Search Utility\n"))
+        .stderr(predicate::eq("2 files matched out of 3 searched\n"));
+}
+
+#[test]
+fn summary_combines_with_list_files() {
+    binary()
+        .args([
+            "Utility",
+            "tests/grep.md",
+            "itests/fixtures/offsets.txt",
+            "-l",
+            "--summary",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md\n"))
+        .stderr(predicate::eq("1 file matched out of 2 searched\n"));
+}
+
+#[test]
+fn stats_totals_files_lines_and_bytes_on_stderr() {
+    let dir = scratch_dir("stats");
+    fs::write(dir.join("a.txt"), "needle\nneedle again\n").unwrap();
+    fs::write(dir.join("b.txt"), "hay\n").unwrap();
+    for threads in ["1", "4"] {
+        binary()
+            .args(["-r", "-c", "--stats", "--threads", threads, "needle"])
+            .arg(&dir)
+            .assert()
+            .success()
+            .stderr(
+                predicate::str::is_match(
+                    "^2 matches\n2 lines matched\n1 file contained matches\n2 files searched\n\
+                 24 bytes searched\n[0-9]+\\.[0-9]{6} seconds elapsed\n$",
+                )
+                .unwrap(),
+            );
+    }
+}
+
+#[test]
+fn line_buffered_prints_each_match_while_input_is_still_open() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+
+    let mut child = binary()
+        .args(["--line-buffered", "needle"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("spawn grep");
+    let mut stdin = child.stdin.take().expect("stdin");
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout"));
+
+    // Each match must arrive before more input is written.
+    for line in ["first needle", "second needle"] {
+        writeln!(stdin, "hay\n{}", line).expect("write stdin");
+        stdin.flush().expect("flush stdin");
+        let mut printed = String::new();
+        stdout.read_line(&mut printed).expect("read stdout");
+        assert_eq!(printed, format!("{}\n", line));
+    }
+    drop(stdin);
+    assert!(child.wait().expect("wait for grep").success());
+}
+
+#[test]
+fn a_closed_stdout_ends_the_run_without_an_error() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let dir = scratch_dir("closed-stdout");
+    let lines = "needle\n".repeat(200_000);
+    fs::write(dir.join("a.txt"), &lines).unwrap();
+    fs::write(dir.join("b.txt"), &lines).unwrap();
+
+    // As with `grep needle * | head -1`.
+    let mut child = binary()
+        .args(["--threads", "1", "needle", "a.txt", "b.txt"])
+        .current_dir(&dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn grep");
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout"));
+    let mut first = String::new();
+    stdout.read_line(&mut first).expect("read stdout");
+    assert_eq!(first, "a.txt: needle\n");
+    drop(stdout);
+    let output = child.wait_with_output().expect("wait for grep");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn watch_searches_changed_files_again() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let dir = scratch_dir("watch");
+    let file = dir.join("notes.txt");
+    fs::write(&file, "needle one\nhay\n").unwrap();
+    let mut child = binary()
+        .args(["--watch", "needle"])
+        .arg(&file)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("spawn grep");
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout"));
+    let mut next_line = || {
+        let mut line = String::new();
+        stdout.read_line(&mut line).expect("read stdout");
+        line
+    };
+    assert_eq!(next_line(), "needle one\n");
+
+    // Output is not a terminal, so the changed file's results are appended.
+    fs::write(&file, "needle one\nhay\nneedle two\n").unwrap();
+    assert_eq!(next_line(), "needle one\n");
+    assert_eq!(next_line(), "needle two\n");
+    child.kill().expect("stop grep");
+    child.wait().expect("wait for grep");
+
+    binary()
+        .args(["--watch", "needle"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("cannot watch standard input"));
+}
+
+#[test]
+fn tail_prints_matching_lines_as_they_are_appended() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+
+    let dir = scratch_dir("tail");
+    let file = dir.join("app.log");
+    fs::write(&file, "error one\nok\n").unwrap();
+    let mut child = binary()
+        .args(["--tail", "-n", "error"])
+        .arg(&file)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("spawn grep");
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout"));
+    let mut next_line = || {
+        let mut line = String::new();
+        stdout.read_line(&mut line).expect("read stdout");
+        line
+    };
+    assert_eq!(next_line(), "1: error one\n");
+
+    // A line is only searched once its terminator has been written.
+    let mut log = fs::OpenOptions::new().append(true).open(&file).unwrap();
+    log.write_all(b"ok\nerror tw").unwrap();
+    log.flush().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(400));
+    log.write_all(b"o\n").unwrap();
+    assert_eq!(next_line(), "4: error two\n");
+    child.kill().expect("stop grep");
+    child.wait().expect("wait for grep");
+
+    binary()
+        .args(["--tail", "-l", "error"])
+        .arg(&file)
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("cannot be combined with --count"));
+}
+
+#[test]
+fn starts_with_anchors_pattern_to_line_start() {
+    binary()
+        .args(["--starts-with", "##", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+    binary()
+        .args(["--starts-with", "Search", "tests/grep.md"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn ends_with_anchors_pattern_to_line_end_and_composes_with_ignore_case() {
+    binary()
+        .args(["--ends-with", "THAT", "tests/grep.md", "-i", "-n"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("2: "));
+}
+
+#[test]
+fn starts_with_and_ends_with_together_anchor_both_ends() {
+    binary()
+        .args([
+            "--starts-with",
+            "--ends-with",
+            "`grep` command.",
+            "tests/grep.md",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("`grep` command.\n"));
+    binary()
+        .args(["--starts-with", "--ends-with", "`grep`", "tests/grep.md"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn o_context_requires_a_numeric_value() {
+    binary()
+        .args(["Utility", "tests/grep.md", "--o-context", "wide"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid value for --o-context: wide",
+        ));
+}
+
+#[test]
+fn generates_completions_for_each_shell() {
+    binary()
+        .args(["--generate-completions", "bash"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "complete -o filenames -F _grep grep",
+        ))
+        .stdout(predicate::str::contains("--sort"));
+    binary()
+        .args(["--generate-completions", "zsh"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("#compdef grep"))
+        .stdout(predicate::str::contains(
+            ":KEY:(path mtime modified size none)",
+        ));
+    binary()
+        .args(["--generate-completions", "fish"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("complete -c grep -s P -l pcre"));
+    binary()
+        .args(["completions", "powershell"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Register-ArgumentCompleter -Native -CommandName grep",
+        ))
+        .stdout(predicate::str::contains(
+            "{ $_ -cin '-t', '--type' } { $choices = @('c', 'cpp',",
+        ));
+}
+
+#[test]
+fn long_help_and_man_page_list_values_and_examples() {
+    binary()
+        .arg("--help-long")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  --sort KEY\n      Order recursive results by path",
+        ))
+        .stdout(predicate::str::contains(
+            "      Values: path, mtime, modified, size, none\n",
+        ))
+        .stdout(predicate::str::contains("Examples:\n  grep -rn TODO src\n"))
+        .stdout(predicate::str::contains("--generate-man").not());
+    binary()
+        .arg("--generate-man")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(".TH GREP 1"))
+        .stdout(predicate::str::contains(
+            ".TP\n\\fB\\-i\\fR, \\fB\\-\\-ignore\\-case\\fR\n",
+        ))
+        .stdout(predicate::str::contains(".SH EXIT STATUS\n"));
+}
+
+#[test]
+fn rejects_unknown_completion_shell_and_hides_flag_from_help() {
+    binary()
+        .args(["--generate-completions", "tcsh"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Unsupported shell for --generate-completions: tcsh",
+        ));
+    binary()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--generate-completions").not());
+}
+
+#[test]
+fn trim_strips_whitespace_from_printed_lines_only() {
+    let dir = scratch_dir("trim");
+    let file = dir.join("indented.txt");
+    fs::write(&file, "    key = value  \n\tother\n").unwrap();
+
+    binary()
+        .args(["--trim", "-n", "    key"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: key = value\n"));
+}
+
+#[test]
+fn line_range_limits_search_but_keeps_true_line_numbers() {
+    let dir = scratch_dir("line_range");
+    let file = dir.join("lines.txt");
+    fs::write(&file, "hit one\nmiss\nhit three\nhit four\nhit five\n").unwrap();
+
+    binary()
+        .args(["-n", "--line-range", "2:4", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("3: hit three\n4: hit four\n"));
+    binary()
+        .args(["-n", "--line-range", "4:", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("4: hit four\n5: hit five\n"));
+    binary()
+        .args(["--count", "--line-range", ":3", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("2\n"));
+}
+
+#[test]
+fn within_searches_only_inside_regions() {
+    let dir = scratch_dir("within");
+    let fenced = dir.join("notes.md");
+    fs::write(
+        &fenced,
+        "needle outside\n```\nneedle in code\n```\nneedle after\n```\nneedle unterminated\n",
+    )
+    .unwrap();
+    let fence = "^```";
+    binary()
+        .args(["-n", "--within", fence, "needle"])
+        .arg(&fenced)
+        .assert()
+        .success()
+        .stdout(predicate::eq("3: needle in code\n7: needle unterminated\n"));
+    // In multiline mode too, and with -v only unmatched lines inside regions are selected.
+    binary()
+        .args(["-n", "-U", "--within", fence, "-E", "needle in \\w+"])
+        .arg(&fenced)
+        .assert()
+        .success()
+        .stdout(predicate::eq("3: needle in code\n"));
+    binary()
+        .args(["-n", "-v", "--within", fence, "code"])
+        .arg(&fenced)
+        .assert()
+        .success()
+        .stdout(predicate::eq("7: needle unterminated\n"));
+
+    let nested = dir.join("nested.txt");
+    fs::write(
+        &nested,
+        "needle 0\nBEGIN\nneedle 1\nBEGIN\nneedle 2\nEND\nneedle 3\nEND\nneedle 4\nBEGIN\nneedle 5\n",
+    )
+    .unwrap();
+    binary()
+        .args(["--within", "^BEGIN", "--within-end", "^END", "needle"])
+        .arg(&nested)
+        .assert()
+        .success()
+        .stdout(predicate::eq("needle 1\nneedle 2\nneedle 3\nneedle 5\n"));
+    // Regions are followed through lines --line-range skips.
+    binary()
+        .args(["--within", "^BEGIN", "--within-end", "^END"])
+        .args(["--line-range", "7:", "needle"])
+        .arg(&nested)
+        .assert()
+        .success()
+        .stdout(predicate::eq("needle 3\nneedle 5\n"));
+
+    binary()
+        .args(["--within-end", "^END", "needle"])
+        .arg(&nested)
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("--within-end needs --within"));
+}
+
+#[test]
+fn line_range_rejects_malformed_specs() {
+    for (spec, reason) in [
+        ("9:3", "START is after END"),
+        ("a:3", "expected START:END"),
+        ("12", "expected START:END"),
+    ] {
+        binary()
+            .args(["--line-range", spec, "Utility", "tests/grep.md"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "Invalid value for --line-range: {} ({})",
+                spec, reason
+            )));
+    }
+}
+
+#[test]
+fn regexp_mode_uses_regex_syntax_and_fixed_strings_stays_literal() {
+    binary()
+        .args(["-E", "^`grep` comm[a-z]+\\.$", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("`grep` command.\n"));
+    binary()
+        .args(["-E", "-F", "comm[a-z]+", "tests/grep.md"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn the_last_syntax_flag_wins_and_debug_pattern_shows_the_result() {
+    binary()
+        .args(["-P", "-E", "(?<=`)grep", "tests/grep.md"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Invalid -E pattern"));
+    binary()
+        .args(["--debug-pattern", "-E", "-iw", "a.c", "tests/grep.md"])
+        .assert()
+        .stderr(predicate::eq(
+            "syntax: regex (-E)\nflags: case-insensitive word\n\
+             pattern: (?i)\\b{start-half}(?:a.c)\\b{end-half}\nbackend: regex\n",
+        ));
+    binary()
+        .args(["--debug-pattern", "-x", "a.c", "tests/grep.md"])
+        .assert()
+        .stderr(predicate::str::contains("syntax: fixed strings (-F)\n"))
+        .stderr(predicate::str::contains("pattern: ^(?:a\\.c)$\n"));
+}
+
+#[test]
+fn regexp_mode_reports_invalid_patterns() {
+    binary()
+        .args(["-E", "(unclosed", "tests/grep.md"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Invalid -E pattern"));
+}
+
+#[test]
+fn reads_standard_input_without_files_or_for_dash() {
+    piped_binary()
+        .arg("beta")
+        .write_stdin("alpha\nbeta\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("beta\n"));
+    piped_binary()
+        .args(["-f", "-n", "beta", "-"])
+        .write_stdin("alpha\nbeta\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("(standard input):2: beta\n"));
+    piped_binary()
+        .args(["--count", "Utility", "tests/grep.md", "-"])
+        .write_stdin("Utility\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md:1\n(standard input):1\n"));
+}
+
+#[test]
+fn label_names_standard_input_wherever_a_file_would_be() {
+    piped_binary()
+        .args(["--label", "dmesg", "-H", "-n", "usb"])
+        .write_stdin("boot\nusb 1-1: new device\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("dmesg:2: usb 1-1: new device\n"));
+    piped_binary()
+        .args(["--label=dmesg", "-l", "usb", "tests/grep.md", "-"])
+        .write_stdin("usb\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("dmesg\n"));
+}
+
+#[test]
+fn context_lines_surround_matches_with_group_separators() {
+    let dir = scratch_dir("context");
+    let file = dir.join("lines.txt");
+    fs::write(&file, "a\nb\nhit1\nc\nd\ne\nf\nhit2\nhit3\ng\n").unwrap();
+
+    binary()
+        .args(["-n", "-C", "1", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "2- b\n3: hit1\n4- c\n--\n7- f\n8: hit2\n9: hit3\n10- g\n",
+        ));
+    binary()
+        .args(["-A1", "--context-separator", "==", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("hit1\nc\n==\nhit2\nhit3\ng\n"));
+    binary()
+        .args(["-B", "2", "--no-group-separator", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("a\nb\nhit1\ne\nf\nhit2\nhit3\n"));
+}
+
+#[test]
+fn parallel_search_keeps_output_in_walk_order() {
+    let dir = scratch_dir("threads");
+    let mut expected = String::new();
+    for index in 0..20 {
+        let path = dir.join(format!("file{:02}.txt", index));
+        fs::write(&path, format!("needle {}\nhay\n", index)).unwrap();
+        expected.push_str(&format!("{}: needle {}\n", path.display(), index));
+    }
+
+    for threads in ["1", "4"] {
+        binary()
+            .args(["-r", "-f", "--sort", "path", "--threads", threads, "needle"])
+            .arg(&dir)
+            .assert()
+            .success()
+            .stdout(predicate::eq(expected.clone()));
+    }
+}
+
+#[test]
+fn heading_groups_numbered_lines_under_each_file() {
+    let dir = scratch_dir("heading");
+    fs::write(dir.join("a.txt"), "needle one\nhay\nneedle two\n").unwrap();
+    fs::write(dir.join("b.txt"), "hay\n").unwrap();
+    fs::write(dir.join("c.txt"), "needle three\n").unwrap();
+    let expected = format!(
+        "{a}\n  1: needle one\n  2- hay\n  3: needle two\n\n{c}\n  1: needle three\n",
+        a = dir.join("a.txt").display(),
+        c = dir.join("c.txt").display()
+    );
+
+    for threads in ["1", "4"] {
+        binary()
+            .args(["-r", "--sort", "path", "--heading", "-A1", "needle"])
+            .args(["--threads", threads])
+            .arg(&dir)
+            .assert()
+            .success()
+            .stdout(predicate::eq(expected.clone()));
+    }
+    // With a single file there is no name to head the lines with.
+    binary()
+        .args(["--heading", "needle"])
+        .arg(dir.join("c.txt"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: needle three\n"));
+}
+
+#[test]
+fn table_lines_up_names_and_numbers_across_files() {
+    let dir = scratch_dir("table");
+    let long: String = (1..=10).map(|n| format!("needle {}\n", n)).collect();
+    fs::write(dir.join("a.txt"), "hay\n  needle\n").unwrap();
+    fs::write(dir.join("longer.txt"), &long).unwrap();
+
+    for threads in ["1", "4"] {
+        let output = binary()
+            .args([
+                "--table",
+                "-n",
+                "--column",
+                "-B1",
+                "needle",
+                "a.txt",
+                "longer.txt",
+            ])
+            .args(["--threads", threads])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        // A context line keeps an empty column field, so its text lines up too.
+        assert_eq!(
+            lines[..3],
+            [
+                "a.txt-      1- - hay",
+                "a.txt:      2:3:   needle",
+                "longer.txt: 1:1: needle 1",
+            ]
+        );
+        assert_eq!(lines[11], "longer.txt:10:1: needle 10");
+    }
+    binary()
+        .args(["--table", "--json", "needle", "a.txt"])
+        .current_dir(&dir)
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn type_filters_walked_files_by_kind() {
+    let dir = scratch_dir("types");
+    for name in ["main.rs", "build.rs.in", "script.py", "notes.txt"] {
+        fs::write(dir.join(name), "needle\n").unwrap();
+    }
+    let search = |args: &[&str]| {
+        let output = binary()
+            .args(["-r", "-l", "--sort", "path"])
+            .args(args)
+            .arg("needle")
+            .arg(&dir)
+            .output()
+            .unwrap();
+        let listed = String::from_utf8(output.stdout).unwrap();
+        listed
+            .lines()
+            .map(|line| line.rsplit('/').next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(search(&["-t", "rust"]), ["main.rs"]);
+    assert_eq!(
+        search(&["--type", "rust", "--type", "py"]),
+        ["main.rs", "script.py"]
+    );
+    assert_eq!(
+        search(&["-T", "rust", "-T", "txt"]),
+        ["build.rs.in", "script.py"]
+    );
+    assert_eq!(
+        search(&["-t", "rust", "--type-add", "rust:*.rs.in"]),
+        ["build.rs.in", "main.rs"]
+    );
+    assert_eq!(
+        search(&["--type-add", "config:*.in,*.txt", "-t", "config"]),
+        ["build.rs.in", "notes.txt"]
+    );
+
+    binary()
+        .args(["-r", "-t", "cobol", "needle"])
+        .arg(&dir)
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "Unknown file type: cobol (see --type-list)",
+        ));
+}
+
+#[test]
+fn files_from_searches_listed_files_through_the_filters() {
+    let dir = scratch_dir("files_from");
+    for name in ["a.rs", "b.py", "c.rs"] {
+        fs::write(dir.join(name), "needle\n").unwrap();
+    }
+    let list = format!(
+        "{}\r\n{}\n\n{}\n",
+        dir.join("a.rs").display(),
+        dir.join("b.py").display(),
+        dir.join("c.rs").display()
+    );
+
+    piped_binary()
+        .args(["--files-from", "-", "--exclude", "c.rs", "-l", "needle"])
+        .write_stdin(list.clone())
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n{}\n",
+            dir.join("a.rs").display(),
+            dir.join("b.py").display()
+        )));
+    // NUL-separated lists work too, and positional inputs are searched unfiltered.
+    let nul_list = dir.join("list");
+    fs::write(&nul_list, list.replace("\r\n", "\0").replace('\n', "\0")).unwrap();
+    binary()
+        .args(["-t", "py", "-l", "needle", "--files-from"])
+        .arg(&nul_list)
+        .arg(dir.join("c.rs"))
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n{}\n",
+            dir.join("c.rs").display(),
+            dir.join("b.py").display()
+        )));
+}
+
+#[test]
+fn files_that_vanish_before_being_read_are_skipped_with_a_warning() {
+    let dir = scratch_dir("vanished");
+    fs::write(dir.join("kept"), "needle\n").unwrap();
+    let list = format!(
+        "{}\n{}\n",
+        dir.join("kept").display(),
+        dir.join("gone").display()
+    );
+
+    piped_binary()
+        .args(["--files-from", "-", "--stats", "needle"])
+        .write_stdin(list)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}: needle\n",
+            dir.join("kept").display()
+        )))
+        .stderr(predicate::str::contains(format!(
+            "Warning: {}: vanished before it could be searched",
+            dir.join("gone").display()
+        )))
+        .stderr(predicate::str::contains(
+            "1 file vanished before being searched",
+        ));
+    // A missing file named on the command line is still an error.
+    binary()
+        .args(["needle"])
+        .arg(dir.join("gone"))
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn group_by_dir_prints_a_tree_of_match_counts() {
+    let dir = scratch_dir("group_by_dir");
+    for sub in ["src/a", "src/b", "docs"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    fs::write(dir.join("src/a/one"), "needle needle\n").unwrap();
+    fs::write(dir.join("src/a/two"), "needle\nhay\n").unwrap();
+    fs::write(dir.join("src/b/three"), "needle\n").unwrap();
+    fs::write(dir.join("docs/four"), "hay\n").unwrap();
+
+    binary()
+        .current_dir(&dir)
+        .args(["-r", "--group-by-dir", "needle", "src", "docs"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "src/: 3 files, 4 matches\n  a/: 2 files, 3 matches\n  b/: 1 file, 1 match\n",
+        ));
+    binary()
+        .args(["--group-by-dir", "--count", "needle"])
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn format_csv_and_tsv_print_quoted_tables() {
+    piped_binary()
+        .args(["-E", "--format", "csv", r#"say "\w+", \w+|x"#])
+        .write_stdin("say \"hi\", you\nnothing\nx\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "path,line,column,match\n\
+             (standard input),1,1,\"say \"\"hi\"\", you\"\n\
+             (standard input),3,1,x\n",
+        ));
+    piped_binary()
+        .args(["-E", "--format", "tsv", "a.b"])
+        .write_stdin("a\tb\naxb\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "path\tline\tcolumn\tmatch\n\
+             (standard input)\t1\t1\t\"a\tb\"\n\
+             (standard input)\t2\t1\taxb\n",
+        ));
+}
+
+#[test]
+fn format_sarif_writes_one_log_with_a_result_per_match() {
+    let dir = scratch_dir("sarif");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/a b.rs"), "// TODO: é TODO\n").unwrap();
+    fs::write(dir.join("src/c.rs"), "fine\n").unwrap();
+
+    let output = binary()
+        .current_dir(&dir)
+        .args(["-r", "--format", "sarif", "TODO", "src"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let log = String::from_utf8(output.stdout).unwrap();
+    assert!(log.starts_with("{\"version\":\"2.1.0\","), "{}", log);
+    assert!(log.ends_with("]}]}\n"), "{}", log);
+    assert_eq!(log.matches("\"ruleId\":\"match\"").count(), 2, "{}", log);
+    assert!(log.contains(
+        "{\"uri\":\"src/a%20b.rs\"},\"region\":{\"startLine\":1,\"startColumn\":12,\
+         \"endLine\":1,\"endColumn\":16,"
+    ));
+    // Results are joined by commas into one array, with none trailing.
+    assert!(log.contains("}]},\n{\"ruleId\""), "{}", log);
+    assert!(!log.contains(",\n]"), "{}", log);
+
+    binary()
+        .args(["--format", "sarif", "--count", "TODO"])
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn rules_report_which_named_pattern_matched() {
+    let dir = scratch_dir("rules");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("rules.toml"),
+        "# Policy checks\n\
+         [[rule]]\n\
+         name = \"no-unwrap\"\n\
+         pattern = '\\.unwrap\\(\\)'\n\
+         severity = \"error\"\n\
+         message = \"handle the error instead\"\n\
+         include = [\"*.rs\"]\n\
+         \n\
+         [[rule]]\n\
+         name = \"todo\"\n\
+         pattern = \"TODO|FIXME\" # either\n",
+    )
+    .unwrap();
+    fs::write(dir.join("src/a.rs"), "let x = y.unwrap(); // TODO\nfine\n").unwrap();
+    fs::write(dir.join("src/b.py"), "x.unwrap() FIXME\n").unwrap();
+
+    binary()
+        .current_dir(&dir)
+        .args(["--rules", "rules.toml", "-r", "--sort", "path", "src"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "src/a.rs:1:10: error[no-unwrap]: handle the error instead\n\
+             src/a.rs:1:24: warning[todo]: matches TODO|FIXME\n\
+             src/b.py:1:12: warning[todo]: matches TODO|FIXME\n",
+        ));
+
+    fs::write(
+        dir.join("bad.toml"),
+        "[[rule]]\nname = \"x\"\nseverity = \"fatal\"\n",
+    )
+    .unwrap();
+    binary()
+        .current_dir(&dir)
+        .args(["--rules", "bad.toml", "src"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "Invalid rules file bad.toml: line 1: severity \"fatal\" is not error, warning, or note",
+        ));
+}
+
+#[test]
+fn line_filters_narrow_what_the_pattern_selects() {
+    let dir = scratch_dir("line_filters");
+    let log = dir.join("app.log");
+    fs::write(
+        &log,
+        "ERROR a retry\nERROR b timeout\nERROR c disk\nINFO d timeout\n",
+    )
+    .unwrap();
+
+    binary()
+        .args(["ERROR", "--none-of", "retry"])
+        .arg(&log)
+        .assert()
+        .success()
+        .stdout(predicate::eq("ERROR b timeout\nERROR c disk\n"));
+    piped_binary()
+        .args(["-i", "error", "--any-of", "TIMEOUT", "--any-of", "disk"])
+        .args(["--all-of", "b", "--none-of", "retry"])
+        .write_stdin(fs::read_to_string(&log).unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::eq("ERROR b timeout\n"));
+    binary()
+        .args(["ERROR", "--all-of", "missing"])
+        .arg(&log)
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn files_lists_what_would_be_searched_without_a_pattern() {
+    let dir = scratch_dir("files_mode");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join(".gitignore"), "ignored.rs\n").unwrap();
+    for name in ["a.rs", "b.py", "ignored.rs", "sub/c.rs"] {
+        fs::write(dir.join(name), "anything\n").unwrap();
+    }
+
+    binary()
+        .args(["--files", "-r", "-t", "rust", "--sort", "path"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n{}\n",
+            dir.join("a.rs").display(),
+            dir.join("sub/c.rs").display()
+        )));
+    binary()
+        .args(["--files", "-r", "-t", "go"])
+        .arg(&dir)
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn explain_says_why_files_are_or_are_not_searched() {
+    let dir = scratch_dir("explain");
+    fs::create_dir_all(dir.join("build")).unwrap();
+    fs::write(dir.join(".gitignore"), "# outputs\nbuild/\n*.log\n").unwrap();
+    for name in ["a.rs", "b.c", "x.log", "build/out.rs"] {
+        fs::write(dir.join(name), "anything\n").unwrap();
+    }
+    fs::write(dir.join("data.rs"), "a\0b\n").unwrap();
+
+    binary()
+        .args(["-r", "--exclude", "*.c"])
+        .args(["--explain", "a.rs", "--explain", "b.c", "--explain", "x.log"])
+        .args(["--explain", "build/out.rs", "--explain", "data.rs"])
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a.rs: searched\n"))
+        .stdout(predicate::str::contains(
+            "b.c: not searched: matches the --exclude glob *.c\n",
+        ))
+        .stdout(predicate::str::contains(
+            "x.log: not searched: ignored by ./.gitignore line 3 (*.log)\n",
+        ))
+        .stdout(predicate::str::contains(
+            "build/out.rs: not searched: inside ./build, which is ignored by ./.gitignore line 2 (build/)\n",
+        ))
+        .stdout(predicate::str::contains("data.rs: searched as binary"));
+    binary()
+        .args(["--explain", "a.rs"])
+        .current_dir(&dir)
+        .assert()
+        .code(1)
+        .stdout("a.rs: not searched: inside ., which is searched only with -r\n");
+}
+
+#[test]
+fn type_list_shows_builtin_and_added_types() {
+    binary()
+        .args(["--type-list", "--type-add", "notes:*.notes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes: *.notes\n"))
+        .stdout(predicate::str::contains("rust: *.rs\n"));
+}
+
+#[test]
+fn recursive_walk_skips_hidden_and_ignored_files_by_default() {
+    let dir = scratch_dir("ignore_rules");
+    fs::create_dir_all(dir.join("build")).unwrap();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join(".gitignore"), "*.log\nbuild/\n!keep.log\n").unwrap();
+    for name in [
+        "a.txt",
+        "x.log",
+        "keep.log",
+        "build/b.txt",
+        ".hidden.txt",
+        "src/c.txt",
+    ] {
+        fs::write(dir.join(name), "needle\n").unwrap();
+    }
+    let listing = |names: &[&str]| {
+        let mut paths: Vec<String> = names
+            .iter()
+            .map(|name| format!("{}\n", dir.join(name).display()))
+            .collect();
+        paths.sort();
+        paths.concat()
+    };
+
+    binary()
+        .args(["-r", "-l", "--sort", "path", "needle"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(listing(&["a.txt", "keep.log", "src/c.txt"])));
+    binary()
+        .args([
+            "-r",
+            "-l",
+            "--sort",
+            "path",
+            "--hidden",
+            "--no-ignore",
+            "needle",
+        ])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(listing(&[
+            ".hidden.txt",
+            "a.txt",
+            "build/b.txt",
+            "keep.log",
+            "src/c.txt",
+            "x.log",
+        ])));
+}
+
+#[test]
+fn nested_ignore_files_override_their_parents_and_the_ignore_file() {
+    let dir = scratch_dir("nested_ignores");
+    fs::create_dir_all(dir.join("sub/deep")).unwrap();
+    fs::write(dir.join(".gitignore"), "*.gen\n*.tmp\n").unwrap();
+    // In one directory .rgignore beats .ignore, which beats .gitignore.
+    fs::write(dir.join(".ignore"), "!*.gen\nnotes.txt\n").unwrap();
+    fs::write(dir.join(".rgignore"), "!notes.txt\n").unwrap();
+    fs::write(dir.join("sub/.gitignore"), "!*.tmp\n").unwrap();
+    fs::write(dir.join("sub/deep/.ignore"), "*.tmp\n").unwrap();
+    fs::write(dir.join("extra-ignores"), "*.md\n*.rs\n").unwrap();
+    fs::write(dir.join("sub/.ignore"), "!*.rs\n").unwrap();
+    for name in [
+        "a.gen",
+        "a.tmp",
+        "notes.txt",
+        "top.md",
+        "top.rs",
+        "sub/b.tmp",
+        "sub/b.rs",
+        "sub/deep/c.tmp",
+    ] {
+        fs::write(dir.join(name), "needle\n").unwrap();
+    }
+    let listing = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| format!("{}\n", dir.join(name).display()))
+            .collect::<String>()
+    };
+
+    let ignore_file = dir.join("extra-ignores");
+    binary()
+        .args(["-r", "-l", "--sort", "path", "--ignore-file"])
+        .arg(&ignore_file)
+        .arg("needle")
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(listing(&[
+            "a.gen",
+            "notes.txt",
+            "sub/b.rs",
+            "sub/b.tmp",
+        ])));
+    // --no-ignore leaves out the files found in the walk, but not one asked for by name.
+    binary()
+        .args(["-r", "-l", "--sort", "path", "--no-ignore", "--ignore-file"])
+        .arg(&ignore_file)
+        .arg("needle")
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(listing(&[
+            "a.gen",
+            "a.tmp",
+            "notes.txt",
+            "sub/b.tmp",
+            "sub/deep/c.tmp",
+        ])));
+    binary()
+        .args(["-r", "--ignore-file", "missing-ignores", "needle"])
+        .arg(&dir)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("missing-ignores"));
+}
+
+#[test]
+fn git_searches_only_tracked_and_staged_files() {
+    let dir = scratch_dir("git-files");
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .is_ok_and(|output| output.status.success())
+    };
+    if !git(&["init", "-q"]) {
+        // Nothing to test against without git.
+        return;
+    }
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join("target")).unwrap();
+    fs::write(dir.join("src/lib.rs"), "needle\n").unwrap();
+    fs::write(dir.join("target/out.txt"), "needle\n").unwrap();
+    fs::write(dir.join("gone.txt"), "needle\n").unwrap();
+    assert!(git(&["add", "src/lib.rs", "gone.txt"]));
+    fs::remove_file(dir.join("gone.txt")).unwrap();
+
+    // The untracked build output is left out, and so is the tracked file deleted since.
+    binary()
+        .args(["--git", "needle"])
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq("./src/lib.rs: needle\n"))
+        .stderr(predicate::eq(""));
+}
+
+#[test]
+fn binary_files_report_a_match_instead_of_their_lines() {
+    let dir = scratch_dir("binary");
+    let file = dir.join("blob.bin");
+    fs::write(&file, b"\x00\x01needle\xff\nmore\n").unwrap();
+
+    binary()
+        .arg("needle")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}: binary file matches\n",
+            file.display()
+        )));
+    binary()
+        .args(["-a", "-n", "needle"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: \0\u{1}needle\u{fffd}\n"));
+    binary()
+        .args(["-l", "needle"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!("{}\n", file.display())));
+}
+
+#[test]
+fn include_and_exclude_globs_filter_walked_files() {
+    let dir = scratch_dir("globs");
+    fs::create_dir_all(dir.join("web")).unwrap();
+    for name in ["main.rs", "notes.txt", "web/app.js", "web/app.min.js"] {
+        fs::write(dir.join(name), "needle\n").unwrap();
+    }
+
+    binary()
+        .args(["-r", "-l", "--include", "*.rs", "needle"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n",
+            dir.join("main.rs").display()
+        )));
+    binary()
+        .args(["-r", "-l", "--sort", "path"])
+        .args(["--include", "*.js", "--exclude", "*.min.js", "needle"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n",
+            dir.join("web/app.js").display()
+        )));
+}
+
+#[test]
+fn json_emits_one_object_per_match() {
+    binary()
+        .args(["--json", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "{\"type\":\"match\",\"path\":\"tests/grep.md\",\"line_number\":1,\"byte_offset\":0,\
+             \"line\":\"## Search Utility\",\"spans\":[{\"start\":10,\"end\":17,\"text\":\"Utility\"}]}\n",
+        ));
+}
+
+#[test]
+fn json_escapes_strings_and_marks_context_lines() {
+    let dir = scratch_dir("json");
+    let file = dir.join("quotes.txt");
+    fs::write(&file, "say \"hi\"\\\tnow\nafter\n").unwrap();
+
+    binary()
+        .args(["--json", "-A", "1", "hi"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"line\":\"say \\\"hi\\\"\\\\\\tnow\"",
+        ))
+        .stdout(predicate::str::contains("{\"type\":\"context\","));
+}
+
+#[test]
+fn word_regexp_only_matches_whole_words() {
+    let dir = scratch_dir("word_regexp");
+    let file = dir.join("words.txt");
+    fs::write(&file, "concatenate\nthe cat sat\n-x flag\nfoo-xy\n").unwrap();
+
+    binary()
+        .args(["-w", "cat"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("the cat sat\n"));
+    binary()
+        .args(["-w", "--", "-x"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("-x flag\n"));
+    binary()
+        .args(["-w", "-P", "(?<=the )cat"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("the cat sat\n"));
+}
+
+#[test]
+fn line_regexp_matches_whole_lines_and_highlights_them() {
+    binary()
+        .args(["-x", "`grep` command.", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("`grep` command.\n"));
+    binary()
+        .args(["-x", "-E", "`grep`.*", "-c", "tests/grep.md"])
+        .env("CLICOLOR_FORCE", "1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("\u{1b}[31m`grep` command.\u{1b}[0m\n"));
+    binary()
+        .args(["-x", "`grep`", "tests/grep.md"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn line_regexp_rejects_starts_with_and_ends_with() {
+    binary()
+        .args(["-x", "--ends-with", "Utility", "tests/grep.md"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("-x already anchors both ends"));
+}
+
+#[test]
+fn repeated_patterns_match_any_and_highlight_each() {
+    binary()
+        .args(["-e", "Utility", "-e", "command.", "-c", "tests/grep.md"])
+        .env("CLICOLOR_FORCE", "1")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "## Search \u{1b}[31mUtility\u{1b}[0m\n`grep` \u{1b}[31mcommand.\u{1b}[0m\n",
+        ));
+}
+
+#[test]
+fn large_fixed_string_sets_match_like_an_alternation() {
+    // Enough strings for the dedicated literal matcher rather than a regex.
+    let dir = scratch_dir("literal_set");
+    let mut list: Vec<String> = (0..5000).map(|index| format!("id{}x", index)).collect();
+    list.push("a.b".to_string());
+    list.push("a.bc".to_string());
+    let patterns = dir.join("patterns.txt");
+    fs::write(&patterns, list.join("\n")).unwrap();
+    let input = dir.join("input.txt");
+    fs::write(&input, "see id42x and id4999x\nsee a.bc\naxb id5000x\n").unwrap();
+
+    binary()
+        .args(["-o", "-n", "--file"])
+        .arg(&patterns)
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: id42x\n1: id4999x\n2: a.b\n"));
+    binary()
+        .args(["--replace", "<$0>", "--file"])
+        .arg(&patterns)
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("see <id42x> and <id4999x>\nsee <a.b>c\n"));
+    binary()
+        .args(["-i", "--count", "--file"])
+        .arg(&patterns)
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("2\n"));
+}
+
+#[test]
+fn pattern_file_supplies_one_pattern_per_line() {
+    let dir = scratch_dir("pattern_file");
+    let patterns = dir.join("patterns.txt");
+    fs::write(&patterns, "Utility\r\n`grep` command\n").unwrap();
+    let empty = dir.join("empty.txt");
+    fs::write(&empty, "").unwrap();
+
+    binary()
+        .arg("--file")
+        .arg(&patterns)
+        .arg("tests/grep.md")
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n`grep` command.\n"));
+    binary()
+        .arg("--file")
+        .arg(&empty)
+        .arg("tests/grep.md")
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+    binary()
+        .args(["--file", "missing-patterns.txt", "tests/grep.md"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Cannot read pattern file missing-patterns.txt",
+        ));
+}
+
+#[test]
+fn pattern_from_reads_one_exact_pattern() {
+    let dir = scratch_dir("pattern_from");
+    let pattern = dir.join("pattern.txt");
+    fs::write(&pattern, " two \n").unwrap();
+    let input = dir.join("input.txt");
+    fs::write(&input, "one two three\nnetwork two\ntwo \n").unwrap();
+
+    binary()
+        .arg("--pattern-from")
+        .arg(&pattern)
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("one two three\n"));
+    piped_binary()
+        .args(["--pattern-from", "-", "-E"])
+        .arg(&input)
+        .write_stdin("^two $")
+        .assert()
+        .success()
+        .stdout(predicate::eq("two \n"));
+    piped_binary()
+        .args(["--pattern-from", "-"])
+        .write_stdin("two")
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("cannot be searched too"));
+}
+
+#[test]
+fn max_total_stops_the_run_at_the_same_line_whatever_the_threads() {
+    let dir = scratch_dir("max_total");
+    for index in 0..40 {
+        fs::write(
+            dir.join(format!("f{:02}.txt", index)),
+            "hit a\nmiss\nhit b\nhit c\n",
+        )
+        .unwrap();
+    }
+    let expected = format!(
+        "{0}: hit a\n{0}: hit b\n{0}: hit c\n{1}: hit a\n{1}: hit b\n",
+        dir.join("f00.txt").display(),
+        dir.join("f01.txt").display()
+    );
+    for threads in ["1", "8"] {
+        binary()
+            .args(["-r", "--max-total", "5", "--threads", threads, "hit"])
+            .arg(&dir)
+            .assert()
+            .success()
+            .stdout(predicate::eq(expected.clone()));
+    }
+    binary()
+        .args(["-r", "--max-total", "4", "--count", "hit"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}:3\n{}:1\n",
+            dir.join("f00.txt").display(),
+            dir.join("f01.txt").display()
+        )));
+}
+
+#[test]
+fn max_count_stops_each_file_after_n_selected_lines() {
+    let dir = scratch_dir("max_count");
+    let file = dir.join("log.txt");
+    fs::write(&file, "hit 1\nhit 2\nquiet\nhit 3\n").unwrap();
+
+    binary()
+        .args(["-m", "2", "-n", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: hit 1\n2: hit 2\n"));
+    binary()
+        .args(["-m1", "-A", "1", "--count", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\n"));
+    binary()
+        .args(["--max-count", "2", "-A", "1", "hit"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("hit 1\nhit 2\nquiet\n"));
+}
+
+#[test]
+fn exit_status_reports_match_no_match_and_error() {
+    binary().args(["Utility", "tests/grep.md"]).assert().code(0);
+    binary()
+        .args(["NonexistentPattern", "tests/grep.md"])
+        .assert()
+        .code(1);
+    binary()
+        .args(["Utility", "tests/missing.md"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn quiet_prints_nothing_and_a_match_beats_errors() {
+    binary()
+        .args(["-q", "Utility", "tests/grep.md"])
+        .assert()
+        .code(0)
+        .stdout(predicate::eq(""));
+    binary()
+        .args(["-q", "NonexistentPattern", "tests/grep.md"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+    binary()
+        .args(["--quiet", "Utility", "tests/missing.md", "tests/grep.md"])
+        .assert()
+        .code(0)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn replace_substitutes_matches_with_capture_references() {
+    binary()
+        .args(["--replace", "Tool", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Tool\n"));
+    binary()
+        .args([
+            "-E",
+            "--replace",
+            "[$2 $1]",
+            r"(\w+) (Utility)",
+            "tests/grep.md",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## [Utility Search]\n"));
+    binary()
+        .args([
+            "-P",
+            "--replace",
+            "<$1>",
+            r"(?<=Search )(Util)ity",
+            "tests/grep.md",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search <Util>\n"));
+}
+
+#[test]
+fn write_rewrites_files_and_dry_run_only_previews() {
+    let dir = scratch_dir("write");
+    let file = dir.join("config.ini");
+    fs::write(&file, "name = old\r\nother = 1\nalias = old\n").unwrap();
+
+    binary()
+        .args(["--write", "--dry-run", "--replace", "new", "old"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "--- a/{0}\n+++ b/{0}\n@@ -1,3 +1,3 @@\n-name = old\n+name = new\n other = 1\n-alias = old\n+alias = new\n",
+            file.display()
+        )));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "name = old\r\nother = 1\nalias = old\n"
+    );
+
+    binary()
+        .args(["--write", "--replace", "new", "old"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq(""));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "name = new\r\nother = 1\nalias = new\n"
+    );
+}
+
+#[test]
+fn write_stops_at_max_count_as_the_dry_run_shows() {
+    let dir = scratch_dir("write-max-count");
+    let file = dir.join("config.ini");
+    fs::write(&file, "name = old\nother = 1\nalias = old\n").unwrap();
+
+    binary()
+        .args(["--write", "--dry-run", "-m1", "--replace", "new", "old"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+name = new\n"))
+        .stdout(predicate::str::contains("alias = new").not());
+    binary()
+        .args(["--write", "-m1", "--replace", "new", "old"])
+        .arg(&file)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "name = new\nother = 1\nalias = old\n"
+    );
+}
+
+#[test]
+fn write_through_a_symlink_rewrites_its_target() {
+    let dir = scratch_dir("write-symlink");
+    let file = dir.join("real.ini");
+    let link = dir.join("link.ini");
+    fs::write(&file, "name = old\n").unwrap();
+    std::os::unix::fs::symlink(&file, &link).unwrap();
+
+    binary()
+        .args(["--write", "--replace", "new", "old"])
+        .arg(&link)
+        .assert()
+        .success();
+    assert!(
+        fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "name = new\n");
+}
+
+#[test]
+fn diff_previews_replacements_as_hunks_with_context() {
+    let dir = scratch_dir("diff");
+    let file = dir.join("notes.txt");
+    let mut contents: String = (1..=12).map(|n| format!("line {}\n", n)).collect();
+    contents = contents.replace("line 2\n", "line 2 old\n");
+    contents = contents.replace("line 11\n", "line 11 old\n");
+    fs::write(&file, &contents).unwrap();
+
+    // Changes too far apart for their context to touch get separate hunks.
+    binary()
+        .args(["--diff", "--replace", "new", "old"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "--- a/{0}\n+++ b/{0}\n\
+             @@ -1,5 +1,5 @@\n line 1\n-line 2 old\n+line 2 new\n line 3\n line 4\n line 5\n\
+             @@ -8,5 +8,5 @@\n line 8\n line 9\n line 10\n-line 11 old\n+line 11 new\n line 12\n",
+            file.display()
+        )));
+    assert_eq!(fs::read_to_string(&file).unwrap(), contents);
+
+    // With --write the diff is printed and the file is changed too.
+    binary()
+        .args(["--write", "--diff", "--replace", "new", "old"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+line 11 new\n"));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        contents.replace("old", "new")
+    );
+
+    binary()
+        .args(["--diff", "old"])
+        .arg(&file)
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("--diff needs --replace"));
+}
+
+#[test]
+fn interactive_makes_only_the_changes_accepted() {
+    let dir = scratch_dir("interactive");
+    fs::write(dir.join("a.txt"), "old 1\nkeep\nold 2\nold 3\n").unwrap();
+    fs::write(dir.join("b.txt"), "old 4\nold 5\n").unwrap();
+
+    // An answer that is not one of y, n, a, or q is asked again; `a` covers later files too.
+    piped_binary()
+        .args(["--write", "--interactive", "--replace", "new", "old"])
+        .args(["a.txt", "b.txt"])
+        .current_dir(&dir)
+        .write_stdin("y\nmaybe\nn\na\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(""))
+        .stderr(predicate::str::starts_with(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,4 +1,4 @@\n-old 1\n+new 1\n keep\n old 2\n old 3\n\
+             Apply this change [y,n,a,q,?]? ",
+        ))
+        .stderr(predicate::str::contains("y - make this change\n"))
+        .stderr(predicate::str::contains("b.txt").not());
+    assert_eq!(
+        fs::read_to_string(dir.join("a.txt")).unwrap(),
+        "new 1\nkeep\nold 2\nnew 3\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("b.txt")).unwrap(),
+        "new 4\nnew 5\n"
+    );
+
+    // The end of the answers stops, leaving every change unmade.
+    piped_binary()
+        .args(["--write", "--interactive", "--replace", "new", "old"])
+        .arg("a.txt")
+        .current_dir(&dir)
+        .write_stdin("")
+        .assert()
+        .code(1);
+    assert_eq!(
+        fs::read_to_string(dir.join("a.txt")).unwrap(),
+        "new 1\nkeep\nold 2\nnew 3\n"
+    );
+}
+
+#[test]
+fn write_requires_replace() {
+    binary()
+        .args(["--write", "Utility", "tests/grep.md"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--write needs --replace"));
+}
+
+#[test]
+fn short_multiline_flag_spans_lines_with_regex_whitespace() {
+    let dir = scratch_dir("short_multiline");
+    let file = dir.join("main.rs");
+    fs::write(&file, "// entry\nfn main()\n{\n}\n").unwrap();
+
+    binary()
+        .args(["-U", "-E", "-n", r"fn main\(\)\s*\{"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("2: fn main()\n{\n"));
+}
+
+#[test]
+fn only_matching_prints_each_match_with_its_own_prefix() {
+    let dir = scratch_dir("only_matching");
+    let file = dir.join("ids.txt");
+    fs::write(&file, "none here\nid=7 and id=42\n").unwrap();
+    binary()
+        .args(["-E", "-o", "-n", "-b", r"id=\d+"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("2:10: id=7\n2:19: id=42\n"));
+    binary()
+        .args(["-E", "-o", "--replace", "<$1>", r"id=(\d+)"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("<7>\n<42>\n"));
+}
+
+#[test]
+fn o_context_widens_matches_on_character_boundaries() {
+    let dir = scratch_dir("o_context");
+    let file = dir.join("text.txt");
+    fs::write(&file, "ééxyzéé\n").unwrap();
+    binary()
+        .args(["-o", "--o-context", "1", "xyz"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("éxyzé\n"));
+    binary()
+        .args(["-o", "--o-context", "5", "-A", "1", "xyz"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("ééxyzéé\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_descends_into_symlinked_directories_without_looping() {
+    use std::os::unix::fs::symlink;
+    let dir = scratch_dir("follow");
+    let root = dir.join("root");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::create_dir_all(dir.join("elsewhere")).unwrap();
+    fs::write(root.join("sub/inner.txt"), "needle\n").unwrap();
+    fs::write(dir.join("elsewhere/outer.txt"), "needle\n").unwrap();
+    symlink(dir.join("elsewhere"), root.join("linked")).unwrap();
+    symlink("..", root.join("sub/back")).unwrap();
+
+    binary()
+        .args(["-r", "-f", "needle"])
+        .arg(&root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("inner.txt"))
+        .stdout(predicate::str::contains("outer.txt").not());
+    binary()
+        .args(["-r", "-f", "--follow", "--sort", "path", "needle"])
+        .arg(&root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("outer.txt"))
+        .stdout(predicate::function(|out: &str| {
+            out.matches("inner.txt").count() == 1
+        }))
+        .stderr(predicate::str::contains("symlink loop"));
+    binary()
+        .args(["-r", "-f", "--follow", "--no-follow", "needle"])
+        .arg(&root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("outer.txt").not());
+}
+
+#[test]
+fn max_filesize_skips_large_walked_files() {
+    let dir = scratch_dir("max_filesize");
+    fs::write(dir.join("small.txt"), "needle\n").unwrap();
+    fs::write(
+        dir.join("dump.sql"),
+        format!("{}needle\n", "x".repeat(2048)),
+    )
+    .unwrap();
+    binary()
+        .args(["-rl", "--max-filesize", "1K", "needle"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}\n",
+            dir.join("small.txt").display()
+        )))
+        .stderr(predicate::eq(""));
+    binary()
+        .args(["-rl", "--max-filesize", "1k", "--verbose", "needle"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stderr(predicate::eq(format!(
+            "Skipped {}: 2055 bytes is over --max-filesize\n",
+            dir.join("dump.sql").display()
+        )));
+    // Files named on the command line are searched whatever their size.
+    binary()
+        .args(["-l", "--max-filesize", "1K", "needle"])
+        .arg(dir.join("dump.sql"))
+        .assert()
+        .success();
+    binary()
+        .args(["--max-filesize", "ten", "needle"])
+        .arg(&dir)
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "Invalid value for --max-filesize: ten",
+        ));
+}
+
+#[test]
+fn newer_than_and_older_than_select_walked_files_by_age() {
+    let dir = scratch_dir("file_age");
+    fs::write(dir.join("today.log"), "needle\n").unwrap();
+    fs::write(dir.join("last_week.log"), "needle\n").unwrap();
+    let week_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(7 * 24 * 60 * 60);
+    fs::File::options()
+        .write(true)
+        .open(dir.join("last_week.log"))
+        .unwrap()
+        .set_modified(week_ago)
+        .unwrap();
+    let listing = |args: &[&str]| {
+        let output = binary()
+            .args(["-rl", "needle"])
+            .args(args)
+            .arg(&dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let path = |name: &str| format!("{}\n", dir.join(name).display());
+    assert_eq!(listing(&["--newer-than", "2d"]), path("today.log"));
+    assert_eq!(listing(&["--older-than", "1d12h"]), path("last_week.log"));
+    assert_eq!(listing(&["--newer-than", "1h", "--older-than", "1m"]), "");
+    binary()
+        .args(["-r", "--newer-than", "soon", "needle"])
+        .arg(&dir)
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "Invalid value for --newer-than: soon",
+        ));
+}
+
+#[test]
+fn max_depth_limits_recursive_walk() {
+    let dir = scratch_dir("max_depth");
+    fs::create_dir_all(dir.join("a/b")).unwrap();
+    fs::write(dir.join("top.txt"), "needle\n").unwrap();
+    fs::write(dir.join("a/mid.txt"), "needle\n").unwrap();
+    fs::write(dir.join("a/b/deep.txt"), "needle\n").unwrap();
+    binary()
+        .args(["-r", "-f", "--max-depth", "2", "needle"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("top.txt"))
+        .stdout(predicate::str::contains("mid.txt"))
+        .stdout(predicate::str::contains("deep.txt").not());
+    binary()
+        .args(["-r", "--max-depth", "many", "needle"])
+        .arg(&dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid value for --max-depth: many",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn walk_errors_are_reported_and_the_search_continues() {
+    let dir = scratch_dir("walk_errors");
+    fs::write(dir.join("good.txt"), "needle\n").unwrap();
+    std::os::unix::fs::symlink(dir.join("gone"), dir.join("broken")).unwrap();
+    binary()
+        .args(["-r", "--follow", "--no-filename", "needle"])
+        .arg(&dir)
+        .assert()
+        .code(2)
+        .stdout(predicate::eq("needle\n"))
+        .stderr(predicate::str::contains("broken"));
+    binary()
+        .args(["-r", "--follow", "-s", "--no-filename", "needle"])
+        .arg(&dir)
+        .assert()
+        .code(2)
+        .stdout(predicate::eq("needle\n"))
+        .stderr(predicate::eq(""));
+}
+
+#[test]
+fn transcodes_utf16_and_latin1_inputs() {
+    let dir = scratch_dir("encoding");
+    let utf16 = |text: &str, big_endian: bool| -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    };
+    let mut le = vec![0xFF, 0xFE];
+    le.extend(utf16("first\ncafé 😀 here\n", false));
+    fs::write(dir.join("le.txt"), le).unwrap();
+    fs::write(dir.join("be.txt"), utf16("café be\n", true)).unwrap();
+    fs::write(dir.join("latin1.txt"), b"caf\xe9 latin\n").unwrap();
+
+    binary()
+        .args(["-n", "café"])
+        .arg(dir.join("le.txt"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("2: café 😀 here\n"));
+    binary()
+        .args(["--encoding", "utf-16be", "café"])
+        .arg(dir.join("be.txt"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("café be\n"));
+    binary()
+        .args(["--encoding", "latin1", "café"])
+        .arg(dir.join("latin1.txt"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("café latin\n"));
+    binary()
+        .args(["--encoding", "ebcdic", "café"])
+        .arg(dir.join("latin1.txt"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid value for --encoding: ebcdic",
+        ));
+}
+
+#[test]
+fn normalize_matches_composed_and_decomposed_forms() {
+    let input = "cafe\u{301} noir\ncaf\u{e9} au lait\n\u{1100}\u{1161}\u{11a8}\n";
+    piped_binary()
+        .args(["--count", "café"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\n"));
+    piped_binary()
+        .args(["--normalize", "-n", "cafe\u{301}"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: caf\u{e9} noir\n2: caf\u{e9} au lait\n"));
+    // Hangul jamo compose arithmetically into their syllable.
+    piped_binary()
+        .args(["--normalize", "-i", "\u{ac01}"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("\u{ac01}\n"));
+}
+
+#[test]
+fn search_zip_reads_compressed_files() {
+    let dir = scratch_dir("search_zip");
+    fs::write(dir.join("app.log"), "start\nERROR disk full\n").unwrap();
+    fs::copy(dir.join("app.log"), dir.join("other.log")).unwrap();
+    Command::new("gzip")
+        .arg(dir.join("app.log"))
+        .status()
+        .unwrap();
+    Command::new("xz")
+        .arg(dir.join("other.log"))
+        .status()
+        .unwrap();
+    // Detected by magic bytes alone once the extension is gone.
+    fs::rename(dir.join("other.log.xz"), dir.join("other.bin")).unwrap();
+    fs::write(dir.join("broken.gz"), "not really gzip\n").unwrap();
+
+    binary()
+        .args(["-z", "-n", "--no-filename", "ERROR"])
+        .arg(dir.join("app.log.gz"))
+        .arg(dir.join("other.bin"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("2: ERROR disk full\n2: ERROR disk full\n"));
+    binary()
+        .args(["-z", "ERROR"])
+        .arg(dir.join("broken.gz"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("gzip failed"));
+    binary()
+        .args(["ERROR"])
+        .arg(dir.join("app.log.gz"))
+        .assert()
+        .code(1);
+    // A file named like an option is still handed to the decompressor as a file.
+    fs::copy(dir.join("app.log.gz"), dir.join("-l.gz")).unwrap();
+    binary()
+        .args(["-z", "ERROR", "--", "-l.gz"])
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq("ERROR disk full\n"));
+}
+
+#[test]
+fn search_zip_reads_a_decompressor_that_says_a_lot_on_stderr() {
+    let dir = scratch_dir("search_zip_stderr");
+    // More than a pipe holds goes to stderr before the output, and then the run fails.
+    let fake = dir.join("gzip");
+    fs::write(
+        &fake,
+        "#!/bin/sh\nhead -c 200000 /dev/zero | tr '\\0' x >&2\necho ERROR found\nexit 1\n",
+    )
+    .unwrap();
+    Command::new("chmod").arg("+x").arg(&fake).status().unwrap();
+    fs::write(dir.join("app.log.gz"), "").unwrap();
+    let path = format!("{}:{}", dir.display(), std::env::var("PATH").unwrap());
+
+    piped_binary()
+        .args(["-z", "ERROR", "app.log.gz"])
+        .current_dir(&dir)
+        .env("PATH", path)
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .code(2)
+        .stdout(predicate::eq("ERROR found\n"))
+        .stderr(predicate::str::contains("gzip failed: xxxx"));
+}
+
+#[test]
+fn tui_needs_a_terminal_and_files() {
+    binary()
+        .args(["--tui", "Utility", "tests/grep.md"])
+        .assert()
+        .code(4)
+        .stdout(predicate::eq(""))
+        .stderr(predicate::str::contains("--tui needs a terminal"));
+    binary()
+        .args(["--tui", "Utility"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("it needs files to search"));
+}
+
+#[test]
+fn archives_search_each_entry_of_tar_and_zip_files() {
+    let dir = scratch_dir("archives");
+    fs::create_dir_all(dir.join("docs")).unwrap();
+    fs::write(dir.join("docs/readme.md"), "intro\nneedle here\n").unwrap();
+    fs::write(dir.join("docs/notes.txt"), "hay\n").unwrap();
+    fs::write(dir.join("main.rs"), "// needle\n").unwrap();
+    let entries = ["docs/readme.md", "docs/notes.txt", "main.rs"];
+    Command::new("tar")
+        .current_dir(&dir)
+        .arg("czf")
+        .arg("bundle.tar.gz")
+        .args(entries)
+        .status()
+        .unwrap();
+    Command::new("zip")
+        .current_dir(&dir)
+        .arg("-q")
+        .arg("bundle.zip")
+        .args(entries)
+        .status()
+        .unwrap();
+
+    for archive in ["bundle.tar.gz", "bundle.zip"] {
+        binary()
+            .args(["--archives", "-n", "needle", archive])
+            .current_dir(&dir)
+            .assert()
+            .success()
+            .stdout(predicate::eq(format!(
+                "{0}!docs/readme.md:2: needle here\n{0}!main.rs:1: // needle\n",
+                archive
+            )));
+    }
+    // An archive named like an option is still read as an archive.
+    fs::copy(dir.join("bundle.zip"), dir.join("-bundle.zip")).unwrap();
+    binary()
+        .args(["--archives", "-l", "needle", "--", "-bundle.zip"])
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "-bundle.zip!docs/readme.md\n-bundle.zip!main.rs\n",
+        ));
+    // Without --archives the archive is just a (binary) file.
+    binary()
+        .args(["-l", "needle", "bundle.tar.gz"])
+        .current_dir(&dir)
+        .assert()
+        .code(1);
+
+    fs::write(dir.join("broken.tar"), "not really tar\n").unwrap();
+    binary()
+        .args(["--archives", "needle", "broken.tar"])
+        .current_dir(&dir)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("truncated tar archive"));
+}
+
+#[test]
+fn null_ends_filename_prefixes_and_counts() {
+    binary()
+        .args(["-f", "-n", "--null", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md\u{0}1: ## Search Utility\n"));
+    binary()
+        .args(["--count", "-f", "-Z", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md\u{0}1\n"));
+}
+
+#[test]
+fn null_data_splits_records_on_nul() {
+    let dir = scratch_dir("null_data");
+    let file = dir.join("records");
+    fs::write(&file, "first\nrecord\0second\0third match\0").unwrap();
+    binary()
+        .args(["--null-data", "-n", "-E", "^(first|third)"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: first\nrecord\u{0}3: third match\u{0}"));
+    binary()
+        .args(["--null-data", "--count", "record"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\n"));
+}
+
+#[test]
+fn filenames_default_to_shown_for_several_inputs() {
+    binary()
+        .args(["Utility", "tests/grep.md", "tests/recursive/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "tests/grep.md: ## Search Utility\ntests/recursive/grep.md: ## Search Utility\n",
+        ));
+    binary()
+        .args([
+            "--no-filename",
+            "Utility",
+            "tests/grep.md",
+            "tests/recursive/grep.md",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n## Search Utility\n"));
+    binary()
+        .args(["-H", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("tests/grep.md: ## Search Utility\n"));
+    binary()
+        .args(["--count", "--no-filename", "-r", "Utility", "tests"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\n1\n"));
+}
+
+#[test]
+fn mmap_search_matches_the_line_loop() {
+    let dir = scratch_dir("mmap");
+    let file = dir.join("log.txt");
+    fs::write(&file, "alpha\r\n\nbeta alpha\nALPHA\n").unwrap();
+    for args in [
+        &["-n", "-b", "alpha"][..],
+        &["-n", "-E", "^$"],
+        &["-i", "-m", "2", "-E", "^alpha$"],
+    ] {
+        let read = binary()
+            .arg("--no-mmap")
+            .args(args)
+            .arg(&file)
+            .output()
+            .unwrap();
+        binary()
+            .arg("--mmap")
+            .args(args)
+            .arg(&file)
+            .assert()
+            .success()
+            .stdout(predicate::eq(read.stdout));
+    }
+    binary()
+        .args(["--mmap", "-n", "-E", "^$"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq("2: \n"));
+}
+
+#[test]
+fn trace_reports_how_each_file_is_read() {
+    let dir = scratch_dir("strategy");
+    fs::write(dir.join("small.txt"), "needle\n").unwrap();
+    fs::write(
+        dir.join("large.txt"),
+        "hay\n".repeat(512 * 1024) + "needle\n",
+    )
+    .unwrap();
+    binary()
+        .current_dir(&dir)
+        .args(["--trace", "needle", "small.txt", "large.txt"])
+        .assert()
+        .success()
+        .stderr(predicate::eq(
+            "Trace: small.txt: stream (7 bytes, under 1 MiB for fixed strings scanned across the file)\n\
+             Trace: large.txt: mmap (2 MiB, at least 1 MiB for fixed strings scanned across the file)\n",
+        ));
+    binary()
+        .current_dir(&dir)
+        .args(["--trace", "-E", "-v", "hay", "large.txt"])
+        .assert()
+        .success()
+        .stderr(predicate::eq(
+            "Trace: large.txt: stream (2 MiB, searched line by line and so under 64 MiB)\n",
+        ));
+    binary()
+        .current_dir(&dir)
+        .args(["--trace", "--engine", "mmap", "needle", "small.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("needle\n"))
+        .stderr(predicate::eq("Trace: small.txt: mmap (--engine mmap)\n"));
+    binary()
+        .args(["--engine", "fast", "needle"])
+        .arg(dir.join("small.txt"))
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("Invalid value for --engine: fast"));
+}
+
+#[test]
+fn cache_replays_unchanged_files_and_searches_changed_ones() {
+    let dir = scratch_dir("cache");
+    let cached = dir.join("cached");
+    fs::write(dir.join("a.txt"), "needle\n").unwrap();
+    fs::write(dir.join("b.txt"), "hay\n").unwrap();
+    let search = || {
+        let mut command = binary();
+        command
+            .current_dir(&dir)
+            .env("XDG_CACHE_HOME", &cached)
+            .args(["--cache", "--trace", "-n", "needle", "a.txt", "b.txt"]);
+        command
+    };
+    search()
+        .assert()
+        .success()
+        .stdout(predicate::eq("a.txt:1: needle\n"))
+        .stderr(predicate::str::contains("cached").not());
+    search()
+        .assert()
+        .success()
+        .stdout(predicate::eq("a.txt:1: needle\n"))
+        .stderr(predicate::eq(
+            "Trace: a.txt: cached (unchanged since it was last searched)\n\
+             Trace: b.txt: cached (unchanged since it was last searched)\n",
+        ));
+
+    fs::write(dir.join("b.txt"), "hay\nneedle\n").unwrap();
+    search()
+        .assert()
+        .success()
+        .stdout(predicate::eq("a.txt:1: needle\nb.txt:2: needle\n"))
+        .stderr(predicate::str::contains("Trace: b.txt: stream"));
+
+    // A damaged cache is simply searched past.
+    for entry in fs::read_dir(cached.join("grep")).unwrap() {
+        let path = entry.unwrap().path();
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+    }
+    search()
+        .assert()
+        .success()
+        .stdout(predicate::eq("a.txt:1: needle\nb.txt:2: needle\n"));
+
+    binary()
+        .env("XDG_CACHE_HOME", &cached)
+        .arg("--cache-clear")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("Cleared the cache at "));
+    assert!(!cached.join("grep").exists());
+}
+
+#[test]
+fn cache_is_not_replayed_once_a_pattern_file_changes() {
+    let dir = scratch_dir("cache_pattern_file");
+    let cached = dir.join("cached");
+    fs::write(dir.join("data.txt"), "alpha\nbeta\n").unwrap();
+    let search = || {
+        binary()
+            .current_dir(&dir)
+            .env("XDG_CACHE_HOME", &cached)
+            .args(["--cache", "--file", "pats", "data.txt"])
+            .output()
+            .unwrap()
+            .stdout
+    };
+    fs::write(dir.join("pats"), "alpha\n").unwrap();
+    assert_eq!(search(), b"alpha\n");
+    assert_eq!(search(), b"alpha\n");
+    fs::write(dir.join("pats"), "beta\n").unwrap();
+    assert_eq!(search(), b"beta\n");
+}
+
+#[test]
+fn config_file_supplies_default_flags() {
+    let home = scratch_dir("config_home");
+    fs::write(home.join(".greprc"), "# defaults\n-n\n--max-count\n1\n").unwrap();
+    binary()
+        .env("HOME", &home)
+        .args(["-E", "^(##|In)", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: ## Search Utility\n"));
+    // Later flags on the command line win, and --no-config skips the file entirely.
+    binary()
+        .env("HOME", &home)
+        .args(["-m", "2", "-E", "^(##|In)", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("1: ## Search Utility\n2: In"));
+    binary()
+        .env("HOME", &home)
+        .args(["--no-config", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+}
+
+#[test]
+fn xdg_config_takes_precedence_over_greprc() {
+    let home = scratch_dir("config_xdg");
+    fs::create_dir_all(home.join("xdg/grep")).unwrap();
+    fs::write(home.join("xdg/grep/config"), "-b\n").unwrap();
+    fs::write(home.join(".greprc"), "-n\n").unwrap();
+    binary()
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", home.join("xdg"))
+        .args(["Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("0: ## Search Utility\n"));
+    fs::write(home.join(".greprc"), "--max-count\nmany\n").unwrap();
+    binary()
+        .env("HOME", &home)
+        .args(["Utility", "tests/grep.md"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid value for --max-count: many",
+        ));
+}
+
+#[test]
+fn options_variable_adds_default_flags_before_the_command_line() {
+    binary()
+        .env("SEARCH_UTILITY_OPTS", "-n --exclude 'sub dir'")
+        .args(["-m", "1", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("1: ## Search Utility\n"));
+    // Only the first variable that is set is read.
+    binary()
+        .env("SEARCH_UTILITY_OPTS", "")
+        .env("GREP_OPTIONS", "-n")
+        .args(["-m", "1", "Utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+    binary()
+        .env("GREP_OPTIONS", "-n \"oops")
+        .args(["Utility", "tests/grep.md"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "GREP_OPTIONS: unterminated \" quote",
+        ));
+}
+
+#[test]
+fn column_counts_characters_unless_byte_columns() {
+    piped_binary()
+        .args(["-n", "--column", "-C", "1", "wörld"])
+        .write_stdin("héllo wörld\nno\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("1:7: héllo wörld\n2- no\n"));
+    piped_binary()
+        .args(["-o", "--byte-columns", "wörld"])
+        .write_stdin("héllo wörld wörld\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("8: wörld\n15: wörld\n"));
+}
+
+#[test]
+fn format_fills_in_a_template_for_every_match() {
+    piped_binary()
+        .args([
+            "-E",
+            "--format",
+            "{file}:{line}:{column}: {key} -> {2} {{{0}}}",
+        ])
+        .arg(r"(?<key>\w+)=(\d+)")
+        .write_stdin("skip\nwidth=80 höhe=24\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "(standard input):2:1: width -> 80 {width=80}\n\
+             (standard input):2:10: höhe -> 24 {höhe=24}\n",
+        ));
+    piped_binary()
+        .args(["--format", "{line}: {1}", "-v", "="])
+        .write_stdin("a=1\nplain\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("2: \n"));
+    binary()
+        .args(["--format", "{file", "grep", "tests/grep.md"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("unterminated {"));
+}
+
+#[test]
+fn vimgrep_prints_a_record_per_match_with_its_column() {
+    piped_binary()
+        .args(["--vimgrep", "-E", "s[a-z]+"])
+        .write_stdin("a sun and sky\nnone\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "(standard input):1:3:a sun and sky\n(standard input):1:11:a sun and sky\n",
+        ));
+    binary()
+        .args([
+            "--vimgrep",
+            "-o",
+            "-i",
+            "utility",
+            "-C",
+            "2",
+            "tests/grep.md",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "tests/grep.md:1:11:Utility\ntests/grep.md:2:78:utility\n",
+        ));
+}
+
+#[test]
+fn smart_case_ignores_case_only_for_lowercase_patterns() {
+    binary()
+        .args(["-S", "search utility", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+    binary()
+        .args(["--smart-case", "--count", "Utility that", "tests/grep.md"])
+        .assert()
+        .code(1)
+        .stdout(predicate::eq("0\n"));
+    // Escapes such as \S are syntax, not uppercase letters.
+    binary()
+        .args(["-S", "-E", r"^## search\S*", "tests/grep.md"])
+        .assert()
+        .success()
+        .stdout(predicate::eq("## Search Utility\n"));
+    binary()
+        .args(["-S", "--case-sensitive", "search utility", "tests/grep.md"])
+        .assert()
+        .code(1);
+}
+
+#[cfg(unix)]
+#[test]
+fn named_pipes_are_streamed_and_devices_skipped() {
+    let dir = scratch_dir("special_files");
+    let fifo = dir.join("feed");
+    let made = Command::new("mkfifo").arg(&fifo).status().unwrap();
+    assert!(made.success());
+    let writer = {
+        let fifo = fifo.clone();
+        std::thread::spawn(move || fs::write(fifo, "one\nneedle two\n").unwrap())
+    };
+    // A pipe's size says nothing of its contents, so forcing a map must not search it as empty.
+    binary()
+        .args(["--mmap", "-z", "needle"])
+        .arg(&fifo)
+        .assert()
+        .success()
+        .stdout("needle two\n");
+    writer.join().unwrap();
+
+    binary()
+        .args(["needle", "/dev/null"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "/dev/null: skipping character device (use --devices read to search it)",
+        ));
+    binary()
+        .args(["--devices", "read", "needle", "/dev/null"])
+        .assert()
+        .code(1)
+        .stderr("");
+    binary()
+        .args(["-D", "open", "needle", "/dev/null"])
+        .assert()
+        .code(4);
+}
+
+#[cfg(unix)]
+#[test]
+fn serve_answers_search_requests_with_json_lines() {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    let dir = scratch_dir("serve");
+    fs::create_dir(dir.join("src")).unwrap();
+    fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.join("notes.txt"), "Main idea\n").unwrap();
+    let elsewhere = scratch_dir("serve-elsewhere");
+    fs::write(elsewhere.join("secret.txt"), "top secret\n").unwrap();
+    std::os::unix::fs::symlink(elsewhere.join("secret.txt"), dir.join("src/leak.txt")).unwrap();
+    std::os::unix::fs::symlink(&elsewhere, dir.join("linked")).unwrap();
+    // A port the system just handed out is very likely still free.
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut server = binary()
+        .args(["--serve", &address.to_string()])
+        .current_dir(&dir)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let get = |target: &str| {
+        let mut stream = (0..100)
+            .find_map(|_| {
+                TcpStream::connect(address)
+                    .map_err(|_| std::thread::sleep(std::time::Duration::from_millis(50)))
+                    .ok()
+            })
+            .expect("the server should start listening");
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let found = get("/search?query=main&path=src");
+    let matched = get("/search?query=main&flags=-i+--sort%3Dpath");
+    let rejected = get("/search?query=%28&flags=-E");
+    let outside = get("/search?query=root&path=/etc/passwd");
+    let escaped = get("/search?query=root&path=src/../..");
+    let clearing = get("/search?query=main&flags=--cache-clear");
+    let reading = get("/search?query=main&flags=-i+--file%3D/etc/passwd");
+    let smuggled = get("/search?query=root&flags=-m+1+/etc/passwd");
+    let leaked = get("/search?query=secret");
+    let followed = get("/search?query=secret&flags=--follow");
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    assert!(found.starts_with("HTTP/1.1 200 OK\r\n"), "{}", found);
+    assert!(found.ends_with(
+        "\r\n\r\n{\"type\":\"match\",\"path\":\"src/main.rs\",\"line_number\":1,\
+         \"byte_offset\":0,\"line\":\"fn main() {}\",\
+         \"spans\":[{\"start\":3,\"end\":7,\"text\":\"main\"}]}\n"
+    ));
+    assert_eq!(matched.matches("\"type\":\"match\"").count(), 2);
+    assert!(rejected.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(rejected.contains("Invalid -E pattern"));
+    // Nothing outside the served directory is searched, and no flag reaches beyond a search.
+    for forbidden in [&outside, &escaped] {
+        assert!(
+            forbidden.starts_with("HTTP/1.1 403 Forbidden\r\n"),
+            "{}",
+            forbidden
+        );
+        assert!(!forbidden.contains("\"match\""), "{}", forbidden);
+    }
+    assert!(clearing.contains("--cache-clear cannot be used in a request"));
+    assert!(reading.contains("--file cannot be used in a request"));
+    assert!(smuggled.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(smuggled.contains("name paths with path="), "{}", smuggled);
+    // Nor does a symlink lead out of it.
+    assert!(leaked.starts_with("HTTP/1.1 200 OK\r\n"), "{}", leaked);
+    assert!(
+        !leaked.contains("secret.txt") && !leaked.contains("top secret"),
+        "{}",
+        leaked
+    );
+    assert!(
+        followed.contains("--follow cannot be used in a request"),
+        "{}",
+        followed
+    );
+}
+
+#[test]
+fn index_query_opens_only_candidate_files_and_sees_changes() {
+    let dir = scratch_dir("index");
+    fs::create_dir(dir.join("src")).unwrap();
+    fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.join("src/lib.rs"), "pub fn parse() {}\n").unwrap();
+    fs::write(dir.join("notes.txt"), "Maintain the parser\n").unwrap();
+    let run = |args: &[&str]| {
+        let output = binary().args(args).current_dir(&dir).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        run(&["index", "build", "."]),
+        "Indexed 3 files (0 unchanged) into ./.grep-index\n"
+    );
+    binary()
+        .args(["index", "query", "--stats", "-E", "fn (main|parse)"])
+        .current_dir(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "./src/lib.rs: pub fn parse() {}\n./src/main.rs: fn main() {}\n",
+        ))
+        .stderr(predicate::str::contains("\n2 files searched\n"));
+    assert_eq!(
+        run(&["index", "query", "-i", "MAIN"]),
+        "./notes.txt: Maintain the parser\n./src/main.rs: fn main() {}\n"
+    );
+
+    // A file written after the build is searched anyway, and the next build picks it up.
+    fs::write(dir.join("todo.txt"), "fn main again\n").unwrap();
+    assert!(run(&["index", "query", "fn main"]).contains("./todo.txt: fn main again\n"));
+    assert_eq!(
+        run(&["index", "build", "."]),
+        "Indexed 4 files (3 unchanged) into ./.grep-index\n"
+    );
+    binary()
+        .args(["index", "query", "main", "src"])
+        .current_dir(&dir)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Cannot read index src/.grep-index",
+        ));
+}
+
+#[test]
+fn search_names_matches_paths_as_well_as_lines() {
+    let dir = scratch_dir("search_names");
+    fs::create_dir(dir.join("parser")).unwrap();
+    fs::write(dir.join("parser/lexer.rs"), "// tokens\n").unwrap();
+    fs::write(dir.join("main.rs"), "mod parser;\n").unwrap();
+    let name = |file: &str| dir.join(file).display().to_string();
+
+    binary()
+        .args(["-r", "--search-names", "parser"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::eq(format!(
+            "{}: mod parser;\n{}\n",
+            name("main.rs"),
+            name("parser/lexer.rs")
+        )));
+    binary()
+        .args(["-r", "--names-only", "--json", "lex"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!(
+            "{{\"type\":\"name\",\"path\":\"{}\",\"spans\":[",
+            name("parser/lexer.rs")
+        )));
+    binary()
+        .args(["-r", "--names-only", "tokens"])
+        .arg(&dir)
+        .assert()
+        .code(1)
+        .stdout(predicate::eq(""));
+}
+
+#[test]
+fn files_reached_twice_are_searched_once_unless_no_dedupe() {
+    let dir = scratch_dir("dedupe");
+    fs::write(dir.join("real.txt"), "needle\n").unwrap();
+    std::os::unix::fs::symlink(dir.join("real.txt"), dir.join("alias.txt")).unwrap();
+    let real = dir.join("real.txt");
+    binary()
+        .args(["--verbose", "--count", "needle"])
+        .arg(&real)
+        .arg(&real)
+        .arg(dir.join("alias.txt"))
+        .assert()
+        .success()
+        .stdout(format!("{}:1\n", real.display()))
+        .stderr(predicate::str::contains(format!(
+            "Skipped {}: same file as {}",
+            dir.join("alias.txt").display(),
+            real.display()
+        )));
+    binary()
+        .args(["--no-dedupe", "--count", "needle"])
+        .arg(&real)
+        .arg(dir.join("alias.txt"))
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:1\n{}:1\n",
+            real.display(),
+            dir.join("alias.txt").display()
+        ));
+}
+
+#[test]
+fn list_files_with_invert_stops_at_first_unmatched_line() {
+    let dir = scratch_dir("first_hit");
+    let file = dir.join("log.txt");
+    fs::write(&file, "keep\nhay\nhay\nhay\n").unwrap();
+    binary()
+        .args(["-v", "-l", "--stats", "keep"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(format!("{}\n", file.display()))
+        .stderr(predicate::str::contains("1 line matched\n"))
+        .stderr(predicate::str::contains("9 bytes searched\n"));
+}
+
+#[test]
+fn head_lines_and_head_bytes_read_only_the_start_of_each_input() {
+    let dir = scratch_dir("head");
+    let file = dir.join("numbers.txt");
+    let numbers: String = (1..=1000).map(|n| format!("{}\n", n)).collect();
+    fs::write(&file, numbers).unwrap();
+    binary()
+        .args(["--head-lines", "3", "--stats", "-E", "^[0-9]+$"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout("1\n2\n3\n")
+        .stderr(predicate::str::contains("6 bytes searched\n"));
+    binary()
+        .args(["--head-bytes", "5", "-E", "[0-9]"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout("1\n2\n3\n");
+    binary()
+        .args(["--head-lines", "2", "999"])
+        .arg(&file)
+        .assert()
+        .code(1);
+    binary()
+        .args(["--head-bytes", "1X", "1"])
+        .arg(&file)
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "Invalid value for --head-bytes: 1X",
+        ));
+}
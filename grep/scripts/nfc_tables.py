@@ -0,0 +1,44 @@
+"""Writes src/nfc_tables.rs: python3 scripts/nfc_tables.py > src/nfc_tables.rs"""
+
+import unicodedata as u
+
+def ch(c):
+    return "'\\u{%X}'" % c
+
+def rows(items, per):
+    return "\n".join("    " + " ".join(items[i:i + per]) for i in range(0, len(items), per))
+
+decompositions, compositions, classes = [], [], []
+for cp in range(0x110000):
+    c = chr(cp)
+    if u.combining(c):
+        if classes and classes[-1][1] == cp - 1 and classes[-1][2] == u.combining(c):
+            classes[-1][1] = cp
+        else:
+            classes.append([cp, cp, u.combining(c)])
+    d = u.decomposition(c)
+    if 0xAC00 <= cp < 0xD7A4 or not d or d.startswith("<"):
+        continue
+    parts = [int(x, 16) for x in d.split()]
+    decompositions.append((cp, parts[0], parts[1] if len(parts) == 2 else None))
+    if len(parts) == 2 and u.normalize("NFC", c) == c:
+        compositions.append((parts[0], parts[1], cp))
+compositions.sort()
+
+print("""//! Unicode %s tables for NFC normalization, generated from the Unicode Character Database
+//! (with Python's `unicodedata`) by `scripts/nfc_tables.py`. Hangul syllables are composed
+//! and decomposed arithmetically instead, so they do not appear here.
+""" % u.unidata_version)
+print("/// Canonical decompositions, sorted: each character and the one or two it decomposes to.")
+print("pub(crate) const DECOMPOSITIONS: &[(char, char, Option<char>)] = &[")
+print(rows(["(%s, %s, %s)," % (ch(a), ch(b), "Some(%s)" % ch(c) if c is not None else "None")
+            for a, b, c in decompositions], 3))
+print("];\n")
+print("/// Primary composites, sorted by the pair they compose from.")
+print("pub(crate) const COMPOSITIONS: &[(char, char, char)] = &[")
+print(rows(["(%s, %s, %s)," % (ch(a), ch(b), ch(c)) for a, b, c in compositions], 4))
+print("];\n")
+print("/// Ranges of characters with a nonzero canonical combining class, and that class.")
+print("pub(crate) const COMBINING_CLASSES: &[(char, char, u8)] = &[")
+print(rows(["(%s, %s, %d)," % (ch(a), ch(b), c) for a, b, c in classes], 4))
+print("];")
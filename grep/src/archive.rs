@@ -0,0 +1,250 @@
+//! `--archives`: searching the files inside zip and tar archives.
+//!
+//! Tar archives are read here (through the decompressors of `-z` when compressed); zip entries
+//! are extracted by the system's `unzip`.
+
+use crate::decompress::Decoder;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str;
+
+/// Kinds of archive, recognised by extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Archive {
+    Zip,
+    /// A tar archive, compressed by the named program if any.
+    Tar(Option<&'static str>),
+}
+
+impl Archive {
+    pub(crate) fn of(path: &Path) -> Option<Archive> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        let ends = |suffixes: &[&str]| suffixes.iter().any(|suffix| name.ends_with(suffix));
+        if ends(&[".zip"]) {
+            Some(Archive::Zip)
+        } else if ends(&[".tar"]) {
+            Some(Archive::Tar(None))
+        } else if ends(&[".tar.gz", ".tgz"]) {
+            Some(Archive::Tar(Some("gzip")))
+        } else if ends(&[".tar.bz2", ".tbz2"]) {
+            Some(Archive::Tar(Some("bzip2")))
+        } else if ends(&[".tar.xz", ".txz"]) {
+            Some(Archive::Tar(Some("xz")))
+        } else {
+            None
+        }
+    }
+}
+
+/// Calls `entry` with the name and contents of every regular file in the archive at `path`,
+/// until it returns `false`. Names look like `bundle.zip!docs/readme.md`.
+pub(crate) fn for_each_entry(
+    path: &Path,
+    archive: Archive,
+    mut entry: impl FnMut(&Path, &mut dyn BufRead) -> io::Result<bool>,
+) -> io::Result<()> {
+    match archive {
+        Archive::Zip => read_zip(path, &mut entry),
+        Archive::Tar(None) => read_tar(BufReader::new(File::open(path)?), path, &mut entry),
+        Archive::Tar(Some(program)) => {
+            let mut decoder = Decoder::spawn(program, path)?;
+            read_tar(&mut decoder.output, path, &mut entry)?;
+            decoder.finish()
+        }
+    }
+}
+
+/// `archive!name`, the label of an entry.
+fn entry_name(archive: &Path, name: &str) -> PathBuf {
+    let mut label = OsString::from(archive);
+    label.push("!");
+    label.push(name);
+    PathBuf::from(label)
+}
+
+type Entry<'e> = dyn FnMut(&Path, &mut dyn BufRead) -> io::Result<bool> + 'e;
+
+fn read_zip(path: &Path, entry: &mut Entry<'_>) -> io::Result<()> {
+    // `unzip` takes no `--` before the archive, so a name that looks like an option is
+    // given a directory in front.
+    let archive = match path.to_string_lossy().starts_with('-') {
+        true => Path::new(".").join(path),
+        false => path.to_path_buf(),
+    };
+    let mut listing = Decoder::run("unzip", Command::new("unzip").arg("-Z1").arg(&archive))?;
+    let mut names = String::new();
+    listing.output.read_to_string(&mut names)?;
+    listing.finish()?;
+    for name in names.lines().filter(|name| !name.ends_with('/')) {
+        let mut contents = Decoder::run(
+            "unzip",
+            Command::new("unzip")
+                .arg("-p")
+                .arg(&archive)
+                .arg(unzip_literal(name)),
+        )?;
+        let more = entry(&entry_name(path, name), &mut contents.output)?;
+        contents.finish()?;
+        if !more {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// `name` as an `unzip` pattern matching only itself: wildcards, and a leading `-` that would
+/// read as an option, are put in brackets.
+fn unzip_literal(name: &str) -> String {
+    let mut pattern = String::with_capacity(name.len());
+    for (index, c) in name.chars().enumerate() {
+        if matches!(c, '*' | '?' | '[') || (index == 0 && c == '-') {
+            pattern.push('[');
+            pattern.push(c);
+            pattern.push(']');
+        } else {
+            pattern.push(c);
+        }
+    }
+    pattern
+}
+
+/// Size of a tar header and the unit entries are padded to.
+const BLOCK: u64 = 512;
+
+fn read_tar(mut reader: impl Read, path: &Path, entry: &mut Entry<'_>) -> io::Result<()> {
+    let corrupt = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    // Set by a GNU long-name or pax header for the entry that follows it.
+    let mut next_name: Option<String> = None;
+    let mut header = [0; BLOCK as usize];
+    loop {
+        let mut filled = 0;
+        while filled < header.len() {
+            match reader.read(&mut header[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        // Archives are meant to end with zero blocks, but a missing end is tolerated.
+        if filled == 0 {
+            return Ok(());
+        }
+        if filled < header.len() {
+            return Err(corrupt("truncated tar archive"));
+        }
+        if header.iter().all(|&byte| byte == 0) {
+            return Ok(());
+        }
+        if !checksum_matches(&header) {
+            return Err(corrupt("not a tar archive (bad header checksum)"));
+        }
+        let size = header_number(&header[124..136]).ok_or_else(|| corrupt("bad tar entry size"))?;
+        let mut data = (&mut reader).take(size);
+        let mut more = true;
+        match header[156] {
+            // GNU long name: the data is the next entry's name.
+            b'L' => next_name = Some(read_name(&mut data)?),
+            // pax extended header: a `path` record renames the next entry.
+            b'x' => {
+                if let Some(name) = pax_path(&read_name(&mut data)?) {
+                    next_name = Some(name);
+                }
+            }
+            b'0' | b'\0' | b'7' => {
+                let name = next_name.take().unwrap_or_else(|| header_name(&header));
+                more = entry(&entry_name(path, &name), &mut BufReader::new(&mut data))?;
+            }
+            // A long link target or global header leaves the next entry's name alone.
+            b'K' | b'g' => {}
+            // Directories, links, and devices hold nothing to search.
+            _ => next_name = None,
+        }
+        if !more {
+            return Ok(());
+        }
+        // Whatever the entry did not read, then the padding to the next header.
+        io::copy(&mut data, &mut io::sink())?;
+        let unread = data.limit();
+        let padding = size.next_multiple_of(BLOCK) - size;
+        let skipped = io::copy(&mut (&mut reader).take(padding), &mut io::sink())?;
+        if unread > 0 || skipped < padding {
+            return Err(corrupt("truncated tar archive"));
+        }
+    }
+}
+
+/// The header's checksum field against the sum of its bytes, with the field itself as spaces.
+fn checksum_matches(header: &[u8; BLOCK as usize]) -> bool {
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(index, &byte)| {
+            if (148..156).contains(&index) {
+                u64::from(b' ')
+            } else {
+                u64::from(byte)
+            }
+        })
+        .sum();
+    header_number(&header[148..156]) == Some(sum)
+}
+
+/// A numeric header field: octal text, or GNU's base-256 for values too large for it.
+fn header_number(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        let value = field[1..]
+            .iter()
+            .fold(u64::from(field[0] & 0x7F), |value, &byte| {
+                (value << 8) | u64::from(byte)
+            });
+        return Some(value);
+    }
+    let text = str::from_utf8(field).ok()?;
+    let digits = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// The name stored in the header itself, joined to the ustar prefix when there is one.
+fn header_name(header: &[u8; BLOCK as usize]) -> String {
+    let text = |field: &[u8]| {
+        let end = field
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(field.len());
+        String::from_utf8_lossy(&field[..end]).into_owned()
+    };
+    let name = text(&header[..100]);
+    let prefix = if &header[257..262] == b"ustar" {
+        text(&header[345..500])
+    } else {
+        String::new()
+    };
+    if prefix.is_empty() {
+        name
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+fn read_name(data: &mut impl Read) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    data.read_to_end(&mut bytes)?;
+    let end = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(bytes.len());
+    Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+/// The `path` of pax records, each written `LENGTH key=value\n`.
+fn pax_path(records: &str) -> Option<String> {
+    records.lines().find_map(|record| {
+        let (_, field) = record.split_once(' ')?;
+        field.strip_prefix("path=").map(str::to_string)
+    })
+}
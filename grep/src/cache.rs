@@ -0,0 +1,325 @@
+//! `--cache`: what each file printed, kept on disk so that running the same search again over a
+//! mostly unchanged tree only reads the files that changed.
+//!
+//! Each distinct run has a file in the cache directory, named for its key: a hash of the
+//! arguments, the working directory, and whatever else decides what is printed. It holds, for
+//! every file the run searched, the file's modification time and size then, what the search
+//! counted, and the bytes it printed. A file whose time and size still match has that output
+//! replayed without being opened; any other is searched and its entry replaced.
+//!
+//! The file format is little-endian: the magic `GREPCCH1`, then one record per file, each its
+//! length, an FNV-1a hash of its contents, and the contents: the path, modification time
+//! (seconds and nanoseconds), size, selected lines, matches, and the output. Loading
+//! stops at the first record that is cut short or fails its hash, keeping those before it, so a
+//! cache damaged by a crash or a full disk only loses what follows the damage.
+
+use crate::Searched;
+use crate::config::Config;
+use crate::error::SearchError;
+use crate::index::Stamp;
+use crate::printer;
+use crate::search::STDIN_PATH;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const MAGIC: &[u8; 8] = b"GREPCCH1";
+
+/// One file's results as they were last searched.
+struct Entry {
+    stamp: Stamp,
+    count: usize,
+    matches: usize,
+    output: Vec<u8>,
+}
+
+/// The results of an earlier run with the same key, and those of this one so far.
+pub(crate) struct Cache {
+    location: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Entries read from disk and not yet used this run.
+    loaded: HashMap<String, Entry>,
+    /// Entries for the files this run has seen, to be saved.
+    kept: Vec<(String, Entry)>,
+}
+
+/// The directory caches are kept in: `$XDG_CACHE_HOME/grep`, with `~/.cache` standing in for
+/// an unset `XDG_CACHE_HOME`.
+fn dir() -> Option<PathBuf> {
+    let non_empty = |name| env::var_os(name).filter(|value| !value.is_empty());
+    non_empty("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| non_empty("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|dir| dir.join("grep"))
+}
+
+/// The key a run is cached under. `read` is the contents of the files the options read, such
+/// as `--file` and `--rules`, which can change while the arguments naming them stay the same.
+/// `colored` and `linked` are what `--color` and `--hyperlink` resolved to, since `auto`
+/// depends on where stdout goes.
+pub(crate) fn key(args: &[String], read: &[String], colored: bool, linked: bool) -> u64 {
+    let mut text = format!(
+        "{}\0{}\0{}\0{}\0{}",
+        env!("CARGO_PKG_VERSION"),
+        env::current_dir().unwrap_or_default().display(),
+        env::var("GREP_COLORS").unwrap_or_default(),
+        colored,
+        linked
+    );
+    for arg in args {
+        text.push('\0');
+        text.push_str(arg);
+    }
+    for contents in read {
+        text.push('\u{1}');
+        text.push_str(contents);
+    }
+    fnv1a(text.as_bytes())
+}
+
+/// `--cache-clear`: removes every cached run, reporting where they were.
+pub(crate) fn clear() -> Result<(), SearchError> {
+    let dir = dir().ok_or_else(|| "--cache-clear needs HOME or XDG_CACHE_HOME set.".to_string())?;
+    match fs::remove_dir_all(&dir) {
+        Ok(()) => println!("Cleared the cache at {}", dir.display()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            println!("Nothing is cached at {}", dir.display())
+        }
+        Err(error) => return Err(SearchError::io("cache", &dir, error)),
+    }
+    Ok(())
+}
+
+impl Cache {
+    /// The cache for this run, or `None` without `--cache`, or where there is nowhere to keep
+    /// one. A missing or unreadable cache file starts out empty. `--write` has to change the
+    /// files each time, and under `--max-total` what a file prints depends on those before it,
+    /// so neither is cached.
+    pub(crate) fn open(config: &Config) -> Option<Cache> {
+        let key = config
+            .cache
+            .filter(|_| !config.write && config.max_total.is_none())?;
+        let location = dir()?.join(format!("{:016x}", key));
+        let loaded = fs::read(&location)
+            .map(|bytes| decode(&bytes))
+            .unwrap_or_default();
+        Some(Cache {
+            location,
+            state: Mutex::new(State {
+                loaded,
+                kept: Vec::new(),
+            }),
+        })
+    }
+
+    /// Replays what `path` printed last time into `out` if the file is unchanged since, and
+    /// otherwise runs `search` on it, keeping what it prints. Standard input, and a path that is
+    /// not UTF-8, are always searched.
+    pub(crate) fn search(
+        &self,
+        config: &Config,
+        path: &Path,
+        mut out: impl Write,
+        search: impl FnOnce(&mut dyn Write) -> io::Result<Searched>,
+    ) -> io::Result<Searched> {
+        let name = path.to_str().filter(|_| path != Path::new(STDIN_PATH));
+        // Taken before the search, so a change made during it is seen next time.
+        let (Some(name), Some(stamp)) = (name, Stamp::of(path)) else {
+            return search(&mut out);
+        };
+        let hit = {
+            let mut state = self.state.lock().unwrap();
+            state
+                .loaded
+                .remove(name)
+                .filter(|entry| entry.stamp == stamp)
+        };
+        if let Some(entry) = hit {
+            if config.trace {
+                eprintln!(
+                    "Trace: {}: cached (unchanged since it was last searched)",
+                    printer::path_label(path)
+                );
+            }
+            out.write_all(&entry.output)?;
+            let searched = Searched {
+                count: entry.count,
+                matches: entry.matches,
+                // Nothing was read.
+                bytes: 0,
+            };
+            self.state
+                .lock()
+                .unwrap()
+                .kept
+                .push((name.to_string(), entry));
+            return Ok(searched);
+        }
+        let mut tee = Tee {
+            out,
+            copy: Vec::new(),
+        };
+        let searched = search(&mut tee)?;
+        let entry = Entry {
+            stamp,
+            count: searched.count,
+            matches: searched.matches,
+            output: tee.copy,
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .kept
+            .push((name.to_string(), entry));
+        Ok(searched)
+    }
+
+    /// Writes out the entries of the files this run saw, and those it did not reach (say after
+    /// `-q` found a match) that are still there.
+    pub(crate) fn save(self, config: &Config) {
+        let mut state = self.state.into_inner().unwrap();
+        let unused = state.loaded.into_iter();
+        state
+            .kept
+            .extend(unused.filter(|(path, _)| Path::new(path).exists()));
+        let Some(dir) = self.location.parent() else {
+            return;
+        };
+        // Written aside and renamed, so a run never reads half a cache.
+        let partial = self
+            .location
+            .with_extension(format!("{}.partial", std::process::id()));
+        let saved = fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&partial, encode(&state.kept)))
+            .and_then(|_| fs::rename(&partial, &self.location));
+        if let Err(error) = saved {
+            let _ = fs::remove_file(&partial);
+            if !config.no_messages {
+                eprintln!(
+                    "Warning: {}: could not save the cache: {}",
+                    self.location.display(),
+                    error
+                );
+            }
+        }
+    }
+}
+
+/// Copies whatever is written through it to `out`, keeping a copy.
+struct Tee<W> {
+    out: W,
+    copy: Vec<u8>,
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.out.write(buf)?;
+        self.copy.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn encode(entries: &[(String, Entry)]) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    for (path, entry) in entries {
+        let mut record = Vec::new();
+        record.extend_from_slice(&(path.len() as u64).to_le_bytes());
+        record.extend_from_slice(path.as_bytes());
+        record.extend_from_slice(&entry.stamp.seconds.to_le_bytes());
+        record.extend_from_slice(&entry.stamp.nanos.to_le_bytes());
+        record.extend_from_slice(&entry.stamp.len.to_le_bytes());
+        record.extend_from_slice(&(entry.count as u64).to_le_bytes());
+        record.extend_from_slice(&(entry.matches as u64).to_le_bytes());
+        record.extend_from_slice(&(entry.output.len() as u64).to_le_bytes());
+        record.extend_from_slice(&entry.output);
+        out.extend_from_slice(&(record.len() as u64).to_le_bytes());
+        out.extend_from_slice(&fnv1a(&record).to_le_bytes());
+        out.extend_from_slice(&record);
+    }
+    out
+}
+
+/// The entries of a cache file, as many as are intact.
+fn decode(bytes: &[u8]) -> HashMap<String, Entry> {
+    let mut entries = HashMap::new();
+    let Some(mut rest) = bytes.strip_prefix(MAGIC) else {
+        return entries;
+    };
+    while !rest.is_empty() {
+        let mut header = Reader { bytes: rest };
+        let Some((len, hash)) = header.u64().zip(header.u64()) else {
+            break;
+        };
+        let Some(record) = header
+            .take(len as usize)
+            .filter(|record| fnv1a(record) == hash)
+        else {
+            break;
+        };
+        rest = header.bytes;
+        if let Some((path, entry)) = decode_record(record) {
+            entries.insert(path, entry);
+        }
+    }
+    entries
+}
+
+fn decode_record(record: &[u8]) -> Option<(String, Entry)> {
+    let mut reader = Reader { bytes: record };
+    let len = reader.u64()? as usize;
+    let path = String::from_utf8(reader.take(len)?.to_vec()).ok()?;
+    let stamp = Stamp {
+        seconds: reader.u64()?,
+        nanos: u32::from_le_bytes(reader.take(4)?.try_into().ok()?),
+        len: reader.u64()?,
+    };
+    let count = reader.u64()? as usize;
+    let matches = reader.u64()? as usize;
+    let len = reader.u64()? as usize;
+    let output = reader.take(len)?.to_vec();
+    let entry = Entry {
+        stamp,
+        count,
+        matches,
+        output,
+    };
+    Some((path, entry))
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, len: usize) -> Option<&'b [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike the standard library's stays the same from one build to
+/// the next.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,200 @@
+//! Shell completion scripts generated from the option table.
+
+use crate::options::{Flag, OPTIONS, OptionSpec};
+use crate::types;
+
+/// The completion script for `shell`, or `None` if the shell is not supported.
+pub(crate) fn generate(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        "powershell" => Some(powershell()),
+        _ => None,
+    }
+}
+
+fn names(spec: &OptionSpec) -> Vec<String> {
+    spec.short
+        .map(|short| format!("-{}", short))
+        .into_iter()
+        .chain(spec.long.map(|long| format!("--{}", long)))
+        .collect()
+}
+
+/// The values to offer after `spec`: its fixed choices, or the built-in file types, which
+/// `--type-add` may add to and so are not in the table.
+fn choices(spec: &OptionSpec) -> Vec<&'static str> {
+    match spec.flag {
+        Flag::Type | Flag::TypeNot => types::builtin_names(),
+        _ => spec.choices.to_vec(),
+    }
+}
+
+fn bash() -> String {
+    let all: Vec<String> = OPTIONS.iter().flat_map(names).collect();
+    let mut value_cases = String::new();
+    for spec in OPTIONS.iter().filter(|spec| spec.value.is_some()) {
+        let choices = choices(spec);
+        let reply = if choices.is_empty() {
+            // Free-form value: offer nothing rather than filenames.
+            "COMPREPLY=()".to_string()
+        } else {
+            format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                choices.join(" ")
+            )
+        };
+        value_cases.push_str(&format!(
+            "        {})\n            {}\n            return\n            ;;\n",
+            names(spec).join("|"),
+            reply
+        ));
+    }
+
+    format!(
+        r#"_grep() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    case "$prev" in
+{value_cases}    esac
+
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{all}" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -f -- "$cur"))
+    fi
+}}
+complete -o filenames -F _grep grep
+"#,
+        value_cases = value_cases,
+        all = all.join(" ")
+    )
+}
+
+fn zsh() -> String {
+    let escape = |text: &str| {
+        text.replace('\\', "\\\\")
+            .replace('\'', "'\\''")
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+            .replace(':', "\\:")
+    };
+
+    let mut script = String::from("#compdef grep\n\n_arguments -s \\\n");
+    for spec in OPTIONS {
+        let names = names(spec);
+        let help = escape(spec.help);
+        let choices = choices(spec);
+        let value = match spec.value {
+            Some(placeholder) if choices.is_empty() => format!(":{}: ", placeholder),
+            Some(placeholder) => format!(":{}:({})", placeholder, choices.join(" ")),
+            None => String::new(),
+        };
+        let entry = if names.len() > 1 {
+            format!(
+                "'({})'{{{}}}'[{}]{}'",
+                names.join(" "),
+                names.join(","),
+                help,
+                value
+            )
+        } else {
+            format!("'{}[{}]{}'", names[0], help, value)
+        };
+        script.push_str(&format!("  {} \\\n", entry));
+    }
+    script.push_str("  '*:file:_files'\n");
+    script
+}
+
+fn fish() -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('\'', "\\'");
+
+    let mut script = String::new();
+    for spec in OPTIONS {
+        let mut line = String::from("complete -c grep");
+        if let Some(short) = spec.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        if let Some(long) = spec.long {
+            line.push_str(&format!(" -l {}", long));
+        }
+        if spec.value.is_some() {
+            line.push_str(" -r");
+            let choices = choices(spec);
+            if !choices.is_empty() {
+                line.push_str(&format!(" -f -a '{}'", choices.join(" ")));
+            }
+        }
+        line.push_str(&format!(" -d '{}'\n", escape(spec.help)));
+        script.push_str(&line);
+    }
+    script
+}
+
+fn powershell() -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    let list = |words: &[&str]| {
+        let quoted: Vec<String> = words.iter().map(|word| quote(word)).collect();
+        quoted.join(", ")
+    };
+
+    let mut value_cases = String::new();
+    let mut option_list = String::new();
+    for spec in OPTIONS {
+        let names = names(spec);
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        if spec.value.is_some() {
+            // Free-form values get an empty list: nothing to offer, and no filenames either.
+            value_cases.push_str(&format!(
+                "        {{ $_ -cin {} }} {{ $choices = @({}) }}\n",
+                list(&names),
+                list(&choices(spec))
+            ));
+        }
+        for name in names {
+            option_list.push_str(&format!(
+                "            @({}, {}),\n",
+                quote(name),
+                quote(spec.help)
+            ));
+        }
+    }
+
+    format!(
+        r#"Register-ArgumentCompleter -Native -CommandName grep -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $previous = ''
+    foreach ($element in $commandAst.CommandElements) {{
+        if ($element.Extent.EndOffset -lt $cursorPosition) {{
+            $previous = $element.ToString()
+        }}
+    }}
+
+    $choices = $null
+    switch ($previous) {{
+{value_cases}    }}
+    if ($null -ne $choices) {{
+        $choices | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+            [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+        }}
+        return
+    }}
+
+    # Anything else that is not an option falls back to completing file names.
+    if ($wordToComplete -like '-*') {{
+        @(
+{option_list}        ) | Where-Object {{ $_[0] -clike "$wordToComplete*" }} | ForEach-Object {{
+            [System.Management.Automation.CompletionResult]::new($_[0], $_[0], 'ParameterName', $_[1])
+        }}
+    }}
+}}
+"#,
+        value_cases = value_cases,
+        option_list = option_list
+    )
+}
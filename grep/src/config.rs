@@ -0,0 +1,1484 @@
+use crate::archive::Archive;
+use crate::cache;
+use crate::completions;
+use crate::encoding::Encoding;
+use crate::error::SearchError;
+use crate::format::Template;
+use crate::glob::GlobSet;
+use crate::help;
+use crate::hyperlink::HyperlinkFormat;
+use crate::ignore::Gitignore;
+use crate::index::IndexMode;
+use crate::matcher::{LineFilter, MatchOptions, Matcher, PatternSpec, Syntax, Within};
+use crate::normalize;
+use crate::options::{self, Flag};
+use crate::rules::{self, Rule, RuleSpec};
+use crate::search::{STDIN_LABEL, STDIN_PATH};
+use crate::strategy::Engine;
+use crate::theme::{ColorChoice, Theme};
+use crate::types::TypeRegistry;
+use crate::walk::SortKey;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Default cap on how much of a file `--multiline` will buffer before giving up.
+const DEFAULT_MAX_BUFFER: usize = 64 * 1024 * 1024;
+
+/// Everything needed to run a search, normally produced by [`Config::parse`].
+pub struct Config {
+    /// Files and directories to search; `-` stands for standard input.
+    pub inputs: Vec<String>,
+    pub show_line_numbers: bool,
+    /// Prefix each line with its 0-based byte offset in the file.
+    pub byte_offset: bool,
+    /// Prefix each matching line with the 1-based column of its first match.
+    pub column: bool,
+    /// Count `column` in bytes rather than characters.
+    pub byte_columns: bool,
+    pub invert_match: bool,
+    pub recursive: bool,
+    /// How to order the files found by a recursive walk; `None` keeps walk order.
+    pub sort: Option<SortKey>,
+    /// With `recursive`, only walked files matching one of these are searched (all if empty).
+    pub include: GlobSet,
+    /// With `recursive`, walked files matching one of these are skipped.
+    pub exclude: GlobSet,
+    /// With `recursive`, only walked files of these `--type`s are searched (all if empty).
+    pub types: GlobSet,
+    /// With `recursive`, walked files of these `--type-not` types are skipped.
+    pub types_not: GlobSet,
+    /// Files named by `--files-from`. Unlike `inputs`, they must pass the walk's filters.
+    pub listed_files: Vec<String>,
+    /// `--files`: print the files that would be searched instead of searching them.
+    pub files_only: bool,
+    /// `--explain`: report why each of these paths would or would not be searched, instead of
+    /// searching.
+    pub explain: Vec<PathBuf>,
+    /// `--serve`: answer search requests over HTTP on this address instead of searching.
+    pub serve: Option<SocketAddr>,
+    /// Also match the pattern against each file's path, printing matching paths as records.
+    pub search_names: bool,
+    /// With `search_names`, leave the contents unsearched.
+    pub names_only: bool,
+    /// `grep index build` or `grep index query`, over the single directory in `inputs`.
+    pub index: Option<IndexMode>,
+    /// Let a recursive walk enter hidden files and directories.
+    pub hidden: bool,
+    /// Let a recursive walk enter paths excluded by `.gitignore`, `.ignore`, or `.rgignore` files.
+    pub no_ignore: bool,
+    /// Rules read from `--ignore-file`, matched against paths below each walk root. Every
+    /// ignore file found in the walk overrides them, and `no_ignore` does not turn them off.
+    pub ignore_file: Gitignore,
+    /// Search the files `git ls-files` lists under each directory in place of walking it, so
+    /// untracked files such as build outputs are never searched. Implies `recursive`.
+    pub git: bool,
+    /// Descend into symlinked directories during a recursive walk.
+    pub follow: bool,
+    /// Search a file once for every path it is found by, rather than only the first.
+    pub no_dedupe: bool,
+    /// How many directory levels a recursive walk may descend; `None` for no limit.
+    pub max_depth: Option<usize>,
+    /// Skip walked and listed files larger than this many bytes.
+    pub max_filesize: Option<u64>,
+    /// Only search walked and listed files modified less than this long ago.
+    pub newer_than: Option<Duration>,
+    /// Only search walked and listed files modified more than this long ago.
+    pub older_than: Option<Duration>,
+    /// Open character devices, block devices, and sockets named as inputs instead of skipping
+    /// them with a warning. Named pipes are always read.
+    pub read_devices: bool,
+    /// Report on stderr the files `max_filesize`, `newer_than`, and `older_than` skip, and the
+    /// duplicates dropped unless `no_dedupe`.
+    pub verbose: bool,
+    /// Prefix printed lines (and label counts) with their file. Unless `-H` or `--no-filename`
+    /// decide, this is on when more than one input is given or a directory is walked.
+    pub show_filenames: bool,
+    /// What standard input is called wherever a file name is shown: `(standard input)` unless
+    /// `--label` names it.
+    pub label: String,
+    /// With `show_filenames`, print each file's name once as a header over its lines instead of
+    /// in every prefix.
+    pub heading: bool,
+    /// Pad the fields of each line's prefix so that results line up in columns.
+    pub table: bool,
+    /// Color output, as `--color` resolved against the terminal and environment.
+    pub colored: bool,
+    /// Wrap printed file paths in OSC 8 links in this format, as `--hyperlink` resolved against
+    /// the terminal.
+    pub hyperlink: Option<HyperlinkFormat>,
+    /// Colors from `GREP_COLORS`, then `--colors`, over the defaults.
+    pub theme: Theme,
+    /// With `colored`, dim the parts of each matching line outside the matches.
+    pub context_dim: bool,
+    /// Stop searching an input once this many lines have been selected.
+    pub max_count: Option<usize>,
+    /// Stop the whole run once this many lines have been selected across every input.
+    pub max_total: Option<usize>,
+    pub list_files: bool,
+    /// Print the names of files with no selected line, stopping each file at its first one.
+    pub files_without_match: bool,
+    pub count: bool,
+    /// With `count` (which it implies), count individual matches rather than matching lines.
+    pub count_matches: bool,
+    /// With `count`, also print the sum across every file.
+    pub total: bool,
+    /// Report each distinct selected line once per file. Every distinct line is held in memory
+    /// until the file is finished.
+    pub unique: bool,
+    /// Print no lines, but once the search is done a tree of the directories holding matching
+    /// files, each with how many it holds and their matches.
+    pub group_by_dir: bool,
+    /// Finish with a one-line "N files matched out of M searched" note on stderr.
+    pub summary: bool,
+    /// Finish with totals for the whole run (files, lines, bytes, and elapsed time) on stderr.
+    pub stats: bool,
+    /// Flush stdout after every line instead of in blocks when it is not a terminal.
+    pub line_buffered: bool,
+    /// Keep running after the search, searching again whenever an input changes.
+    pub watch: bool,
+    /// Keep running after the search, searching each line appended to a file as it arrives.
+    pub tail: bool,
+    /// Browse the results in an interactive full-screen list instead of printing them.
+    pub tui: bool,
+    /// Print nothing to stdout and stop at the first selected line; only the exit status tells.
+    pub quiet: bool,
+    pub null_terminated: bool,
+    /// Records in and out end with NUL rather than newline.
+    pub null_data: bool,
+    /// Skip unreadable files silently instead of reporting them on stderr.
+    pub no_messages: bool,
+    /// Unselected lines to print after each selected line.
+    pub after_context: usize,
+    /// Unselected lines to print before each selected line.
+    pub before_context: usize,
+    pub field_separator: String,
+    /// Line printed between non-contiguous groups of context output; `None` omits it.
+    pub group_separator: Option<String>,
+    /// Truncate printed lines to this many characters; `0` means no limit.
+    pub max_columns: usize,
+    /// With `max_columns`, show the start of a long line rather than the part around its match.
+    pub max_columns_preview: bool,
+    /// Print selected and context lines as JSON objects, one per line.
+    pub json: bool,
+    /// Print a `file:line:column:text` record for every match instead of each selected line.
+    pub vimgrep: bool,
+    /// Print this template once per match instead of each selected line.
+    pub format: Option<Template>,
+    /// `--format sarif`: print a SARIF log with a result for every match instead of lines.
+    pub sarif: bool,
+    /// Print matching lines with every match replaced by this template; files are not changed.
+    pub replace: Option<String>,
+    /// Apply `replace` to the files in place rather than printing matching lines.
+    pub write: bool,
+    /// With `write`, print a unified diff of the changes and leave the files alone.
+    pub dry_run: bool,
+    /// With `write`, show each change on stderr and make it only if the user says to.
+    pub interactive: bool,
+    /// Print what `replace` changes as a unified diff, instead of the replaced lines (or, with
+    /// `write`, as well as making the changes).
+    pub diff: bool,
+    /// Strip leading and trailing whitespace from printed lines; matching still sees them.
+    pub trim: bool,
+    /// Print each match on its own line instead of the line containing it.
+    pub only_matching: bool,
+    /// Characters of surrounding text to include on each side of an only-matching span.
+    pub o_context: usize,
+    pub multiline: bool,
+    /// 1-based, inclusive range of lines to search; `None` searches every line.
+    pub line_range: Option<RangeInclusive<usize>>,
+    /// Stop reading each input after this many lines.
+    pub head_lines: Option<usize>,
+    /// Stop reading each input after this many bytes, before any decoding.
+    pub head_bytes: Option<u64>,
+    /// Largest input, in bytes, that multiline mode will buffer.
+    pub max_buffer: usize,
+    /// The most of one input held in memory at once: larger files are read rather than mapped,
+    /// those that would have to be buffered whole are skipped, and longer lines are searched in
+    /// windows of this size, the file being skipped if such a line has to be held whole to be
+    /// printed.
+    pub max_memory: Option<u64>,
+    /// Treat inputs containing NUL bytes as text, decoding invalid UTF-8 lossily.
+    pub binary: bool,
+    /// Encoding every input is read in; `None` detects a byte-order mark and otherwise assumes UTF-8.
+    pub encoding: Option<Encoding>,
+    /// Put patterns and input lines in Unicode NFC, so `é` matches `e` followed by U+0301.
+    pub normalize: bool,
+    /// Decompress gzip, bzip2, and xz files before searching them.
+    pub search_zip: bool,
+    /// Search the entries of zip and tar archives, labelled like `bundle.zip!docs/readme.md`.
+    pub archives: bool,
+    /// How files are read: streamed, memory-mapped, or chosen for each file.
+    pub engine: Engine,
+    /// Report on stderr how each input is read, and why.
+    pub trace: bool,
+    /// `--cache`: the key this run's results are kept under, so that only a run given the same
+    /// arguments reuses them.
+    pub cache: Option<u64>,
+    /// Files to search at once; `0` picks one per available CPU.
+    pub threads: usize,
+    /// Give up on a single input once searching it takes longer than this.
+    pub timeout: Option<Duration>,
+    /// Stop the whole run this long after it started, leaving any files not yet searched.
+    pub deadline: Option<Duration>,
+    /// Print the compiled pattern and the engine chosen for it to stderr before searching.
+    pub debug_pattern: bool,
+    /// The patterns and flags the matcher was compiled from.
+    pub pattern: PatternSpec,
+    pub matcher: Matcher,
+    /// `--rules`: named patterns, each match reported with the rule's name and message. Their
+    /// patterns are also what `matcher` matches.
+    pub rules: Vec<Rule>,
+    /// `--all-of`, `--any-of`, and `--none-of`, which a line `matcher` matched must also pass.
+    pub filter: LineFilter,
+    /// `--within`: the regions lines must be inside to be searched.
+    pub within: Option<Within>,
+}
+
+// Parsed once per process, so the size of the `Run` variant does not matter.
+#[allow(clippy::large_enum_variant)]
+pub enum ParseOutcome {
+    /// Help or another informational text was printed; there is nothing to search.
+    HelpPrinted,
+    Run(Config),
+}
+
+impl Config {
+    /// Parses command-line arguments (without the binary name) into a runnable configuration.
+    pub fn parse(args: Vec<String>) -> Result<ParseOutcome, SearchError> {
+        if args.is_empty() {
+            return Err("Missing arguments. Use -h for help.".to_string().into());
+        }
+
+        let mut case_insensitive = false;
+        let mut smart_case = false;
+        let mut show_line_numbers = false;
+        let mut byte_offset = false;
+        let mut column = false;
+        let mut byte_columns = false;
+        let mut invert_match = false;
+        let mut recursive = false;
+        let mut git = false;
+        let mut sort = None;
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        let mut types = Vec::new();
+        let mut types_not = Vec::new();
+        let mut registry = TypeRegistry::new();
+        let mut type_list = false;
+        let mut listed_files = Vec::new();
+        let mut files_from = false;
+        let mut files_only = false;
+        let mut explain = Vec::new();
+        let mut serve = None;
+        let mut search_names = false;
+        let mut names_only = false;
+        let mut index = None;
+        let mut hidden = false;
+        let mut no_ignore = false;
+        let mut ignore_rules = Gitignore::default();
+        let mut follow = false;
+        let mut no_dedupe = false;
+        let mut max_depth = None;
+        let mut max_filesize = None;
+        let mut newer_than = None;
+        let mut older_than = None;
+        let mut read_devices = false;
+        let mut verbose = false;
+        let mut with_filename = None;
+        let mut label = STDIN_LABEL.to_string();
+        let mut heading = false;
+        let mut table = false;
+        let mut color = ColorChoice::Never;
+        let mut hyperlink = ColorChoice::Never;
+        let mut hyperlink_format = None;
+        let mut colors = Vec::new();
+        let mut context_dim = false;
+        let mut max_count = None;
+        let mut max_total = None;
+        let mut list_files = false;
+        let mut files_without_match = false;
+        let mut count = false;
+        let mut count_matches = false;
+        let mut total = false;
+        let mut unique = false;
+        let mut group_by_dir = false;
+        let mut summary = false;
+        let mut stats = false;
+        let mut line_buffered = false;
+        let mut watch = false;
+        let mut tail = false;
+        let mut tui = false;
+        let mut null_terminated = false;
+        let mut null_data = false;
+        let mut no_messages = false;
+        let mut quiet = false;
+        let mut after_context = 0;
+        let mut before_context = 0;
+        let mut field_separator = ":".to_string();
+        let mut group_separator = Some("--".to_string());
+        let mut max_columns = 0;
+        let mut max_columns_preview = false;
+        let mut replace = None;
+        let mut write = false;
+        let mut dry_run = false;
+        let mut interactive = false;
+        let mut diff = false;
+        let mut trim = false;
+        let mut json = false;
+        let mut vimgrep = false;
+        let mut format = None;
+        let mut sarif = false;
+        let mut line_range = None;
+        let mut head_lines = None;
+        let mut head_bytes = None;
+        let mut only_matching = false;
+        let mut o_context = 0;
+        let mut multiline = false;
+        let mut syntax = Syntax::Literal;
+        let mut debug_pattern = false;
+        let mut timeout = None;
+        let mut deadline = None;
+        let mut threads = 0;
+        let mut binary = false;
+        let mut encoding = None;
+        let mut normalize = false;
+        let mut search_zip = false;
+        let mut archives = false;
+        let mut engine = Engine::Auto;
+        let mut trace = false;
+        let mut cache = false;
+        let mut cache_clear = false;
+        let mut word = false;
+        let mut line_regexp = false;
+        let mut starts_with = false;
+        let mut ends_with = false;
+        let mut max_buffer = DEFAULT_MAX_BUFFER;
+        let mut max_memory = None;
+        let mut patterns: Vec<String> = Vec::new();
+        let mut patterns_given = false;
+        let mut rule_specs: Vec<RuleSpec> = Vec::new();
+        let mut all_of = Vec::new();
+        let mut within = None;
+        let mut within_end = None;
+        let mut any_of = Vec::new();
+        let mut none_of = Vec::new();
+        let mut pattern_from_stdin = false;
+        let mut positionals: Vec<String> = Vec::new();
+        let mut options_done = false;
+
+        // Kept whole for --cache, whose key they are most of.
+        let given = args.clone();
+        // What the options read from files, such as `--file` patterns, for the cache key.
+        let mut read = Vec::new();
+        let mut args: VecDeque<String> = args.into();
+        while let Some(arg) = args.pop_front() {
+            if !options_done && is_short_bundle(&arg) {
+                // Expand `-inr` into `-i -n -r` and parse those in its place.
+                for flag in expand_short_bundle(&arg)?.into_iter().rev() {
+                    args.push_front(flag);
+                }
+                continue;
+            }
+
+            if !options_done && arg == "--" {
+                options_done = true;
+                continue;
+            }
+
+            if !options_done
+                && arg.starts_with("--")
+                && let Some((name, value)) = arg.split_once('=')
+                && options::lookup(name).is_some_and(|spec| spec.value.is_some())
+            {
+                // `--sort=size` is the same as `--sort size`.
+                args.push_front(value.to_string());
+                args.push_front(name.to_string());
+                continue;
+            }
+
+            if let Some(spec) = options::lookup(&arg).filter(|_| !options_done) {
+                let value = match spec.value {
+                    Some(_) => next_value(&mut args, &arg)?,
+                    None => String::new(),
+                };
+                match spec.flag {
+                    Flag::Help => {
+                        print!("{}", help::short());
+                        return Ok(ParseOutcome::HelpPrinted);
+                    }
+                    Flag::HelpLong => {
+                        print!("{}", help::long());
+                        return Ok(ParseOutcome::HelpPrinted);
+                    }
+                    Flag::GenerateMan => {
+                        print!("{}", help::man());
+                        return Ok(ParseOutcome::HelpPrinted);
+                    }
+                    // Read by the binary before parsing; the defaults are already in place here.
+                    Flag::NoConfig => {}
+                    Flag::GenerateCompletions => {
+                        let script = completions::generate(&value).ok_or_else(|| {
+                            format!(
+                                "Unsupported shell for --generate-completions: {} (expected {})",
+                                value,
+                                options::SHELLS.join(", ")
+                            )
+                        })?;
+                        print!("{}", script);
+                        return Ok(ParseOutcome::HelpPrinted);
+                    }
+                    // Whichever of -i, -S, and --case-sensitive comes last decides.
+                    Flag::IgnoreCase => (case_insensitive, smart_case) = (true, false),
+                    Flag::SmartCase => (case_insensitive, smart_case) = (false, true),
+                    Flag::CaseSensitive => (case_insensitive, smart_case) = (false, false),
+                    Flag::LineNumber => show_line_numbers = true,
+                    Flag::ByteOffset => byte_offset = true,
+                    Flag::Column => column = true,
+                    Flag::ByteColumns => {
+                        column = true;
+                        byte_columns = true;
+                    }
+                    Flag::InvertMatch => invert_match = true,
+                    Flag::Recursive => recursive = true,
+                    Flag::Sort => {
+                        sort = Some(SortKey::parse(&value).ok_or_else(|| {
+                            format!(
+                                "Invalid value for --sort: {} (expected path, mtime, modified, size, or none)",
+                                value
+                            )
+                        })?)
+                    }
+                    Flag::Include => include.push(value),
+                    Flag::Exclude => exclude.push(value),
+                    Flag::Type => types.push(value),
+                    Flag::TypeNot => types_not.push(value),
+                    Flag::TypeAdd => registry.add(&value)?,
+                    Flag::TypeList => type_list = true,
+                    Flag::Files => files_only = true,
+                    Flag::Explain => explain.push(PathBuf::from(&value)),
+                    Flag::SearchNames => search_names = true,
+                    Flag::NamesOnly => {
+                        search_names = true;
+                        names_only = true;
+                    }
+                    // An index covers everything under its directory.
+                    Flag::IndexBuild => {
+                        index = Some(IndexMode::Build);
+                        recursive = true;
+                    }
+                    Flag::IndexQuery => {
+                        index = Some(IndexMode::Query);
+                        recursive = true;
+                    }
+                    Flag::Serve => {
+                        serve = Some(value.parse::<SocketAddr>().map_err(|_| {
+                            format!(
+                                "Invalid value for --serve: {} (expected an address such as 127.0.0.1:8080)",
+                                value
+                            )
+                        })?)
+                    }
+                    Flag::FilesFrom => {
+                        listed_files.extend(read_file_list(&value, &mut read)?);
+                        files_from = true;
+                    }
+                    Flag::Hidden => hidden = true,
+                    Flag::NoIgnore => no_ignore = true,
+                    Flag::Git => {
+                        git = true;
+                        recursive = true;
+                    }
+                    Flag::IgnoreFile => {
+                        let contents = fs::read_to_string(&value)
+                            .map_err(|err| SearchError::io("ignore file", &value, err))?;
+                        ignore_rules.extend(&contents, Path::new(&value));
+                        read.push(contents);
+                    }
+                    Flag::Follow => follow = true,
+                    Flag::NoFollow => follow = false,
+                    Flag::NoDedupe => no_dedupe = true,
+                    Flag::MaxDepth => max_depth = Some(parse_number(&value, "--max-depth")?),
+                    Flag::MaxFilesize => max_filesize = Some(parse_size(&value, "--max-filesize")?),
+                    Flag::NewerThan => newer_than = Some(parse_age(&value, &arg)?),
+                    Flag::OlderThan => older_than = Some(parse_age(&value, &arg)?),
+                    Flag::Devices => {
+                        read_devices = match value.as_str() {
+                            "read" => true,
+                            "skip" => false,
+                            _ => {
+                                return Err(format!(
+                                    "Invalid value for --devices: {} (expected read or skip)",
+                                    value
+                                )
+                                .into());
+                            }
+                        }
+                    }
+                    Flag::Verbose => verbose = true,
+                    Flag::WithFilename => with_filename = Some(true),
+                    Flag::NoFilename => with_filename = Some(false),
+                    Flag::Label => label = value,
+                    Flag::Heading => heading = true,
+                    Flag::Table => table = true,
+                    Flag::Color => color = ColorChoice::Auto,
+                    Flag::ColorWhen => {
+                        color = ColorChoice::parse(&value).ok_or_else(|| {
+                            format!(
+                                "Invalid value for --color: {} (expected {})",
+                                value,
+                                options::COLOR_WHEN.join(", ")
+                            )
+                        })?
+                    }
+                    Flag::Colors => colors.push(value),
+                    Flag::Hyperlink => {
+                        hyperlink = ColorChoice::parse(&value).ok_or_else(|| {
+                            format!(
+                                "Invalid value for --hyperlink: {} (expected {})",
+                                value,
+                                options::COLOR_WHEN.join(", ")
+                            )
+                        })?
+                    }
+                    Flag::HyperlinkFormat => {
+                        hyperlink_format = Some(HyperlinkFormat::parse(&value)?)
+                    }
+                    Flag::ContextDim => context_dim = true,
+                    Flag::MaxCount => max_count = Some(parse_number(&value, &arg)?),
+                    Flag::MaxTotal => max_total = Some(parse_number(&value, &arg)?),
+                    Flag::FilesWithMatches => list_files = true,
+                    Flag::FilesWithoutMatch => files_without_match = true,
+                    Flag::Count => count = true,
+                    Flag::CountMatches => {
+                        count = true;
+                        count_matches = true;
+                    }
+                    Flag::Total => total = true,
+                    Flag::Unique => unique = true,
+                    Flag::GroupByDir => group_by_dir = true,
+                    Flag::Summary => summary = true,
+                    Flag::Stats => stats = true,
+                    Flag::LineBuffered => line_buffered = true,
+                    Flag::Watch => watch = true,
+                    Flag::Tail => tail = true,
+                    Flag::Tui => tui = true,
+                    Flag::Quiet => quiet = true,
+                    Flag::NoMessages => no_messages = true,
+                    Flag::Null => null_terminated = true,
+                    Flag::NullData => null_data = true,
+                    Flag::AfterContext => after_context = parse_number(&value, &arg)?,
+                    Flag::BeforeContext => before_context = parse_number(&value, &arg)?,
+                    Flag::Context => {
+                        after_context = parse_number(&value, &arg)?;
+                        before_context = after_context;
+                    }
+                    Flag::FieldSeparator => field_separator = value,
+                    Flag::ContextSeparator => group_separator = Some(value),
+                    Flag::NoGroupSeparator => group_separator = None,
+                    Flag::MaxColumns => max_columns = parse_number(&value, "--max-columns")?,
+                    Flag::MaxColumnsPreview => max_columns_preview = true,
+                    Flag::OnlyMatching => only_matching = true,
+                    Flag::OContext => o_context = parse_number(&value, "--o-context")?,
+                    Flag::Multiline => multiline = true,
+                    Flag::MaxBuffer => max_buffer = parse_number(&value, "--max-buffer")?,
+                    Flag::MaxMemory => max_memory = Some(parse_size(&value, "--max-memory")?),
+                    Flag::Pattern => {
+                        patterns.push(value);
+                        patterns_given = true;
+                    }
+                    Flag::PatternFile => {
+                        patterns.extend(read_patterns(&value, &mut read)?);
+                        patterns_given = true;
+                    }
+                    Flag::PatternFrom => {
+                        pattern_from_stdin |= value == STDIN_PATH;
+                        patterns.push(read_pattern(&value, &mut read)?);
+                        patterns_given = true;
+                    }
+                    Flag::Rules => {
+                        rule_specs.extend(read_rules(&value, &mut read)?);
+                        patterns_given = true;
+                    }
+                    Flag::AllOf => all_of.push(value),
+                    Flag::Within => within = Some(value),
+                    Flag::WithinEnd => within_end = Some(value),
+                    Flag::AnyOf => any_of.push(value),
+                    Flag::NoneOf => none_of.push(value),
+                    Flag::WordRegexp => word = true,
+                    Flag::LineRegexp => line_regexp = true,
+                    Flag::StartsWith => starts_with = true,
+                    Flag::EndsWith => ends_with = true,
+                    Flag::Timeout => {
+                        let millis: u64 = parse_number(&value, "--timeout")?;
+                        timeout = (millis > 0).then(|| Duration::from_millis(millis));
+                    }
+                    Flag::Deadline => {
+                        let millis: u64 = parse_number(&value, "--deadline")?;
+                        deadline = (millis > 0).then(|| Duration::from_millis(millis));
+                    }
+                    // Whichever of -E, -F, and -P comes last decides.
+                    Flag::Regexp => syntax = Syntax::Regex,
+                    Flag::FixedStrings => syntax = Syntax::Literal,
+                    Flag::Pcre => syntax = Syntax::Pcre,
+                    Flag::DebugPattern => debug_pattern = true,
+                    Flag::Binary => binary = true,
+                    Flag::SearchZip => search_zip = true,
+                    Flag::Archives => archives = true,
+                    Flag::Mmap => engine = Engine::Mmap,
+                    Flag::NoMmap => engine = Engine::Stream,
+                    Flag::Engine => {
+                        engine = Engine::parse(&value).ok_or_else(|| {
+                            format!(
+                                "Invalid value for --engine: {} (expected auto, stream, or mmap)",
+                                value
+                            )
+                        })?
+                    }
+                    Flag::Trace => trace = true,
+                    Flag::Cache => cache = true,
+                    Flag::CacheClear => cache_clear = true,
+                    Flag::Normalize => normalize = true,
+                    Flag::Encoding => {
+                        encoding = Some(Encoding::parse(&value).ok_or_else(|| {
+                            format!(
+                                "Invalid value for --encoding: {} (expected {})",
+                                value,
+                                options::ENCODINGS.join(", ")
+                            )
+                        })?)
+                    }
+                    Flag::Threads => threads = parse_number(&value, "--threads")?,
+                    Flag::Replace => replace = Some(value),
+                    Flag::Write => write = true,
+                    Flag::DryRun => dry_run = true,
+                    Flag::Interactive => interactive = true,
+                    Flag::Diff => diff = true,
+                    Flag::Trim => trim = true,
+                    Flag::Json => json = true,
+                    Flag::Vimgrep => vimgrep = true,
+                    Flag::Format if value == "sarif" => {
+                        sarif = true;
+                        format = None;
+                    }
+                    Flag::Format => {
+                        sarif = false;
+                        format = Some(Template::parse(&value)?);
+                    }
+                    Flag::LineRange => line_range = Some(parse_line_range(&value)?),
+                    Flag::HeadLines => head_lines = Some(parse_number(&value, "--head-lines")?),
+                    Flag::HeadBytes => head_bytes = Some(parse_size(&value, "--head-bytes")?),
+                }
+                continue;
+            }
+
+            if !options_done && arg.starts_with('-') && arg != STDIN_PATH {
+                // A pattern or file that starts with `-` goes after `--`, or after `-e`.
+                return Err(match arg.split_once('=') {
+                    Some((name, _)) if arg.starts_with("--") && options::lookup(name).is_some() => {
+                        format!("Option {} does not take a value.", name)
+                    }
+                    _ => format!("Unknown option {}. Use -h for help.", arg),
+                }
+                .into());
+            }
+            positionals.push(arg);
+        }
+
+        // Listed once every --type-add has been seen, wherever it came on the command line.
+        if type_list {
+            print!("{}", registry.list());
+            return Ok(ParseOutcome::HelpPrinted);
+        }
+        if cache_clear {
+            cache::clear()?;
+            return Ok(ParseOutcome::HelpPrinted);
+        }
+
+        // Without -e or --file, the first positional argument is the pattern (none for --files
+        // or --explain, or for --serve, whose requests bring their own).
+        let mut positionals = positionals.into_iter();
+        let building = index == Some(IndexMode::Build);
+        let listing = files_only || !explain.is_empty();
+        if !patterns_given && !listing && serve.is_none() && !building {
+            patterns.push(
+                positionals
+                    .next()
+                    .ok_or_else(|| "Missing search pattern.".to_string())?,
+            );
+        }
+        if !rule_specs.is_empty() {
+            if !patterns.is_empty() {
+                return Err(
+                    "--rules cannot be combined with -e, --file, or --pattern-from."
+                        .to_string()
+                        .into(),
+                );
+            }
+            patterns = rule_specs.iter().map(|rule| rule.pattern.clone()).collect();
+            // Rules are regexes, if not already look-around ones.
+            if syntax == Syntax::Literal {
+                syntax = Syntax::Regex;
+            }
+        }
+        let mut inputs: Vec<String> = positionals.collect();
+        if inputs.is_empty() && !files_from {
+            // There is nothing to list or serve from standard input, so these look here instead.
+            let default = if listing || git || serve.is_some() || index.is_some() {
+                "."
+            } else {
+                STDIN_PATH
+            };
+            inputs.push(default.to_string());
+        }
+        if pattern_from_stdin && inputs.iter().any(|input| input == STDIN_PATH) {
+            return Err(
+                "--pattern-from - reads the pattern from standard input, so it cannot be searched too."
+                    .to_string()
+                    .into(),
+            );
+        }
+        let mut theme = Theme::default();
+        if let Ok(spec) = env::var("GREP_COLORS") {
+            // A malformed variable is ignored, as GNU grep does; --colors is checked below.
+            let mut from_env = theme.clone();
+            if from_env.apply(&spec).is_ok() {
+                theme = from_env;
+            }
+        }
+        for spec in &colors {
+            theme
+                .apply(spec)
+                .map_err(|error| format!("Invalid value for --colors: {}", error))?;
+        }
+        let colored = color.enabled();
+        // Unlike colour, links take no notice of NO_COLOR and the like.
+        let linked = match hyperlink {
+            ColorChoice::Always => true,
+            ColorChoice::Auto => io::stdout().is_terminal(),
+            ColorChoice::Never => false,
+        };
+
+        let show_filenames = with_filename.unwrap_or_else(|| {
+            inputs.len() + listed_files.len() > 1
+                || (recursive && inputs.iter().any(|input| Path::new(input).is_dir()))
+                || (archives
+                    && inputs
+                        .iter()
+                        .any(|input| Archive::of(Path::new(input)).is_some()))
+        });
+
+        if line_regexp && (starts_with || ends_with) {
+            return Err(
+                "-x already anchors both ends; it cannot be combined with --starts-with or --ends-with."
+                    .to_string()
+                    .into(),
+            );
+        }
+
+        let include = GlobSet::new(&include)?;
+        let exclude = GlobSet::new(&exclude)?;
+        let types = GlobSet::new(&registry.globs(&types)?)?;
+        let types_not = GlobSet::new(&registry.globs(&types_not)?)?;
+
+        if normalize {
+            let filters = all_of.iter_mut().chain(&mut any_of).chain(&mut none_of);
+            for pattern in patterns.iter_mut().chain(filters) {
+                *pattern = normalize::nfc(pattern).into_owned();
+            }
+        }
+        if smart_case {
+            let literal = syntax == Syntax::Literal;
+            case_insensitive = !patterns
+                .iter()
+                .any(|pattern| has_uppercase(pattern, literal));
+        }
+        let options = MatchOptions {
+            case_insensitive,
+            multiline,
+            word,
+            starts_with: starts_with || line_regexp,
+            ends_with: ends_with || line_regexp,
+        };
+        let pattern = PatternSpec {
+            patterns,
+            syntax,
+            options,
+        };
+        let matcher = pattern.compile().map_err(SearchError::InvalidPattern)?;
+        let rules = rule_specs
+            .into_iter()
+            .zip(&pattern.patterns)
+            .map(|(rule, pattern)| rule.compile(pattern.clone(), syntax, options))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(SearchError::InvalidPattern)?;
+        let compile_each = |patterns: Vec<String>| {
+            patterns
+                .into_iter()
+                .map(|pattern| {
+                    PatternSpec {
+                        patterns: vec![pattern],
+                        syntax,
+                        options,
+                    }
+                    .compile()
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(SearchError::InvalidPattern)
+        };
+        let filter = LineFilter {
+            all: compile_each(all_of)?,
+            any: compile_each(any_of)?,
+            none: compile_each(none_of)?,
+        };
+        // Region markers are regexes of their own, whatever the main pattern's syntax and case.
+        let marker = |flag: &str, pattern: &str| {
+            Matcher::standard(pattern, MatchOptions::default()).map_err(|error| {
+                SearchError::InvalidPattern(format!("Invalid {} pattern: {}", flag, error))
+            })
+        };
+        let within = match (within, within_end) {
+            (Some(start), end) => Some(Within {
+                start: marker("--within", &start)?,
+                end: end.map(|end| marker("--within-end", &end)).transpose()?,
+            }),
+            (None, Some(_)) => {
+                return Err("--within-end needs --within to open the regions it ends."
+                    .to_string()
+                    .into());
+            }
+            (None, None) => None,
+        };
+
+        let config = Config {
+            inputs,
+            // Headings drop the filename from each line, so the line number locates it instead.
+            show_line_numbers: show_line_numbers || heading,
+            byte_offset,
+            column,
+            byte_columns,
+            invert_match,
+            recursive,
+            sort,
+            include,
+            exclude,
+            types,
+            types_not,
+            listed_files,
+            files_only,
+            explain,
+            serve,
+            search_names,
+            names_only,
+            index,
+            hidden,
+            no_ignore,
+            ignore_file: ignore_rules,
+            git,
+            follow,
+            no_dedupe,
+            max_depth,
+            max_filesize,
+            newer_than,
+            older_than,
+            read_devices,
+            verbose,
+            show_filenames,
+            label,
+            heading,
+            table,
+            colored,
+            hyperlink: linked.then(|| hyperlink_format.unwrap_or_default()),
+            theme,
+            context_dim,
+            max_count,
+            max_total,
+            list_files,
+            files_without_match,
+            count,
+            count_matches,
+            total,
+            unique,
+            group_by_dir,
+            summary,
+            stats,
+            line_buffered,
+            watch,
+            tail,
+            tui,
+            quiet,
+            null_terminated,
+            null_data,
+            no_messages,
+            after_context,
+            before_context,
+            field_separator,
+            group_separator,
+            max_columns,
+            max_columns_preview,
+            replace,
+            write,
+            dry_run,
+            interactive,
+            diff,
+            trim,
+            json,
+            vimgrep,
+            format,
+            sarif,
+            only_matching,
+            o_context,
+            multiline,
+            line_range,
+            head_lines,
+            head_bytes,
+            max_buffer,
+            max_memory,
+            binary,
+            encoding,
+            normalize,
+            search_zip,
+            archives,
+            engine,
+            trace,
+            cache: cache.then(|| cache::key(&given, &read, colored, linked)),
+            threads,
+            timeout,
+            deadline,
+            debug_pattern,
+            pattern,
+            matcher,
+            rules,
+            filter,
+            within,
+        };
+        for note in config.validate()? {
+            if !config.no_messages {
+                eprintln!("Warning: {}", note);
+            }
+        }
+        if config.tui && !(io::stdin().is_terminal() && io::stdout().is_terminal()) {
+            return Err("--tui needs a terminal.".to_string().into());
+        }
+        Ok(ParseOutcome::Run(config))
+    }
+
+    /// Checks that the options make sense together. Options that cannot be combined are an
+    /// error; for options that would silently do nothing, a note saying so is returned.
+    pub fn validate(&self) -> Result<Vec<String>, SearchError> {
+        if self.write && self.replace.is_none() {
+            return Err("--write needs --replace to say what to write."
+                .to_string()
+                .into());
+        }
+        if self.dry_run && !self.write {
+            return Err("--dry-run only applies to --write.".to_string().into());
+        }
+        if self.interactive && (!self.write || self.dry_run) {
+            return Err(
+                "--interactive only applies to --write, and cannot be combined with --dry-run."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.interactive && self.inputs.iter().any(|input| input == STDIN_PATH) {
+            return Err(
+                "--interactive reads answers from standard input, so it needs files to rewrite."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.table
+            && (self.json
+                || self.sarif
+                || self.vimgrep
+                || self.format.is_some()
+                || !self.rules.is_empty()
+                || self.tui)
+        {
+            return Err(
+                "--table cannot be combined with --json, --format, --vimgrep, --rules, or --tui."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.vimgrep && self.json {
+            return Err("--vimgrep cannot be combined with --json."
+                .to_string()
+                .into());
+        }
+        if (self.format.is_some() || self.sarif)
+            && (self.vimgrep || self.json || self.replace.is_some())
+        {
+            return Err(
+                "--format cannot be combined with --vimgrep, --json, or --replace."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.sarif
+            && (self.count
+                || self.list_files
+                || self.files_without_match
+                || self.group_by_dir
+                || self.write
+                || self.diff
+                || self.tui
+                || self.tail
+                || self.files_only
+                || self.search_names)
+        {
+            return Err("--format sarif cannot be combined with --count, -l, -L, --group-by-dir, --write, --diff, --tui, --tail, --files, or --search-names."
+            .to_string()
+            .into());
+        }
+        if self.diff && self.replace.is_none() {
+            return Err("--diff needs --replace to know what would change."
+                .to_string()
+                .into());
+        }
+        if self.count && (self.list_files || self.files_without_match) {
+            return Err(
+                "--count cannot be combined with -l or -L, which print names, not counts."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.invert_match && self.only_matching {
+            return Err(
+                "-o prints matches, but -v selects lines without any; they cannot be combined."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.write && (self.invert_match || self.multiline) {
+            return Err("--write cannot be combined with -v or --multiline."
+                .to_string()
+                .into());
+        }
+        if self.diff && (self.invert_match || self.multiline) {
+            return Err("--diff cannot be combined with -v or --multiline."
+                .to_string()
+                .into());
+        }
+        if (self.write || self.diff) && (self.head_lines.is_some() || self.head_bytes.is_some()) {
+            return Err(
+                "--head-lines and --head-bytes cannot be combined with --write or --diff."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.watch && self.inputs.iter().any(|input| input == STDIN_PATH) {
+            return Err(
+                "--watch needs files to watch; it cannot watch standard input."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.tui && self.inputs.iter().any(|input| input == STDIN_PATH) {
+            return Err(
+                "--tui reads keys from standard input, so it needs files to search."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.index.is_some() {
+            if self.inputs.len() != 1 || !self.listed_files.is_empty() {
+                return Err("grep index works on a single directory.".to_string().into());
+            }
+            if self.watch
+                || self.tail
+                || self.tui
+                || self.write
+                || self.files_only
+                || self.serve.is_some()
+            {
+                return Err(
+                "grep index cannot be combined with --watch, --tail, --tui, --write, --files, or --serve."
+                    .to_string()
+                    .into(),
+            );
+            }
+        }
+        if self.search_names
+            && (self.count
+                || self.list_files
+                || self.files_without_match
+                || self.invert_match
+                || self.write
+                || self.diff
+                || self.tui
+                || self.tail
+                || self.files_only
+                || self.vimgrep
+                || self.format.is_some()
+                || self.max_total.is_some())
+        {
+            return Err("--search-names and --names-only cannot be combined with --count, -l, -L, -v, --write, --diff, --tui, --tail, --files, --vimgrep, --format, or --max-total."
+            .to_string()
+            .into());
+        }
+        if self.serve.is_some()
+            && (self.watch || self.tail || self.tui || self.write || self.files_only)
+        {
+            return Err(
+                "--serve cannot be combined with --watch, --tail, --tui, --write, or --files."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if !self.explain.is_empty()
+            && (self.serve.is_some() || self.watch || self.tail || self.tui || self.write)
+        {
+            return Err(
+                "--explain cannot be combined with --serve, --watch, --tail, --tui, or --write."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.tui && (self.watch || self.write || self.files_only) {
+            return Err(
+                "--tui cannot be combined with --watch, --write, or --files."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.tail && self.inputs.iter().any(|input| input == STDIN_PATH) {
+            return Err(
+                "--tail needs files to follow; standard input is already read as it arrives."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.tail && (self.watch || self.write || self.tui || self.files_only) {
+            return Err(
+                "--tail cannot be combined with --watch, --write, --tui, or --files."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if self.tail && (self.count || self.list_files || self.files_without_match) {
+            return Err(
+            "--tail prints lines as they arrive, so it cannot be combined with --count, -l, or -L."
+                .to_string()
+                .into(),
+        );
+        }
+        if self.group_by_dir
+            && (self.count
+                || self.list_files
+                || self.files_without_match
+                || self.write
+                || self.diff
+                || self.tui
+                || self.tail
+                || self.files_only
+                || self.json)
+        {
+            return Err("--group-by-dir cannot be combined with --count, -l, -L, --write, --diff, --tui, --tail, --files, or --json."
+            .to_string()
+            .into());
+        }
+        let filtered = !self.filter.is_empty();
+        if filtered && self.multiline {
+            return Err(
+            "--all-of, --any-of, and --none-of filter lines, so they cannot be combined with --multiline."
+                .to_string()
+                .into(),
+        );
+        }
+        if !self.rules.is_empty()
+            && (filtered
+                || self.invert_match
+                || self.count
+                || self.list_files
+                || self.files_without_match
+                || self.json
+                || self.vimgrep
+                || self.format.is_some()
+                || self.sarif
+                || self.replace.is_some()
+                || self.write
+                || self.diff
+                || self.tui
+                || self.group_by_dir
+                || self.archives
+                || self.search_names)
+        {
+            return Err("--rules cannot be combined with --all-of, --any-of, --none-of, -v, --count, -l, -L, --json, --vimgrep, --format, --replace, --write, --diff, --tui, --group-by-dir, --archives, or --search-names."
+            .to_string()
+            .into());
+        }
+        if self.max_total.is_some() && (self.write || self.diff) {
+            return Err("--max-total cannot be combined with --write or --diff."
+                .to_string()
+                .into());
+        }
+        // Each rewrite would be a change to search (and rewrite) again.
+        if self.watch && self.write {
+            return Err("--watch cannot be combined with --write."
+                .to_string()
+                .into());
+        }
+
+        let mut notes = Vec::new();
+        // Options such as these may come from the configuration file or `SEARCH_UTILITY_OPTS`,
+        // so they are only noted, never refused.
+        if self.total && !self.count {
+            notes.push("--total has no effect without --count.".to_string());
+        }
+        if self.cache.is_some() && (self.write || self.max_total.is_some()) {
+            notes.push("--cache has no effect with --write or --max-total.".to_string());
+        }
+        if self.max_columns_preview && self.max_columns == 0 {
+            notes.push("--max-columns-preview has no effect without --max-columns.".to_string());
+        }
+        if self.json && (self.count || self.list_files || self.files_without_match) {
+            notes.push(
+                "--json has no effect with --count, -l, or -L, which print as they do without it."
+                    .to_string(),
+            );
+        }
+        if self.colored && (self.json || self.sarif) {
+            notes.push(
+                "-c and --color have no effect with --json or --format sarif, which are never coloured."
+                    .to_string(),
+            );
+        }
+        if (self.before_context > 0 || self.after_context > 0)
+            && (self.only_matching
+                || self.count
+                || self.list_files
+                || self.files_without_match
+                || self.quiet)
+        {
+            notes.push(
+                "-A, -B, and -C have no effect with -o, --count, -l, -L, or -q, which print no context lines."
+                    .to_string(),
+            );
+        }
+        if self.recursive
+            && !self.git
+            && self.serve.is_none()
+            && self.index.is_none()
+            && self.listed_files.is_empty()
+            && self
+                .inputs
+                .iter()
+                .all(|input| input != STDIN_PATH && Path::new(input).is_file())
+        {
+            notes.push(
+                "-r has no effect when only files are given; name a directory to search it recursively."
+                    .to_string(),
+            );
+        }
+        Ok(notes)
+    }
+
+    /// The byte that ends each input and output record: NUL with `--null-data`, else newline.
+    pub(crate) fn record_terminator(&self) -> u8 {
+        if self.null_data { b'\0' } else { b'\n' }
+    }
+}
+
+/// For `--smart-case`: whether `pattern` asks for an uppercase letter. In a regex, escapes such
+/// as `\S` or `\p{Lu}` are syntax rather than letters to match, so they do not count.
+fn has_uppercase(pattern: &str, literal: bool) -> bool {
+    if literal {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            // The escaped character is skipped too; after `\p` or `\P` comes a class name,
+            // either one letter or in braces.
+            if let Some('p' | 'P') = chars.next()
+                && chars.next() == Some('{')
+            {
+                chars.by_ref().find(|&c| c == '}');
+            }
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Reads `--rules`, whose problems are reported with the line they are on.
+fn read_rules(path: &str, read: &mut Vec<String>) -> Result<Vec<RuleSpec>, SearchError> {
+    let contents =
+        fs::read_to_string(path).map_err(|err| SearchError::io("rules file", path, err))?;
+    read.push(contents.clone());
+    rules::parse(&contents)
+        .map_err(|problem| format!("Invalid rules file {}: {}", path, problem).into())
+}
+
+/// Reads `--file`: one pattern per line.
+fn read_patterns(path: &str, read: &mut Vec<String>) -> Result<Vec<String>, SearchError> {
+    let contents =
+        fs::read_to_string(path).map_err(|err| SearchError::io("pattern file", path, err))?;
+    read.push(contents.clone());
+    Ok(contents
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+        .collect())
+}
+
+/// Reads `--pattern-from`: the whole file is one pattern, spaces and all. Only the newline that
+/// ends the file is dropped, since a line's terminator is never part of what it matches.
+fn read_pattern(path: &str, read: &mut Vec<String>) -> Result<String, SearchError> {
+    let mut contents = if path == STDIN_PATH {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(path)
+    }
+    .map_err(|err| SearchError::io("pattern file", path, err))?;
+    read.push(contents.clone());
+    if contents.ends_with('\n') {
+        contents.pop();
+        if contents.ends_with('\r') {
+            contents.pop();
+        }
+    }
+    Ok(contents)
+}
+
+/// Reads `--files-from`: one path per line, or per NUL-terminated record if there are any NULs
+/// (as `git ls-files -z` and `find -print0` write). `-` reads standard input.
+fn read_file_list(path: &str, read: &mut Vec<String>) -> Result<Vec<String>, SearchError> {
+    let contents = if path == STDIN_PATH {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(path)
+    }
+    .map_err(|err| SearchError::io("file list", path, err))?;
+    read.push(contents.clone());
+    let entries: Vec<&str> = if contents.contains('\0') {
+        contents.split('\0').collect()
+    } else {
+        contents
+            .lines()
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect()
+    };
+    Ok(entries
+        .into_iter()
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn next_value(args: &mut VecDeque<String>, flag: &str) -> Result<String, String> {
+    args.pop_front()
+        .ok_or_else(|| format!("Missing value for {}.", flag))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, flag: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+/// Parses a size such as `512`, `64K`, `10M`, or `2G`, in bytes (with 1K as 1024).
+fn parse_size(value: &str, flag: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid value for {}: {}", flag, value);
+    let (digits, unit) = match value.char_indices().last() {
+        Some((at, c)) if c.is_ascii_alphabetic() => (&value[..at], c.to_ascii_uppercase()),
+        _ => (value, 'B'),
+    };
+    let shift = match unit {
+        'B' => 0,
+        'K' => 10,
+        'M' => 20,
+        'G' => 30,
+        _ => return Err(invalid()),
+    };
+    let number: u64 = digits.parse().map_err(|_| invalid())?;
+    number.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+/// Parses an age such as `90s`, `30m`, `2d`, or `1h30m`: numbers each followed by `s`, `m`,
+/// `h`, `d`, or `w`.
+fn parse_age(value: &str, flag: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid value for {}: {} (expected an age such as 2d or 3h)",
+            flag, value
+        )
+    };
+    if value.is_empty() {
+        return Err(invalid());
+    }
+    let mut seconds: u64 = 0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let number: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = match rest[digits..].chars().next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 60 * 60,
+            Some('d') => 24 * 60 * 60,
+            Some('w') => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        seconds = number
+            .checked_mul(unit)
+            .and_then(|part| seconds.checked_add(part))
+            .ok_or_else(invalid)?;
+        rest = &rest[digits + 1..];
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Parses `START:END`, where either side may be empty for an open end.
+fn parse_line_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let invalid = |reason: &str| format!("Invalid value for --line-range: {} ({})", value, reason);
+    let (start, end) = value
+        .split_once(':')
+        .ok_or_else(|| invalid("expected START:END"))?;
+    let bound = |text: &str, open: usize| match text {
+        "" => Ok(open),
+        text => match text.parse::<usize>() {
+            Ok(0) => Err(invalid("line numbers start at 1")),
+            Ok(line) => Ok(line),
+            Err(_) => Err(invalid("expected START:END")),
+        },
+    };
+    let start = bound(start, 1)?;
+    let end = bound(end, usize::MAX)?;
+    if start > end {
+        return Err(invalid("START is after END"));
+    }
+    Ok(start..=end)
+}
+
+pub(crate) fn is_short_bundle(arg: &str) -> bool {
+    arg.len() > 2 && arg.starts_with('-') && !arg.starts_with("--")
+}
+
+/// Splits a bundle such as `-inr` into separate flags. A flag that takes a value ends the
+/// bundle, and any remaining characters become its value.
+pub(crate) fn expand_short_bundle(arg: &str) -> Result<Vec<String>, String> {
+    let mut flags = Vec::new();
+    for (index, flag) in arg.char_indices().skip(1) {
+        let spec = options::lookup_short(flag)
+            .ok_or_else(|| format!("Unknown option -{} in {}.", flag, arg))?;
+        flags.push(format!("-{}", flag));
+        if spec.value.is_some() {
+            let rest = &arg[index + flag.len_utf8()..];
+            if !rest.is_empty() {
+                flags.push(rest.to_string());
+            }
+            break;
+        }
+    }
+    Ok(flags)
+}
@@ -0,0 +1,115 @@
+//! `-z`: reading compressed files through the system's decompression tools.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+/// The program that decompresses `path` to stdout, chosen by extension and then by magic bytes;
+/// `None` for files that do not look compressed. Only regular files are sniffed, since taking
+/// the magic bytes from a pipe would lose them.
+pub(crate) fn program_for(path: &Path) -> io::Result<Option<&'static str>> {
+    let by_extension = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz" | "tgz") => Some("gzip"),
+        Some("bz2" | "tbz2") => Some("bzip2"),
+        Some("xz" | "txz") => Some("xz"),
+        _ => None,
+    };
+    if by_extension.is_some() || !path.metadata()?.is_file() {
+        return Ok(by_extension);
+    }
+    let mut magic = [0; 6];
+    let mut file = File::open(path)?;
+    let mut read = 0;
+    while read < magic.len() {
+        match file.read(&mut magic[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    let magic = &magic[..read];
+    Ok(if magic.starts_with(&[0x1F, 0x8B]) {
+        Some("gzip")
+    } else if magic.starts_with(b"BZh") {
+        Some("bzip2")
+    } else if magic.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0]) {
+        Some("xz")
+    } else {
+        None
+    })
+}
+
+/// A running decompressor whose output is read as the file's contents. Its stderr is read on a
+/// thread of its own, so that it never fills the pipe and stalls the decompressor, and it is
+/// waited on when dropped, however the search ended.
+pub(crate) struct Decoder {
+    program: &'static str,
+    child: Child,
+    pub(crate) output: BufReader<ChildStdout>,
+    stderr: Option<JoinHandle<String>>,
+}
+
+impl Decoder {
+    pub(crate) fn spawn(program: &'static str, path: &Path) -> io::Result<Decoder> {
+        Decoder::run(program, Command::new(program).args(["-dc", "--"]).arg(path))
+    }
+
+    /// Runs `command`, an invocation of `program` that writes the wanted contents to stdout.
+    pub(crate) fn run(program: &'static str, command: &mut Command) -> io::Result<Decoder> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| {
+                io::Error::new(error.kind(), format!("cannot run {}: {}", program, error))
+            })?;
+        let output = BufReader::new(child.stdout.take().expect("piped stdout"));
+        let mut stderr = child.stderr.take().expect("piped stderr");
+        let stderr = thread::spawn(move || {
+            let mut message = String::new();
+            let _ = stderr.read_to_string(&mut message);
+            message
+        });
+        Ok(Decoder {
+            program,
+            child,
+            output,
+            stderr: Some(stderr),
+        })
+    }
+
+    /// Waits for the decompressor, turning a failure (such as a corrupt archive) into an error.
+    /// A search that stopped early stops the decompressor too, without complaint.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if !self.output.fill_buf()?.is_empty() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            return Ok(());
+        }
+        let status = self.child.wait()?;
+        if status.success() {
+            return Ok(());
+        }
+        let message = self
+            .stderr
+            .take()
+            .and_then(|stderr| stderr.join().ok())
+            .unwrap_or_default();
+        Err(io::Error::other(format!(
+            "{} failed: {}",
+            self.program,
+            message.trim()
+        )))
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
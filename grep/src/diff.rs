@@ -0,0 +1,111 @@
+//! Unified diffs of line-for-line replacements, for `--diff` and `--dry-run`.
+
+use crate::config::Config;
+use crate::theme::paint;
+use std::io::{self, Write};
+
+/// Unchanged lines shown around each change, as `diff -u` does.
+const CONTEXT: usize = 3;
+
+/// Colours for the parts of a diff, like `git diff`'s.
+const HEADER: &str = "1";
+const HUNK: &str = "36";
+const REMOVED: &str = "31";
+const ADDED: &str = "32";
+
+/// Writes the diff of `lines` against a copy with each `(index, replacement)` of `changes`
+/// applied, under a `--- a/name` / `+++ b/name` header. `changes` are in line order.
+pub(crate) fn write_unified(
+    out: &mut impl Write,
+    name: &str,
+    lines: &[&str],
+    changes: &[(usize, String)],
+    config: &Config,
+) -> io::Result<()> {
+    write_header(out, name, config)?;
+    let mut rest = changes;
+    while !rest.is_empty() {
+        // A hunk runs until the gap to the next change is too wide for their contexts to meet.
+        let mut taken = 1;
+        while taken < rest.len() && rest[taken].0 - rest[taken - 1].0 <= 2 * CONTEXT + 1 {
+            taken += 1;
+        }
+        let (hunk, after) = rest.split_at(taken);
+        rest = after;
+        write_hunk(out, lines, hunk, config)?;
+    }
+    Ok(())
+}
+
+/// Writes the `--- a/name` / `+++ b/name` lines that start a file's diff.
+pub(crate) fn write_header(out: &mut impl Write, name: &str, config: &Config) -> io::Result<()> {
+    writeln!(
+        out,
+        "{}",
+        painter(config)(&format!("--- a/{}", name), HEADER)
+    )?;
+    writeln!(
+        out,
+        "{}",
+        painter(config)(&format!("+++ b/{}", name), HEADER)
+    )
+}
+
+/// Writes one hunk holding every change in `hunk`, which must be non-empty and in line order,
+/// with the unchanged lines around them.
+pub(crate) fn write_hunk(
+    out: &mut impl Write,
+    lines: &[&str],
+    hunk: &[(usize, String)],
+    config: &Config,
+) -> io::Result<()> {
+    let paint = painter(config);
+    let start = hunk[0].0.saturating_sub(CONTEXT);
+    let end = (hunk[hunk.len() - 1].0 + CONTEXT + 1).min(lines.len());
+
+    let range = |start: usize, len: usize| match len {
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    };
+    let span = range(start, end - start);
+    writeln!(
+        out,
+        "{}",
+        paint(&format!("@@ -{} +{} @@", span, span), HUNK)
+    )?;
+
+    let mut at = start;
+    let mut pending = hunk;
+    while at < end {
+        // Consecutive changed lines print all their removals before their additions.
+        let run = pending
+            .iter()
+            .enumerate()
+            .take_while(|&(offset, &(index, _))| index == at + offset)
+            .count();
+        if run == 0 {
+            writeln!(out, " {}", lines[at])?;
+            at += 1;
+            continue;
+        }
+        for &(index, _) in &pending[..run] {
+            writeln!(out, "{}", paint(&format!("-{}", lines[index]), REMOVED))?;
+        }
+        for (_, replaced) in &pending[..run] {
+            writeln!(out, "{}", paint(&format!("+{}", replaced), ADDED))?;
+        }
+        pending = &pending[run..];
+        at += run;
+    }
+    Ok(())
+}
+
+fn painter(config: &Config) -> impl Fn(&str, &str) -> String + '_ {
+    move |text, sgr| {
+        if config.colored {
+            paint(text, sgr)
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Transcoding of non-UTF-8 inputs, so patterns match whatever the file's on-disk encoding.
+
+use std::io::{self, BufRead, Read};
+
+/// Text encoding of an input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1: every byte is the code point of the same value.
+    Latin1,
+}
+
+impl Encoding {
+    pub fn parse(value: &str) -> Option<Encoding> {
+        match value.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// Detects a byte-order mark at the start of `head`, returning the encoding and the mark's
+    /// length in bytes.
+    pub(crate) fn sniff(head: &[u8]) -> Option<(Encoding, usize)> {
+        if head.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            Some((Encoding::Utf8, 3))
+        } else if head.starts_with(&[0xFF, 0xFE]) {
+            Some((Encoding::Utf16Le, 2))
+        } else if head.starts_with(&[0xFE, 0xFF]) {
+            Some((Encoding::Utf16Be, 2))
+        } else {
+            None
+        }
+    }
+}
+
+/// Wraps a reader in some other encoding, yielding its text as UTF-8. Malformed input (such as
+/// an unpaired surrogate or a trailing odd byte in UTF-16) becomes `U+FFFD`.
+pub(crate) struct Transcoder<R> {
+    inner: R,
+    encoding: Encoding,
+    decoded: Vec<u8>,
+    position: usize,
+    /// A high surrogate whose partner has not been read yet.
+    pending: Option<u16>,
+}
+
+impl<R: BufRead> Transcoder<R> {
+    pub(crate) fn new(inner: R, encoding: Encoding) -> Self {
+        Transcoder {
+            inner,
+            encoding,
+            decoded: Vec::new(),
+            position: 0,
+            pending: None,
+        }
+    }
+
+    /// Decodes the next block of input into `decoded`; leaves it empty only at the end.
+    fn refill(&mut self) -> io::Result<()> {
+        self.decoded.clear();
+        self.position = 0;
+        while self.decoded.is_empty() {
+            let block = self.inner.fill_buf()?;
+            if block.is_empty() {
+                if self.pending.take().is_some() {
+                    push_char(&mut self.decoded, char::REPLACEMENT_CHARACTER);
+                }
+                return Ok(());
+            }
+            let used = match self.encoding {
+                Encoding::Utf8 => {
+                    self.decoded.extend_from_slice(block);
+                    block.len()
+                }
+                Encoding::Latin1 => {
+                    for &byte in block {
+                        push_char(&mut self.decoded, char::from(byte));
+                    }
+                    block.len()
+                }
+                Encoding::Utf16Le | Encoding::Utf16Be => {
+                    let used = block.len() - block.len() % 2;
+                    if used == 0 {
+                        // A lone byte: either the next read completes it or the input ends on it.
+                        let byte = block[0];
+                        self.inner.consume(1);
+                        let mut pair = [byte, 0];
+                        if self.inner.read(&mut pair[1..])? == 0 {
+                            push_char(&mut self.decoded, char::REPLACEMENT_CHARACTER);
+                            continue;
+                        }
+                        self.decode_units(&pair);
+                        continue;
+                    }
+                    let block = block[..used].to_vec();
+                    self.decode_units(&block);
+                    used
+                }
+            };
+            self.inner.consume(used);
+        }
+        Ok(())
+    }
+
+    fn decode_units(&mut self, bytes: &[u8]) {
+        let big_endian = self.encoding == Encoding::Utf16Be;
+        let mut units: Vec<u16> = self.pending.take().into_iter().collect();
+        units.extend(bytes.chunks_exact(2).map(|pair| {
+            if big_endian {
+                u16::from_be_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_le_bytes([pair[0], pair[1]])
+            }
+        }));
+        // Hold back a trailing high surrogate until its partner arrives.
+        if let Some(&last) = units.last()
+            && (0xD800..0xDC00).contains(&last)
+        {
+            self.pending = units.pop();
+        }
+        for decoded in char::decode_utf16(units) {
+            push_char(
+                &mut self.decoded,
+                decoded.unwrap_or(char::REPLACEMENT_CHARACTER),
+            );
+        }
+    }
+}
+
+fn push_char(out: &mut Vec<u8>, c: char) {
+    let mut buffer = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+}
+
+impl<R: BufRead> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Transcoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.decoded.len() {
+            self.refill()?;
+        }
+        Ok(&self.decoded[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.decoded.len());
+    }
+}
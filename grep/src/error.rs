@@ -0,0 +1,71 @@
+//! Errors that stop a run before (or instead of) searching.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Why a configuration could not be built or a run could not start.
+#[derive(Debug)]
+pub enum SearchError {
+    /// An option, its value, or a combination of options was not accepted.
+    InvalidFlag(String),
+    /// A search pattern did not compile.
+    InvalidPattern(String),
+    /// A file the run depends on, such as a `--file` pattern list, could not be read.
+    Io {
+        /// What the file is for, such as `pattern file`.
+        what: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+}
+
+impl SearchError {
+    /// The CLI's exit status for this error. Like an error during the search (2), each is above
+    /// 1, so scripts treating anything past "no match" as trouble keep working.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SearchError::Io { .. } => 2,
+            SearchError::InvalidPattern(_) => 3,
+            SearchError::InvalidFlag(_) => 4,
+        }
+    }
+
+    pub(crate) fn io(what: &'static str, path: impl Into<PathBuf>, source: io::Error) -> Self {
+        SearchError::Io {
+            what,
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::InvalidFlag(message) | SearchError::InvalidPattern(message) => {
+                f.write_str(message)
+            }
+            SearchError::Io { what, path, source } => {
+                write!(f, "Cannot read {} {}: {}", what, path.display(), source)
+            }
+        }
+    }
+}
+
+impl Error for SearchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SearchError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Option-handling code reports problems as plain messages.
+impl From<String> for SearchError {
+    fn from(message: String) -> Self {
+        SearchError::InvalidFlag(message)
+    }
+}
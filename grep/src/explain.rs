@@ -0,0 +1,55 @@
+//! `--explain`: why a file would or would not be searched, found by the same checks a search
+//! makes, so that a tangle of `--include`, `--exclude`, `--type`, and ignore files can be
+//! debugged one path at a time.
+
+use crate::Summary;
+use crate::config::Config;
+use crate::printer;
+use crate::search::{BINARY_PROBE, probe_binary};
+use crate::walk;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Prints a line for every `--explain` path, as in `src/main.rs: searched` or `build/out.o: not
+/// searched: ignored by ./.gitignore line 2 (build/)`. Each path that would be searched counts as
+/// a matched file, for the exit status.
+pub(crate) fn explain(config: &Config) -> Summary {
+    let mut summary = Summary::default();
+    for path in &config.explain {
+        let name = printer::path_label(path);
+        match verdict(path, config) {
+            Ok((searched, verdict)) => {
+                summary.files_matched += usize::from(searched);
+                println!("{}: {}", name, verdict);
+            }
+            Err(error) => {
+                summary.had_errors = true;
+                if !config.no_messages {
+                    eprintln!("Error: {}: {}", name, error);
+                }
+            }
+        }
+    }
+    summary
+}
+
+/// Whether `path` would be searched, and what to say about it.
+fn verdict(path: &Path, config: &Config) -> io::Result<(bool, String)> {
+    if let Some(reason) = walk::why_skipped(path, config)? {
+        return Ok((false, format!("not searched: {}", reason)));
+    }
+    // The file is searched, but a binary one only has whether it matches reported.
+    if !config.binary && !config.null_data {
+        let mut head = Vec::new();
+        File::open(path)?
+            .take(BINARY_PROBE as u64)
+            .read_to_end(&mut head)?;
+        if probe_binary(&head) {
+            let verdict = "searched as binary, since its first block has a NUL byte, so its lines \
+                           are not printed (-a searches it as text)";
+            return Ok((true, verdict.to_string()));
+        }
+    }
+    Ok((true, "searched".to_string()))
+}
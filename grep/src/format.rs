@@ -0,0 +1,165 @@
+//! `--format`: a template printed once per match, such as `{file}:{line}: {1} -> {2}`, or one of
+//! the named table formats `csv` and `tsv`.
+
+use crate::config::Config;
+use std::ops::Range;
+
+/// A parsed `--format` template.
+pub struct Template {
+    pieces: Vec<Piece>,
+    /// For `csv` and `tsv`, how fields are separated and quoted.
+    table: Option<Table>,
+}
+
+/// A table of `path`, `line`, `column`, and `match` columns, one row per match.
+#[derive(Clone, Copy)]
+enum Table {
+    Csv,
+    Tsv,
+}
+
+impl Table {
+    fn delimiter(self) -> char {
+        match self {
+            Table::Csv => ',',
+            Table::Tsv => '\t',
+        }
+    }
+
+    /// `field` quoted as RFC 4180 asks: in double quotes, with its own doubled, if it holds the
+    /// delimiter, a quote, or a line break. Spreadsheets and pandas read TSV quoted the same way.
+    fn quote(self, field: &str, out: &mut String) {
+        if field.contains([self.delimiter(), '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+}
+
+enum Piece {
+    Text(String),
+    File,
+    Line,
+    Column,
+    /// A capture group by number or name; `0` is the whole match.
+    Group(String),
+}
+
+impl Template {
+    /// Parses `template`, where `{file}`, `{line}`, `{column}`, and `{N}` or `{name}` for a
+    /// capture group are replaced, and `{{` and `}}` stand for single braces. `csv` and `tsv`
+    /// name tables instead.
+    pub(crate) fn parse(template: &str) -> Result<Template, String> {
+        let table = match template {
+            "csv" => Some(Table::Csv),
+            "tsv" => Some(Table::Tsv),
+            _ => None,
+        };
+        if let Some(table) = table {
+            let delimiter = || Piece::Text(table.delimiter().to_string());
+            return Ok(Template {
+                pieces: vec![
+                    Piece::File,
+                    delimiter(),
+                    Piece::Line,
+                    delimiter(),
+                    Piece::Column,
+                    delimiter(),
+                    Piece::Group("0".to_string()),
+                ],
+                table: Some(table),
+            });
+        }
+        let invalid = |why: &str| format!("Invalid --format template {:?}: {}", template, why);
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(invalid("unmatched } (write }} for a brace)")),
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| invalid("unterminated { (write {{ for a brace)"))?;
+                    let field = &rest[..end];
+                    if field.is_empty() || !field.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                        return Err(invalid(&format!("{{{}}} is not a field", field)));
+                    }
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(match field {
+                        "file" => Piece::File,
+                        "line" => Piece::Line,
+                        "column" => Piece::Column,
+                        group => Piece::Group(group.to_string()),
+                    });
+                    chars = rest[end + 1..].chars();
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Template {
+            pieces,
+            table: None,
+        })
+    }
+
+    /// The row naming a table's columns, printed once before any match.
+    pub(crate) fn header(&self) -> Option<String> {
+        let table = self.table?;
+        Some(["path", "line", "column", "match"].join(&table.delimiter().to_string()))
+    }
+
+    /// The template filled in for the match at `span` of `text`. Groups are empty where they
+    /// took no part in the match, and for a line selected by `-v`, which has no `span`.
+    pub(crate) fn render(
+        &self,
+        file: &str,
+        line: usize,
+        column: usize,
+        text: &str,
+        span: Option<Range<usize>>,
+        config: &Config,
+    ) -> String {
+        let mut rendered = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => rendered.push_str(text),
+                Piece::File => self.push_field(file, &mut rendered),
+                Piece::Line => rendered.push_str(&line.to_string()),
+                Piece::Column => rendered.push_str(&column.to_string()),
+                Piece::Group(group) => {
+                    if let Some(span) = &span {
+                        let group = format!("${{{}}}", group);
+                        let value = config.matcher.expand_at(text, span.clone(), &group);
+                        self.push_field(&value, &mut rendered);
+                    }
+                }
+            }
+        }
+        rendered
+    }
+
+    fn push_field(&self, value: &str, out: &mut String) {
+        match self.table {
+            Some(table) => table.quote(value, out),
+            None => out.push_str(value),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Shell-style globs, shared by `--include`/`--exclude` and ignore files.
+
+use regex::Regex;
+
+/// A set of globs such as `*.rs`, matched against a file name, or against the whole path when
+/// the glob contains a `/`.
+#[derive(Default)]
+pub struct GlobSet {
+    /// Globs matched against the file name alone, each with the glob as given.
+    names: Vec<(Regex, String)>,
+    /// Globs matched against the whole path.
+    paths: Vec<(Regex, String)>,
+}
+
+impl GlobSet {
+    pub fn new(globs: &[String]) -> Result<GlobSet, String> {
+        let mut set = GlobSet::default();
+        for glob in globs {
+            let regex = Regex::new(&format!("^{}$", glob_to_regex(glob)))
+                .map_err(|_| format!("Invalid glob: {}", glob))?;
+            if glob.contains('/') {
+                set.paths.push((regex, glob.clone()));
+            } else {
+                set.names.push((regex, glob.clone()));
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.paths.is_empty()
+    }
+
+    /// Whether any glob matches `path`, a `/`-separated path ending in the file name.
+    pub fn is_match(&self, path: &str) -> bool {
+        self.matching(path).is_some()
+    }
+
+    /// The first glob that matches `path`, as it was given.
+    pub(crate) fn matching(&self, path: &str) -> Option<&str> {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let names = self.names.iter().map(|glob| (glob, name));
+        let paths = self.paths.iter().map(|glob| (glob, path));
+        names
+            .chain(paths)
+            .find(|((regex, _), against)| regex.is_match(against))
+            .map(|((_, glob), _)| glob.as_str())
+    }
+}
+
+/// Translates gitignore glob syntax (`*`, `?`, `**`, `[...]`) into an unanchored regex.
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let mut class = String::from("[");
+                if chars.next_if(|&c| c == '!' || c == '^').is_some() {
+                    class.push('^');
+                }
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        class.push('\\');
+                    }
+                    class.push(c);
+                }
+                if closed {
+                    regex.push_str(&class);
+                    regex.push(']');
+                } else {
+                    // An unterminated class is just a literal `[` and what follows.
+                    regex.push_str(&regex::escape(&class));
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    regex.push_str(&regex::escape(&escaped.to_string()));
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex
+}
@@ -0,0 +1,99 @@
+//! `--group-by-dir`: matching files tallied by directory, printed as a tree once the search is
+//! done.
+//!
+//! Each directory's counts take in everything below it, and the tree starts at the deepest
+//! directory holding every match, so a search of `/home/me/src` does not list `/home` and
+//! `/home/me` above it.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Default)]
+struct Tally {
+    files: usize,
+    matches: usize,
+}
+
+/// Matching files and their matches, by the directory directly holding them.
+#[derive(Default)]
+pub(crate) struct DirTree {
+    dirs: BTreeMap<PathBuf, Tally>,
+}
+
+impl DirTree {
+    /// Counts the file at `path` as matching `matches` times.
+    pub(crate) fn add(&mut self, path: &Path, matches: usize) {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let tally = self.dirs.entry(dir.to_path_buf()).or_default();
+        tally.files += 1;
+        tally.matches += matches;
+    }
+
+    /// Prints one line per directory, each indented under its parent, as in
+    /// `  src/: 2 files, 5 matches`.
+    pub(crate) fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        let Some(root) = self.root() else {
+            return Ok(());
+        };
+        let depth = root.components().count();
+        // BTreeMap orders paths component by component, so each directory follows its parent.
+        let mut totals: BTreeMap<&Path, Tally> = BTreeMap::new();
+        for (dir, tally) in &self.dirs {
+            for ancestor in dir.ancestors().take(dir.components().count() - depth + 1) {
+                let total = totals.entry(ancestor).or_default();
+                total.files += tally.files;
+                total.matches += tally.matches;
+            }
+        }
+        for (dir, total) in totals {
+            let level = dir.components().count() - depth;
+            let name = if level == 0 {
+                match dir.to_string_lossy() {
+                    name if name.is_empty() => ".".to_string(),
+                    name => name.into_owned(),
+                }
+            } else {
+                let last = dir.components().next_back().expect("below the root");
+                last.as_os_str().to_string_lossy().into_owned()
+            };
+            let slash = if name.ends_with(std::path::MAIN_SEPARATOR) {
+                ""
+            } else {
+                std::path::MAIN_SEPARATOR_STR
+            };
+            writeln!(
+                out,
+                "{:indent$}{}{}: {}, {}",
+                "",
+                name,
+                slash,
+                plural(total.files, "file"),
+                plural(total.matches, "match"),
+                indent = level * 2
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The deepest directory every tallied directory is in or under.
+    fn root(&self) -> Option<PathBuf> {
+        let mut dirs = self.dirs.keys();
+        let mut root = dirs.next()?.clone();
+        for dir in dirs {
+            while !dir.starts_with(&root) {
+                root.pop();
+            }
+        }
+        Some(root)
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    let suffix = match (count, noun.ends_with("ch")) {
+        (1, _) => "",
+        (_, true) => "es",
+        (_, false) => "s",
+    };
+    format!("{} {}{}", count, noun, suffix)
+}
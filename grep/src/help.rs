@@ -0,0 +1,132 @@
+//! `--help`, `--help-long`, and the `--generate-man` page, all rendered from [`OPTIONS`] and
+//! [`EXAMPLES`] so that none of them can list a flag the others do not.
+
+use crate::options::{EXAMPLES, OPTIONS, OptionSpec};
+
+const USAGE: &str = "grep [OPTIONS] <pattern> <files...>";
+const INPUTS: &str = "With no files, or where a file is -, standard input is searched.";
+const BUNDLES: &str = "Short flags can be combined, e.g. -inr is the same as -i -n -r.";
+const EXIT_STATUS: &[(&str, &str)] = &[
+    ("0", "A line was selected"),
+    ("1", "No line was selected"),
+    (
+        "2",
+        "A file could not be read, or another error occurred during the search",
+    ),
+    ("3", "A pattern did not compile"),
+    (
+        "4",
+        "An unknown option, a bad option value, or options that cannot be combined",
+    ),
+];
+
+fn visible() -> impl Iterator<Item = &'static OptionSpec> {
+    OPTIONS.iter().filter(|spec| !spec.hidden)
+}
+
+/// The option list printed by `-h`.
+pub(crate) fn short() -> String {
+    let mut text = format!("Usage: {}\n{}\n\nOptions:\n{}\n", USAGE, INPUTS, BUNDLES);
+    let width = visible()
+        .map(|spec| spec.synopsis().len())
+        .max()
+        .unwrap_or(0);
+    for spec in visible() {
+        text.push_str(&format!(
+            "{:<width$}  {}\n",
+            spec.synopsis(),
+            spec.help,
+            width = width
+        ));
+    }
+    text.push_str("\nRun with --help-long for accepted values, examples, and exit statuses.\n");
+    text
+}
+
+/// `--help-long`: each option on its own lines with the values it accepts, then examples of
+/// options used together and the exit statuses.
+pub(crate) fn long() -> String {
+    let mut text = format!("Usage: {}\n{}\n{}\n\nOptions:\n", USAGE, INPUTS, BUNDLES);
+    for spec in visible() {
+        text.push_str(&format!("  {}\n      {}\n", spec.synopsis(), spec.help));
+        if !spec.choices.is_empty() {
+            text.push_str(&format!("      Values: {}\n", spec.choices.join(", ")));
+        }
+    }
+    text.push_str("\nExamples:\n");
+    for example in EXAMPLES {
+        text.push_str(&format!(
+            "  {}\n      {}\n",
+            example.command, example.description
+        ));
+    }
+    text.push_str("\nExit status:\n");
+    for (status, meaning) in EXIT_STATUS {
+        text.push_str(&format!("  {}  {}\n", status, meaning));
+    }
+    text
+}
+
+/// `--generate-man`: the same content as `--help-long` as a roff page for section 1.
+pub(crate) fn man() -> String {
+    let mut page = format!(
+        ".TH GREP 1 \"\" \"grep {}\" \"User Commands\"\n\
+         .SH NAME\n\
+         grep \\- search files for lines matching a pattern\n\
+         .SH SYNOPSIS\n\
+         .B grep\n\
+         [\\fIOPTIONS\\fR] \\fIpattern\\fR [\\fIfiles\\fR...]\n\
+         .SH DESCRIPTION\n\
+         {}\n\
+         {}\n\
+         .SH OPTIONS\n",
+        env!("CARGO_PKG_VERSION"),
+        roff(INPUTS),
+        roff(BUNDLES)
+    );
+    for spec in visible() {
+        let names: Vec<String> = spec
+            .short
+            .map(|short| format!("-{}", short))
+            .into_iter()
+            .chain(spec.long.map(|long| format!("--{}", long)))
+            .map(|name| format!("\\fB{}\\fR", roff(&name)))
+            .collect();
+        let value = spec
+            .value
+            .map_or_else(String::new, |value| format!(" \\fI{}\\fR", roff(value)));
+        page.push_str(&format!(
+            ".TP\n{}{}\n{}\n",
+            names.join(", "),
+            value,
+            roff(spec.help)
+        ));
+        if !spec.choices.is_empty() {
+            page.push_str(&format!("Values: {}.\n", roff(&spec.choices.join(", "))));
+        }
+    }
+    page.push_str(".SH EXAMPLES\n");
+    for example in EXAMPLES {
+        page.push_str(&format!(
+            ".TP\n.B {}\n{}\n",
+            roff(example.command),
+            roff(example.description)
+        ));
+    }
+    page.push_str(".SH EXIT STATUS\n");
+    for (status, meaning) in EXIT_STATUS {
+        page.push_str(&format!(".TP\n.B {}\n{}\n", status, roff(meaning)));
+    }
+    page
+}
+
+/// `text` escaped for roff: backslashes and hyphens spelled out, and a leading `.` or `'`
+/// kept from being read as a request.
+fn roff(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
@@ -0,0 +1,118 @@
+//! `--hyperlink`: printed file paths wrapped in OSC 8 escapes, so terminals that support them
+//! (iTerm2, WezTerm, kitty, VS Code's, and others) open the file when a path is clicked.
+//!
+//! Where a link points comes from a format holding `{path}`, and optionally `{host}`, `{line}`,
+//! and `{column}`, or one of the names in [`ALIASES`].
+
+use std::path::{self, Path};
+
+/// Formats known by name; `file` is the default.
+const ALIASES: &[(&str, &str)] = &[
+    ("file", "file://{host}{path}"),
+    ("vscode", "vscode://file{path}:{line}:{column}"),
+    ("cursor", "cursor://file{path}:{line}:{column}"),
+    (
+        "idea",
+        "idea://open?file={path}&line={line}&column={column}",
+    ),
+];
+
+/// A parsed `--hyperlink-format`.
+pub struct HyperlinkFormat {
+    template: String,
+    /// This machine's name, for `{host}`; looked up once.
+    host: String,
+}
+
+impl Default for HyperlinkFormat {
+    fn default() -> Self {
+        HyperlinkFormat::parse("file").expect("the default format is valid")
+    }
+}
+
+impl HyperlinkFormat {
+    /// Reads a format, or the name of one.
+    pub fn parse(value: &str) -> Result<HyperlinkFormat, String> {
+        let template = ALIASES
+            .iter()
+            .find(|&&(name, _)| name == value)
+            .map_or(value, |&(_, template)| template);
+        if !template.contains("{path}") {
+            let names: Vec<_> = ALIASES.iter().map(|&(name, _)| name).collect();
+            return Err(format!(
+                "Invalid value for --hyperlink-format: {} (expected {}, or a format with {{path}})",
+                value,
+                names.join(", ")
+            ));
+        }
+        let host = if template.contains("{host}") {
+            host_name()
+        } else {
+            String::new()
+        };
+        Ok(HyperlinkFormat {
+            template: template.to_string(),
+            host,
+        })
+    }
+
+    /// `text` as a link to `path`, at `line` and `column` for a format that has them (or 1
+    /// where the file as a whole is meant). A path that cannot be made absolute is not linked.
+    pub(crate) fn link(
+        &self,
+        text: &str,
+        path: &Path,
+        line: Option<usize>,
+        column: Option<usize>,
+    ) -> String {
+        let Ok(absolute) = path::absolute(path) else {
+            return text.to_string();
+        };
+        let url = self
+            .template
+            .replace("{host}", &self.host)
+            .replace("{line}", &line.unwrap_or(1).to_string())
+            .replace("{column}", &column.unwrap_or(1).to_string())
+            .replace("{path}", &encode(&absolute));
+        format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+    }
+}
+
+/// `path` with `/` separators, starting with one, and every byte a URL cannot hold as is
+/// percent-encoded.
+fn encode(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut encoded = String::with_capacity(path.len() + 1);
+    // A Windows path starts with its drive, as in `/C:/Users`.
+    if !path.starts_with('/') {
+        encoded.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(char::from(byte))
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(unix)]
+fn host_name() -> String {
+    let mut name = [0u8; 256];
+    // SAFETY: the buffer is valid for its whole length, which is what gethostname is told.
+    if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } != 0 {
+        return String::new();
+    }
+    let end = name
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..end]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
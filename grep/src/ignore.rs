@@ -0,0 +1,116 @@
+//! `.gitignore`-style rules used to prune recursive walks.
+
+use crate::glob::glob_to_regex;
+use regex::Regex;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files in a directory whose rules apply to everything beneath it. Where they disagree, the
+/// last named wins, as ripgrep has it.
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".rgignore"];
+
+/// The rules from one directory's ignore files, or from `--ignore-file`, in file order.
+#[derive(Default)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+}
+
+/// One line of an ignore file.
+pub(crate) struct Rule {
+    /// The file the rule is from, and its line number there.
+    source: PathBuf,
+    line: usize,
+    /// The line as written.
+    text: String,
+    regex: Regex,
+    /// `!pattern`: re-include something an earlier rule ignored.
+    negated: bool,
+    /// `pattern/`: only matches directories.
+    dir_only: bool,
+}
+
+impl Gitignore {
+    /// Reads the ignore files in `dir`, or `None` if it has none.
+    pub(crate) fn from_dir(dir: &Path) -> Option<Gitignore> {
+        let mut ignore = Gitignore::default();
+        for name in IGNORE_FILES {
+            let source = dir.join(name);
+            if let Ok(text) = fs::read_to_string(&source) {
+                ignore.extend(&text, &source);
+            }
+        }
+        (!ignore.rules.is_empty()).then_some(ignore)
+    }
+
+    /// Adds the rules in `contents`, read from the file `source`, after those already here.
+    pub(crate) fn extend(&mut self, contents: &str, source: &Path) {
+        let rules = contents
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| Rule::parse(line, source, index + 1));
+        self.rules.extend(rules);
+    }
+
+    /// The rule that decides whether `relative` (a `/`-separated path below this directory) is
+    /// ignored: the last one matching it, which may re-include it. `None` if no rule mentions it.
+    pub(crate) fn deciding(&self, relative: &str, is_dir: bool) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(relative))
+    }
+}
+
+impl Rule {
+    /// Whether the rule ignores what it matches, rather than re-including it.
+    pub(crate) fn ignores(&self) -> bool {
+        !self.negated
+    }
+
+    fn parse(text: &str, source: &Path, number: usize) -> Option<Rule> {
+        let line = text.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        if pattern.is_empty() {
+            return None;
+        }
+
+        // A slash anywhere but the end ties the pattern to this directory; otherwise it matches
+        // a name at any depth.
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+        let regex = Regex::new(&format!("{}{}$", prefix, glob_to_regex(pattern))).ok()?;
+        Some(Rule {
+            source: source.to_path_buf(),
+            line: number,
+            text: line.to_string(),
+            regex,
+            negated,
+            dir_only,
+        })
+    }
+}
+
+/// Where the rule is, as in `src/.gitignore line 3 (*.tmp)`.
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} line {} ({})",
+            self.source.display(),
+            self.line,
+            self.text
+        )
+    }
+}
@@ -0,0 +1,476 @@
+//! `grep index build` and `grep index query`: a trigram index kept on disk, so that repeated
+//! searches of a large tree only open the files that could match.
+//!
+//! The index lists every file a walk of its directory found, with each file's modification
+//! time and size, and for each three-byte sequence the files containing it. Bytes are indexed
+//! with ASCII letters lowercased, so one index serves case-sensitive and `-i` searches alike.
+//! A query works out which trigrams any match must contain, and skips the indexed files that
+//! lack them; a file added or changed since the index was built is always searched, so results
+//! never go stale, only slower until the index is built again.
+//!
+//! The file format is little-endian: the magic `GREPIDX1`; the file count, then for each file
+//! its path relative to the directory, modification time (seconds and nanoseconds), size, and
+//! whether its contents were indexed; the trigram count, then for each trigram its three
+//! bytes and the ids of the files containing it, as varint gaps.
+
+use crate::config::Config;
+use crate::encoding::Encoding;
+use crate::error::SearchError;
+use crate::matcher::{PatternSpec, Syntax};
+use crate::walk;
+use regex_syntax::hir::{Hir, HirKind};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Name of the index file, kept in the directory it covers.
+pub const INDEX_FILE: &str = ".grep-index";
+
+const MAGIC: &[u8; 8] = b"GREPIDX1";
+
+/// Which index subcommand a run is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexMode {
+    /// `grep index build DIR`: index DIR, reusing what is unchanged from the last build.
+    Build,
+    /// `grep index query PATTERN [DIR]`: search DIR, opening only candidate files.
+    Query,
+}
+
+/// A file's modification time and size when it was indexed (or, for `--cache`, searched).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Stamp {
+    pub(crate) seconds: u64,
+    pub(crate) nanos: u32,
+    pub(crate) len: u64,
+}
+
+impl Stamp {
+    pub(crate) fn of(path: &Path) -> Option<Stamp> {
+        let metadata = path.metadata().ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Stamp {
+            seconds: modified.as_secs(),
+            nanos: modified.subsec_nanos(),
+            len: metadata.len(),
+        })
+    }
+}
+
+struct Entry {
+    /// Relative to the indexed directory, with the walk's separators.
+    path: String,
+    stamp: Stamp,
+    /// Whether the trigrams were taken; a UTF-16 file, say, is searched whatever the query.
+    indexed: bool,
+}
+
+struct Index {
+    files: Vec<Entry>,
+    /// For each trigram, the ids of the files containing it, in order.
+    postings: HashMap<u32, Vec<u32>>,
+}
+
+/// The directory a build or query covers; the config allows exactly one.
+fn root(config: &Config) -> &Path {
+    Path::new(&config.inputs[0])
+}
+
+/// Builds (or brings up to date) the index of the directory in `config`, reporting how many
+/// files it lists. Files whose time and size are unchanged keep their trigrams without being
+/// read again.
+pub(crate) fn build(config: &Config) -> Result<usize, SearchError> {
+    let root = root(config);
+    let location = root.join(INDEX_FILE);
+    // A missing, corrupt, or older index is simply rebuilt from scratch.
+    let previous = fs::read(&location)
+        .ok()
+        .and_then(|bytes| Index::decode(&bytes).ok());
+    let mut reusable: HashMap<&str, (Stamp, Vec<u32>)> = HashMap::new();
+    if let Some(previous) = &previous {
+        let mut trigrams = vec![Vec::new(); previous.files.len()];
+        for (&trigram, ids) in &previous.postings {
+            for &id in ids {
+                trigrams[id as usize].push(trigram);
+            }
+        }
+        for (entry, trigrams) in previous.files.iter().zip(trigrams) {
+            if entry.indexed {
+                reusable.insert(&entry.path, (entry.stamp, trigrams));
+            }
+        }
+    }
+
+    let (targets, walk_errors) = walk::collect_targets(config);
+    for (path, error) in walk_errors {
+        if !config.no_messages {
+            eprintln!("Error: {}: {}", path.display(), error);
+        }
+    }
+    let mut files = Vec::new();
+    let mut postings: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    let mut reused = 0;
+    for path in targets {
+        let Some(relative) = path.strip_prefix(root).ok().and_then(Path::to_str) else {
+            continue;
+        };
+        if relative == INDEX_FILE {
+            continue;
+        }
+        let Some(stamp) = Stamp::of(&path) else {
+            continue;
+        };
+        let trigrams = match reusable.remove(relative) {
+            Some((old, trigrams)) if old == stamp => {
+                reused += 1;
+                Some(trigrams)
+            }
+            _ => match fs::read(&path) {
+                Ok(bytes) => file_trigrams(&bytes),
+                Err(error) => {
+                    if !config.no_messages {
+                        eprintln!("Error: {}: {}", path.display(), error);
+                    }
+                    continue;
+                }
+            },
+        };
+        let id = files.len() as u32;
+        if let Some(trigrams) = &trigrams {
+            for &trigram in trigrams {
+                postings.entry(trigram).or_default().push(id);
+            }
+        }
+        files.push(Entry {
+            path: relative.to_string(),
+            stamp,
+            indexed: trigrams.is_some(),
+        });
+    }
+
+    let index = Index {
+        files,
+        postings: postings.into_iter().collect(),
+    };
+    // Written aside and renamed, so a query never reads half an index.
+    let partial = root.join(format!("{}.partial", INDEX_FILE));
+    fs::write(&partial, index.encode())
+        .and_then(|_| fs::rename(&partial, &location))
+        .map_err(|error| SearchError::io("index", &location, error))?;
+    println!(
+        "Indexed {} files ({} unchanged) into {}",
+        index.files.len(),
+        reused,
+        location.display()
+    );
+    Ok(index.files.len())
+}
+
+/// The distinct trigrams of a file's bytes, or `None` for content that is not searched as its
+/// raw bytes: a file with a UTF-16 byte-order mark is searched once transcoded.
+fn file_trigrams(bytes: &[u8]) -> Option<Vec<u32>> {
+    if matches!(Encoding::sniff(bytes), Some((encoding, _)) if encoding != Encoding::Utf8) {
+        return None;
+    }
+    let mut trigrams: Vec<u32> = bytes
+        .windows(3)
+        .map(trigram)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    trigrams.sort_unstable();
+    Some(trigrams)
+}
+
+fn trigram(bytes: &[u8]) -> u32 {
+    let lower = |byte: u8| u32::from(byte.to_ascii_lowercase());
+    (lower(bytes[0]) << 16) | (lower(bytes[1]) << 8) | lower(bytes[2])
+}
+
+/// `targets` (the walk of the indexed directory) without the files the index rules out.
+pub(crate) fn prune(config: &Config, targets: Vec<PathBuf>) -> Result<Vec<PathBuf>, SearchError> {
+    let root = root(config);
+    let location = root.join(INDEX_FILE);
+    let bytes = fs::read(&location).map_err(|error| SearchError::io("index", &location, error))?;
+    let index =
+        Index::decode(&bytes).map_err(|error| SearchError::io("index", &location, error))?;
+    // Anything that changes what a file's bytes mean, or that prints files without matches,
+    // leaves nothing to rule out.
+    if config.invert_match
+        || config.files_without_match
+        || config.count
+        || config.search_zip
+        || config.archives
+        || config.normalize
+        || config
+            .encoding
+            .is_some_and(|encoding| encoding != Encoding::Utf8)
+    {
+        return Ok(targets);
+    }
+    let Some(candidates) = plan(&config.pattern).candidates(&index) else {
+        return Ok(targets);
+    };
+    let ids: HashMap<&str, usize> = index
+        .files
+        .iter()
+        .enumerate()
+        .map(|(id, entry)| (entry.path.as_str(), id))
+        .collect();
+    Ok(targets
+        .into_iter()
+        .filter(|path| {
+            let known = path
+                .strip_prefix(root)
+                .ok()
+                .and_then(Path::to_str)
+                .and_then(|relative| ids.get(relative));
+            match known {
+                Some(&id) => {
+                    let entry = &index.files[id];
+                    // A file changed since the build may now hold anything.
+                    !entry.indexed
+                        || Stamp::of(path) != Some(entry.stamp)
+                        || candidates.contains(&(id as u32))
+                }
+                None => true,
+            }
+        })
+        .collect())
+}
+
+/// What a file must contain for the pattern to match somewhere in it.
+enum Query {
+    /// Nothing can be ruled out.
+    All,
+    /// Every trigram of these bytes.
+    Literal(Vec<u8>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+fn plan(spec: &PatternSpec) -> Query {
+    let folded = spec.options.case_insensitive;
+    let alternatives = spec.patterns.iter().map(|pattern| match spec.syntax {
+        Syntax::Literal => literal(pattern.as_bytes(), folded),
+        // Look-around is not regex-crate syntax, so `-P` patterns with it plan as All.
+        Syntax::Regex | Syntax::Pcre => regex_syntax::ParserBuilder::new()
+            .multi_line(spec.options.multiline)
+            .dot_matches_new_line(spec.options.multiline)
+            .build()
+            .parse(pattern)
+            .map_or(Query::All, |hir| required(&hir, folded)),
+    });
+    Query::Or(alternatives.collect())
+}
+
+/// The query for a literal. With `-i`, ASCII `k` and `s` also match the Kelvin sign and long
+/// s, and other non-ASCII letters fold in ways the lowercased index cannot follow, so only the
+/// runs between them are required.
+fn literal(bytes: &[u8], folded: bool) -> Query {
+    if !folded {
+        return Query::Literal(bytes.to_vec());
+    }
+    let unsure = |byte: &u8| !byte.is_ascii() || matches!(byte, b'k' | b'K' | b's' | b'S');
+    Query::And(
+        bytes
+            .split(unsure)
+            .map(|run| Query::Literal(run.to_vec()))
+            .collect(),
+    )
+}
+
+/// The query a match of `hir` implies: literals must appear, alternatives need one branch,
+/// and anything optional or a class requires nothing.
+fn required(hir: &Hir, folded: bool) -> Query {
+    match hir.kind() {
+        HirKind::Literal(found) => literal(&found.0, folded),
+        HirKind::Capture(capture) => required(&capture.sub, folded),
+        HirKind::Repetition(repetition) if repetition.min > 0 => required(&repetition.sub, folded),
+        HirKind::Alternation(branches) => Query::Or(
+            branches
+                .iter()
+                .map(|branch| required(branch, folded))
+                .collect(),
+        ),
+        HirKind::Concat(parts) => {
+            // Neighbouring literals run together, so trigrams across them count too.
+            let mut queries = Vec::new();
+            let mut run = Vec::new();
+            for part in parts {
+                match part.kind() {
+                    HirKind::Literal(found) => run.extend_from_slice(&found.0),
+                    _ => {
+                        if !run.is_empty() {
+                            queries.push(literal(&std::mem::take(&mut run), folded));
+                        }
+                        queries.push(required(part, folded));
+                    }
+                }
+            }
+            if !run.is_empty() {
+                queries.push(literal(&run, folded));
+            }
+            Query::And(queries)
+        }
+        _ => Query::All,
+    }
+}
+
+impl Query {
+    /// Ids of the files that could match, or `None` if any might.
+    fn candidates(&self, index: &Index) -> Option<HashSet<u32>> {
+        match self {
+            Query::All => None,
+            Query::Literal(bytes) if bytes.len() < 3 => None,
+            Query::Literal(bytes) => {
+                let trigrams: HashSet<u32> = bytes.windows(3).map(trigram).collect();
+                intersect(trigrams.into_iter().map(|trigram| {
+                    Some(
+                        index
+                            .postings
+                            .get(&trigram)
+                            .map_or_else(HashSet::new, |ids| ids.iter().copied().collect()),
+                    )
+                }))
+            }
+            Query::And(parts) => intersect(parts.iter().map(|part| part.candidates(index))),
+            Query::Or(parts) => {
+                let mut union = HashSet::new();
+                for part in parts {
+                    union.extend(part.candidates(index)?);
+                }
+                Some(union)
+            }
+        }
+    }
+}
+
+/// The files in every one of `sets`, where `None` stands for all of them.
+fn intersect(sets: impl Iterator<Item = Option<HashSet<u32>>>) -> Option<HashSet<u32>> {
+    sets.flatten()
+        .reduce(|kept, set| kept.into_iter().filter(|id| set.contains(id)).collect())
+}
+
+impl Index {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for entry in &self.files {
+            out.extend_from_slice(&(entry.path.len() as u32).to_le_bytes());
+            out.extend_from_slice(entry.path.as_bytes());
+            out.extend_from_slice(&entry.stamp.seconds.to_le_bytes());
+            out.extend_from_slice(&entry.stamp.nanos.to_le_bytes());
+            out.extend_from_slice(&entry.stamp.len.to_le_bytes());
+            out.push(u8::from(entry.indexed));
+        }
+        let mut trigrams: Vec<&u32> = self.postings.keys().collect();
+        trigrams.sort_unstable();
+        out.extend_from_slice(&(trigrams.len() as u32).to_le_bytes());
+        for trigram in trigrams {
+            out.extend_from_slice(&trigram.to_le_bytes()[..3]);
+            let ids = &self.postings[trigram];
+            write_varint(&mut out, ids.len() as u64);
+            let mut last = 0;
+            for &id in ids {
+                write_varint(&mut out, u64::from(id - last));
+                last = id;
+            }
+        }
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Index> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(corrupt());
+        }
+        let count = reader.u32()?;
+        let mut files = Vec::new();
+        for _ in 0..count {
+            let len = reader.u32()? as usize;
+            let path = String::from_utf8(reader.take(len)?.to_vec()).map_err(|_| corrupt())?;
+            let stamp = Stamp {
+                seconds: reader.u64()?,
+                nanos: reader.u32()?,
+                len: reader.u64()?,
+            };
+            let indexed = reader.take(1)?[0] != 0;
+            files.push(Entry {
+                path,
+                stamp,
+                indexed,
+            });
+        }
+        let trigrams = reader.u32()?;
+        let mut postings = HashMap::new();
+        for _ in 0..trigrams {
+            let key = reader.take(3)?;
+            let trigram = u32::from_le_bytes([key[0], key[1], key[2], 0]);
+            let len = reader.varint()?;
+            let mut ids = Vec::new();
+            let mut id = 0u64;
+            for _ in 0..len {
+                id += reader.varint()?;
+                if id >= u64::from(count) {
+                    return Err(corrupt());
+                }
+                ids.push(id as u32);
+            }
+            postings.insert(trigram, ids);
+        }
+        Ok(Index { files, postings })
+    }
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "not an index from this version of grep; run grep index build again",
+    )
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, len: usize) -> io::Result<&'b [u8]> {
+        if self.bytes.len() < len {
+            return Err(corrupt());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(corrupt())
+    }
+}
@@ -0,0 +1,113 @@
+//! `--json` output, and `--serve` responses: one JSON object per line for every selected or
+//! context line.
+
+use crate::printer;
+use crate::search::SinkMatch;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// Writes `found` as an object of the given `kind` (`"match"` or `"context"`). Spans are byte
+/// ranges within `line`, with the text they cover.
+pub(crate) fn write_line(
+    out: &mut impl Write,
+    kind: &str,
+    found: &SinkMatch<'_>,
+) -> io::Result<()> {
+    let mut object = format!("{{\"type\":{},", string(kind));
+    if let Some(path) = found.path {
+        let _ = write!(object, "\"path\":{},", string(&printer::path_label(path)));
+    }
+    let _ = write!(
+        object,
+        "\"line_number\":{},\"byte_offset\":{},\"line\":{},\"spans\":[",
+        found.line_number,
+        found.byte_offset,
+        string(found.line)
+    );
+    push_spans(&mut object, found.line, &found.spans);
+    object.push_str("]}");
+    writeln!(out, "{}", object)
+}
+
+/// Writes a `--search-names` hit: an object of type `"name"` whose spans are within the path.
+pub(crate) fn write_name(
+    out: &mut (impl Write + ?Sized),
+    name: &str,
+    spans: &[Range<usize>],
+) -> io::Result<()> {
+    let mut object = format!("{{\"type\":\"name\",\"path\":{},\"spans\":[", string(name));
+    push_spans(&mut object, name, spans);
+    object.push_str("]}");
+    writeln!(out, "{}", object)
+}
+
+fn push_spans(object: &mut String, text: &str, spans: &[Range<usize>]) {
+    for (index, span) in spans.iter().enumerate() {
+        if index > 0 {
+            object.push(',');
+        }
+        let _ = write!(
+            object,
+            "{{\"start\":{},\"end\":{},\"text\":{}}}",
+            span.start,
+            span.end,
+            string(&text[span.clone()])
+        );
+    }
+}
+
+/// Writes the notice for a binary input that matched.
+pub(crate) fn write_binary(out: &mut impl Write, path: Option<&Path>) -> io::Result<()> {
+    match path {
+        Some(path) => writeln!(
+            out,
+            "{{\"type\":\"binary\",\"path\":{}}}",
+            string(&printer::path_label(path))
+        ),
+        None => writeln!(out, "{{\"type\":\"binary\"}}"),
+    }
+}
+
+/// Writes an error, such as an input that could not be read, as an object of type `"error"`.
+pub(crate) fn write_error(
+    out: &mut impl Write,
+    path: Option<&Path>,
+    message: &str,
+) -> io::Result<()> {
+    match path {
+        Some(path) => writeln!(
+            out,
+            "{{\"type\":\"error\",\"path\":{},\"message\":{}}}",
+            string(&printer::path_label(path)),
+            string(message)
+        ),
+        None => writeln!(
+            out,
+            "{{\"type\":\"error\",\"message\":{}}}",
+            string(message)
+        ),
+    }
+}
+
+/// `text` as a quoted JSON string.
+pub(crate) fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,774 @@
+//! Search engine behind the `grep` binary.
+//!
+//! The binary is a thin wrapper over [`run`]; other programs can build a [`Config`] and drive a
+//! [`Searcher`] directly, receiving structured matches through a [`MatchSink`] instead of
+//! parsing stdout.
+
+mod archive;
+mod cache;
+mod completions;
+mod config;
+mod decompress;
+mod diff;
+mod encoding;
+mod error;
+mod explain;
+mod format;
+mod glob;
+mod group;
+mod help;
+mod hyperlink;
+mod ignore;
+mod index;
+mod json;
+mod matcher;
+mod mmap;
+#[rustfmt::skip]
+mod nfc_tables;
+mod normalize;
+mod options;
+mod pool;
+mod printer;
+mod rcfile;
+mod rewrite;
+mod rules;
+mod sarif;
+mod search;
+mod serve;
+mod strategy;
+#[cfg(feature = "async")]
+mod stream;
+mod table;
+mod tail;
+mod theme;
+mod tui;
+mod types;
+mod walk;
+mod watch;
+
+pub use config::{Config, ParseOutcome};
+pub use encoding::Encoding;
+pub use error::SearchError;
+pub use glob::GlobSet;
+pub use index::{INDEX_FILE, IndexMode};
+pub use matcher::{Literal, Lookaround, MatchOptions, Matcher, PatternSpec, Syntax};
+pub use rcfile::{NO_CONFIG, config_path, default_args};
+pub use search::{LongText, MatchSink, STDIN_LABEL, SearchResult, Searcher, SinkMatch};
+pub use strategy::Engine;
+#[cfg(feature = "async")]
+pub use stream::{Recv, ResultStream, search_async, search_path_async};
+pub use theme::{ColorChoice, Theme};
+pub use walk::SortKey;
+
+use archive::Archive;
+use cache::Cache;
+use printer::Printer;
+use rules::RulePrinter;
+use std::borrow::Cow;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use walk::Target;
+
+/// Outcome of a [`run`], used by the binary to choose its exit code.
+#[derive(Default)]
+pub struct Summary {
+    /// At least one input could not be searched.
+    pub had_errors: bool,
+    /// Files that were read to the end (or until the search was satisfied).
+    pub files_searched: usize,
+    /// Files that produced at least one selected line.
+    pub files_matched: usize,
+    /// Files a walk or `--files-from` list found that were gone by the time they were read.
+    /// They are warned about but are not errors.
+    pub files_vanished: usize,
+    /// Files cut short or never started because the run reached its `--deadline`.
+    pub files_past_deadline: usize,
+    /// Selected lines across every file.
+    pub lines_matched: usize,
+    /// Matches on those lines, where a line with three counts three times.
+    pub matches: usize,
+    /// Bytes read from the inputs that were searched.
+    pub bytes_searched: u64,
+    /// Time from the start of the run until every input had been searched.
+    pub elapsed: Duration,
+}
+
+/// Searches every input named by `config`, printing results to stdout.
+///
+/// A file that cannot be read is reported on stderr (unless `no_messages` is set) and skipped,
+/// so one bad path does not stop the rest of the search. With `watch` set this does not return:
+/// the inputs are searched again whenever they change, until the process is stopped. Nor does
+/// it with `tail`, which goes on to print the matching lines appended to each file.
+pub fn run(config: &Config) -> Result<Summary, SearchError> {
+    let started = Instant::now();
+    if let Some(address) = config.serve {
+        let served = serve::serve(address, &config.inputs);
+        if let Err(error) = &served {
+            eprintln!("Cannot serve on {}: {}", address, error);
+        }
+        return Ok(Summary {
+            had_errors: served.is_err(),
+            ..Summary::default()
+        });
+    }
+    if config.index == Some(IndexMode::Build) {
+        let indexed = index::build(config)?;
+        return Ok(Summary {
+            files_matched: indexed,
+            elapsed: started.elapsed(),
+            ..Summary::default()
+        });
+    }
+    if !config.explain.is_empty() {
+        return Ok(explain::explain(config));
+    }
+    if config.debug_pattern {
+        eprint!("{}", config.pattern.describe(&config.matcher));
+    }
+    // Otherwise files are searched as the walk finds them, so results start straight away.
+    if config.index != Some(IndexMode::Query) && !config.watch && !config.tail {
+        return Ok(walk::stream_targets(config, |targets| {
+            search_targets(config, targets, started)
+        }));
+    }
+    let (mut targets, walk_errors) = walk::collect_targets(config);
+    if config.index == Some(IndexMode::Query) {
+        targets = index::prune(config, targets)?;
+    }
+    let before = config.watch.then(|| watch::snapshot(&targets));
+    let followed = config.tail.then(|| tail::follow(config, &targets));
+    let summary = search_targets(config, listed(&targets, walk_errors), started);
+    if let Some(before) = before {
+        watch::watch(config, before, |targets, walk_errors| {
+            search_targets(config, listed(targets, walk_errors), Instant::now());
+        });
+    }
+    if let Some(followed) = followed {
+        tail::tail(config, followed);
+    }
+    Ok(summary)
+}
+
+/// A walk's errors and then its files, as targets.
+fn listed(
+    targets: &[PathBuf],
+    walk_errors: Vec<(PathBuf, io::Error)>,
+) -> impl Iterator<Item = Target> + Send + '_ {
+    let files = targets.iter().cloned().map(Ok);
+    walk_errors.into_iter().map(Err).chain(files)
+}
+
+/// Searches (or for `--files`, lists) `targets`, reporting the entries a walk could not read
+/// where they come.
+fn search_targets(
+    config: &Config,
+    targets: impl Iterator<Item = Target> + Send,
+    started: Instant,
+) -> Summary {
+    if config.tui {
+        return browse_targets(config, targets, started);
+    }
+    let mut summary = Summary::default();
+    let mut stdout = stdout_writer(config);
+    if config.sarif {
+        stdout = Box::new(sarif::Log::new(stdout, config));
+    }
+    if config.table {
+        stdout = Box::new(table::Table::new(stdout, config));
+    }
+    let mut dirs = group::DirTree::default();
+    let cache = Cache::open(config);
+    let until = config.deadline.map(|deadline| started + deadline);
+    let past_deadline = || until.is_some_and(|until| Instant::now() >= until);
+    if config.files_only {
+        list_targets(config, targets, &mut summary, &mut stdout);
+        // A failed write to stdout has nowhere better to be reported.
+        let _ = stdout.flush();
+        summary.elapsed = started.elapsed();
+        return summary;
+    }
+    let _ = printer::print_header(&mut stdout, config);
+
+    // Returns whether the file had a selected line.
+    let mut report = |out: &mut dyn Write, path: &Path, result: io::Result<Searched>| -> bool {
+        match result {
+            Ok(Searched {
+                count,
+                matches,
+                bytes,
+            }) => {
+                summary.files_searched += 1;
+                if count > 0 {
+                    summary.files_matched += 1;
+                }
+                summary.lines_matched += count;
+                summary.matches += matches;
+                summary.bytes_searched += bytes;
+                if config.quiet {
+                    return count > 0;
+                }
+                if config.group_by_dir && count > 0 {
+                    dirs.add(Path::new(&*display_name(path, config)), matches);
+                }
+                if config.files_without_match && count == 0 {
+                    let name = display_name(path, config);
+                    // A failed write to stdout has nowhere better to be reported.
+                    let _ = printer::print_filename(&mut *out, Path::new(&*name), config);
+                }
+                // Every searched file gets a count line, zeros included, so tallies are complete.
+                if config.count {
+                    let label = display_name(path, config);
+                    let label = config.show_filenames.then_some(&*label);
+                    let tally = if config.count_matches { matches } else { count };
+                    let _ = printer::print_count(&mut *out, label, tally, config);
+                }
+                count > 0
+            }
+            Err(error) if vanished(config, path, &error) => {
+                summary.files_vanished += 1;
+                warn_vanished(config, path);
+                false
+            }
+            // Whatever reads stdout (say `| head`) has gone away, having been sent this file's
+            // first selected lines. That ends the run, as it would grep's, but is no error.
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {
+                summary.files_searched += 1;
+                summary.files_matched += 1;
+                true
+            }
+            Err(error) if error.kind() == io::ErrorKind::OutOfMemory => {
+                if !config.no_messages {
+                    eprintln!("Warning: {}: {}", display_name(path, config), error);
+                }
+                false
+            }
+            // Reported all together below, rather than once for each file left.
+            Err(error) if error.kind() == io::ErrorKind::TimedOut && past_deadline() => {
+                summary.had_errors = true;
+                summary.files_past_deadline += 1;
+                false
+            }
+            Err(error) => {
+                summary.had_errors = true;
+                if !config.no_messages {
+                    eprintln!("Error: {}: {}", display_name(path, config), error);
+                }
+                false
+            }
+        }
+    };
+
+    // Standard input cannot be searched a second time, as a file crossing --max-total may be.
+    let rereadable = config.max_total.is_none()
+        || !config
+            .inputs
+            .iter()
+            .chain(&config.listed_files)
+            .any(|input| input == search::STDIN_PATH);
+    let threads = match config.threads {
+        0 if rereadable => thread::available_parallelism().map_or(1, |threads| threads.get()),
+        0 => 1,
+        threads => threads,
+    }
+    .min(targets.size_hint().1.unwrap_or(usize::MAX));
+    // --max-total: lines still allowed, and a flag telling workers the rest are not wanted.
+    let mut remaining = config.max_total;
+    let spent = AtomicBool::new(false);
+    // Whether stdout's reader has gone away, so nothing more is worth searching.
+    let mut closed = false;
+    // --heading puts a blank line between files, which only shows once a later file prints.
+    let heading = printer::uses_heading(config);
+    let mut printed = false;
+    // --interactive asks about one file's changes at a time.
+    if threads <= 1 || config.quiet || config.interactive {
+        // Print straight to stdout, so with --line-buffered output from a slow input (say a
+        // pipe) shows up at once.
+        for target in targets {
+            if remaining == Some(0) {
+                break;
+            }
+            // An unreadable directory or entry met during a walk counts as an error, like a
+            // failed file.
+            let path = match target {
+                Ok(path) => path,
+                Err((path, error)) => {
+                    report(&mut stdout, &path, Err(error));
+                    continue;
+                }
+            };
+            let path = &path;
+            let gap: &[u8] = if heading && printed { b"\n" } else { b"" };
+            let mut out = Separated::new(&mut stdout, gap);
+            let result = match &cache {
+                Some(cache) => cache.search(config, path, &mut out, |out| {
+                    process_file(path, config, remaining, None, until, out)
+                }),
+                None => process_file(path, config, remaining, None, until, &mut out),
+            };
+            printed |= out.written;
+            let selected = result.as_ref().map_or(0, |searched| searched.count);
+            remaining = remaining.map(|left| left.saturating_sub(selected));
+            let closed = stdout_closed(&result);
+            let matched = report(&mut stdout, path, result);
+            // For -q one match settles the exit status, so the remaining files are not needed.
+            if closed || (matched && config.quiet) {
+                break;
+            }
+        }
+    } else {
+        // Each file is printed to a buffer by a worker, then copied out in the original order.
+        pool::for_each_ordered(
+            targets,
+            threads,
+            |target| {
+                let Ok(path) = target else {
+                    return None;
+                };
+                let mut output = Vec::new();
+                let result = if spent.load(Ordering::Relaxed) {
+                    Err(io::Error::new(
+                        io::ErrorKind::Interrupted,
+                        "search cancelled",
+                    ))
+                } else if let Some(cache) = &cache {
+                    cache.search(config, path, &mut output, |out| {
+                        process_file(path, config, None, None, until, out)
+                    })
+                } else {
+                    process_file(
+                        path,
+                        config,
+                        config.max_total,
+                        Some(&spent),
+                        until,
+                        &mut output,
+                    )
+                };
+                Some((output, result))
+            },
+            |target, searched| {
+                // Once --max-total is reached, later files are cancelled and nothing is shown.
+                if remaining == Some(0) || closed {
+                    return;
+                }
+                let (path, (mut output, mut result)) = match (target, searched) {
+                    (Ok(path), Some(searched)) => (path, searched),
+                    (Err((path, error)), _) => {
+                        report(&mut stdout, &path, Err(error));
+                        return;
+                    }
+                    (Ok(_), None) => return,
+                };
+                let path = &path;
+                let selected = result.as_ref().map_or(0, |searched| searched.count);
+                if let Some(left) = remaining
+                    && selected > left
+                {
+                    // Workers cannot know what earlier files will use, so the file that crosses
+                    // the limit is searched again to stop where it falls.
+                    output.clear();
+                    result = process_file(path, config, Some(left), None, until, &mut output);
+                }
+                let selected = result.as_ref().map_or(0, |searched| searched.count);
+                remaining = remaining.map(|left| left.saturating_sub(selected));
+                if remaining == Some(0) {
+                    spent.store(true, Ordering::Relaxed);
+                }
+                let gap: &[u8] = if heading && printed && !output.is_empty() {
+                    b"\n"
+                } else {
+                    b""
+                };
+                printed |= !output.is_empty();
+                let result = stdout
+                    .write_all(gap)
+                    .and_then(|_| stdout.write_all(&output))
+                    .and(result);
+                if stdout_closed(&result) {
+                    closed = true;
+                    spent.store(true, Ordering::Relaxed);
+                }
+                report(&mut stdout, path, result);
+            },
+        );
+    }
+
+    if let Some(cache) = cache {
+        cache.save(config);
+    }
+    if config.count && config.total && !config.quiet {
+        let total = if config.count_matches {
+            summary.matches
+        } else {
+            summary.lines_matched
+        };
+        let _ = printer::print_count(&mut stdout, Some("total"), total, config);
+    }
+    if config.group_by_dir && !config.quiet {
+        let _ = dirs.write(&mut stdout);
+    }
+    // Everything on stdout goes out before the run's notes on stderr.
+    let _ = stdout.flush();
+    if summary.files_past_deadline > 0
+        && !config.no_messages
+        && let Some(deadline) = config.deadline
+    {
+        let files = match summary.files_past_deadline {
+            1 => "1 file was".to_string(),
+            count => format!("{} files were", count),
+        };
+        eprintln!(
+            "Error: the --deadline of {} ms was reached; {} cut short or not searched",
+            deadline.as_millis(),
+            files
+        );
+    }
+    summary.elapsed = started.elapsed();
+    if config.summary {
+        // stderr keeps the summary out of anything parsing the matches on stdout.
+        let noun = if summary.files_matched == 1 {
+            "file"
+        } else {
+            "files"
+        };
+        eprintln!(
+            "{} {} matched out of {} searched",
+            summary.files_matched, noun, summary.files_searched
+        );
+    }
+    if config.stats {
+        print_stats(&summary);
+    }
+    summary
+}
+
+/// Whether `result` failed because whatever reads stdout has gone away.
+fn stdout_closed(result: &io::Result<Searched>) -> bool {
+    result
+        .as_ref()
+        .is_err_and(|error| error.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// `--files`: prints every file that would be searched, counting each as a match, and reports
+/// the ones that cannot be.
+fn list_targets(
+    config: &Config,
+    targets: impl Iterator<Item = Target>,
+    summary: &mut Summary,
+    stdout: &mut dyn Write,
+) {
+    let mut fail = |path: &Path, error: io::Error| {
+        if vanished(config, path, &error) {
+            summary.files_vanished += 1;
+            warn_vanished(config, path);
+            return;
+        }
+        summary.had_errors = true;
+        if !config.no_messages {
+            eprintln!("Error: {}: {}", display_name(path, config), error);
+        }
+    };
+    for target in targets {
+        let path = match target {
+            Ok(path) => path,
+            Err((path, error)) => {
+                fail(&path, error);
+                continue;
+            }
+        };
+        if path != Path::new(search::STDIN_PATH)
+            && let Err(error) = path.metadata()
+        {
+            fail(&path, error);
+            continue;
+        }
+        summary.files_matched += 1;
+        let name = display_name(&path, config);
+        // A failed write to stdout has nowhere better to be reported.
+        let _ = printer::print_filename(&mut *stdout, Path::new(&*name), config);
+    }
+}
+
+/// Where a run's results go: stdout, flushed after every line for `--line-buffered` or a
+/// terminal, and otherwise in large blocks, which is much faster for big result sets.
+fn stdout_writer(config: &Config) -> Box<dyn Write> {
+    // The standard library's stdout already flushes at each newline.
+    let stdout = io::stdout().lock();
+    if config.line_buffered || stdout.is_terminal() {
+        Box::new(stdout)
+    } else {
+        Box::new(io::BufWriter::with_capacity(64 * 1024, stdout))
+    }
+}
+
+/// `--tui`: searches `targets` up front, then hands the results to the interactive browser.
+fn browse_targets(
+    config: &Config,
+    targets: impl Iterator<Item = Target>,
+    started: Instant,
+) -> Summary {
+    let mut summary = Summary::default();
+    let mut fail = |path: &Path, error: io::Error| {
+        if vanished(config, path, &error) {
+            summary.files_vanished += 1;
+            warn_vanished(config, path);
+            return;
+        }
+        summary.had_errors = true;
+        if !config.no_messages {
+            eprintln!("Error: {}: {}", display_name(path, config), error);
+        }
+    };
+    let mut results = Vec::new();
+    for target in targets {
+        let path = match target {
+            Ok(path) => path,
+            Err((path, error)) => {
+                fail(&path, error);
+                continue;
+            }
+        };
+        let searcher = Searcher::new(config);
+        match searcher.collect_path(&path) {
+            Ok(found) => {
+                summary.files_searched += 1;
+                summary.files_matched += usize::from(!found.is_empty());
+                summary.lines_matched += found.len();
+                summary.matches += found
+                    .iter()
+                    .map(|result| result.spans.len().max(1))
+                    .sum::<usize>();
+                summary.bytes_searched += searcher.bytes_searched();
+                results.extend(found);
+            }
+            Err(error) => fail(&path, error),
+        }
+    }
+    // Errors printed above stay on the main screen, to be seen once the browser closes.
+    if let Err(error) = tui::browse(config, &results) {
+        summary.had_errors = true;
+        eprintln!("Error: {}", error);
+    }
+    summary.elapsed = started.elapsed();
+    summary
+}
+
+/// Whether `error` is a file that a walk (or a `--files-from` list) found disappearing before
+/// it could be read, as build outputs and rotated logs do. Only a file named as an input is
+/// missed badly enough to be an error, and so is a broken symlink, which is still there.
+fn vanished(config: &Config, path: &Path, error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::NotFound
+        && !config.inputs.iter().any(|input| Path::new(input) == path)
+        && std::fs::symlink_metadata(path).is_err()
+}
+
+fn warn_vanished(config: &Config, path: &Path) {
+    if !config.no_messages {
+        eprintln!(
+            "Warning: {}: vanished before it could be searched",
+            display_name(path, config)
+        );
+    }
+}
+
+/// `--stats`: the run's totals, on stderr like `--summary`.
+fn print_stats(summary: &Summary) {
+    let plural =
+        |count: u64, noun: &str| format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" });
+    let matches = match summary.matches {
+        1 => "1 match".to_string(),
+        count => format!("{} matches", count),
+    };
+    eprintln!("{}", matches);
+    eprintln!("{} matched", plural(summary.lines_matched as u64, "line"));
+    eprintln!(
+        "{} contained matches",
+        plural(summary.files_matched as u64, "file")
+    );
+    eprintln!("{} searched", plural(summary.files_searched as u64, "file"));
+    if summary.files_vanished > 0 {
+        eprintln!(
+            "{} vanished before being searched",
+            plural(summary.files_vanished as u64, "file")
+        );
+    }
+    eprintln!("{} searched", plural(summary.bytes_searched, "byte"));
+    eprintln!("{:.6} seconds elapsed", summary.elapsed.as_secs_f64());
+}
+
+/// What searching one input found.
+struct Searched {
+    /// Selected lines.
+    count: usize,
+    /// Matches on the selected lines.
+    matches: usize,
+    /// Bytes read from the input.
+    bytes: u64,
+}
+
+/// Searches one file, or standard input for `-`, selecting at most `limit` lines (what
+/// `--max-total` leaves) and giving up once `cancel` is set or at `until`, the run's `--deadline`.
+fn process_file(
+    path: &Path,
+    config: &Config,
+    limit: Option<usize>,
+    cancel: Option<&AtomicBool>,
+    until: Option<Instant>,
+    mut out: impl Write,
+) -> io::Result<Searched> {
+    if until.is_some_and(|until| Instant::now() >= until) {
+        return Err(search::past_deadline());
+    }
+    if config.write || config.diff {
+        if config.write && path == Path::new(search::STDIN_PATH) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--write cannot rewrite standard input",
+            ));
+        }
+        let count = rewrite::rewrite_file(path, config, &mut out)?;
+        // A rewrite reads the whole file.
+        let bytes = path.metadata().map_or(0, |metadata| metadata.len());
+        return Ok(Searched {
+            count,
+            matches: count,
+            bytes,
+        });
+    }
+    // A matching name counts as a selected line, printed ahead of the file's own.
+    let named = usize::from(
+        config.search_names
+            && path != Path::new(search::STDIN_PATH)
+            && printer::print_name(&mut out, &printer::path_label(path), config)?,
+    );
+    if config.names_only {
+        return Ok(Searched {
+            count: named,
+            matches: named,
+            bytes: 0,
+        });
+    }
+    if config.archives
+        && let Some(archive) = Archive::of(path)
+    {
+        return search_archive(path, archive, config, limit, cancel, until, out).map(|searched| {
+            Searched {
+                count: searched.count + named,
+                matches: searched.matches + named,
+                ..searched
+            }
+        });
+    }
+    let searcher = Searcher::new(config)
+        .limit(limit)
+        .cancellable(cancel)
+        .until(until);
+    if !config.rules.is_empty() {
+        let mut printer = RulePrinter::new(config, out);
+        if path == Path::new(search::STDIN_PATH) {
+            searcher.search_stdin(&mut printer)?;
+        } else {
+            searcher.search_path(path, &mut printer)?;
+        }
+        return Ok(Searched {
+            count: printer.count(),
+            matches: printer.matches(),
+            bytes: searcher.bytes_searched(),
+        });
+    }
+    let mut printer = Printer::new(config, out);
+    if path == Path::new(search::STDIN_PATH) {
+        searcher.search_stdin(&mut printer)?;
+    } else {
+        searcher.search_path(path, &mut printer)?;
+    }
+    Ok(Searched {
+        count: printer.count() + named,
+        matches: printer.matches() + named,
+        bytes: searcher.bytes_searched(),
+    })
+}
+
+/// `--archives`: searches every file in an archive, each under its own name, as if they had
+/// been named separately (though they count as one file in the summary).
+fn search_archive(
+    path: &Path,
+    archive: Archive,
+    config: &Config,
+    limit: Option<usize>,
+    cancel: Option<&AtomicBool>,
+    until: Option<Instant>,
+    mut out: impl Write,
+) -> io::Result<Searched> {
+    let heading = printer::uses_heading(config);
+    let mut count = 0;
+    let mut matches = 0;
+    let mut bytes = 0;
+    let mut printed = false;
+    archive::for_each_entry(path, archive, |name, contents| {
+        let gap: &[u8] = if heading && printed { b"\n" } else { b"" };
+        let mut out = Separated::new(&mut out, gap);
+        let mut printer = Printer::new(config, &mut out);
+        let left = limit.map(|limit| limit - count);
+        let searcher = Searcher::new(config)
+            .limit(left)
+            .cancellable(cancel)
+            .until(until);
+        searcher.search_entry(contents, name, &mut printer)?;
+        count += printer.count();
+        matches += printer.matches();
+        bytes += searcher.bytes_searched();
+        printed |= out.written;
+        // For -q one match settles the exit status, so the remaining entries are not needed;
+        // nor are they once --max-total is reached.
+        Ok(!(config.quiet && count > 0) && limit.is_none_or(|limit| count < limit))
+    })?;
+    Ok(Searched {
+        count,
+        matches,
+        bytes,
+    })
+}
+
+/// Writes `gap` ahead of whatever is written through it, if anything is.
+struct Separated<W> {
+    inner: W,
+    gap: &'static [u8],
+    written: bool,
+}
+
+impl<W: Write> Separated<W> {
+    fn new(inner: W, gap: &'static [u8]) -> Self {
+        Separated {
+            inner,
+            gap,
+            written: false,
+        }
+    }
+}
+
+impl<W: Write> Write for Separated<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.written && !buf.is_empty() {
+            self.inner.write_all(self.gap)?;
+            self.written = true;
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `path` as shown to the user, with `-` spelled out as standard input's label.
+fn display_name<'a>(path: &'a Path, config: &'a Config) -> Cow<'a, str> {
+    if path == Path::new(search::STDIN_PATH) {
+        Cow::Borrowed(&config.label)
+    } else {
+        printer::path_label(path)
+    }
+}
@@ -1,229 +1,62 @@
-use colored::Colorize;
-use regex::{Regex, RegexBuilder};
+use grep::{Config, ParseOutcome};
 use std::env;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
 
 fn main() {
     // Skip the binary name so options can be provided before or after the pattern.
-    let args: Vec<String> = env::args().skip(1).collect();
-    match Config::parse(args) {
-        Ok(ParseOutcome::HelpPrinted) => {}
-        Ok(ParseOutcome::Run(config)) => {
-            if let Err(error) = run(&config) {
-                eprintln!("Error: {}", error);
-                std::process::exit(1);
-            }
-        }
-        Err(message) => {
-            eprintln!("{}", message);
-            std::process::exit(1);
-        }
-    }
-}
-
-fn run(config: &Config) -> io::Result<()> {
-    let targets = collect_targets(&config.inputs, config.recursive);
-    for path in targets {
-        process_file(&path, config)?;
-    }
-    Ok(())
-}
-
-fn collect_targets(inputs: &[String], recursive: bool) -> Vec<PathBuf> {
-    let mut files = Vec::new();
-
-    for input in inputs {
-        let path = PathBuf::from(input);
-        if path.is_dir() {
-            if recursive {
-                // Walk nested directories when -r is present, queuing every file for scanning.
-                for entry in WalkDir::new(&path).into_iter().filter_map(Result::ok) {
-                    let entry_path = entry.path();
-                    if entry_path.is_file() {
-                        files.push(entry_path.to_path_buf());
-                    }
-                }
+    let mut cli: Vec<String> = env::args().skip(1).collect();
+    // `grep index build|query ...` is spelled as a hidden flag; `grep index` alone still
+    // searches for "index".
+    if cli.first().is_some_and(|word| word == "index")
+        && let Some(flag) = cli.get(1).and_then(|word| match word.as_str() {
+            "build" => Some("--index-build"),
+            "query" => Some("--index-query"),
+            _ => None,
+        })
+    {
+        cli.splice(0..2, [flag.to_string()]);
+    }
+    // Likewise `grep completions SHELL`, for the shells there are scripts for.
+    if cli.first().is_some_and(|word| word == "completions")
+        && cli
+            .get(1)
+            .is_some_and(|shell| matches!(shell.as_str(), "bash" | "zsh" | "fish" | "powershell"))
+    {
+        cli[0] = "--generate-completions".to_string();
+    }
+    // With no arguments at all, usage is the answer, whatever defaults are configured.
+    let args = if cli.is_empty() {
+        cli
+    } else {
+        match grep::default_args(&cli) {
+            Ok(mut args) => {
+                args.extend(cli);
+                args
             }
-        } else if path.is_file() {
-            files.push(path);
-        } else {
-            // Keep the original path even if it does not exist; processing will raise an error.
-            files.push(path);
-        }
-    }
-
-    files
-}
-
-fn process_file(path: &Path, config: &Config) -> io::Result<()> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-
-    for (index, line) in reader.lines().enumerate() {
-        let line = line?;
-        let is_match = config.matcher.is_match(&line);
-        let should_print = if config.invert_match {
-            !is_match
-        } else {
-            is_match
-        };
-
-        if should_print {
-            let display_line = if config.colored && is_match && !config.invert_match {
-                highlight_line(&line, &config.matcher)
-            } else {
-                line.clone()
-            };
-
-            if let Some(prefix) = build_prefix(path, index + 1, config) {
-                println!("{}: {}", prefix, display_line);
-            } else {
-                println!("{}", display_line);
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(error.exit_code());
             }
         }
-    }
-
-    Ok(())
-}
-
-fn highlight_line(line: &str, matcher: &Regex) -> String {
-    // Replace each match with a colored version so only the pattern stands out.
-    matcher
-        .replace_all(line, |caps: &regex::Captures| caps[0].red().to_string())
-        .to_string()
-}
-
-fn build_prefix(path: &Path, line_number: usize, config: &Config) -> Option<String> {
-    let mut parts = Vec::new();
-
-    if config.show_filenames {
-        parts.push(path.to_string_lossy().into_owned());
-    }
-
-    if config.show_line_numbers {
-        parts.push(line_number.to_string());
-    }
-
-    if parts.is_empty() {
-        None
-    } else {
-        Some(parts.join(":"))
-    }
-}
-
-fn print_usage() {
-    println!("Usage: grep [OPTIONS] <pattern> <files...>");
-    println!();
-    println!("Options:");
-    println!("-i                Case-insensitive search");
-    println!("-n                Print line numbers");
-    println!("-v                Invert match (exclude lines that match the pattern)");
-    println!("-r                Recursive directory search");
-    println!("-f                Print filenames");
-    println!("-c                Enable colored output");
-    println!("-h, --help        Show help information");
-}
-
-struct Config {
-    inputs: Vec<String>,
-    show_line_numbers: bool,
-    invert_match: bool,
-    recursive: bool,
-    show_filenames: bool,
-    colored: bool,
-    matcher: Regex,
-}
-
-enum ParseOutcome {
-    HelpPrinted,
-    Run(Config),
-}
-
-impl Config {
-    fn parse(args: Vec<String>) -> Result<ParseOutcome, String> {
-        if args.is_empty() {
-            return Err("Missing arguments. Use -h for help.".to_string());
-        }
-
-        let mut case_insensitive = false;
-        let mut show_line_numbers = false;
-        let mut invert_match = false;
-        let mut recursive = false;
-        let mut show_filenames = false;
-        let mut colored = false;
-        let mut pattern: Option<String> = None;
-        let mut inputs: Vec<String> = Vec::new();
-        let mut options_done = false;
-
-        for arg in args {
-            if !options_done {
-                match arg.as_str() {
-                    "-h" | "--help" => {
-                        print_usage();
-                        return Ok(ParseOutcome::HelpPrinted);
-                    }
-                    "-i" => {
-                        case_insensitive = true;
-                        continue;
-                    }
-                    "-n" => {
-                        show_line_numbers = true;
-                        continue;
-                    }
-                    "-v" => {
-                        invert_match = true;
-                        continue;
-                    }
-                    "-r" => {
-                        recursive = true;
-                        continue;
-                    }
-                    "-f" => {
-                        show_filenames = true;
-                        continue;
-                    }
-                    "-c" => {
-                        colored = true;
-                        continue;
-                    }
-                    "--" => {
-                        options_done = true;
-                        continue;
-                    }
-                    _ => {}
-                }
+    };
+    match Config::parse(args) {
+        Ok(ParseOutcome::HelpPrinted) => {}
+        // Exit 0 if a line was selected, 1 if none was, 2 on errors; as in GNU grep, a match
+        // under -q wins over errors.
+        Ok(ParseOutcome::Run(config)) => match grep::run(&config) {
+            Ok(summary) if summary.had_errors && !(config.quiet && summary.files_matched > 0) => {
+                std::process::exit(2)
             }
-
-            if pattern.is_none() {
-                pattern = Some(arg);
-            } else {
-                inputs.push(arg);
+            Ok(summary) if summary.files_matched == 0 => std::process::exit(1),
+            Ok(_) => {}
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                std::process::exit(error.exit_code());
             }
+        },
+        // Each kind of error has its own status: 2 for files, 3 for patterns, 4 for options.
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(error.exit_code());
         }
-
-        let pattern = pattern.ok_or_else(|| "Missing search pattern.".to_string())?;
-
-        if inputs.is_empty() {
-            return Err("Missing input files.".to_string());
-        }
-
-        // Escape the literal pattern so flags behave the same regardless of special characters.
-        let matcher = RegexBuilder::new(&regex::escape(&pattern))
-            .case_insensitive(case_insensitive)
-            .build()
-            .map_err(|err| err.to_string())?;
-
-        Ok(ParseOutcome::Run(Config {
-            inputs,
-            show_line_numbers,
-            invert_match,
-            recursive,
-            show_filenames,
-            colored,
-            matcher,
-        }))
     }
 }
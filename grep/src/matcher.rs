@@ -0,0 +1,754 @@
+use aho_corasick::{AhoCorasick, AhoCorasickKind, MatchKind};
+use regex::{Regex, RegexBuilder};
+use regex_automata::util::syntax;
+use regex_automata::{Anchored, Input, meta};
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// Flags shared by every matching engine.
+#[derive(Clone, Copy, Default)]
+pub struct MatchOptions {
+    pub case_insensitive: bool,
+    pub multiline: bool,
+    /// Only match where the pattern is not next to a word character on either side.
+    pub word: bool,
+    /// Only match at the start of a line.
+    pub starts_with: bool,
+    /// Only match at the end of a line.
+    pub ends_with: bool,
+}
+
+/// How the patterns are read: `-F`, `-E`, or `-P`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Syntax {
+    /// Fixed strings, the default.
+    #[default]
+    Literal,
+    Regex,
+    Pcre,
+}
+
+impl Syntax {
+    fn flag(self) -> &'static str {
+        match self {
+            Syntax::Literal => "-F",
+            Syntax::Regex => "-E",
+            Syntax::Pcre => "-P",
+        }
+    }
+}
+
+/// Everything the matcher is compiled from, kept so that it can be described or rebuilt.
+#[derive(Clone, Default)]
+pub struct PatternSpec {
+    pub patterns: Vec<String>,
+    pub syntax: Syntax,
+    pub options: MatchOptions,
+}
+
+impl PatternSpec {
+    pub fn compile(&self) -> Result<Matcher, String> {
+        let flag = self.syntax.flag();
+        match self.syntax {
+            Syntax::Literal => Matcher::literal(&self.patterns, self.options),
+            Syntax::Regex => Matcher::standard(&join_patterns(&self.patterns), self.options)
+                .map_err(|err| format!("Invalid {} pattern: {}", flag, err)),
+            Syntax::Pcre => {
+                if self.patterns.len() > 1 {
+                    return Err(
+                        "-P takes a single pattern; combine alternatives into one pattern instead."
+                            .to_string(),
+                    );
+                }
+                Matcher::pcre(&join_patterns(&self.patterns), self.options)
+                    .map_err(|err| format!("Invalid {} pattern: {}", flag, err))
+            }
+        }
+    }
+
+    /// For `--debug-pattern`: the syntax, flags, compiled pattern, and engine of `matcher`, which
+    /// was compiled from this spec.
+    pub fn describe(&self, matcher: &Matcher) -> String {
+        let options = self.options;
+        let flags: Vec<&str> = [
+            (options.case_insensitive, "case-insensitive"),
+            (options.multiline, "multiline"),
+            (options.word, "word"),
+            (options.starts_with, "starts-with"),
+            (options.ends_with, "ends-with"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect();
+        let syntax = match self.syntax {
+            Syntax::Literal => "fixed strings",
+            Syntax::Regex => "regex",
+            Syntax::Pcre => "regex with look-around",
+        };
+        format!(
+            "syntax: {} ({})\nflags: {}\npattern: {}\nbackend: {}\n",
+            syntax,
+            self.syntax.flag(),
+            if flags.is_empty() {
+                "none".to_string()
+            } else {
+                flags.join(" ")
+            },
+            matcher.pattern(),
+            matcher.backend()
+        )
+    }
+}
+
+/// `--all-of`, `--any-of`, and `--none-of`: patterns a line the main pattern matched must also
+/// match, one of which it must match, and which it must not match, to be selected.
+#[derive(Default)]
+pub struct LineFilter {
+    pub all: Vec<Matcher>,
+    pub any: Vec<Matcher>,
+    pub none: Vec<Matcher>,
+}
+
+impl LineFilter {
+    pub fn is_empty(&self) -> bool {
+        self.all.is_empty() && self.any.is_empty() && self.none.is_empty()
+    }
+
+    /// Whether `line`, already matched by the main pattern, passes every filter.
+    pub fn accepts(&self, line: &str) -> bool {
+        self.all.iter().all(|matcher| matcher.is_match(line))
+            && (self.any.is_empty() || self.any.iter().any(|matcher| matcher.is_match(line)))
+            && !self.none.iter().any(|matcher| matcher.is_match(line))
+    }
+}
+
+/// `--within` and `--within-end`: the regions a line must be inside to be searched, each opened
+/// by a line matching `start` and closed by one matching `end`.
+pub struct Within {
+    pub start: Matcher,
+    /// `None` when the one pattern both opens and closes a region, as a code fence does.
+    pub end: Option<Matcher>,
+}
+
+impl Within {
+    /// A tracker for one file, starting outside any region.
+    pub(crate) fn regions(&self) -> Regions<'_> {
+        Regions {
+            within: self,
+            depth: 0,
+        }
+    }
+}
+
+/// Follows a file's lines in and out of `--within` regions.
+pub(crate) struct Regions<'w> {
+    within: &'w Within,
+    /// How many regions are open. With distinct start and end patterns they nest, and a line is
+    /// inside until the outermost closes; a region still open at the end of the file runs to it.
+    depth: usize,
+}
+
+impl Regions<'_> {
+    /// Whether `line`, the file's next, is inside a region. The lines that open and close a
+    /// region are not part of it.
+    pub(crate) fn inside(&mut self, line: &str) -> bool {
+        let Within { start, end } = self.within;
+        match end {
+            // The same pattern opens and closes, so regions cannot nest.
+            None if start.is_match(line) => {
+                self.depth = 1 - self.depth;
+                return false;
+            }
+            Some(end) if self.depth > 0 && end.is_match(line) => {
+                self.depth -= 1;
+                return false;
+            }
+            Some(_) if start.is_match(line) => {
+                self.depth += 1;
+                return false;
+            }
+            _ => {}
+        }
+        self.depth > 0
+    }
+}
+
+/// The compiled pattern, dispatching to whichever engine was requested.
+pub enum Matcher {
+    /// The default engine, backed by the `regex` crate.
+    Standard(Regex),
+    /// `-P`: a `regex` core with look-behind and look-ahead assertions around it.
+    Pcre(Lookaround),
+    /// Fixed strings searched for directly, without a regex.
+    Literal(Literal),
+}
+
+impl Matcher {
+    pub fn standard(pattern: &str, options: MatchOptions) -> Result<Matcher, String> {
+        build_regex(&bound(pattern, options), options).map(Matcher::Standard)
+    }
+
+    pub fn pcre(pattern: &str, options: MatchOptions) -> Result<Matcher, String> {
+        Lookaround::new(pattern, options).map(Matcher::Pcre)
+    }
+
+    /// Matches any of `patterns` as fixed strings. A large set of plain case-sensitive strings
+    /// gets a [`Literal`] searcher; otherwise they are escaped into a regex, which also handles
+    /// Unicode case folding, word boundaries, anchors, and empty patterns.
+    pub fn literal(patterns: &[String], options: MatchOptions) -> Result<Matcher, String> {
+        // Escaped, flags behave the same regardless of special characters.
+        let escaped: Vec<String> = patterns
+            .iter()
+            .map(|pattern| regex::escape(pattern))
+            .collect();
+        let pattern = join_patterns(&escaped);
+        let plain = patterns.len() >= LITERAL_SET_MIN
+            && !options.case_insensitive
+            && !options.multiline
+            && !options.word
+            && !options.starts_with
+            && !options.ends_with
+            && patterns.iter().all(|pattern| !pattern.is_empty());
+        if !plain {
+            return Matcher::standard(&pattern, options);
+        }
+        Literal::new(patterns, pattern).map(Matcher::Literal)
+    }
+
+    /// The pattern as the engine runs it, with flags, anchors, and escapes applied.
+    pub fn pattern(&self) -> &str {
+        match self {
+            Matcher::Standard(regex) => regex.as_str(),
+            Matcher::Pcre(lookaround) => &lookaround.pattern,
+            Matcher::Literal(literal) => &literal.pattern,
+        }
+    }
+
+    /// The name of the engine doing the matching.
+    pub fn backend(&self) -> &'static str {
+        match self {
+            Matcher::Standard(_) => "regex",
+            Matcher::Pcre(_) => "regex-automata with look-around",
+            Matcher::Literal(_) => "aho-corasick",
+        }
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        match self {
+            Matcher::Standard(regex) => regex.is_match(haystack),
+            Matcher::Pcre(lookaround) => lookaround.find_at(haystack, 0).is_some(),
+            Matcher::Literal(literal) => literal.find_at(haystack, 0).is_some(),
+        }
+    }
+
+    pub fn find(&self, haystack: &str) -> Option<Range<usize>> {
+        match self {
+            Matcher::Standard(regex) => regex.find(haystack).map(|found| found.range()),
+            Matcher::Pcre(lookaround) => lookaround.find_at(haystack, 0),
+            Matcher::Literal(literal) => literal.find_at(haystack, 0),
+        }
+    }
+
+    /// The first match starting at or after `at`, with what comes before `at` still seen by
+    /// `^`, `\b`, and look-behind.
+    pub(crate) fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        match self {
+            Matcher::Standard(regex) => regex.find_at(haystack, at).map(|found| found.range()),
+            Matcher::Pcre(lookaround) => lookaround.find_at(haystack, at),
+            Matcher::Literal(literal) => literal.find_at(haystack, at),
+        }
+    }
+
+    /// Byte ranges of every non-overlapping match, in order.
+    pub fn find_iter<'h>(
+        &'h self,
+        haystack: &'h str,
+    ) -> Box<dyn Iterator<Item = Range<usize>> + 'h> {
+        match self {
+            Matcher::Standard(regex) => {
+                Box::new(regex.find_iter(haystack).map(|found| found.range()))
+            }
+            Matcher::Pcre(lookaround) => Box::new(lookaround.find_iter(haystack)),
+            Matcher::Literal(literal) => literal.find_iter(haystack),
+        }
+    }
+
+    /// Rebuilds `haystack` with every match replaced by `template`, in which `$1`, `${name}`,
+    /// and `$0` refer to capture groups of the match and `$$` is a literal `$`.
+    pub fn replace_template(&self, haystack: &str, template: &str) -> String {
+        match self {
+            Matcher::Standard(regex) => regex.replace_all(haystack, template).into_owned(),
+            Matcher::Pcre(lookaround) => lookaround.replace_template(haystack, template),
+            Matcher::Literal(literal) => {
+                literal.regex().replace_all(haystack, template).into_owned()
+            }
+        }
+    }
+
+    /// Expands `template` for the single match of `haystack` at `span`, as found by
+    /// [`Matcher::find_iter`].
+    pub fn expand_at(&self, haystack: &str, span: Range<usize>, template: &str) -> String {
+        let mut expanded = String::new();
+        match self {
+            Matcher::Standard(regex) => {
+                if let Some(captures) = regex.captures_at(haystack, span.start) {
+                    captures.expand(template, &mut expanded);
+                }
+            }
+            Matcher::Literal(literal) => {
+                if let Some(captures) = literal.regex().captures_at(haystack, span.start) {
+                    captures.expand(template, &mut expanded);
+                }
+            }
+            Matcher::Pcre(lookaround) => {
+                lookaround.expand_at(haystack, span, template, &mut expanded)
+            }
+        }
+        expanded
+    }
+
+    /// The spans of capture groups 1 and up in the match of `haystack` at `span`, as found by
+    /// [`Matcher::find_iter`]; `None` for a group that took no part in it.
+    pub fn group_spans(&self, haystack: &str, span: Range<usize>) -> Vec<Option<Range<usize>>> {
+        match self {
+            Matcher::Standard(regex) => {
+                regex
+                    .captures_at(haystack, span.start)
+                    .map_or_else(Vec::new, |captures| {
+                        captures
+                            .iter()
+                            .skip(1)
+                            .map(|group| group.map(|group| group.range()))
+                            .collect()
+                    })
+            }
+            Matcher::Pcre(lookaround) => lookaround.group_spans(haystack, span),
+            // A fixed string has no groups.
+            Matcher::Literal(_) => Vec::new(),
+        }
+    }
+
+    /// The pattern prepared for searching a whole buffer of lines at once, with `^` and `$`
+    /// matching at every `\n` or `\r\n` line boundary. Whatever a line matches, the buffer
+    /// matches at or before it. `None` for `-P`, whose assertions only hold line by line.
+    pub(crate) fn buffer_matcher(&self) -> Option<BufferMatcher<'_>> {
+        match self {
+            // The pattern string carries its flags inline, so only the line handling changes.
+            Matcher::Standard(regex) => RegexBuilder::new(regex.as_str())
+                .multi_line(true)
+                .crlf(true)
+                .build()
+                .ok()
+                .map(BufferMatcher::Regex),
+            Matcher::Pcre(_) => None,
+            // A fixed string cannot span a line, so it means the same in a buffer.
+            Matcher::Literal(literal) => Some(BufferMatcher::Literal(literal)),
+        }
+    }
+
+    /// Rebuilds `haystack` with every match passed through `replacer`.
+    pub fn replace_all(&self, haystack: &str, mut replacer: impl FnMut(&str) -> String) -> String {
+        let mut replaced = String::with_capacity(haystack.len());
+        let mut last = 0;
+        for span in self.find_iter(haystack) {
+            replaced.push_str(&haystack[last..span.start]);
+            replaced.push_str(&replacer(&haystack[span.clone()]));
+            last = span.end;
+        }
+        replaced.push_str(&haystack[last..]);
+        replaced
+    }
+}
+
+/// What [`Matcher::buffer_matcher`] searches a whole buffer with.
+pub(crate) enum BufferMatcher<'m> {
+    Regex(Regex),
+    Literal(&'m Literal),
+}
+
+impl BufferMatcher<'_> {
+    pub(crate) fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        match self {
+            BufferMatcher::Regex(regex) => regex.find_at(haystack, at).map(|found| found.range()),
+            BufferMatcher::Literal(literal) => literal.find_at(haystack, at),
+        }
+    }
+}
+
+/// How many fixed strings it takes for [`Literal`] to beat the regex engine. With fewer, the
+/// `regex` crate's own literal search (memmem for one string, SIMD Teddy for a few dozen, a
+/// lazy DFA beyond) is as fast or faster; around a few thousand its DFA stops fitting in cache
+/// and compiling the alternation starts to show, while Aho-Corasick keeps scanning at one speed.
+const LITERAL_SET_MIN: usize = 4096;
+
+/// A large set of fixed, non-empty strings, found with an Aho-Corasick automaton. Where several
+/// start at the same place, the one given first wins, as in a regex alternation.
+pub struct Literal {
+    automaton: AhoCorasick,
+    /// The strings escaped and joined into a regex, for `--replace` templates.
+    pattern: String,
+    regex: OnceLock<Regex>,
+}
+
+impl Literal {
+    fn new(patterns: &[String], pattern: String) -> Result<Literal, String> {
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostFirst)
+            .kind(Some(AhoCorasickKind::DFA))
+            .build(patterns)
+            .map_err(|err| err.to_string())?;
+        Ok(Literal {
+            automaton,
+            pattern,
+            regex: OnceLock::new(),
+        })
+    }
+
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        self.automaton
+            .find(aho_corasick::Input::new(haystack).span(at..haystack.len()))
+            .map(|found| found.range())
+    }
+
+    fn find_iter<'h>(&'h self, haystack: &'h str) -> Box<dyn Iterator<Item = Range<usize>> + 'h> {
+        Box::new(
+            self.automaton
+                .find_iter(haystack)
+                .map(|found| found.range()),
+        )
+    }
+
+    fn regex(&self) -> &Regex {
+        self.regex.get_or_init(|| {
+            build_regex(&self.pattern, MatchOptions::default()).expect("escaped strings compile")
+        })
+    }
+}
+
+/// Combines patterns into one regex that matches wherever any of them does.
+pub(crate) fn join_patterns(patterns: &[String]) -> String {
+    match patterns {
+        // An empty pattern file selects nothing; `\b\B` can never match.
+        [] => r"\b\B".to_string(),
+        [pattern] => pattern.clone(),
+        patterns => {
+            let groups: Vec<String> = patterns
+                .iter()
+                .map(|pattern| format!("(?:{})", pattern))
+                .collect();
+            groups.join("|")
+        }
+    }
+}
+
+/// Wraps `pattern` in the word-boundary and line anchors `options` ask for.
+fn bound(pattern: &str, options: MatchOptions) -> String {
+    let mut pattern = pattern.to_string();
+    if options.word {
+        // Half boundaries, unlike `\b`, also work for patterns that start or end with a
+        // non-word character, such as `-x`.
+        pattern = format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern);
+    }
+    if options.starts_with || options.ends_with {
+        pattern = format!(
+            "{}(?:{}){}",
+            if options.starts_with { "^" } else { "" },
+            pattern,
+            if options.ends_with { "$" } else { "" }
+        );
+    }
+    pattern
+}
+
+/// Compiles `pattern`, spelling the flags inline so that [`Regex::as_str`] reproduces it exactly.
+fn build_regex(pattern: &str, options: MatchOptions) -> Result<Regex, String> {
+    RegexBuilder::new(&format!("{}{}", inline_flags(options), pattern))
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+fn inline_flags(options: MatchOptions) -> &'static str {
+    match (options.case_insensitive, options.multiline) {
+        (false, false) => "",
+        (true, false) => "(?i)",
+        // CRLF mode keeps `$` from failing before the `\r` of a Windows line ending.
+        (false, true) => "(?msR)",
+        (true, true) => "(?imsR)",
+    }
+}
+
+/// A pattern of the form `(?<=…)(?<!…)core(?=…)(?!…)`.
+///
+/// The `regex` crate has no look-around, so the assertions are peeled off the ends of the pattern
+/// and checked separately around each candidate match of the core. Look-around anywhere else in
+/// the pattern is rejected when it is compiled.
+pub struct Lookaround {
+    /// The whole pattern, spelled with its flags inline, for [`Matcher::pattern`].
+    pattern: String,
+    core: meta::Regex,
+    behind: Vec<Assertion>,
+    ahead: Vec<Assertion>,
+}
+
+struct Assertion {
+    regex: Regex,
+    negated: bool,
+}
+
+impl Assertion {
+    fn holds(&self, text: &str) -> bool {
+        self.regex.is_match(text) != self.negated
+    }
+}
+
+impl Lookaround {
+    fn new(pattern: &str, options: MatchOptions) -> Result<Lookaround, String> {
+        let (behind, core, ahead) = split_lookaround(pattern)?;
+
+        let syntax = syntax::Config::new()
+            .case_insensitive(options.case_insensitive)
+            .multi_line(options.multiline)
+            .crlf(options.multiline)
+            .dot_matches_new_line(options.multiline);
+        let core = meta::Regex::builder()
+            .syntax(syntax)
+            .build(&bound(core, options))
+            .map_err(|err| {
+                err.syntax_error()
+                    .map_or_else(|| err.to_string(), |syntax| syntax.to_string())
+            })?;
+
+        // Each assertion is anchored against the text on its side of the candidate match.
+        let compile = |groups: Vec<(&str, bool)>, anchor: fn(&str) -> String| {
+            groups
+                .into_iter()
+                .map(|(inner, negated)| {
+                    let regex = build_regex(&anchor(inner), options)?;
+                    Ok(Assertion { regex, negated })
+                })
+                .collect::<Result<Vec<_>, String>>()
+        };
+        let behind = compile(behind, |inner| format!("(?:{})\\z", inner))?;
+        let ahead = compile(ahead, |inner| format!("\\A(?:{})", inner))?;
+
+        Ok(Lookaround {
+            pattern: format!("{}{}", inline_flags(options), bound(pattern, options)),
+            core,
+            behind,
+            ahead,
+        })
+    }
+
+    fn find_at(&self, haystack: &str, from: usize) -> Option<Range<usize>> {
+        let mut position = from;
+        while position <= haystack.len() {
+            let candidate = self
+                .core
+                .search(&Input::new(haystack).span(position..haystack.len()))?;
+            let start = candidate.start();
+
+            if self
+                .behind
+                .iter()
+                .all(|assertion| assertion.holds(&haystack[..start]))
+            {
+                // Like a backtracking engine, retry shorter matches from the same start until the
+                // look-ahead accepts one.
+                let mut end = candidate.end();
+                loop {
+                    if self
+                        .ahead
+                        .iter()
+                        .all(|assertion| assertion.holds(&haystack[end..]))
+                    {
+                        return Some(start..end);
+                    }
+                    let Some(limit) = haystack[..end].char_indices().last().map(|(at, _)| at)
+                    else {
+                        break;
+                    };
+                    if limit < start {
+                        break;
+                    }
+                    let input = Input::new(haystack)
+                        .span(start..limit)
+                        .anchored(Anchored::Yes);
+                    match self.core.search(&input) {
+                        Some(shorter) => end = shorter.end(),
+                        None => break,
+                    }
+                }
+            }
+
+            position = start + haystack[start..].chars().next().map_or(1, char::len_utf8);
+        }
+        None
+    }
+
+    fn replace_template(&self, haystack: &str, template: &str) -> String {
+        let mut replaced = String::with_capacity(haystack.len());
+        let mut last = 0;
+        for span in self.find_iter(haystack) {
+            replaced.push_str(&haystack[last..span.start]);
+            last = span.end;
+            self.expand_at(haystack, span, template, &mut replaced);
+        }
+        replaced.push_str(&haystack[last..]);
+        replaced
+    }
+
+    fn expand_at(&self, haystack: &str, span: Range<usize>, template: &str, out: &mut String) {
+        // Re-run the core on exactly this match to recover its groups.
+        let mut captures = self.core.create_captures();
+        let input = Input::new(haystack).range(span).anchored(Anchored::Yes);
+        self.core.search_captures(&input, &mut captures);
+        captures.interpolate_string_into(haystack, template, out);
+    }
+
+    fn group_spans(&self, haystack: &str, span: Range<usize>) -> Vec<Option<Range<usize>>> {
+        let mut captures = self.core.create_captures();
+        let input = Input::new(haystack).range(span).anchored(Anchored::Yes);
+        self.core.search_captures(&input, &mut captures);
+        (1..captures.group_len())
+            .map(|index| captures.get_group(index).map(|group| group.range()))
+            .collect()
+    }
+
+    fn find_iter<'h>(&'h self, haystack: &'h str) -> impl Iterator<Item = Range<usize>> + 'h {
+        let mut position = 0;
+        std::iter::from_fn(move || {
+            let found = self.find_at(haystack, position)?;
+            position = if found.is_empty() {
+                // Step over empty matches so the iterator always makes progress.
+                found.end
+                    + haystack[found.end..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8)
+            } else {
+                found.end
+            };
+            Some(found)
+        })
+    }
+}
+
+const LOOKBEHIND: [&str; 2] = ["(?<=", "(?<!"];
+const LOOKAHEAD: [&str; 2] = ["(?=", "(?!"];
+
+/// Leading look-behinds, the core, and trailing look-aheads, each assertion as `(inner, negated)`.
+type Split<'p> = (Vec<(&'p str, bool)>, &'p str, Vec<(&'p str, bool)>);
+
+/// Peels look-around groups off both ends of `pattern`.
+fn split_lookaround(pattern: &str) -> Result<Split<'_>, String> {
+    let (groups, bars) = scan_groups(pattern)?;
+    let mut top_level: Vec<&Group> = groups.iter().filter(|&&(_, _, depth)| depth == 0).collect();
+    top_level.sort_unstable();
+
+    let group = |start: usize, end: usize, prefix: &[&str; 2]| {
+        let text = &pattern[start..end];
+        prefix
+            .iter()
+            .find(|open| text.starts_with(**open))
+            .map(|open| (&text[open.len()..text.len() - 1], *open == prefix[1]))
+    };
+
+    let mut behind = Vec::new();
+    let mut core_start = 0;
+    for &&(start, end, _) in &top_level {
+        match group(start, end, &LOOKBEHIND) {
+            Some(assertion) if start == core_start => {
+                behind.push(assertion);
+                core_start = end;
+            }
+            _ => break,
+        }
+    }
+
+    let mut ahead = Vec::new();
+    let mut core_end = pattern.len();
+    for &&(start, end, _) in top_level.iter().rev() {
+        match group(start, end, &LOOKAHEAD) {
+            Some(assertion) if end == core_end && start >= core_start => {
+                ahead.insert(0, assertion);
+                core_end = start;
+            }
+            _ => break,
+        }
+    }
+
+    let inside_core = |at: usize| at >= core_start && at < core_end;
+    let stray = groups.iter().any(|&(start, end, _)| {
+        inside_core(start)
+            && (group(start, end, &LOOKBEHIND).is_some() || group(start, end, &LOOKAHEAD).is_some())
+    });
+    if stray {
+        return Err("-P supports look-around only at the start or end of the pattern".to_string());
+    }
+    if (!behind.is_empty() || !ahead.is_empty()) && bars.iter().any(|&at| inside_core(at)) {
+        return Err(
+            "-P needs alternatives grouped, e.g. (?<=x)(?:a|b), when using look-around".to_string(),
+        );
+    }
+
+    Ok((behind, &pattern[core_start..core_end], ahead))
+}
+
+/// A parenthesised group as `(start, end, depth)`.
+type Group = (usize, usize, usize);
+
+/// Every group in `pattern`, plus the positions of top-level `|`. Unbalanced parentheses or
+/// classes are an error naming the one left open or closed; the core regex would only see a
+/// look-around it does not support.
+fn scan_groups(pattern: &str) -> Result<(Vec<Group>, Vec<usize>), String> {
+    let bytes = pattern.as_bytes();
+    let mut open = Vec::new();
+    let mut groups = Vec::new();
+    let mut bars = Vec::new();
+    let mut class_depth = 0;
+    let mut class_start = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            // Escapes never start structure; any UTF-8 continuation bytes are skipped harmlessly.
+            b'\\' => index += 1,
+            b'[' => {
+                if class_depth == 0 {
+                    class_start = index;
+                }
+                class_depth += 1;
+                // A `]` straight after `[` or `[^` is a literal member of the class.
+                if bytes.get(index + 1) == Some(&b'^') {
+                    index += 1;
+                }
+                if bytes.get(index + 1) == Some(&b']') {
+                    index += 1;
+                }
+            }
+            b']' if class_depth > 0 => class_depth -= 1,
+            _ if class_depth > 0 => {}
+            b'(' => open.push(index),
+            b')' => {
+                let Some(start) = open.pop() else {
+                    return Err(format!("unopened group: the ) at byte {} has no (", index));
+                };
+                groups.push((start, index + 1, open.len()));
+            }
+            b'|' if open.is_empty() => bars.push(index),
+            _ => {}
+        }
+        index += 1;
+    }
+
+    if let Some(start) = open.pop() {
+        return Err(format!("unclosed group: the ( at byte {} has no )", start));
+    }
+    if class_depth > 0 {
+        return Err(format!(
+            "unclosed class: the [ at byte {} has no ]",
+            class_start
+        ));
+    }
+    Ok((groups, bars))
+}
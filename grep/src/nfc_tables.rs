@@ -0,0 +1,1034 @@
+//! Unicode 14.0.0 tables for NFC normalization, generated from the Unicode Character Database
+//! (with Python's `unicodedata`) by `scripts/nfc_tables.py`. Hangul syllables are composed
+//! and decomposed arithmetically instead, so they do not appear here.
+
+/// Canonical decompositions, sorted: each character and the one or two it decomposes to.
+pub(crate) const DECOMPOSITIONS: &[(char, char, Option<char>)] = &[
+    ('\u{C0}', '\u{41}', Some('\u{300}')), ('\u{C1}', '\u{41}', Some('\u{301}')), ('\u{C2}', '\u{41}', Some('\u{302}')),
+    ('\u{C3}', '\u{41}', Some('\u{303}')), ('\u{C4}', '\u{41}', Some('\u{308}')), ('\u{C5}', '\u{41}', Some('\u{30A}')),
+    ('\u{C7}', '\u{43}', Some('\u{327}')), ('\u{C8}', '\u{45}', Some('\u{300}')), ('\u{C9}', '\u{45}', Some('\u{301}')),
+    ('\u{CA}', '\u{45}', Some('\u{302}')), ('\u{CB}', '\u{45}', Some('\u{308}')), ('\u{CC}', '\u{49}', Some('\u{300}')),
+    ('\u{CD}', '\u{49}', Some('\u{301}')), ('\u{CE}', '\u{49}', Some('\u{302}')), ('\u{CF}', '\u{49}', Some('\u{308}')),
+    ('\u{D1}', '\u{4E}', Some('\u{303}')), ('\u{D2}', '\u{4F}', Some('\u{300}')), ('\u{D3}', '\u{4F}', Some('\u{301}')),
+    ('\u{D4}', '\u{4F}', Some('\u{302}')), ('\u{D5}', '\u{4F}', Some('\u{303}')), ('\u{D6}', '\u{4F}', Some('\u{308}')),
+    ('\u{D9}', '\u{55}', Some('\u{300}')), ('\u{DA}', '\u{55}', Some('\u{301}')), ('\u{DB}', '\u{55}', Some('\u{302}')),
+    ('\u{DC}', '\u{55}', Some('\u{308}')), ('\u{DD}', '\u{59}', Some('\u{301}')), ('\u{E0}', '\u{61}', Some('\u{300}')),
+    ('\u{E1}', '\u{61}', Some('\u{301}')), ('\u{E2}', '\u{61}', Some('\u{302}')), ('\u{E3}', '\u{61}', Some('\u{303}')),
+    ('\u{E4}', '\u{61}', Some('\u{308}')), ('\u{E5}', '\u{61}', Some('\u{30A}')), ('\u{E7}', '\u{63}', Some('\u{327}')),
+    ('\u{E8}', '\u{65}', Some('\u{300}')), ('\u{E9}', '\u{65}', Some('\u{301}')), ('\u{EA}', '\u{65}', Some('\u{302}')),
+    ('\u{EB}', '\u{65}', Some('\u{308}')), ('\u{EC}', '\u{69}', Some('\u{300}')), ('\u{ED}', '\u{69}', Some('\u{301}')),
+    ('\u{EE}', '\u{69}', Some('\u{302}')), ('\u{EF}', '\u{69}', Some('\u{308}')), ('\u{F1}', '\u{6E}', Some('\u{303}')),
+    ('\u{F2}', '\u{6F}', Some('\u{300}')), ('\u{F3}', '\u{6F}', Some('\u{301}')), ('\u{F4}', '\u{6F}', Some('\u{302}')),
+    ('\u{F5}', '\u{6F}', Some('\u{303}')), ('\u{F6}', '\u{6F}', Some('\u{308}')), ('\u{F9}', '\u{75}', Some('\u{300}')),
+    ('\u{FA}', '\u{75}', Some('\u{301}')), ('\u{FB}', '\u{75}', Some('\u{302}')), ('\u{FC}', '\u{75}', Some('\u{308}')),
+    ('\u{FD}', '\u{79}', Some('\u{301}')), ('\u{FF}', '\u{79}', Some('\u{308}')), ('\u{100}', '\u{41}', Some('\u{304}')),
+    ('\u{101}', '\u{61}', Some('\u{304}')), ('\u{102}', '\u{41}', Some('\u{306}')), ('\u{103}', '\u{61}', Some('\u{306}')),
+    ('\u{104}', '\u{41}', Some('\u{328}')), ('\u{105}', '\u{61}', Some('\u{328}')), ('\u{106}', '\u{43}', Some('\u{301}')),
+    ('\u{107}', '\u{63}', Some('\u{301}')), ('\u{108}', '\u{43}', Some('\u{302}')), ('\u{109}', '\u{63}', Some('\u{302}')),
+    ('\u{10A}', '\u{43}', Some('\u{307}')), ('\u{10B}', '\u{63}', Some('\u{307}')), ('\u{10C}', '\u{43}', Some('\u{30C}')),
+    ('\u{10D}', '\u{63}', Some('\u{30C}')), ('\u{10E}', '\u{44}', Some('\u{30C}')), ('\u{10F}', '\u{64}', Some('\u{30C}')),
+    ('\u{112}', '\u{45}', Some('\u{304}')), ('\u{113}', '\u{65}', Some('\u{304}')), ('\u{114}', '\u{45}', Some('\u{306}')),
+    ('\u{115}', '\u{65}', Some('\u{306}')), ('\u{116}', '\u{45}', Some('\u{307}')), ('\u{117}', '\u{65}', Some('\u{307}')),
+    ('\u{118}', '\u{45}', Some('\u{328}')), ('\u{119}', '\u{65}', Some('\u{328}')), ('\u{11A}', '\u{45}', Some('\u{30C}')),
+    ('\u{11B}', '\u{65}', Some('\u{30C}')), ('\u{11C}', '\u{47}', Some('\u{302}')), ('\u{11D}', '\u{67}', Some('\u{302}')),
+    ('\u{11E}', '\u{47}', Some('\u{306}')), ('\u{11F}', '\u{67}', Some('\u{306}')), ('\u{120}', '\u{47}', Some('\u{307}')),
+    ('\u{121}', '\u{67}', Some('\u{307}')), ('\u{122}', '\u{47}', Some('\u{327}')), ('\u{123}', '\u{67}', Some('\u{327}')),
+    ('\u{124}', '\u{48}', Some('\u{302}')), ('\u{125}', '\u{68}', Some('\u{302}')), ('\u{128}', '\u{49}', Some('\u{303}')),
+    ('\u{129}', '\u{69}', Some('\u{303}')), ('\u{12A}', '\u{49}', Some('\u{304}')), ('\u{12B}', '\u{69}', Some('\u{304}')),
+    ('\u{12C}', '\u{49}', Some('\u{306}')), ('\u{12D}', '\u{69}', Some('\u{306}')), ('\u{12E}', '\u{49}', Some('\u{328}')),
+    ('\u{12F}', '\u{69}', Some('\u{328}')), ('\u{130}', '\u{49}', Some('\u{307}')), ('\u{134}', '\u{4A}', Some('\u{302}')),
+    ('\u{135}', '\u{6A}', Some('\u{302}')), ('\u{136}', '\u{4B}', Some('\u{327}')), ('\u{137}', '\u{6B}', Some('\u{327}')),
+    ('\u{139}', '\u{4C}', Some('\u{301}')), ('\u{13A}', '\u{6C}', Some('\u{301}')), ('\u{13B}', '\u{4C}', Some('\u{327}')),
+    ('\u{13C}', '\u{6C}', Some('\u{327}')), ('\u{13D}', '\u{4C}', Some('\u{30C}')), ('\u{13E}', '\u{6C}', Some('\u{30C}')),
+    ('\u{143}', '\u{4E}', Some('\u{301}')), ('\u{144}', '\u{6E}', Some('\u{301}')), ('\u{145}', '\u{4E}', Some('\u{327}')),
+    ('\u{146}', '\u{6E}', Some('\u{327}')), ('\u{147}', '\u{4E}', Some('\u{30C}')), ('\u{148}', '\u{6E}', Some('\u{30C}')),
+    ('\u{14C}', '\u{4F}', Some('\u{304}')), ('\u{14D}', '\u{6F}', Some('\u{304}')), ('\u{14E}', '\u{4F}', Some('\u{306}')),
+    ('\u{14F}', '\u{6F}', Some('\u{306}')), ('\u{150}', '\u{4F}', Some('\u{30B}')), ('\u{151}', '\u{6F}', Some('\u{30B}')),
+    ('\u{154}', '\u{52}', Some('\u{301}')), ('\u{155}', '\u{72}', Some('\u{301}')), ('\u{156}', '\u{52}', Some('\u{327}')),
+    ('\u{157}', '\u{72}', Some('\u{327}')), ('\u{158}', '\u{52}', Some('\u{30C}')), ('\u{159}', '\u{72}', Some('\u{30C}')),
+    ('\u{15A}', '\u{53}', Some('\u{301}')), ('\u{15B}', '\u{73}', Some('\u{301}')), ('\u{15C}', '\u{53}', Some('\u{302}')),
+    ('\u{15D}', '\u{73}', Some('\u{302}')), ('\u{15E}', '\u{53}', Some('\u{327}')), ('\u{15F}', '\u{73}', Some('\u{327}')),
+    ('\u{160}', '\u{53}', Some('\u{30C}')), ('\u{161}', '\u{73}', Some('\u{30C}')), ('\u{162}', '\u{54}', Some('\u{327}')),
+    ('\u{163}', '\u{74}', Some('\u{327}')), ('\u{164}', '\u{54}', Some('\u{30C}')), ('\u{165}', '\u{74}', Some('\u{30C}')),
+    ('\u{168}', '\u{55}', Some('\u{303}')), ('\u{169}', '\u{75}', Some('\u{303}')), ('\u{16A}', '\u{55}', Some('\u{304}')),
+    ('\u{16B}', '\u{75}', Some('\u{304}')), ('\u{16C}', '\u{55}', Some('\u{306}')), ('\u{16D}', '\u{75}', Some('\u{306}')),
+    ('\u{16E}', '\u{55}', Some('\u{30A}')), ('\u{16F}', '\u{75}', Some('\u{30A}')), ('\u{170}', '\u{55}', Some('\u{30B}')),
+    ('\u{171}', '\u{75}', Some('\u{30B}')), ('\u{172}', '\u{55}', Some('\u{328}')), ('\u{173}', '\u{75}', Some('\u{328}')),
+    ('\u{174}', '\u{57}', Some('\u{302}')), ('\u{175}', '\u{77}', Some('\u{302}')), ('\u{176}', '\u{59}', Some('\u{302}')),
+    ('\u{177}', '\u{79}', Some('\u{302}')), ('\u{178}', '\u{59}', Some('\u{308}')), ('\u{179}', '\u{5A}', Some('\u{301}')),
+    ('\u{17A}', '\u{7A}', Some('\u{301}')), ('\u{17B}', '\u{5A}', Some('\u{307}')), ('\u{17C}', '\u{7A}', Some('\u{307}')),
+    ('\u{17D}', '\u{5A}', Some('\u{30C}')), ('\u{17E}', '\u{7A}', Some('\u{30C}')), ('\u{1A0}', '\u{4F}', Some('\u{31B}')),
+    ('\u{1A1}', '\u{6F}', Some('\u{31B}')), ('\u{1AF}', '\u{55}', Some('\u{31B}')), ('\u{1B0}', '\u{75}', Some('\u{31B}')),
+    ('\u{1CD}', '\u{41}', Some('\u{30C}')), ('\u{1CE}', '\u{61}', Some('\u{30C}')), ('\u{1CF}', '\u{49}', Some('\u{30C}')),
+    ('\u{1D0}', '\u{69}', Some('\u{30C}')), ('\u{1D1}', '\u{4F}', Some('\u{30C}')), ('\u{1D2}', '\u{6F}', Some('\u{30C}')),
+    ('\u{1D3}', '\u{55}', Some('\u{30C}')), ('\u{1D4}', '\u{75}', Some('\u{30C}')), ('\u{1D5}', '\u{DC}', Some('\u{304}')),
+    ('\u{1D6}', '\u{FC}', Some('\u{304}')), ('\u{1D7}', '\u{DC}', Some('\u{301}')), ('\u{1D8}', '\u{FC}', Some('\u{301}')),
+    ('\u{1D9}', '\u{DC}', Some('\u{30C}')), ('\u{1DA}', '\u{FC}', Some('\u{30C}')), ('\u{1DB}', '\u{DC}', Some('\u{300}')),
+    ('\u{1DC}', '\u{FC}', Some('\u{300}')), ('\u{1DE}', '\u{C4}', Some('\u{304}')), ('\u{1DF}', '\u{E4}', Some('\u{304}')),
+    ('\u{1E0}', '\u{226}', Some('\u{304}')), ('\u{1E1}', '\u{227}', Some('\u{304}')), ('\u{1E2}', '\u{C6}', Some('\u{304}')),
+    ('\u{1E3}', '\u{E6}', Some('\u{304}')), ('\u{1E6}', '\u{47}', Some('\u{30C}')), ('\u{1E7}', '\u{67}', Some('\u{30C}')),
+    ('\u{1E8}', '\u{4B}', Some('\u{30C}')), ('\u{1E9}', '\u{6B}', Some('\u{30C}')), ('\u{1EA}', '\u{4F}', Some('\u{328}')),
+    ('\u{1EB}', '\u{6F}', Some('\u{328}')), ('\u{1EC}', '\u{1EA}', Some('\u{304}')), ('\u{1ED}', '\u{1EB}', Some('\u{304}')),
+    ('\u{1EE}', '\u{1B7}', Some('\u{30C}')), ('\u{1EF}', '\u{292}', Some('\u{30C}')), ('\u{1F0}', '\u{6A}', Some('\u{30C}')),
+    ('\u{1F4}', '\u{47}', Some('\u{301}')), ('\u{1F5}', '\u{67}', Some('\u{301}')), ('\u{1F8}', '\u{4E}', Some('\u{300}')),
+    ('\u{1F9}', '\u{6E}', Some('\u{300}')), ('\u{1FA}', '\u{C5}', Some('\u{301}')), ('\u{1FB}', '\u{E5}', Some('\u{301}')),
+    ('\u{1FC}', '\u{C6}', Some('\u{301}')), ('\u{1FD}', '\u{E6}', Some('\u{301}')), ('\u{1FE}', '\u{D8}', Some('\u{301}')),
+    ('\u{1FF}', '\u{F8}', Some('\u{301}')), ('\u{200}', '\u{41}', Some('\u{30F}')), ('\u{201}', '\u{61}', Some('\u{30F}')),
+    ('\u{202}', '\u{41}', Some('\u{311}')), ('\u{203}', '\u{61}', Some('\u{311}')), ('\u{204}', '\u{45}', Some('\u{30F}')),
+    ('\u{205}', '\u{65}', Some('\u{30F}')), ('\u{206}', '\u{45}', Some('\u{311}')), ('\u{207}', '\u{65}', Some('\u{311}')),
+    ('\u{208}', '\u{49}', Some('\u{30F}')), ('\u{209}', '\u{69}', Some('\u{30F}')), ('\u{20A}', '\u{49}', Some('\u{311}')),
+    ('\u{20B}', '\u{69}', Some('\u{311}')), ('\u{20C}', '\u{4F}', Some('\u{30F}')), ('\u{20D}', '\u{6F}', Some('\u{30F}')),
+    ('\u{20E}', '\u{4F}', Some('\u{311}')), ('\u{20F}', '\u{6F}', Some('\u{311}')), ('\u{210}', '\u{52}', Some('\u{30F}')),
+    ('\u{211}', '\u{72}', Some('\u{30F}')), ('\u{212}', '\u{52}', Some('\u{311}')), ('\u{213}', '\u{72}', Some('\u{311}')),
+    ('\u{214}', '\u{55}', Some('\u{30F}')), ('\u{215}', '\u{75}', Some('\u{30F}')), ('\u{216}', '\u{55}', Some('\u{311}')),
+    ('\u{217}', '\u{75}', Some('\u{311}')), ('\u{218}', '\u{53}', Some('\u{326}')), ('\u{219}', '\u{73}', Some('\u{326}')),
+    ('\u{21A}', '\u{54}', Some('\u{326}')), ('\u{21B}', '\u{74}', Some('\u{326}')), ('\u{21E}', '\u{48}', Some('\u{30C}')),
+    ('\u{21F}', '\u{68}', Some('\u{30C}')), ('\u{226}', '\u{41}', Some('\u{307}')), ('\u{227}', '\u{61}', Some('\u{307}')),
+    ('\u{228}', '\u{45}', Some('\u{327}')), ('\u{229}', '\u{65}', Some('\u{327}')), ('\u{22A}', '\u{D6}', Some('\u{304}')),
+    ('\u{22B}', '\u{F6}', Some('\u{304}')), ('\u{22C}', '\u{D5}', Some('\u{304}')), ('\u{22D}', '\u{F5}', Some('\u{304}')),
+    ('\u{22E}', '\u{4F}', Some('\u{307}')), ('\u{22F}', '\u{6F}', Some('\u{307}')), ('\u{230}', '\u{22E}', Some('\u{304}')),
+    ('\u{231}', '\u{22F}', Some('\u{304}')), ('\u{232}', '\u{59}', Some('\u{304}')), ('\u{233}', '\u{79}', Some('\u{304}')),
+    ('\u{340}', '\u{300}', None), ('\u{341}', '\u{301}', None), ('\u{343}', '\u{313}', None),
+    ('\u{344}', '\u{308}', Some('\u{301}')), ('\u{374}', '\u{2B9}', None), ('\u{37E}', '\u{3B}', None),
+    ('\u{385}', '\u{A8}', Some('\u{301}')), ('\u{386}', '\u{391}', Some('\u{301}')), ('\u{387}', '\u{B7}', None),
+    ('\u{388}', '\u{395}', Some('\u{301}')), ('\u{389}', '\u{397}', Some('\u{301}')), ('\u{38A}', '\u{399}', Some('\u{301}')),
+    ('\u{38C}', '\u{39F}', Some('\u{301}')), ('\u{38E}', '\u{3A5}', Some('\u{301}')), ('\u{38F}', '\u{3A9}', Some('\u{301}')),
+    ('\u{390}', '\u{3CA}', Some('\u{301}')), ('\u{3AA}', '\u{399}', Some('\u{308}')), ('\u{3AB}', '\u{3A5}', Some('\u{308}')),
+    ('\u{3AC}', '\u{3B1}', Some('\u{301}')), ('\u{3AD}', '\u{3B5}', Some('\u{301}')), ('\u{3AE}', '\u{3B7}', Some('\u{301}')),
+    ('\u{3AF}', '\u{3B9}', Some('\u{301}')), ('\u{3B0}', '\u{3CB}', Some('\u{301}')), ('\u{3CA}', '\u{3B9}', Some('\u{308}')),
+    ('\u{3CB}', '\u{3C5}', Some('\u{308}')), ('\u{3CC}', '\u{3BF}', Some('\u{301}')), ('\u{3CD}', '\u{3C5}', Some('\u{301}')),
+    ('\u{3CE}', '\u{3C9}', Some('\u{301}')), ('\u{3D3}', '\u{3D2}', Some('\u{301}')), ('\u{3D4}', '\u{3D2}', Some('\u{308}')),
+    ('\u{400}', '\u{415}', Some('\u{300}')), ('\u{401}', '\u{415}', Some('\u{308}')), ('\u{403}', '\u{413}', Some('\u{301}')),
+    ('\u{407}', '\u{406}', Some('\u{308}')), ('\u{40C}', '\u{41A}', Some('\u{301}')), ('\u{40D}', '\u{418}', Some('\u{300}')),
+    ('\u{40E}', '\u{423}', Some('\u{306}')), ('\u{419}', '\u{418}', Some('\u{306}')), ('\u{439}', '\u{438}', Some('\u{306}')),
+    ('\u{450}', '\u{435}', Some('\u{300}')), ('\u{451}', '\u{435}', Some('\u{308}')), ('\u{453}', '\u{433}', Some('\u{301}')),
+    ('\u{457}', '\u{456}', Some('\u{308}')), ('\u{45C}', '\u{43A}', Some('\u{301}')), ('\u{45D}', '\u{438}', Some('\u{300}')),
+    ('\u{45E}', '\u{443}', Some('\u{306}')), ('\u{476}', '\u{474}', Some('\u{30F}')), ('\u{477}', '\u{475}', Some('\u{30F}')),
+    ('\u{4C1}', '\u{416}', Some('\u{306}')), ('\u{4C2}', '\u{436}', Some('\u{306}')), ('\u{4D0}', '\u{410}', Some('\u{306}')),
+    ('\u{4D1}', '\u{430}', Some('\u{306}')), ('\u{4D2}', '\u{410}', Some('\u{308}')), ('\u{4D3}', '\u{430}', Some('\u{308}')),
+    ('\u{4D6}', '\u{415}', Some('\u{306}')), ('\u{4D7}', '\u{435}', Some('\u{306}')), ('\u{4DA}', '\u{4D8}', Some('\u{308}')),
+    ('\u{4DB}', '\u{4D9}', Some('\u{308}')), ('\u{4DC}', '\u{416}', Some('\u{308}')), ('\u{4DD}', '\u{436}', Some('\u{308}')),
+    ('\u{4DE}', '\u{417}', Some('\u{308}')), ('\u{4DF}', '\u{437}', Some('\u{308}')), ('\u{4E2}', '\u{418}', Some('\u{304}')),
+    ('\u{4E3}', '\u{438}', Some('\u{304}')), ('\u{4E4}', '\u{418}', Some('\u{308}')), ('\u{4E5}', '\u{438}', Some('\u{308}')),
+    ('\u{4E6}', '\u{41E}', Some('\u{308}')), ('\u{4E7}', '\u{43E}', Some('\u{308}')), ('\u{4EA}', '\u{4E8}', Some('\u{308}')),
+    ('\u{4EB}', '\u{4E9}', Some('\u{308}')), ('\u{4EC}', '\u{42D}', Some('\u{308}')), ('\u{4ED}', '\u{44D}', Some('\u{308}')),
+    ('\u{4EE}', '\u{423}', Some('\u{304}')), ('\u{4EF}', '\u{443}', Some('\u{304}')), ('\u{4F0}', '\u{423}', Some('\u{308}')),
+    ('\u{4F1}', '\u{443}', Some('\u{308}')), ('\u{4F2}', '\u{423}', Some('\u{30B}')), ('\u{4F3}', '\u{443}', Some('\u{30B}')),
+    ('\u{4F4}', '\u{427}', Some('\u{308}')), ('\u{4F5}', '\u{447}', Some('\u{308}')), ('\u{4F8}', '\u{42B}', Some('\u{308}')),
+    ('\u{4F9}', '\u{44B}', Some('\u{308}')), ('\u{622}', '\u{627}', Some('\u{653}')), ('\u{623}', '\u{627}', Some('\u{654}')),
+    ('\u{624}', '\u{648}', Some('\u{654}')), ('\u{625}', '\u{627}', Some('\u{655}')), ('\u{626}', '\u{64A}', Some('\u{654}')),
+    ('\u{6C0}', '\u{6D5}', Some('\u{654}')), ('\u{6C2}', '\u{6C1}', Some('\u{654}')), ('\u{6D3}', '\u{6D2}', Some('\u{654}')),
+    ('\u{929}', '\u{928}', Some('\u{93C}')), ('\u{931}', '\u{930}', Some('\u{93C}')), ('\u{934}', '\u{933}', Some('\u{93C}')),
+    ('\u{958}', '\u{915}', Some('\u{93C}')), ('\u{959}', '\u{916}', Some('\u{93C}')), ('\u{95A}', '\u{917}', Some('\u{93C}')),
+    ('\u{95B}', '\u{91C}', Some('\u{93C}')), ('\u{95C}', '\u{921}', Some('\u{93C}')), ('\u{95D}', '\u{922}', Some('\u{93C}')),
+    ('\u{95E}', '\u{92B}', Some('\u{93C}')), ('\u{95F}', '\u{92F}', Some('\u{93C}')), ('\u{9CB}', '\u{9C7}', Some('\u{9BE}')),
+    ('\u{9CC}', '\u{9C7}', Some('\u{9D7}')), ('\u{9DC}', '\u{9A1}', Some('\u{9BC}')), ('\u{9DD}', '\u{9A2}', Some('\u{9BC}')),
+    ('\u{9DF}', '\u{9AF}', Some('\u{9BC}')), ('\u{A33}', '\u{A32}', Some('\u{A3C}')), ('\u{A36}', '\u{A38}', Some('\u{A3C}')),
+    ('\u{A59}', '\u{A16}', Some('\u{A3C}')), ('\u{A5A}', '\u{A17}', Some('\u{A3C}')), ('\u{A5B}', '\u{A1C}', Some('\u{A3C}')),
+    ('\u{A5E}', '\u{A2B}', Some('\u{A3C}')), ('\u{B48}', '\u{B47}', Some('\u{B56}')), ('\u{B4B}', '\u{B47}', Some('\u{B3E}')),
+    ('\u{B4C}', '\u{B47}', Some('\u{B57}')), ('\u{B5C}', '\u{B21}', Some('\u{B3C}')), ('\u{B5D}', '\u{B22}', Some('\u{B3C}')),
+    ('\u{B94}', '\u{B92}', Some('\u{BD7}')), ('\u{BCA}', '\u{BC6}', Some('\u{BBE}')), ('\u{BCB}', '\u{BC7}', Some('\u{BBE}')),
+    ('\u{BCC}', '\u{BC6}', Some('\u{BD7}')), ('\u{C48}', '\u{C46}', Some('\u{C56}')), ('\u{CC0}', '\u{CBF}', Some('\u{CD5}')),
+    ('\u{CC7}', '\u{CC6}', Some('\u{CD5}')), ('\u{CC8}', '\u{CC6}', Some('\u{CD6}')), ('\u{CCA}', '\u{CC6}', Some('\u{CC2}')),
+    ('\u{CCB}', '\u{CCA}', Some('\u{CD5}')), ('\u{D4A}', '\u{D46}', Some('\u{D3E}')), ('\u{D4B}', '\u{D47}', Some('\u{D3E}')),
+    ('\u{D4C}', '\u{D46}', Some('\u{D57}')), ('\u{DDA}', '\u{DD9}', Some('\u{DCA}')), ('\u{DDC}', '\u{DD9}', Some('\u{DCF}')),
+    ('\u{DDD}', '\u{DDC}', Some('\u{DCA}')), ('\u{DDE}', '\u{DD9}', Some('\u{DDF}')), ('\u{F43}', '\u{F42}', Some('\u{FB7}')),
+    ('\u{F4D}', '\u{F4C}', Some('\u{FB7}')), ('\u{F52}', '\u{F51}', Some('\u{FB7}')), ('\u{F57}', '\u{F56}', Some('\u{FB7}')),
+    ('\u{F5C}', '\u{F5B}', Some('\u{FB7}')), ('\u{F69}', '\u{F40}', Some('\u{FB5}')), ('\u{F73}', '\u{F71}', Some('\u{F72}')),
+    ('\u{F75}', '\u{F71}', Some('\u{F74}')), ('\u{F76}', '\u{FB2}', Some('\u{F80}')), ('\u{F78}', '\u{FB3}', Some('\u{F80}')),
+    ('\u{F81}', '\u{F71}', Some('\u{F80}')), ('\u{F93}', '\u{F92}', Some('\u{FB7}')), ('\u{F9D}', '\u{F9C}', Some('\u{FB7}')),
+    ('\u{FA2}', '\u{FA1}', Some('\u{FB7}')), ('\u{FA7}', '\u{FA6}', Some('\u{FB7}')), ('\u{FAC}', '\u{FAB}', Some('\u{FB7}')),
+    ('\u{FB9}', '\u{F90}', Some('\u{FB5}')), ('\u{1026}', '\u{1025}', Some('\u{102E}')), ('\u{1B06}', '\u{1B05}', Some('\u{1B35}')),
+    ('\u{1B08}', '\u{1B07}', Some('\u{1B35}')), ('\u{1B0A}', '\u{1B09}', Some('\u{1B35}')), ('\u{1B0C}', '\u{1B0B}', Some('\u{1B35}')),
+    ('\u{1B0E}', '\u{1B0D}', Some('\u{1B35}')), ('\u{1B12}', '\u{1B11}', Some('\u{1B35}')), ('\u{1B3B}', '\u{1B3A}', Some('\u{1B35}')),
+    ('\u{1B3D}', '\u{1B3C}', Some('\u{1B35}')), ('\u{1B40}', '\u{1B3E}', Some('\u{1B35}')), ('\u{1B41}', '\u{1B3F}', Some('\u{1B35}')),
+    ('\u{1B43}', '\u{1B42}', Some('\u{1B35}')), ('\u{1E00}', '\u{41}', Some('\u{325}')), ('\u{1E01}', '\u{61}', Some('\u{325}')),
+    ('\u{1E02}', '\u{42}', Some('\u{307}')), ('\u{1E03}', '\u{62}', Some('\u{307}')), ('\u{1E04}', '\u{42}', Some('\u{323}')),
+    ('\u{1E05}', '\u{62}', Some('\u{323}')), ('\u{1E06}', '\u{42}', Some('\u{331}')), ('\u{1E07}', '\u{62}', Some('\u{331}')),
+    ('\u{1E08}', '\u{C7}', Some('\u{301}')), ('\u{1E09}', '\u{E7}', Some('\u{301}')), ('\u{1E0A}', '\u{44}', Some('\u{307}')),
+    ('\u{1E0B}', '\u{64}', Some('\u{307}')), ('\u{1E0C}', '\u{44}', Some('\u{323}')), ('\u{1E0D}', '\u{64}', Some('\u{323}')),
+    ('\u{1E0E}', '\u{44}', Some('\u{331}')), ('\u{1E0F}', '\u{64}', Some('\u{331}')), ('\u{1E10}', '\u{44}', Some('\u{327}')),
+    ('\u{1E11}', '\u{64}', Some('\u{327}')), ('\u{1E12}', '\u{44}', Some('\u{32D}')), ('\u{1E13}', '\u{64}', Some('\u{32D}')),
+    ('\u{1E14}', '\u{112}', Some('\u{300}')), ('\u{1E15}', '\u{113}', Some('\u{300}')), ('\u{1E16}', '\u{112}', Some('\u{301}')),
+    ('\u{1E17}', '\u{113}', Some('\u{301}')), ('\u{1E18}', '\u{45}', Some('\u{32D}')), ('\u{1E19}', '\u{65}', Some('\u{32D}')),
+    ('\u{1E1A}', '\u{45}', Some('\u{330}')), ('\u{1E1B}', '\u{65}', Some('\u{330}')), ('\u{1E1C}', '\u{228}', Some('\u{306}')),
+    ('\u{1E1D}', '\u{229}', Some('\u{306}')), ('\u{1E1E}', '\u{46}', Some('\u{307}')), ('\u{1E1F}', '\u{66}', Some('\u{307}')),
+    ('\u{1E20}', '\u{47}', Some('\u{304}')), ('\u{1E21}', '\u{67}', Some('\u{304}')), ('\u{1E22}', '\u{48}', Some('\u{307}')),
+    ('\u{1E23}', '\u{68}', Some('\u{307}')), ('\u{1E24}', '\u{48}', Some('\u{323}')), ('\u{1E25}', '\u{68}', Some('\u{323}')),
+    ('\u{1E26}', '\u{48}', Some('\u{308}')), ('\u{1E27}', '\u{68}', Some('\u{308}')), ('\u{1E28}', '\u{48}', Some('\u{327}')),
+    ('\u{1E29}', '\u{68}', Some('\u{327}')), ('\u{1E2A}', '\u{48}', Some('\u{32E}')), ('\u{1E2B}', '\u{68}', Some('\u{32E}')),
+    ('\u{1E2C}', '\u{49}', Some('\u{330}')), ('\u{1E2D}', '\u{69}', Some('\u{330}')), ('\u{1E2E}', '\u{CF}', Some('\u{301}')),
+    ('\u{1E2F}', '\u{EF}', Some('\u{301}')), ('\u{1E30}', '\u{4B}', Some('\u{301}')), ('\u{1E31}', '\u{6B}', Some('\u{301}')),
+    ('\u{1E32}', '\u{4B}', Some('\u{323}')), ('\u{1E33}', '\u{6B}', Some('\u{323}')), ('\u{1E34}', '\u{4B}', Some('\u{331}')),
+    ('\u{1E35}', '\u{6B}', Some('\u{331}')), ('\u{1E36}', '\u{4C}', Some('\u{323}')), ('\u{1E37}', '\u{6C}', Some('\u{323}')),
+    ('\u{1E38}', '\u{1E36}', Some('\u{304}')), ('\u{1E39}', '\u{1E37}', Some('\u{304}')), ('\u{1E3A}', '\u{4C}', Some('\u{331}')),
+    ('\u{1E3B}', '\u{6C}', Some('\u{331}')), ('\u{1E3C}', '\u{4C}', Some('\u{32D}')), ('\u{1E3D}', '\u{6C}', Some('\u{32D}')),
+    ('\u{1E3E}', '\u{4D}', Some('\u{301}')), ('\u{1E3F}', '\u{6D}', Some('\u{301}')), ('\u{1E40}', '\u{4D}', Some('\u{307}')),
+    ('\u{1E41}', '\u{6D}', Some('\u{307}')), ('\u{1E42}', '\u{4D}', Some('\u{323}')), ('\u{1E43}', '\u{6D}', Some('\u{323}')),
+    ('\u{1E44}', '\u{4E}', Some('\u{307}')), ('\u{1E45}', '\u{6E}', Some('\u{307}')), ('\u{1E46}', '\u{4E}', Some('\u{323}')),
+    ('\u{1E47}', '\u{6E}', Some('\u{323}')), ('\u{1E48}', '\u{4E}', Some('\u{331}')), ('\u{1E49}', '\u{6E}', Some('\u{331}')),
+    ('\u{1E4A}', '\u{4E}', Some('\u{32D}')), ('\u{1E4B}', '\u{6E}', Some('\u{32D}')), ('\u{1E4C}', '\u{D5}', Some('\u{301}')),
+    ('\u{1E4D}', '\u{F5}', Some('\u{301}')), ('\u{1E4E}', '\u{D5}', Some('\u{308}')), ('\u{1E4F}', '\u{F5}', Some('\u{308}')),
+    ('\u{1E50}', '\u{14C}', Some('\u{300}')), ('\u{1E51}', '\u{14D}', Some('\u{300}')), ('\u{1E52}', '\u{14C}', Some('\u{301}')),
+    ('\u{1E53}', '\u{14D}', Some('\u{301}')), ('\u{1E54}', '\u{50}', Some('\u{301}')), ('\u{1E55}', '\u{70}', Some('\u{301}')),
+    ('\u{1E56}', '\u{50}', Some('\u{307}')), ('\u{1E57}', '\u{70}', Some('\u{307}')), ('\u{1E58}', '\u{52}', Some('\u{307}')),
+    ('\u{1E59}', '\u{72}', Some('\u{307}')), ('\u{1E5A}', '\u{52}', Some('\u{323}')), ('\u{1E5B}', '\u{72}', Some('\u{323}')),
+    ('\u{1E5C}', '\u{1E5A}', Some('\u{304}')), ('\u{1E5D}', '\u{1E5B}', Some('\u{304}')), ('\u{1E5E}', '\u{52}', Some('\u{331}')),
+    ('\u{1E5F}', '\u{72}', Some('\u{331}')), ('\u{1E60}', '\u{53}', Some('\u{307}')), ('\u{1E61}', '\u{73}', Some('\u{307}')),
+    ('\u{1E62}', '\u{53}', Some('\u{323}')), ('\u{1E63}', '\u{73}', Some('\u{323}')), ('\u{1E64}', '\u{15A}', Some('\u{307}')),
+    ('\u{1E65}', '\u{15B}', Some('\u{307}')), ('\u{1E66}', '\u{160}', Some('\u{307}')), ('\u{1E67}', '\u{161}', Some('\u{307}')),
+    ('\u{1E68}', '\u{1E62}', Some('\u{307}')), ('\u{1E69}', '\u{1E63}', Some('\u{307}')), ('\u{1E6A}', '\u{54}', Some('\u{307}')),
+    ('\u{1E6B}', '\u{74}', Some('\u{307}')), ('\u{1E6C}', '\u{54}', Some('\u{323}')), ('\u{1E6D}', '\u{74}', Some('\u{323}')),
+    ('\u{1E6E}', '\u{54}', Some('\u{331}')), ('\u{1E6F}', '\u{74}', Some('\u{331}')), ('\u{1E70}', '\u{54}', Some('\u{32D}')),
+    ('\u{1E71}', '\u{74}', Some('\u{32D}')), ('\u{1E72}', '\u{55}', Some('\u{324}')), ('\u{1E73}', '\u{75}', Some('\u{324}')),
+    ('\u{1E74}', '\u{55}', Some('\u{330}')), ('\u{1E75}', '\u{75}', Some('\u{330}')), ('\u{1E76}', '\u{55}', Some('\u{32D}')),
+    ('\u{1E77}', '\u{75}', Some('\u{32D}')), ('\u{1E78}', '\u{168}', Some('\u{301}')), ('\u{1E79}', '\u{169}', Some('\u{301}')),
+    ('\u{1E7A}', '\u{16A}', Some('\u{308}')), ('\u{1E7B}', '\u{16B}', Some('\u{308}')), ('\u{1E7C}', '\u{56}', Some('\u{303}')),
+    ('\u{1E7D}', '\u{76}', Some('\u{303}')), ('\u{1E7E}', '\u{56}', Some('\u{323}')), ('\u{1E7F}', '\u{76}', Some('\u{323}')),
+    ('\u{1E80}', '\u{57}', Some('\u{300}')), ('\u{1E81}', '\u{77}', Some('\u{300}')), ('\u{1E82}', '\u{57}', Some('\u{301}')),
+    ('\u{1E83}', '\u{77}', Some('\u{301}')), ('\u{1E84}', '\u{57}', Some('\u{308}')), ('\u{1E85}', '\u{77}', Some('\u{308}')),
+    ('\u{1E86}', '\u{57}', Some('\u{307}')), ('\u{1E87}', '\u{77}', Some('\u{307}')), ('\u{1E88}', '\u{57}', Some('\u{323}')),
+    ('\u{1E89}', '\u{77}', Some('\u{323}')), ('\u{1E8A}', '\u{58}', Some('\u{307}')), ('\u{1E8B}', '\u{78}', Some('\u{307}')),
+    ('\u{1E8C}', '\u{58}', Some('\u{308}')), ('\u{1E8D}', '\u{78}', Some('\u{308}')), ('\u{1E8E}', '\u{59}', Some('\u{307}')),
+    ('\u{1E8F}', '\u{79}', Some('\u{307}')), ('\u{1E90}', '\u{5A}', Some('\u{302}')), ('\u{1E91}', '\u{7A}', Some('\u{302}')),
+    ('\u{1E92}', '\u{5A}', Some('\u{323}')), ('\u{1E93}', '\u{7A}', Some('\u{323}')), ('\u{1E94}', '\u{5A}', Some('\u{331}')),
+    ('\u{1E95}', '\u{7A}', Some('\u{331}')), ('\u{1E96}', '\u{68}', Some('\u{331}')), ('\u{1E97}', '\u{74}', Some('\u{308}')),
+    ('\u{1E98}', '\u{77}', Some('\u{30A}')), ('\u{1E99}', '\u{79}', Some('\u{30A}')), ('\u{1E9B}', '\u{17F}', Some('\u{307}')),
+    ('\u{1EA0}', '\u{41}', Some('\u{323}')), ('\u{1EA1}', '\u{61}', Some('\u{323}')), ('\u{1EA2}', '\u{41}', Some('\u{309}')),
+    ('\u{1EA3}', '\u{61}', Some('\u{309}')), ('\u{1EA4}', '\u{C2}', Some('\u{301}')), ('\u{1EA5}', '\u{E2}', Some('\u{301}')),
+    ('\u{1EA6}', '\u{C2}', Some('\u{300}')), ('\u{1EA7}', '\u{E2}', Some('\u{300}')), ('\u{1EA8}', '\u{C2}', Some('\u{309}')),
+    ('\u{1EA9}', '\u{E2}', Some('\u{309}')), ('\u{1EAA}', '\u{C2}', Some('\u{303}')), ('\u{1EAB}', '\u{E2}', Some('\u{303}')),
+    ('\u{1EAC}', '\u{1EA0}', Some('\u{302}')), ('\u{1EAD}', '\u{1EA1}', Some('\u{302}')), ('\u{1EAE}', '\u{102}', Some('\u{301}')),
+    ('\u{1EAF}', '\u{103}', Some('\u{301}')), ('\u{1EB0}', '\u{102}', Some('\u{300}')), ('\u{1EB1}', '\u{103}', Some('\u{300}')),
+    ('\u{1EB2}', '\u{102}', Some('\u{309}')), ('\u{1EB3}', '\u{103}', Some('\u{309}')), ('\u{1EB4}', '\u{102}', Some('\u{303}')),
+    ('\u{1EB5}', '\u{103}', Some('\u{303}')), ('\u{1EB6}', '\u{1EA0}', Some('\u{306}')), ('\u{1EB7}', '\u{1EA1}', Some('\u{306}')),
+    ('\u{1EB8}', '\u{45}', Some('\u{323}')), ('\u{1EB9}', '\u{65}', Some('\u{323}')), ('\u{1EBA}', '\u{45}', Some('\u{309}')),
+    ('\u{1EBB}', '\u{65}', Some('\u{309}')), ('\u{1EBC}', '\u{45}', Some('\u{303}')), ('\u{1EBD}', '\u{65}', Some('\u{303}')),
+    ('\u{1EBE}', '\u{CA}', Some('\u{301}')), ('\u{1EBF}', '\u{EA}', Some('\u{301}')), ('\u{1EC0}', '\u{CA}', Some('\u{300}')),
+    ('\u{1EC1}', '\u{EA}', Some('\u{300}')), ('\u{1EC2}', '\u{CA}', Some('\u{309}')), ('\u{1EC3}', '\u{EA}', Some('\u{309}')),
+    ('\u{1EC4}', '\u{CA}', Some('\u{303}')), ('\u{1EC5}', '\u{EA}', Some('\u{303}')), ('\u{1EC6}', '\u{1EB8}', Some('\u{302}')),
+    ('\u{1EC7}', '\u{1EB9}', Some('\u{302}')), ('\u{1EC8}', '\u{49}', Some('\u{309}')), ('\u{1EC9}', '\u{69}', Some('\u{309}')),
+    ('\u{1ECA}', '\u{49}', Some('\u{323}')), ('\u{1ECB}', '\u{69}', Some('\u{323}')), ('\u{1ECC}', '\u{4F}', Some('\u{323}')),
+    ('\u{1ECD}', '\u{6F}', Some('\u{323}')), ('\u{1ECE}', '\u{4F}', Some('\u{309}')), ('\u{1ECF}', '\u{6F}', Some('\u{309}')),
+    ('\u{1ED0}', '\u{D4}', Some('\u{301}')), ('\u{1ED1}', '\u{F4}', Some('\u{301}')), ('\u{1ED2}', '\u{D4}', Some('\u{300}')),
+    ('\u{1ED3}', '\u{F4}', Some('\u{300}')), ('\u{1ED4}', '\u{D4}', Some('\u{309}')), ('\u{1ED5}', '\u{F4}', Some('\u{309}')),
+    ('\u{1ED6}', '\u{D4}', Some('\u{303}')), ('\u{1ED7}', '\u{F4}', Some('\u{303}')), ('\u{1ED8}', '\u{1ECC}', Some('\u{302}')),
+    ('\u{1ED9}', '\u{1ECD}', Some('\u{302}')), ('\u{1EDA}', '\u{1A0}', Some('\u{301}')), ('\u{1EDB}', '\u{1A1}', Some('\u{301}')),
+    ('\u{1EDC}', '\u{1A0}', Some('\u{300}')), ('\u{1EDD}', '\u{1A1}', Some('\u{300}')), ('\u{1EDE}', '\u{1A0}', Some('\u{309}')),
+    ('\u{1EDF}', '\u{1A1}', Some('\u{309}')), ('\u{1EE0}', '\u{1A0}', Some('\u{303}')), ('\u{1EE1}', '\u{1A1}', Some('\u{303}')),
+    ('\u{1EE2}', '\u{1A0}', Some('\u{323}')), ('\u{1EE3}', '\u{1A1}', Some('\u{323}')), ('\u{1EE4}', '\u{55}', Some('\u{323}')),
+    ('\u{1EE5}', '\u{75}', Some('\u{323}')), ('\u{1EE6}', '\u{55}', Some('\u{309}')), ('\u{1EE7}', '\u{75}', Some('\u{309}')),
+    ('\u{1EE8}', '\u{1AF}', Some('\u{301}')), ('\u{1EE9}', '\u{1B0}', Some('\u{301}')), ('\u{1EEA}', '\u{1AF}', Some('\u{300}')),
+    ('\u{1EEB}', '\u{1B0}', Some('\u{300}')), ('\u{1EEC}', '\u{1AF}', Some('\u{309}')), ('\u{1EED}', '\u{1B0}', Some('\u{309}')),
+    ('\u{1EEE}', '\u{1AF}', Some('\u{303}')), ('\u{1EEF}', '\u{1B0}', Some('\u{303}')), ('\u{1EF0}', '\u{1AF}', Some('\u{323}')),
+    ('\u{1EF1}', '\u{1B0}', Some('\u{323}')), ('\u{1EF2}', '\u{59}', Some('\u{300}')), ('\u{1EF3}', '\u{79}', Some('\u{300}')),
+    ('\u{1EF4}', '\u{59}', Some('\u{323}')), ('\u{1EF5}', '\u{79}', Some('\u{323}')), ('\u{1EF6}', '\u{59}', Some('\u{309}')),
+    ('\u{1EF7}', '\u{79}', Some('\u{309}')), ('\u{1EF8}', '\u{59}', Some('\u{303}')), ('\u{1EF9}', '\u{79}', Some('\u{303}')),
+    ('\u{1F00}', '\u{3B1}', Some('\u{313}')), ('\u{1F01}', '\u{3B1}', Some('\u{314}')), ('\u{1F02}', '\u{1F00}', Some('\u{300}')),
+    ('\u{1F03}', '\u{1F01}', Some('\u{300}')), ('\u{1F04}', '\u{1F00}', Some('\u{301}')), ('\u{1F05}', '\u{1F01}', Some('\u{301}')),
+    ('\u{1F06}', '\u{1F00}', Some('\u{342}')), ('\u{1F07}', '\u{1F01}', Some('\u{342}')), ('\u{1F08}', '\u{391}', Some('\u{313}')),
+    ('\u{1F09}', '\u{391}', Some('\u{314}')), ('\u{1F0A}', '\u{1F08}', Some('\u{300}')), ('\u{1F0B}', '\u{1F09}', Some('\u{300}')),
+    ('\u{1F0C}', '\u{1F08}', Some('\u{301}')), ('\u{1F0D}', '\u{1F09}', Some('\u{301}')), ('\u{1F0E}', '\u{1F08}', Some('\u{342}')),
+    ('\u{1F0F}', '\u{1F09}', Some('\u{342}')), ('\u{1F10}', '\u{3B5}', Some('\u{313}')), ('\u{1F11}', '\u{3B5}', Some('\u{314}')),
+    ('\u{1F12}', '\u{1F10}', Some('\u{300}')), ('\u{1F13}', '\u{1F11}', Some('\u{300}')), ('\u{1F14}', '\u{1F10}', Some('\u{301}')),
+    ('\u{1F15}', '\u{1F11}', Some('\u{301}')), ('\u{1F18}', '\u{395}', Some('\u{313}')), ('\u{1F19}', '\u{395}', Some('\u{314}')),
+    ('\u{1F1A}', '\u{1F18}', Some('\u{300}')), ('\u{1F1B}', '\u{1F19}', Some('\u{300}')), ('\u{1F1C}', '\u{1F18}', Some('\u{301}')),
+    ('\u{1F1D}', '\u{1F19}', Some('\u{301}')), ('\u{1F20}', '\u{3B7}', Some('\u{313}')), ('\u{1F21}', '\u{3B7}', Some('\u{314}')),
+    ('\u{1F22}', '\u{1F20}', Some('\u{300}')), ('\u{1F23}', '\u{1F21}', Some('\u{300}')), ('\u{1F24}', '\u{1F20}', Some('\u{301}')),
+    ('\u{1F25}', '\u{1F21}', Some('\u{301}')), ('\u{1F26}', '\u{1F20}', Some('\u{342}')), ('\u{1F27}', '\u{1F21}', Some('\u{342}')),
+    ('\u{1F28}', '\u{397}', Some('\u{313}')), ('\u{1F29}', '\u{397}', Some('\u{314}')), ('\u{1F2A}', '\u{1F28}', Some('\u{300}')),
+    ('\u{1F2B}', '\u{1F29}', Some('\u{300}')), ('\u{1F2C}', '\u{1F28}', Some('\u{301}')), ('\u{1F2D}', '\u{1F29}', Some('\u{301}')),
+    ('\u{1F2E}', '\u{1F28}', Some('\u{342}')), ('\u{1F2F}', '\u{1F29}', Some('\u{342}')), ('\u{1F30}', '\u{3B9}', Some('\u{313}')),
+    ('\u{1F31}', '\u{3B9}', Some('\u{314}')), ('\u{1F32}', '\u{1F30}', Some('\u{300}')), ('\u{1F33}', '\u{1F31}', Some('\u{300}')),
+    ('\u{1F34}', '\u{1F30}', Some('\u{301}')), ('\u{1F35}', '\u{1F31}', Some('\u{301}')), ('\u{1F36}', '\u{1F30}', Some('\u{342}')),
+    ('\u{1F37}', '\u{1F31}', Some('\u{342}')), ('\u{1F38}', '\u{399}', Some('\u{313}')), ('\u{1F39}', '\u{399}', Some('\u{314}')),
+    ('\u{1F3A}', '\u{1F38}', Some('\u{300}')), ('\u{1F3B}', '\u{1F39}', Some('\u{300}')), ('\u{1F3C}', '\u{1F38}', Some('\u{301}')),
+    ('\u{1F3D}', '\u{1F39}', Some('\u{301}')), ('\u{1F3E}', '\u{1F38}', Some('\u{342}')), ('\u{1F3F}', '\u{1F39}', Some('\u{342}')),
+    ('\u{1F40}', '\u{3BF}', Some('\u{313}')), ('\u{1F41}', '\u{3BF}', Some('\u{314}')), ('\u{1F42}', '\u{1F40}', Some('\u{300}')),
+    ('\u{1F43}', '\u{1F41}', Some('\u{300}')), ('\u{1F44}', '\u{1F40}', Some('\u{301}')), ('\u{1F45}', '\u{1F41}', Some('\u{301}')),
+    ('\u{1F48}', '\u{39F}', Some('\u{313}')), ('\u{1F49}', '\u{39F}', Some('\u{314}')), ('\u{1F4A}', '\u{1F48}', Some('\u{300}')),
+    ('\u{1F4B}', '\u{1F49}', Some('\u{300}')), ('\u{1F4C}', '\u{1F48}', Some('\u{301}')), ('\u{1F4D}', '\u{1F49}', Some('\u{301}')),
+    ('\u{1F50}', '\u{3C5}', Some('\u{313}')), ('\u{1F51}', '\u{3C5}', Some('\u{314}')), ('\u{1F52}', '\u{1F50}', Some('\u{300}')),
+    ('\u{1F53}', '\u{1F51}', Some('\u{300}')), ('\u{1F54}', '\u{1F50}', Some('\u{301}')), ('\u{1F55}', '\u{1F51}', Some('\u{301}')),
+    ('\u{1F56}', '\u{1F50}', Some('\u{342}')), ('\u{1F57}', '\u{1F51}', Some('\u{342}')), ('\u{1F59}', '\u{3A5}', Some('\u{314}')),
+    ('\u{1F5B}', '\u{1F59}', Some('\u{300}')), ('\u{1F5D}', '\u{1F59}', Some('\u{301}')), ('\u{1F5F}', '\u{1F59}', Some('\u{342}')),
+    ('\u{1F60}', '\u{3C9}', Some('\u{313}')), ('\u{1F61}', '\u{3C9}', Some('\u{314}')), ('\u{1F62}', '\u{1F60}', Some('\u{300}')),
+    ('\u{1F63}', '\u{1F61}', Some('\u{300}')), ('\u{1F64}', '\u{1F60}', Some('\u{301}')), ('\u{1F65}', '\u{1F61}', Some('\u{301}')),
+    ('\u{1F66}', '\u{1F60}', Some('\u{342}')), ('\u{1F67}', '\u{1F61}', Some('\u{342}')), ('\u{1F68}', '\u{3A9}', Some('\u{313}')),
+    ('\u{1F69}', '\u{3A9}', Some('\u{314}')), ('\u{1F6A}', '\u{1F68}', Some('\u{300}')), ('\u{1F6B}', '\u{1F69}', Some('\u{300}')),
+    ('\u{1F6C}', '\u{1F68}', Some('\u{301}')), ('\u{1F6D}', '\u{1F69}', Some('\u{301}')), ('\u{1F6E}', '\u{1F68}', Some('\u{342}')),
+    ('\u{1F6F}', '\u{1F69}', Some('\u{342}')), ('\u{1F70}', '\u{3B1}', Some('\u{300}')), ('\u{1F71}', '\u{3AC}', None),
+    ('\u{1F72}', '\u{3B5}', Some('\u{300}')), ('\u{1F73}', '\u{3AD}', None), ('\u{1F74}', '\u{3B7}', Some('\u{300}')),
+    ('\u{1F75}', '\u{3AE}', None), ('\u{1F76}', '\u{3B9}', Some('\u{300}')), ('\u{1F77}', '\u{3AF}', None),
+    ('\u{1F78}', '\u{3BF}', Some('\u{300}')), ('\u{1F79}', '\u{3CC}', None), ('\u{1F7A}', '\u{3C5}', Some('\u{300}')),
+    ('\u{1F7B}', '\u{3CD}', None), ('\u{1F7C}', '\u{3C9}', Some('\u{300}')), ('\u{1F7D}', '\u{3CE}', None),
+    ('\u{1F80}', '\u{1F00}', Some('\u{345}')), ('\u{1F81}', '\u{1F01}', Some('\u{345}')), ('\u{1F82}', '\u{1F02}', Some('\u{345}')),
+    ('\u{1F83}', '\u{1F03}', Some('\u{345}')), ('\u{1F84}', '\u{1F04}', Some('\u{345}')), ('\u{1F85}', '\u{1F05}', Some('\u{345}')),
+    ('\u{1F86}', '\u{1F06}', Some('\u{345}')), ('\u{1F87}', '\u{1F07}', Some('\u{345}')), ('\u{1F88}', '\u{1F08}', Some('\u{345}')),
+    ('\u{1F89}', '\u{1F09}', Some('\u{345}')), ('\u{1F8A}', '\u{1F0A}', Some('\u{345}')), ('\u{1F8B}', '\u{1F0B}', Some('\u{345}')),
+    ('\u{1F8C}', '\u{1F0C}', Some('\u{345}')), ('\u{1F8D}', '\u{1F0D}', Some('\u{345}')), ('\u{1F8E}', '\u{1F0E}', Some('\u{345}')),
+    ('\u{1F8F}', '\u{1F0F}', Some('\u{345}')), ('\u{1F90}', '\u{1F20}', Some('\u{345}')), ('\u{1F91}', '\u{1F21}', Some('\u{345}')),
+    ('\u{1F92}', '\u{1F22}', Some('\u{345}')), ('\u{1F93}', '\u{1F23}', Some('\u{345}')), ('\u{1F94}', '\u{1F24}', Some('\u{345}')),
+    ('\u{1F95}', '\u{1F25}', Some('\u{345}')), ('\u{1F96}', '\u{1F26}', Some('\u{345}')), ('\u{1F97}', '\u{1F27}', Some('\u{345}')),
+    ('\u{1F98}', '\u{1F28}', Some('\u{345}')), ('\u{1F99}', '\u{1F29}', Some('\u{345}')), ('\u{1F9A}', '\u{1F2A}', Some('\u{345}')),
+    ('\u{1F9B}', '\u{1F2B}', Some('\u{345}')), ('\u{1F9C}', '\u{1F2C}', Some('\u{345}')), ('\u{1F9D}', '\u{1F2D}', Some('\u{345}')),
+    ('\u{1F9E}', '\u{1F2E}', Some('\u{345}')), ('\u{1F9F}', '\u{1F2F}', Some('\u{345}')), ('\u{1FA0}', '\u{1F60}', Some('\u{345}')),
+    ('\u{1FA1}', '\u{1F61}', Some('\u{345}')), ('\u{1FA2}', '\u{1F62}', Some('\u{345}')), ('\u{1FA3}', '\u{1F63}', Some('\u{345}')),
+    ('\u{1FA4}', '\u{1F64}', Some('\u{345}')), ('\u{1FA5}', '\u{1F65}', Some('\u{345}')), ('\u{1FA6}', '\u{1F66}', Some('\u{345}')),
+    ('\u{1FA7}', '\u{1F67}', Some('\u{345}')), ('\u{1FA8}', '\u{1F68}', Some('\u{345}')), ('\u{1FA9}', '\u{1F69}', Some('\u{345}')),
+    ('\u{1FAA}', '\u{1F6A}', Some('\u{345}')), ('\u{1FAB}', '\u{1F6B}', Some('\u{345}')), ('\u{1FAC}', '\u{1F6C}', Some('\u{345}')),
+    ('\u{1FAD}', '\u{1F6D}', Some('\u{345}')), ('\u{1FAE}', '\u{1F6E}', Some('\u{345}')), ('\u{1FAF}', '\u{1F6F}', Some('\u{345}')),
+    ('\u{1FB0}', '\u{3B1}', Some('\u{306}')), ('\u{1FB1}', '\u{3B1}', Some('\u{304}')), ('\u{1FB2}', '\u{1F70}', Some('\u{345}')),
+    ('\u{1FB3}', '\u{3B1}', Some('\u{345}')), ('\u{1FB4}', '\u{3AC}', Some('\u{345}')), ('\u{1FB6}', '\u{3B1}', Some('\u{342}')),
+    ('\u{1FB7}', '\u{1FB6}', Some('\u{345}')), ('\u{1FB8}', '\u{391}', Some('\u{306}')), ('\u{1FB9}', '\u{391}', Some('\u{304}')),
+    ('\u{1FBA}', '\u{391}', Some('\u{300}')), ('\u{1FBB}', '\u{386}', None), ('\u{1FBC}', '\u{391}', Some('\u{345}')),
+    ('\u{1FBE}', '\u{3B9}', None), ('\u{1FC1}', '\u{A8}', Some('\u{342}')), ('\u{1FC2}', '\u{1F74}', Some('\u{345}')),
+    ('\u{1FC3}', '\u{3B7}', Some('\u{345}')), ('\u{1FC4}', '\u{3AE}', Some('\u{345}')), ('\u{1FC6}', '\u{3B7}', Some('\u{342}')),
+    ('\u{1FC7}', '\u{1FC6}', Some('\u{345}')), ('\u{1FC8}', '\u{395}', Some('\u{300}')), ('\u{1FC9}', '\u{388}', None),
+    ('\u{1FCA}', '\u{397}', Some('\u{300}')), ('\u{1FCB}', '\u{389}', None), ('\u{1FCC}', '\u{397}', Some('\u{345}')),
+    ('\u{1FCD}', '\u{1FBF}', Some('\u{300}')), ('\u{1FCE}', '\u{1FBF}', Some('\u{301}')), ('\u{1FCF}', '\u{1FBF}', Some('\u{342}')),
+    ('\u{1FD0}', '\u{3B9}', Some('\u{306}')), ('\u{1FD1}', '\u{3B9}', Some('\u{304}')), ('\u{1FD2}', '\u{3CA}', Some('\u{300}')),
+    ('\u{1FD3}', '\u{390}', None), ('\u{1FD6}', '\u{3B9}', Some('\u{342}')), ('\u{1FD7}', '\u{3CA}', Some('\u{342}')),
+    ('\u{1FD8}', '\u{399}', Some('\u{306}')), ('\u{1FD9}', '\u{399}', Some('\u{304}')), ('\u{1FDA}', '\u{399}', Some('\u{300}')),
+    ('\u{1FDB}', '\u{38A}', None), ('\u{1FDD}', '\u{1FFE}', Some('\u{300}')), ('\u{1FDE}', '\u{1FFE}', Some('\u{301}')),
+    ('\u{1FDF}', '\u{1FFE}', Some('\u{342}')), ('\u{1FE0}', '\u{3C5}', Some('\u{306}')), ('\u{1FE1}', '\u{3C5}', Some('\u{304}')),
+    ('\u{1FE2}', '\u{3CB}', Some('\u{300}')), ('\u{1FE3}', '\u{3B0}', None), ('\u{1FE4}', '\u{3C1}', Some('\u{313}')),
+    ('\u{1FE5}', '\u{3C1}', Some('\u{314}')), ('\u{1FE6}', '\u{3C5}', Some('\u{342}')), ('\u{1FE7}', '\u{3CB}', Some('\u{342}')),
+    ('\u{1FE8}', '\u{3A5}', Some('\u{306}')), ('\u{1FE9}', '\u{3A5}', Some('\u{304}')), ('\u{1FEA}', '\u{3A5}', Some('\u{300}')),
+    ('\u{1FEB}', '\u{38E}', None), ('\u{1FEC}', '\u{3A1}', Some('\u{314}')), ('\u{1FED}', '\u{A8}', Some('\u{300}')),
+    ('\u{1FEE}', '\u{385}', None), ('\u{1FEF}', '\u{60}', None), ('\u{1FF2}', '\u{1F7C}', Some('\u{345}')),
+    ('\u{1FF3}', '\u{3C9}', Some('\u{345}')), ('\u{1FF4}', '\u{3CE}', Some('\u{345}')), ('\u{1FF6}', '\u{3C9}', Some('\u{342}')),
+    ('\u{1FF7}', '\u{1FF6}', Some('\u{345}')), ('\u{1FF8}', '\u{39F}', Some('\u{300}')), ('\u{1FF9}', '\u{38C}', None),
+    ('\u{1FFA}', '\u{3A9}', Some('\u{300}')), ('\u{1FFB}', '\u{38F}', None), ('\u{1FFC}', '\u{3A9}', Some('\u{345}')),
+    ('\u{1FFD}', '\u{B4}', None), ('\u{2000}', '\u{2002}', None), ('\u{2001}', '\u{2003}', None),
+    ('\u{2126}', '\u{3A9}', None), ('\u{212A}', '\u{4B}', None), ('\u{212B}', '\u{C5}', None),
+    ('\u{219A}', '\u{2190}', Some('\u{338}')), ('\u{219B}', '\u{2192}', Some('\u{338}')), ('\u{21AE}', '\u{2194}', Some('\u{338}')),
+    ('\u{21CD}', '\u{21D0}', Some('\u{338}')), ('\u{21CE}', '\u{21D4}', Some('\u{338}')), ('\u{21CF}', '\u{21D2}', Some('\u{338}')),
+    ('\u{2204}', '\u{2203}', Some('\u{338}')), ('\u{2209}', '\u{2208}', Some('\u{338}')), ('\u{220C}', '\u{220B}', Some('\u{338}')),
+    ('\u{2224}', '\u{2223}', Some('\u{338}')), ('\u{2226}', '\u{2225}', Some('\u{338}')), ('\u{2241}', '\u{223C}', Some('\u{338}')),
+    ('\u{2244}', '\u{2243}', Some('\u{338}')), ('\u{2247}', '\u{2245}', Some('\u{338}')), ('\u{2249}', '\u{2248}', Some('\u{338}')),
+    ('\u{2260}', '\u{3D}', Some('\u{338}')), ('\u{2262}', '\u{2261}', Some('\u{338}')), ('\u{226D}', '\u{224D}', Some('\u{338}')),
+    ('\u{226E}', '\u{3C}', Some('\u{338}')), ('\u{226F}', '\u{3E}', Some('\u{338}')), ('\u{2270}', '\u{2264}', Some('\u{338}')),
+    ('\u{2271}', '\u{2265}', Some('\u{338}')), ('\u{2274}', '\u{2272}', Some('\u{338}')), ('\u{2275}', '\u{2273}', Some('\u{338}')),
+    ('\u{2278}', '\u{2276}', Some('\u{338}')), ('\u{2279}', '\u{2277}', Some('\u{338}')), ('\u{2280}', '\u{227A}', Some('\u{338}')),
+    ('\u{2281}', '\u{227B}', Some('\u{338}')), ('\u{2284}', '\u{2282}', Some('\u{338}')), ('\u{2285}', '\u{2283}', Some('\u{338}')),
+    ('\u{2288}', '\u{2286}', Some('\u{338}')), ('\u{2289}', '\u{2287}', Some('\u{338}')), ('\u{22AC}', '\u{22A2}', Some('\u{338}')),
+    ('\u{22AD}', '\u{22A8}', Some('\u{338}')), ('\u{22AE}', '\u{22A9}', Some('\u{338}')), ('\u{22AF}', '\u{22AB}', Some('\u{338}')),
+    ('\u{22E0}', '\u{227C}', Some('\u{338}')), ('\u{22E1}', '\u{227D}', Some('\u{338}')), ('\u{22E2}', '\u{2291}', Some('\u{338}')),
+    ('\u{22E3}', '\u{2292}', Some('\u{338}')), ('\u{22EA}', '\u{22B2}', Some('\u{338}')), ('\u{22EB}', '\u{22B3}', Some('\u{338}')),
+    ('\u{22EC}', '\u{22B4}', Some('\u{338}')), ('\u{22ED}', '\u{22B5}', Some('\u{338}')), ('\u{2329}', '\u{3008}', None),
+    ('\u{232A}', '\u{3009}', None), ('\u{2ADC}', '\u{2ADD}', Some('\u{338}')), ('\u{304C}', '\u{304B}', Some('\u{3099}')),
+    ('\u{304E}', '\u{304D}', Some('\u{3099}')), ('\u{3050}', '\u{304F}', Some('\u{3099}')), ('\u{3052}', '\u{3051}', Some('\u{3099}')),
+    ('\u{3054}', '\u{3053}', Some('\u{3099}')), ('\u{3056}', '\u{3055}', Some('\u{3099}')), ('\u{3058}', '\u{3057}', Some('\u{3099}')),
+    ('\u{305A}', '\u{3059}', Some('\u{3099}')), ('\u{305C}', '\u{305B}', Some('\u{3099}')), ('\u{305E}', '\u{305D}', Some('\u{3099}')),
+    ('\u{3060}', '\u{305F}', Some('\u{3099}')), ('\u{3062}', '\u{3061}', Some('\u{3099}')), ('\u{3065}', '\u{3064}', Some('\u{3099}')),
+    ('\u{3067}', '\u{3066}', Some('\u{3099}')), ('\u{3069}', '\u{3068}', Some('\u{3099}')), ('\u{3070}', '\u{306F}', Some('\u{3099}')),
+    ('\u{3071}', '\u{306F}', Some('\u{309A}')), ('\u{3073}', '\u{3072}', Some('\u{3099}')), ('\u{3074}', '\u{3072}', Some('\u{309A}')),
+    ('\u{3076}', '\u{3075}', Some('\u{3099}')), ('\u{3077}', '\u{3075}', Some('\u{309A}')), ('\u{3079}', '\u{3078}', Some('\u{3099}')),
+    ('\u{307A}', '\u{3078}', Some('\u{309A}')), ('\u{307C}', '\u{307B}', Some('\u{3099}')), ('\u{307D}', '\u{307B}', Some('\u{309A}')),
+    ('\u{3094}', '\u{3046}', Some('\u{3099}')), ('\u{309E}', '\u{309D}', Some('\u{3099}')), ('\u{30AC}', '\u{30AB}', Some('\u{3099}')),
+    ('\u{30AE}', '\u{30AD}', Some('\u{3099}')), ('\u{30B0}', '\u{30AF}', Some('\u{3099}')), ('\u{30B2}', '\u{30B1}', Some('\u{3099}')),
+    ('\u{30B4}', '\u{30B3}', Some('\u{3099}')), ('\u{30B6}', '\u{30B5}', Some('\u{3099}')), ('\u{30B8}', '\u{30B7}', Some('\u{3099}')),
+    ('\u{30BA}', '\u{30B9}', Some('\u{3099}')), ('\u{30BC}', '\u{30BB}', Some('\u{3099}')), ('\u{30BE}', '\u{30BD}', Some('\u{3099}')),
+    ('\u{30C0}', '\u{30BF}', Some('\u{3099}')), ('\u{30C2}', '\u{30C1}', Some('\u{3099}')), ('\u{30C5}', '\u{30C4}', Some('\u{3099}')),
+    ('\u{30C7}', '\u{30C6}', Some('\u{3099}')), ('\u{30C9}', '\u{30C8}', Some('\u{3099}')), ('\u{30D0}', '\u{30CF}', Some('\u{3099}')),
+    ('\u{30D1}', '\u{30CF}', Some('\u{309A}')), ('\u{30D3}', '\u{30D2}', Some('\u{3099}')), ('\u{30D4}', '\u{30D2}', Some('\u{309A}')),
+    ('\u{30D6}', '\u{30D5}', Some('\u{3099}')), ('\u{30D7}', '\u{30D5}', Some('\u{309A}')), ('\u{30D9}', '\u{30D8}', Some('\u{3099}')),
+    ('\u{30DA}', '\u{30D8}', Some('\u{309A}')), ('\u{30DC}', '\u{30DB}', Some('\u{3099}')), ('\u{30DD}', '\u{30DB}', Some('\u{309A}')),
+    ('\u{30F4}', '\u{30A6}', Some('\u{3099}')), ('\u{30F7}', '\u{30EF}', Some('\u{3099}')), ('\u{30F8}', '\u{30F0}', Some('\u{3099}')),
+    ('\u{30F9}', '\u{30F1}', Some('\u{3099}')), ('\u{30FA}', '\u{30F2}', Some('\u{3099}')), ('\u{30FE}', '\u{30FD}', Some('\u{3099}')),
+    ('\u{F900}', '\u{8C48}', None), ('\u{F901}', '\u{66F4}', None), ('\u{F902}', '\u{8ECA}', None),
+    ('\u{F903}', '\u{8CC8}', None), ('\u{F904}', '\u{6ED1}', None), ('\u{F905}', '\u{4E32}', None),
+    ('\u{F906}', '\u{53E5}', None), ('\u{F907}', '\u{9F9C}', None), ('\u{F908}', '\u{9F9C}', None),
+    ('\u{F909}', '\u{5951}', None), ('\u{F90A}', '\u{91D1}', None), ('\u{F90B}', '\u{5587}', None),
+    ('\u{F90C}', '\u{5948}', None), ('\u{F90D}', '\u{61F6}', None), ('\u{F90E}', '\u{7669}', None),
+    ('\u{F90F}', '\u{7F85}', None), ('\u{F910}', '\u{863F}', None), ('\u{F911}', '\u{87BA}', None),
+    ('\u{F912}', '\u{88F8}', None), ('\u{F913}', '\u{908F}', None), ('\u{F914}', '\u{6A02}', None),
+    ('\u{F915}', '\u{6D1B}', None), ('\u{F916}', '\u{70D9}', None), ('\u{F917}', '\u{73DE}', None),
+    ('\u{F918}', '\u{843D}', None), ('\u{F919}', '\u{916A}', None), ('\u{F91A}', '\u{99F1}', None),
+    ('\u{F91B}', '\u{4E82}', None), ('\u{F91C}', '\u{5375}', None), ('\u{F91D}', '\u{6B04}', None),
+    ('\u{F91E}', '\u{721B}', None), ('\u{F91F}', '\u{862D}', None), ('\u{F920}', '\u{9E1E}', None),
+    ('\u{F921}', '\u{5D50}', None), ('\u{F922}', '\u{6FEB}', None), ('\u{F923}', '\u{85CD}', None),
+    ('\u{F924}', '\u{8964}', None), ('\u{F925}', '\u{62C9}', None), ('\u{F926}', '\u{81D8}', None),
+    ('\u{F927}', '\u{881F}', None), ('\u{F928}', '\u{5ECA}', None), ('\u{F929}', '\u{6717}', None),
+    ('\u{F92A}', '\u{6D6A}', None), ('\u{F92B}', '\u{72FC}', None), ('\u{F92C}', '\u{90CE}', None),
+    ('\u{F92D}', '\u{4F86}', None), ('\u{F92E}', '\u{51B7}', None), ('\u{F92F}', '\u{52DE}', None),
+    ('\u{F930}', '\u{64C4}', None), ('\u{F931}', '\u{6AD3}', None), ('\u{F932}', '\u{7210}', None),
+    ('\u{F933}', '\u{76E7}', None), ('\u{F934}', '\u{8001}', None), ('\u{F935}', '\u{8606}', None),
+    ('\u{F936}', '\u{865C}', None), ('\u{F937}', '\u{8DEF}', None), ('\u{F938}', '\u{9732}', None),
+    ('\u{F939}', '\u{9B6F}', None), ('\u{F93A}', '\u{9DFA}', None), ('\u{F93B}', '\u{788C}', None),
+    ('\u{F93C}', '\u{797F}', None), ('\u{F93D}', '\u{7DA0}', None), ('\u{F93E}', '\u{83C9}', None),
+    ('\u{F93F}', '\u{9304}', None), ('\u{F940}', '\u{9E7F}', None), ('\u{F941}', '\u{8AD6}', None),
+    ('\u{F942}', '\u{58DF}', None), ('\u{F943}', '\u{5F04}', None), ('\u{F944}', '\u{7C60}', None),
+    ('\u{F945}', '\u{807E}', None), ('\u{F946}', '\u{7262}', None), ('\u{F947}', '\u{78CA}', None),
+    ('\u{F948}', '\u{8CC2}', None), ('\u{F949}', '\u{96F7}', None), ('\u{F94A}', '\u{58D8}', None),
+    ('\u{F94B}', '\u{5C62}', None), ('\u{F94C}', '\u{6A13}', None), ('\u{F94D}', '\u{6DDA}', None),
+    ('\u{F94E}', '\u{6F0F}', None), ('\u{F94F}', '\u{7D2F}', None), ('\u{F950}', '\u{7E37}', None),
+    ('\u{F951}', '\u{964B}', None), ('\u{F952}', '\u{52D2}', None), ('\u{F953}', '\u{808B}', None),
+    ('\u{F954}', '\u{51DC}', None), ('\u{F955}', '\u{51CC}', None), ('\u{F956}', '\u{7A1C}', None),
+    ('\u{F957}', '\u{7DBE}', None), ('\u{F958}', '\u{83F1}', None), ('\u{F959}', '\u{9675}', None),
+    ('\u{F95A}', '\u{8B80}', None), ('\u{F95B}', '\u{62CF}', None), ('\u{F95C}', '\u{6A02}', None),
+    ('\u{F95D}', '\u{8AFE}', None), ('\u{F95E}', '\u{4E39}', None), ('\u{F95F}', '\u{5BE7}', None),
+    ('\u{F960}', '\u{6012}', None), ('\u{F961}', '\u{7387}', None), ('\u{F962}', '\u{7570}', None),
+    ('\u{F963}', '\u{5317}', None), ('\u{F964}', '\u{78FB}', None), ('\u{F965}', '\u{4FBF}', None),
+    ('\u{F966}', '\u{5FA9}', None), ('\u{F967}', '\u{4E0D}', None), ('\u{F968}', '\u{6CCC}', None),
+    ('\u{F969}', '\u{6578}', None), ('\u{F96A}', '\u{7D22}', None), ('\u{F96B}', '\u{53C3}', None),
+    ('\u{F96C}', '\u{585E}', None), ('\u{F96D}', '\u{7701}', None), ('\u{F96E}', '\u{8449}', None),
+    ('\u{F96F}', '\u{8AAA}', None), ('\u{F970}', '\u{6BBA}', None), ('\u{F971}', '\u{8FB0}', None),
+    ('\u{F972}', '\u{6C88}', None), ('\u{F973}', '\u{62FE}', None), ('\u{F974}', '\u{82E5}', None),
+    ('\u{F975}', '\u{63A0}', None), ('\u{F976}', '\u{7565}', None), ('\u{F977}', '\u{4EAE}', None),
+    ('\u{F978}', '\u{5169}', None), ('\u{F979}', '\u{51C9}', None), ('\u{F97A}', '\u{6881}', None),
+    ('\u{F97B}', '\u{7CE7}', None), ('\u{F97C}', '\u{826F}', None), ('\u{F97D}', '\u{8AD2}', None),
+    ('\u{F97E}', '\u{91CF}', None), ('\u{F97F}', '\u{52F5}', None), ('\u{F980}', '\u{5442}', None),
+    ('\u{F981}', '\u{5973}', None), ('\u{F982}', '\u{5EEC}', None), ('\u{F983}', '\u{65C5}', None),
+    ('\u{F984}', '\u{6FFE}', None), ('\u{F985}', '\u{792A}', None), ('\u{F986}', '\u{95AD}', None),
+    ('\u{F987}', '\u{9A6A}', None), ('\u{F988}', '\u{9E97}', None), ('\u{F989}', '\u{9ECE}', None),
+    ('\u{F98A}', '\u{529B}', None), ('\u{F98B}', '\u{66C6}', None), ('\u{F98C}', '\u{6B77}', None),
+    ('\u{F98D}', '\u{8F62}', None), ('\u{F98E}', '\u{5E74}', None), ('\u{F98F}', '\u{6190}', None),
+    ('\u{F990}', '\u{6200}', None), ('\u{F991}', '\u{649A}', None), ('\u{F992}', '\u{6F23}', None),
+    ('\u{F993}', '\u{7149}', None), ('\u{F994}', '\u{7489}', None), ('\u{F995}', '\u{79CA}', None),
+    ('\u{F996}', '\u{7DF4}', None), ('\u{F997}', '\u{806F}', None), ('\u{F998}', '\u{8F26}', None),
+    ('\u{F999}', '\u{84EE}', None), ('\u{F99A}', '\u{9023}', None), ('\u{F99B}', '\u{934A}', None),
+    ('\u{F99C}', '\u{5217}', None), ('\u{F99D}', '\u{52A3}', None), ('\u{F99E}', '\u{54BD}', None),
+    ('\u{F99F}', '\u{70C8}', None), ('\u{F9A0}', '\u{88C2}', None), ('\u{F9A1}', '\u{8AAA}', None),
+    ('\u{F9A2}', '\u{5EC9}', None), ('\u{F9A3}', '\u{5FF5}', None), ('\u{F9A4}', '\u{637B}', None),
+    ('\u{F9A5}', '\u{6BAE}', None), ('\u{F9A6}', '\u{7C3E}', None), ('\u{F9A7}', '\u{7375}', None),
+    ('\u{F9A8}', '\u{4EE4}', None), ('\u{F9A9}', '\u{56F9}', None), ('\u{F9AA}', '\u{5BE7}', None),
+    ('\u{F9AB}', '\u{5DBA}', None), ('\u{F9AC}', '\u{601C}', None), ('\u{F9AD}', '\u{73B2}', None),
+    ('\u{F9AE}', '\u{7469}', None), ('\u{F9AF}', '\u{7F9A}', None), ('\u{F9B0}', '\u{8046}', None),
+    ('\u{F9B1}', '\u{9234}', None), ('\u{F9B2}', '\u{96F6}', None), ('\u{F9B3}', '\u{9748}', None),
+    ('\u{F9B4}', '\u{9818}', None), ('\u{F9B5}', '\u{4F8B}', None), ('\u{F9B6}', '\u{79AE}', None),
+    ('\u{F9B7}', '\u{91B4}', None), ('\u{F9B8}', '\u{96B8}', None), ('\u{F9B9}', '\u{60E1}', None),
+    ('\u{F9BA}', '\u{4E86}', None), ('\u{F9BB}', '\u{50DA}', None), ('\u{F9BC}', '\u{5BEE}', None),
+    ('\u{F9BD}', '\u{5C3F}', None), ('\u{F9BE}', '\u{6599}', None), ('\u{F9BF}', '\u{6A02}', None),
+    ('\u{F9C0}', '\u{71CE}', None), ('\u{F9C1}', '\u{7642}', None), ('\u{F9C2}', '\u{84FC}', None),
+    ('\u{F9C3}', '\u{907C}', None), ('\u{F9C4}', '\u{9F8D}', None), ('\u{F9C5}', '\u{6688}', None),
+    ('\u{F9C6}', '\u{962E}', None), ('\u{F9C7}', '\u{5289}', None), ('\u{F9C8}', '\u{677B}', None),
+    ('\u{F9C9}', '\u{67F3}', None), ('\u{F9CA}', '\u{6D41}', None), ('\u{F9CB}', '\u{6E9C}', None),
+    ('\u{F9CC}', '\u{7409}', None), ('\u{F9CD}', '\u{7559}', None), ('\u{F9CE}', '\u{786B}', None),
+    ('\u{F9CF}', '\u{7D10}', None), ('\u{F9D0}', '\u{985E}', None), ('\u{F9D1}', '\u{516D}', None),
+    ('\u{F9D2}', '\u{622E}', None), ('\u{F9D3}', '\u{9678}', None), ('\u{F9D4}', '\u{502B}', None),
+    ('\u{F9D5}', '\u{5D19}', None), ('\u{F9D6}', '\u{6DEA}', None), ('\u{F9D7}', '\u{8F2A}', None),
+    ('\u{F9D8}', '\u{5F8B}', None), ('\u{F9D9}', '\u{6144}', None), ('\u{F9DA}', '\u{6817}', None),
+    ('\u{F9DB}', '\u{7387}', None), ('\u{F9DC}', '\u{9686}', None), ('\u{F9DD}', '\u{5229}', None),
+    ('\u{F9DE}', '\u{540F}', None), ('\u{F9DF}', '\u{5C65}', None), ('\u{F9E0}', '\u{6613}', None),
+    ('\u{F9E1}', '\u{674E}', None), ('\u{F9E2}', '\u{68A8}', None), ('\u{F9E3}', '\u{6CE5}', None),
+    ('\u{F9E4}', '\u{7406}', None), ('\u{F9E5}', '\u{75E2}', None), ('\u{F9E6}', '\u{7F79}', None),
+    ('\u{F9E7}', '\u{88CF}', None), ('\u{F9E8}', '\u{88E1}', None), ('\u{F9E9}', '\u{91CC}', None),
+    ('\u{F9EA}', '\u{96E2}', None), ('\u{F9EB}', '\u{533F}', None), ('\u{F9EC}', '\u{6EBA}', None),
+    ('\u{F9ED}', '\u{541D}', None), ('\u{F9EE}', '\u{71D0}', None), ('\u{F9EF}', '\u{7498}', None),
+    ('\u{F9F0}', '\u{85FA}', None), ('\u{F9F1}', '\u{96A3}', None), ('\u{F9F2}', '\u{9C57}', None),
+    ('\u{F9F3}', '\u{9E9F}', None), ('\u{F9F4}', '\u{6797}', None), ('\u{F9F5}', '\u{6DCB}', None),
+    ('\u{F9F6}', '\u{81E8}', None), ('\u{F9F7}', '\u{7ACB}', None), ('\u{F9F8}', '\u{7B20}', None),
+    ('\u{F9F9}', '\u{7C92}', None), ('\u{F9FA}', '\u{72C0}', None), ('\u{F9FB}', '\u{7099}', None),
+    ('\u{F9FC}', '\u{8B58}', None), ('\u{F9FD}', '\u{4EC0}', None), ('\u{F9FE}', '\u{8336}', None),
+    ('\u{F9FF}', '\u{523A}', None), ('\u{FA00}', '\u{5207}', None), ('\u{FA01}', '\u{5EA6}', None),
+    ('\u{FA02}', '\u{62D3}', None), ('\u{FA03}', '\u{7CD6}', None), ('\u{FA04}', '\u{5B85}', None),
+    ('\u{FA05}', '\u{6D1E}', None), ('\u{FA06}', '\u{66B4}', None), ('\u{FA07}', '\u{8F3B}', None),
+    ('\u{FA08}', '\u{884C}', None), ('\u{FA09}', '\u{964D}', None), ('\u{FA0A}', '\u{898B}', None),
+    ('\u{FA0B}', '\u{5ED3}', None), ('\u{FA0C}', '\u{5140}', None), ('\u{FA0D}', '\u{55C0}', None),
+    ('\u{FA10}', '\u{585A}', None), ('\u{FA12}', '\u{6674}', None), ('\u{FA15}', '\u{51DE}', None),
+    ('\u{FA16}', '\u{732A}', None), ('\u{FA17}', '\u{76CA}', None), ('\u{FA18}', '\u{793C}', None),
+    ('\u{FA19}', '\u{795E}', None), ('\u{FA1A}', '\u{7965}', None), ('\u{FA1B}', '\u{798F}', None),
+    ('\u{FA1C}', '\u{9756}', None), ('\u{FA1D}', '\u{7CBE}', None), ('\u{FA1E}', '\u{7FBD}', None),
+    ('\u{FA20}', '\u{8612}', None), ('\u{FA22}', '\u{8AF8}', None), ('\u{FA25}', '\u{9038}', None),
+    ('\u{FA26}', '\u{90FD}', None), ('\u{FA2A}', '\u{98EF}', None), ('\u{FA2B}', '\u{98FC}', None),
+    ('\u{FA2C}', '\u{9928}', None), ('\u{FA2D}', '\u{9DB4}', None), ('\u{FA2E}', '\u{90DE}', None),
+    ('\u{FA2F}', '\u{96B7}', None), ('\u{FA30}', '\u{4FAE}', None), ('\u{FA31}', '\u{50E7}', None),
+    ('\u{FA32}', '\u{514D}', None), ('\u{FA33}', '\u{52C9}', None), ('\u{FA34}', '\u{52E4}', None),
+    ('\u{FA35}', '\u{5351}', None), ('\u{FA36}', '\u{559D}', None), ('\u{FA37}', '\u{5606}', None),
+    ('\u{FA38}', '\u{5668}', None), ('\u{FA39}', '\u{5840}', None), ('\u{FA3A}', '\u{58A8}', None),
+    ('\u{FA3B}', '\u{5C64}', None), ('\u{FA3C}', '\u{5C6E}', None), ('\u{FA3D}', '\u{6094}', None),
+    ('\u{FA3E}', '\u{6168}', None), ('\u{FA3F}', '\u{618E}', None), ('\u{FA40}', '\u{61F2}', None),
+    ('\u{FA41}', '\u{654F}', None), ('\u{FA42}', '\u{65E2}', None), ('\u{FA43}', '\u{6691}', None),
+    ('\u{FA44}', '\u{6885}', None), ('\u{FA45}', '\u{6D77}', None), ('\u{FA46}', '\u{6E1A}', None),
+    ('\u{FA47}', '\u{6F22}', None), ('\u{FA48}', '\u{716E}', None), ('\u{FA49}', '\u{722B}', None),
+    ('\u{FA4A}', '\u{7422}', None), ('\u{FA4B}', '\u{7891}', None), ('\u{FA4C}', '\u{793E}', None),
+    ('\u{FA4D}', '\u{7949}', None), ('\u{FA4E}', '\u{7948}', None), ('\u{FA4F}', '\u{7950}', None),
+    ('\u{FA50}', '\u{7956}', None), ('\u{FA51}', '\u{795D}', None), ('\u{FA52}', '\u{798D}', None),
+    ('\u{FA53}', '\u{798E}', None), ('\u{FA54}', '\u{7A40}', None), ('\u{FA55}', '\u{7A81}', None),
+    ('\u{FA56}', '\u{7BC0}', None), ('\u{FA57}', '\u{7DF4}', None), ('\u{FA58}', '\u{7E09}', None),
+    ('\u{FA59}', '\u{7E41}', None), ('\u{FA5A}', '\u{7F72}', None), ('\u{FA5B}', '\u{8005}', None),
+    ('\u{FA5C}', '\u{81ED}', None), ('\u{FA5D}', '\u{8279}', None), ('\u{FA5E}', '\u{8279}', None),
+    ('\u{FA5F}', '\u{8457}', None), ('\u{FA60}', '\u{8910}', None), ('\u{FA61}', '\u{8996}', None),
+    ('\u{FA62}', '\u{8B01}', None), ('\u{FA63}', '\u{8B39}', None), ('\u{FA64}', '\u{8CD3}', None),
+    ('\u{FA65}', '\u{8D08}', None), ('\u{FA66}', '\u{8FB6}', None), ('\u{FA67}', '\u{9038}', None),
+    ('\u{FA68}', '\u{96E3}', None), ('\u{FA69}', '\u{97FF}', None), ('\u{FA6A}', '\u{983B}', None),
+    ('\u{FA6B}', '\u{6075}', None), ('\u{FA6C}', '\u{242EE}', None), ('\u{FA6D}', '\u{8218}', None),
+    ('\u{FA70}', '\u{4E26}', None), ('\u{FA71}', '\u{51B5}', None), ('\u{FA72}', '\u{5168}', None),
+    ('\u{FA73}', '\u{4F80}', None), ('\u{FA74}', '\u{5145}', None), ('\u{FA75}', '\u{5180}', None),
+    ('\u{FA76}', '\u{52C7}', None), ('\u{FA77}', '\u{52FA}', None), ('\u{FA78}', '\u{559D}', None),
+    ('\u{FA79}', '\u{5555}', None), ('\u{FA7A}', '\u{5599}', None), ('\u{FA7B}', '\u{55E2}', None),
+    ('\u{FA7C}', '\u{585A}', None), ('\u{FA7D}', '\u{58B3}', None), ('\u{FA7E}', '\u{5944}', None),
+    ('\u{FA7F}', '\u{5954}', None), ('\u{FA80}', '\u{5A62}', None), ('\u{FA81}', '\u{5B28}', None),
+    ('\u{FA82}', '\u{5ED2}', None), ('\u{FA83}', '\u{5ED9}', None), ('\u{FA84}', '\u{5F69}', None),
+    ('\u{FA85}', '\u{5FAD}', None), ('\u{FA86}', '\u{60D8}', None), ('\u{FA87}', '\u{614E}', None),
+    ('\u{FA88}', '\u{6108}', None), ('\u{FA89}', '\u{618E}', None), ('\u{FA8A}', '\u{6160}', None),
+    ('\u{FA8B}', '\u{61F2}', None), ('\u{FA8C}', '\u{6234}', None), ('\u{FA8D}', '\u{63C4}', None),
+    ('\u{FA8E}', '\u{641C}', None), ('\u{FA8F}', '\u{6452}', None), ('\u{FA90}', '\u{6556}', None),
+    ('\u{FA91}', '\u{6674}', None), ('\u{FA92}', '\u{6717}', None), ('\u{FA93}', '\u{671B}', None),
+    ('\u{FA94}', '\u{6756}', None), ('\u{FA95}', '\u{6B79}', None), ('\u{FA96}', '\u{6BBA}', None),
+    ('\u{FA97}', '\u{6D41}', None), ('\u{FA98}', '\u{6EDB}', None), ('\u{FA99}', '\u{6ECB}', None),
+    ('\u{FA9A}', '\u{6F22}', None), ('\u{FA9B}', '\u{701E}', None), ('\u{FA9C}', '\u{716E}', None),
+    ('\u{FA9D}', '\u{77A7}', None), ('\u{FA9E}', '\u{7235}', None), ('\u{FA9F}', '\u{72AF}', None),
+    ('\u{FAA0}', '\u{732A}', None), ('\u{FAA1}', '\u{7471}', None), ('\u{FAA2}', '\u{7506}', None),
+    ('\u{FAA3}', '\u{753B}', None), ('\u{FAA4}', '\u{761D}', None), ('\u{FAA5}', '\u{761F}', None),
+    ('\u{FAA6}', '\u{76CA}', None), ('\u{FAA7}', '\u{76DB}', None), ('\u{FAA8}', '\u{76F4}', None),
+    ('\u{FAA9}', '\u{774A}', None), ('\u{FAAA}', '\u{7740}', None), ('\u{FAAB}', '\u{78CC}', None),
+    ('\u{FAAC}', '\u{7AB1}', None), ('\u{FAAD}', '\u{7BC0}', None), ('\u{FAAE}', '\u{7C7B}', None),
+    ('\u{FAAF}', '\u{7D5B}', None), ('\u{FAB0}', '\u{7DF4}', None), ('\u{FAB1}', '\u{7F3E}', None),
+    ('\u{FAB2}', '\u{8005}', None), ('\u{FAB3}', '\u{8352}', None), ('\u{FAB4}', '\u{83EF}', None),
+    ('\u{FAB5}', '\u{8779}', None), ('\u{FAB6}', '\u{8941}', None), ('\u{FAB7}', '\u{8986}', None),
+    ('\u{FAB8}', '\u{8996}', None), ('\u{FAB9}', '\u{8ABF}', None), ('\u{FABA}', '\u{8AF8}', None),
+    ('\u{FABB}', '\u{8ACB}', None), ('\u{FABC}', '\u{8B01}', None), ('\u{FABD}', '\u{8AFE}', None),
+    ('\u{FABE}', '\u{8AED}', None), ('\u{FABF}', '\u{8B39}', None), ('\u{FAC0}', '\u{8B8A}', None),
+    ('\u{FAC1}', '\u{8D08}', None), ('\u{FAC2}', '\u{8F38}', None), ('\u{FAC3}', '\u{9072}', None),
+    ('\u{FAC4}', '\u{9199}', None), ('\u{FAC5}', '\u{9276}', None), ('\u{FAC6}', '\u{967C}', None),
+    ('\u{FAC7}', '\u{96E3}', None), ('\u{FAC8}', '\u{9756}', None), ('\u{FAC9}', '\u{97DB}', None),
+    ('\u{FACA}', '\u{97FF}', None), ('\u{FACB}', '\u{980B}', None), ('\u{FACC}', '\u{983B}', None),
+    ('\u{FACD}', '\u{9B12}', None), ('\u{FACE}', '\u{9F9C}', None), ('\u{FACF}', '\u{2284A}', None),
+    ('\u{FAD0}', '\u{22844}', None), ('\u{FAD1}', '\u{233D5}', None), ('\u{FAD2}', '\u{3B9D}', None),
+    ('\u{FAD3}', '\u{4018}', None), ('\u{FAD4}', '\u{4039}', None), ('\u{FAD5}', '\u{25249}', None),
+    ('\u{FAD6}', '\u{25CD0}', None), ('\u{FAD7}', '\u{27ED3}', None), ('\u{FAD8}', '\u{9F43}', None),
+    ('\u{FAD9}', '\u{9F8E}', None), ('\u{FB1D}', '\u{5D9}', Some('\u{5B4}')), ('\u{FB1F}', '\u{5F2}', Some('\u{5B7}')),
+    ('\u{FB2A}', '\u{5E9}', Some('\u{5C1}')), ('\u{FB2B}', '\u{5E9}', Some('\u{5C2}')), ('\u{FB2C}', '\u{FB49}', Some('\u{5C1}')),
+    ('\u{FB2D}', '\u{FB49}', Some('\u{5C2}')), ('\u{FB2E}', '\u{5D0}', Some('\u{5B7}')), ('\u{FB2F}', '\u{5D0}', Some('\u{5B8}')),
+    ('\u{FB30}', '\u{5D0}', Some('\u{5BC}')), ('\u{FB31}', '\u{5D1}', Some('\u{5BC}')), ('\u{FB32}', '\u{5D2}', Some('\u{5BC}')),
+    ('\u{FB33}', '\u{5D3}', Some('\u{5BC}')), ('\u{FB34}', '\u{5D4}', Some('\u{5BC}')), ('\u{FB35}', '\u{5D5}', Some('\u{5BC}')),
+    ('\u{FB36}', '\u{5D6}', Some('\u{5BC}')), ('\u{FB38}', '\u{5D8}', Some('\u{5BC}')), ('\u{FB39}', '\u{5D9}', Some('\u{5BC}')),
+    ('\u{FB3A}', '\u{5DA}', Some('\u{5BC}')), ('\u{FB3B}', '\u{5DB}', Some('\u{5BC}')), ('\u{FB3C}', '\u{5DC}', Some('\u{5BC}')),
+    ('\u{FB3E}', '\u{5DE}', Some('\u{5BC}')), ('\u{FB40}', '\u{5E0}', Some('\u{5BC}')), ('\u{FB41}', '\u{5E1}', Some('\u{5BC}')),
+    ('\u{FB43}', '\u{5E3}', Some('\u{5BC}')), ('\u{FB44}', '\u{5E4}', Some('\u{5BC}')), ('\u{FB46}', '\u{5E6}', Some('\u{5BC}')),
+    ('\u{FB47}', '\u{5E7}', Some('\u{5BC}')), ('\u{FB48}', '\u{5E8}', Some('\u{5BC}')), ('\u{FB49}', '\u{5E9}', Some('\u{5BC}')),
+    ('\u{FB4A}', '\u{5EA}', Some('\u{5BC}')), ('\u{FB4B}', '\u{5D5}', Some('\u{5B9}')), ('\u{FB4C}', '\u{5D1}', Some('\u{5BF}')),
+    ('\u{FB4D}', '\u{5DB}', Some('\u{5BF}')), ('\u{FB4E}', '\u{5E4}', Some('\u{5BF}')), ('\u{1109A}', '\u{11099}', Some('\u{110BA}')),
+    ('\u{1109C}', '\u{1109B}', Some('\u{110BA}')), ('\u{110AB}', '\u{110A5}', Some('\u{110BA}')), ('\u{1112E}', '\u{11131}', Some('\u{11127}')),
+    ('\u{1112F}', '\u{11132}', Some('\u{11127}')), ('\u{1134B}', '\u{11347}', Some('\u{1133E}')), ('\u{1134C}', '\u{11347}', Some('\u{11357}')),
+    ('\u{114BB}', '\u{114B9}', Some('\u{114BA}')), ('\u{114BC}', '\u{114B9}', Some('\u{114B0}')), ('\u{114BE}', '\u{114B9}', Some('\u{114BD}')),
+    ('\u{115BA}', '\u{115B8}', Some('\u{115AF}')), ('\u{115BB}', '\u{115B9}', Some('\u{115AF}')), ('\u{11938}', '\u{11935}', Some('\u{11930}')),
+    ('\u{1D15E}', '\u{1D157}', Some('\u{1D165}')), ('\u{1D15F}', '\u{1D158}', Some('\u{1D165}')), ('\u{1D160}', '\u{1D15F}', Some('\u{1D16E}')),
+    ('\u{1D161}', '\u{1D15F}', Some('\u{1D16F}')), ('\u{1D162}', '\u{1D15F}', Some('\u{1D170}')), ('\u{1D163}', '\u{1D15F}', Some('\u{1D171}')),
+    ('\u{1D164}', '\u{1D15F}', Some('\u{1D172}')), ('\u{1D1BB}', '\u{1D1B9}', Some('\u{1D165}')), ('\u{1D1BC}', '\u{1D1BA}', Some('\u{1D165}')),
+    ('\u{1D1BD}', '\u{1D1BB}', Some('\u{1D16E}')), ('\u{1D1BE}', '\u{1D1BC}', Some('\u{1D16E}')), ('\u{1D1BF}', '\u{1D1BB}', Some('\u{1D16F}')),
+    ('\u{1D1C0}', '\u{1D1BC}', Some('\u{1D16F}')), ('\u{2F800}', '\u{4E3D}', None), ('\u{2F801}', '\u{4E38}', None),
+    ('\u{2F802}', '\u{4E41}', None), ('\u{2F803}', '\u{20122}', None), ('\u{2F804}', '\u{4F60}', None),
+    ('\u{2F805}', '\u{4FAE}', None), ('\u{2F806}', '\u{4FBB}', None), ('\u{2F807}', '\u{5002}', None),
+    ('\u{2F808}', '\u{507A}', None), ('\u{2F809}', '\u{5099}', None), ('\u{2F80A}', '\u{50E7}', None),
+    ('\u{2F80B}', '\u{50CF}', None), ('\u{2F80C}', '\u{349E}', None), ('\u{2F80D}', '\u{2063A}', None),
+    ('\u{2F80E}', '\u{514D}', None), ('\u{2F80F}', '\u{5154}', None), ('\u{2F810}', '\u{5164}', None),
+    ('\u{2F811}', '\u{5177}', None), ('\u{2F812}', '\u{2051C}', None), ('\u{2F813}', '\u{34B9}', None),
+    ('\u{2F814}', '\u{5167}', None), ('\u{2F815}', '\u{518D}', None), ('\u{2F816}', '\u{2054B}', None),
+    ('\u{2F817}', '\u{5197}', None), ('\u{2F818}', '\u{51A4}', None), ('\u{2F819}', '\u{4ECC}', None),
+    ('\u{2F81A}', '\u{51AC}', None), ('\u{2F81B}', '\u{51B5}', None), ('\u{2F81C}', '\u{291DF}', None),
+    ('\u{2F81D}', '\u{51F5}', None), ('\u{2F81E}', '\u{5203}', None), ('\u{2F81F}', '\u{34DF}', None),
+    ('\u{2F820}', '\u{523B}', None), ('\u{2F821}', '\u{5246}', None), ('\u{2F822}', '\u{5272}', None),
+    ('\u{2F823}', '\u{5277}', None), ('\u{2F824}', '\u{3515}', None), ('\u{2F825}', '\u{52C7}', None),
+    ('\u{2F826}', '\u{52C9}', None), ('\u{2F827}', '\u{52E4}', None), ('\u{2F828}', '\u{52FA}', None),
+    ('\u{2F829}', '\u{5305}', None), ('\u{2F82A}', '\u{5306}', None), ('\u{2F82B}', '\u{5317}', None),
+    ('\u{2F82C}', '\u{5349}', None), ('\u{2F82D}', '\u{5351}', None), ('\u{2F82E}', '\u{535A}', None),
+    ('\u{2F82F}', '\u{5373}', None), ('\u{2F830}', '\u{537D}', None), ('\u{2F831}', '\u{537F}', None),
+    ('\u{2F832}', '\u{537F}', None), ('\u{2F833}', '\u{537F}', None), ('\u{2F834}', '\u{20A2C}', None),
+    ('\u{2F835}', '\u{7070}', None), ('\u{2F836}', '\u{53CA}', None), ('\u{2F837}', '\u{53DF}', None),
+    ('\u{2F838}', '\u{20B63}', None), ('\u{2F839}', '\u{53EB}', None), ('\u{2F83A}', '\u{53F1}', None),
+    ('\u{2F83B}', '\u{5406}', None), ('\u{2F83C}', '\u{549E}', None), ('\u{2F83D}', '\u{5438}', None),
+    ('\u{2F83E}', '\u{5448}', None), ('\u{2F83F}', '\u{5468}', None), ('\u{2F840}', '\u{54A2}', None),
+    ('\u{2F841}', '\u{54F6}', None), ('\u{2F842}', '\u{5510}', None), ('\u{2F843}', '\u{5553}', None),
+    ('\u{2F844}', '\u{5563}', None), ('\u{2F845}', '\u{5584}', None), ('\u{2F846}', '\u{5584}', None),
+    ('\u{2F847}', '\u{5599}', None), ('\u{2F848}', '\u{55AB}', None), ('\u{2F849}', '\u{55B3}', None),
+    ('\u{2F84A}', '\u{55C2}', None), ('\u{2F84B}', '\u{5716}', None), ('\u{2F84C}', '\u{5606}', None),
+    ('\u{2F84D}', '\u{5717}', None), ('\u{2F84E}', '\u{5651}', None), ('\u{2F84F}', '\u{5674}', None),
+    ('\u{2F850}', '\u{5207}', None), ('\u{2F851}', '\u{58EE}', None), ('\u{2F852}', '\u{57CE}', None),
+    ('\u{2F853}', '\u{57F4}', None), ('\u{2F854}', '\u{580D}', None), ('\u{2F855}', '\u{578B}', None),
+    ('\u{2F856}', '\u{5832}', None), ('\u{2F857}', '\u{5831}', None), ('\u{2F858}', '\u{58AC}', None),
+    ('\u{2F859}', '\u{214E4}', None), ('\u{2F85A}', '\u{58F2}', None), ('\u{2F85B}', '\u{58F7}', None),
+    ('\u{2F85C}', '\u{5906}', None), ('\u{2F85D}', '\u{591A}', None), ('\u{2F85E}', '\u{5922}', None),
+    ('\u{2F85F}', '\u{5962}', None), ('\u{2F860}', '\u{216A8}', None), ('\u{2F861}', '\u{216EA}', None),
+    ('\u{2F862}', '\u{59EC}', None), ('\u{2F863}', '\u{5A1B}', None), ('\u{2F864}', '\u{5A27}', None),
+    ('\u{2F865}', '\u{59D8}', None), ('\u{2F866}', '\u{5A66}', None), ('\u{2F867}', '\u{36EE}', None),
+    ('\u{2F868}', '\u{36FC}', None), ('\u{2F869}', '\u{5B08}', None), ('\u{2F86A}', '\u{5B3E}', None),
+    ('\u{2F86B}', '\u{5B3E}', None), ('\u{2F86C}', '\u{219C8}', None), ('\u{2F86D}', '\u{5BC3}', None),
+    ('\u{2F86E}', '\u{5BD8}', None), ('\u{2F86F}', '\u{5BE7}', None), ('\u{2F870}', '\u{5BF3}', None),
+    ('\u{2F871}', '\u{21B18}', None), ('\u{2F872}', '\u{5BFF}', None), ('\u{2F873}', '\u{5C06}', None),
+    ('\u{2F874}', '\u{5F53}', None), ('\u{2F875}', '\u{5C22}', None), ('\u{2F876}', '\u{3781}', None),
+    ('\u{2F877}', '\u{5C60}', None), ('\u{2F878}', '\u{5C6E}', None), ('\u{2F879}', '\u{5CC0}', None),
+    ('\u{2F87A}', '\u{5C8D}', None), ('\u{2F87B}', '\u{21DE4}', None), ('\u{2F87C}', '\u{5D43}', None),
+    ('\u{2F87D}', '\u{21DE6}', None), ('\u{2F87E}', '\u{5D6E}', None), ('\u{2F87F}', '\u{5D6B}', None),
+    ('\u{2F880}', '\u{5D7C}', None), ('\u{2F881}', '\u{5DE1}', None), ('\u{2F882}', '\u{5DE2}', None),
+    ('\u{2F883}', '\u{382F}', None), ('\u{2F884}', '\u{5DFD}', None), ('\u{2F885}', '\u{5E28}', None),
+    ('\u{2F886}', '\u{5E3D}', None), ('\u{2F887}', '\u{5E69}', None), ('\u{2F888}', '\u{3862}', None),
+    ('\u{2F889}', '\u{22183}', None), ('\u{2F88A}', '\u{387C}', None), ('\u{2F88B}', '\u{5EB0}', None),
+    ('\u{2F88C}', '\u{5EB3}', None), ('\u{2F88D}', '\u{5EB6}', None), ('\u{2F88E}', '\u{5ECA}', None),
+    ('\u{2F88F}', '\u{2A392}', None), ('\u{2F890}', '\u{5EFE}', None), ('\u{2F891}', '\u{22331}', None),
+    ('\u{2F892}', '\u{22331}', None), ('\u{2F893}', '\u{8201}', None), ('\u{2F894}', '\u{5F22}', None),
+    ('\u{2F895}', '\u{5F22}', None), ('\u{2F896}', '\u{38C7}', None), ('\u{2F897}', '\u{232B8}', None),
+    ('\u{2F898}', '\u{261DA}', None), ('\u{2F899}', '\u{5F62}', None), ('\u{2F89A}', '\u{5F6B}', None),
+    ('\u{2F89B}', '\u{38E3}', None), ('\u{2F89C}', '\u{5F9A}', None), ('\u{2F89D}', '\u{5FCD}', None),
+    ('\u{2F89E}', '\u{5FD7}', None), ('\u{2F89F}', '\u{5FF9}', None), ('\u{2F8A0}', '\u{6081}', None),
+    ('\u{2F8A1}', '\u{393A}', None), ('\u{2F8A2}', '\u{391C}', None), ('\u{2F8A3}', '\u{6094}', None),
+    ('\u{2F8A4}', '\u{226D4}', None), ('\u{2F8A5}', '\u{60C7}', None), ('\u{2F8A6}', '\u{6148}', None),
+    ('\u{2F8A7}', '\u{614C}', None), ('\u{2F8A8}', '\u{614E}', None), ('\u{2F8A9}', '\u{614C}', None),
+    ('\u{2F8AA}', '\u{617A}', None), ('\u{2F8AB}', '\u{618E}', None), ('\u{2F8AC}', '\u{61B2}', None),
+    ('\u{2F8AD}', '\u{61A4}', None), ('\u{2F8AE}', '\u{61AF}', None), ('\u{2F8AF}', '\u{61DE}', None),
+    ('\u{2F8B0}', '\u{61F2}', None), ('\u{2F8B1}', '\u{61F6}', None), ('\u{2F8B2}', '\u{6210}', None),
+    ('\u{2F8B3}', '\u{621B}', None), ('\u{2F8B4}', '\u{625D}', None), ('\u{2F8B5}', '\u{62B1}', None),
+    ('\u{2F8B6}', '\u{62D4}', None), ('\u{2F8B7}', '\u{6350}', None), ('\u{2F8B8}', '\u{22B0C}', None),
+    ('\u{2F8B9}', '\u{633D}', None), ('\u{2F8BA}', '\u{62FC}', None), ('\u{2F8BB}', '\u{6368}', None),
+    ('\u{2F8BC}', '\u{6383}', None), ('\u{2F8BD}', '\u{63E4}', None), ('\u{2F8BE}', '\u{22BF1}', None),
+    ('\u{2F8BF}', '\u{6422}', None), ('\u{2F8C0}', '\u{63C5}', None), ('\u{2F8C1}', '\u{63A9}', None),
+    ('\u{2F8C2}', '\u{3A2E}', None), ('\u{2F8C3}', '\u{6469}', None), ('\u{2F8C4}', '\u{647E}', None),
+    ('\u{2F8C5}', '\u{649D}', None), ('\u{2F8C6}', '\u{6477}', None), ('\u{2F8C7}', '\u{3A6C}', None),
+    ('\u{2F8C8}', '\u{654F}', None), ('\u{2F8C9}', '\u{656C}', None), ('\u{2F8CA}', '\u{2300A}', None),
+    ('\u{2F8CB}', '\u{65E3}', None), ('\u{2F8CC}', '\u{66F8}', None), ('\u{2F8CD}', '\u{6649}', None),
+    ('\u{2F8CE}', '\u{3B19}', None), ('\u{2F8CF}', '\u{6691}', None), ('\u{2F8D0}', '\u{3B08}', None),
+    ('\u{2F8D1}', '\u{3AE4}', None), ('\u{2F8D2}', '\u{5192}', None), ('\u{2F8D3}', '\u{5195}', None),
+    ('\u{2F8D4}', '\u{6700}', None), ('\u{2F8D5}', '\u{669C}', None), ('\u{2F8D6}', '\u{80AD}', None),
+    ('\u{2F8D7}', '\u{43D9}', None), ('\u{2F8D8}', '\u{6717}', None), ('\u{2F8D9}', '\u{671B}', None),
+    ('\u{2F8DA}', '\u{6721}', None), ('\u{2F8DB}', '\u{675E}', None), ('\u{2F8DC}', '\u{6753}', None),
+    ('\u{2F8DD}', '\u{233C3}', None), ('\u{2F8DE}', '\u{3B49}', None), ('\u{2F8DF}', '\u{67FA}', None),
+    ('\u{2F8E0}', '\u{6785}', None), ('\u{2F8E1}', '\u{6852}', None), ('\u{2F8E2}', '\u{6885}', None),
+    ('\u{2F8E3}', '\u{2346D}', None), ('\u{2F8E4}', '\u{688E}', None), ('\u{2F8E5}', '\u{681F}', None),
+    ('\u{2F8E6}', '\u{6914}', None), ('\u{2F8E7}', '\u{3B9D}', None), ('\u{2F8E8}', '\u{6942}', None),
+    ('\u{2F8E9}', '\u{69A3}', None), ('\u{2F8EA}', '\u{69EA}', None), ('\u{2F8EB}', '\u{6AA8}', None),
+    ('\u{2F8EC}', '\u{236A3}', None), ('\u{2F8ED}', '\u{6ADB}', None), ('\u{2F8EE}', '\u{3C18}', None),
+    ('\u{2F8EF}', '\u{6B21}', None), ('\u{2F8F0}', '\u{238A7}', None), ('\u{2F8F1}', '\u{6B54}', None),
+    ('\u{2F8F2}', '\u{3C4E}', None), ('\u{2F8F3}', '\u{6B72}', None), ('\u{2F8F4}', '\u{6B9F}', None),
+    ('\u{2F8F5}', '\u{6BBA}', None), ('\u{2F8F6}', '\u{6BBB}', None), ('\u{2F8F7}', '\u{23A8D}', None),
+    ('\u{2F8F8}', '\u{21D0B}', None), ('\u{2F8F9}', '\u{23AFA}', None), ('\u{2F8FA}', '\u{6C4E}', None),
+    ('\u{2F8FB}', '\u{23CBC}', None), ('\u{2F8FC}', '\u{6CBF}', None), ('\u{2F8FD}', '\u{6CCD}', None),
+    ('\u{2F8FE}', '\u{6C67}', None), ('\u{2F8FF}', '\u{6D16}', None), ('\u{2F900}', '\u{6D3E}', None),
+    ('\u{2F901}', '\u{6D77}', None), ('\u{2F902}', '\u{6D41}', None), ('\u{2F903}', '\u{6D69}', None),
+    ('\u{2F904}', '\u{6D78}', None), ('\u{2F905}', '\u{6D85}', None), ('\u{2F906}', '\u{23D1E}', None),
+    ('\u{2F907}', '\u{6D34}', None), ('\u{2F908}', '\u{6E2F}', None), ('\u{2F909}', '\u{6E6E}', None),
+    ('\u{2F90A}', '\u{3D33}', None), ('\u{2F90B}', '\u{6ECB}', None), ('\u{2F90C}', '\u{6EC7}', None),
+    ('\u{2F90D}', '\u{23ED1}', None), ('\u{2F90E}', '\u{6DF9}', None), ('\u{2F90F}', '\u{6F6E}', None),
+    ('\u{2F910}', '\u{23F5E}', None), ('\u{2F911}', '\u{23F8E}', None), ('\u{2F912}', '\u{6FC6}', None),
+    ('\u{2F913}', '\u{7039}', None), ('\u{2F914}', '\u{701E}', None), ('\u{2F915}', '\u{701B}', None),
+    ('\u{2F916}', '\u{3D96}', None), ('\u{2F917}', '\u{704A}', None), ('\u{2F918}', '\u{707D}', None),
+    ('\u{2F919}', '\u{7077}', None), ('\u{2F91A}', '\u{70AD}', None), ('\u{2F91B}', '\u{20525}', None),
+    ('\u{2F91C}', '\u{7145}', None), ('\u{2F91D}', '\u{24263}', None), ('\u{2F91E}', '\u{719C}', None),
+    ('\u{2F91F}', '\u{243AB}', None), ('\u{2F920}', '\u{7228}', None), ('\u{2F921}', '\u{7235}', None),
+    ('\u{2F922}', '\u{7250}', None), ('\u{2F923}', '\u{24608}', None), ('\u{2F924}', '\u{7280}', None),
+    ('\u{2F925}', '\u{7295}', None), ('\u{2F926}', '\u{24735}', None), ('\u{2F927}', '\u{24814}', None),
+    ('\u{2F928}', '\u{737A}', None), ('\u{2F929}', '\u{738B}', None), ('\u{2F92A}', '\u{3EAC}', None),
+    ('\u{2F92B}', '\u{73A5}', None), ('\u{2F92C}', '\u{3EB8}', None), ('\u{2F92D}', '\u{3EB8}', None),
+    ('\u{2F92E}', '\u{7447}', None), ('\u{2F92F}', '\u{745C}', None), ('\u{2F930}', '\u{7471}', None),
+    ('\u{2F931}', '\u{7485}', None), ('\u{2F932}', '\u{74CA}', None), ('\u{2F933}', '\u{3F1B}', None),
+    ('\u{2F934}', '\u{7524}', None), ('\u{2F935}', '\u{24C36}', None), ('\u{2F936}', '\u{753E}', None),
+    ('\u{2F937}', '\u{24C92}', None), ('\u{2F938}', '\u{7570}', None), ('\u{2F939}', '\u{2219F}', None),
+    ('\u{2F93A}', '\u{7610}', None), ('\u{2F93B}', '\u{24FA1}', None), ('\u{2F93C}', '\u{24FB8}', None),
+    ('\u{2F93D}', '\u{25044}', None), ('\u{2F93E}', '\u{3FFC}', None), ('\u{2F93F}', '\u{4008}', None),
+    ('\u{2F940}', '\u{76F4}', None), ('\u{2F941}', '\u{250F3}', None), ('\u{2F942}', '\u{250F2}', None),
+    ('\u{2F943}', '\u{25119}', None), ('\u{2F944}', '\u{25133}', None), ('\u{2F945}', '\u{771E}', None),
+    ('\u{2F946}', '\u{771F}', None), ('\u{2F947}', '\u{771F}', None), ('\u{2F948}', '\u{774A}', None),
+    ('\u{2F949}', '\u{4039}', None), ('\u{2F94A}', '\u{778B}', None), ('\u{2F94B}', '\u{4046}', None),
+    ('\u{2F94C}', '\u{4096}', None), ('\u{2F94D}', '\u{2541D}', None), ('\u{2F94E}', '\u{784E}', None),
+    ('\u{2F94F}', '\u{788C}', None), ('\u{2F950}', '\u{78CC}', None), ('\u{2F951}', '\u{40E3}', None),
+    ('\u{2F952}', '\u{25626}', None), ('\u{2F953}', '\u{7956}', None), ('\u{2F954}', '\u{2569A}', None),
+    ('\u{2F955}', '\u{256C5}', None), ('\u{2F956}', '\u{798F}', None), ('\u{2F957}', '\u{79EB}', None),
+    ('\u{2F958}', '\u{412F}', None), ('\u{2F959}', '\u{7A40}', None), ('\u{2F95A}', '\u{7A4A}', None),
+    ('\u{2F95B}', '\u{7A4F}', None), ('\u{2F95C}', '\u{2597C}', None), ('\u{2F95D}', '\u{25AA7}', None),
+    ('\u{2F95E}', '\u{25AA7}', None), ('\u{2F95F}', '\u{7AEE}', None), ('\u{2F960}', '\u{4202}', None),
+    ('\u{2F961}', '\u{25BAB}', None), ('\u{2F962}', '\u{7BC6}', None), ('\u{2F963}', '\u{7BC9}', None),
+    ('\u{2F964}', '\u{4227}', None), ('\u{2F965}', '\u{25C80}', None), ('\u{2F966}', '\u{7CD2}', None),
+    ('\u{2F967}', '\u{42A0}', None), ('\u{2F968}', '\u{7CE8}', None), ('\u{2F969}', '\u{7CE3}', None),
+    ('\u{2F96A}', '\u{7D00}', None), ('\u{2F96B}', '\u{25F86}', None), ('\u{2F96C}', '\u{7D63}', None),
+    ('\u{2F96D}', '\u{4301}', None), ('\u{2F96E}', '\u{7DC7}', None), ('\u{2F96F}', '\u{7E02}', None),
+    ('\u{2F970}', '\u{7E45}', None), ('\u{2F971}', '\u{4334}', None), ('\u{2F972}', '\u{26228}', None),
+    ('\u{2F973}', '\u{26247}', None), ('\u{2F974}', '\u{4359}', None), ('\u{2F975}', '\u{262D9}', None),
+    ('\u{2F976}', '\u{7F7A}', None), ('\u{2F977}', '\u{2633E}', None), ('\u{2F978}', '\u{7F95}', None),
+    ('\u{2F979}', '\u{7FFA}', None), ('\u{2F97A}', '\u{8005}', None), ('\u{2F97B}', '\u{264DA}', None),
+    ('\u{2F97C}', '\u{26523}', None), ('\u{2F97D}', '\u{8060}', None), ('\u{2F97E}', '\u{265A8}', None),
+    ('\u{2F97F}', '\u{8070}', None), ('\u{2F980}', '\u{2335F}', None), ('\u{2F981}', '\u{43D5}', None),
+    ('\u{2F982}', '\u{80B2}', None), ('\u{2F983}', '\u{8103}', None), ('\u{2F984}', '\u{440B}', None),
+    ('\u{2F985}', '\u{813E}', None), ('\u{2F986}', '\u{5AB5}', None), ('\u{2F987}', '\u{267A7}', None),
+    ('\u{2F988}', '\u{267B5}', None), ('\u{2F989}', '\u{23393}', None), ('\u{2F98A}', '\u{2339C}', None),
+    ('\u{2F98B}', '\u{8201}', None), ('\u{2F98C}', '\u{8204}', None), ('\u{2F98D}', '\u{8F9E}', None),
+    ('\u{2F98E}', '\u{446B}', None), ('\u{2F98F}', '\u{8291}', None), ('\u{2F990}', '\u{828B}', None),
+    ('\u{2F991}', '\u{829D}', None), ('\u{2F992}', '\u{52B3}', None), ('\u{2F993}', '\u{82B1}', None),
+    ('\u{2F994}', '\u{82B3}', None), ('\u{2F995}', '\u{82BD}', None), ('\u{2F996}', '\u{82E6}', None),
+    ('\u{2F997}', '\u{26B3C}', None), ('\u{2F998}', '\u{82E5}', None), ('\u{2F999}', '\u{831D}', None),
+    ('\u{2F99A}', '\u{8363}', None), ('\u{2F99B}', '\u{83AD}', None), ('\u{2F99C}', '\u{8323}', None),
+    ('\u{2F99D}', '\u{83BD}', None), ('\u{2F99E}', '\u{83E7}', None), ('\u{2F99F}', '\u{8457}', None),
+    ('\u{2F9A0}', '\u{8353}', None), ('\u{2F9A1}', '\u{83CA}', None), ('\u{2F9A2}', '\u{83CC}', None),
+    ('\u{2F9A3}', '\u{83DC}', None), ('\u{2F9A4}', '\u{26C36}', None), ('\u{2F9A5}', '\u{26D6B}', None),
+    ('\u{2F9A6}', '\u{26CD5}', None), ('\u{2F9A7}', '\u{452B}', None), ('\u{2F9A8}', '\u{84F1}', None),
+    ('\u{2F9A9}', '\u{84F3}', None), ('\u{2F9AA}', '\u{8516}', None), ('\u{2F9AB}', '\u{273CA}', None),
+    ('\u{2F9AC}', '\u{8564}', None), ('\u{2F9AD}', '\u{26F2C}', None), ('\u{2F9AE}', '\u{455D}', None),
+    ('\u{2F9AF}', '\u{4561}', None), ('\u{2F9B0}', '\u{26FB1}', None), ('\u{2F9B1}', '\u{270D2}', None),
+    ('\u{2F9B2}', '\u{456B}', None), ('\u{2F9B3}', '\u{8650}', None), ('\u{2F9B4}', '\u{865C}', None),
+    ('\u{2F9B5}', '\u{8667}', None), ('\u{2F9B6}', '\u{8669}', None), ('\u{2F9B7}', '\u{86A9}', None),
+    ('\u{2F9B8}', '\u{8688}', None), ('\u{2F9B9}', '\u{870E}', None), ('\u{2F9BA}', '\u{86E2}', None),
+    ('\u{2F9BB}', '\u{8779}', None), ('\u{2F9BC}', '\u{8728}', None), ('\u{2F9BD}', '\u{876B}', None),
+    ('\u{2F9BE}', '\u{8786}', None), ('\u{2F9BF}', '\u{45D7}', None), ('\u{2F9C0}', '\u{87E1}', None),
+    ('\u{2F9C1}', '\u{8801}', None), ('\u{2F9C2}', '\u{45F9}', None), ('\u{2F9C3}', '\u{8860}', None),
+    ('\u{2F9C4}', '\u{8863}', None), ('\u{2F9C5}', '\u{27667}', None), ('\u{2F9C6}', '\u{88D7}', None),
+    ('\u{2F9C7}', '\u{88DE}', None), ('\u{2F9C8}', '\u{4635}', None), ('\u{2F9C9}', '\u{88FA}', None),
+    ('\u{2F9CA}', '\u{34BB}', None), ('\u{2F9CB}', '\u{278AE}', None), ('\u{2F9CC}', '\u{27966}', None),
+    ('\u{2F9CD}', '\u{46BE}', None), ('\u{2F9CE}', '\u{46C7}', None), ('\u{2F9CF}', '\u{8AA0}', None),
+    ('\u{2F9D0}', '\u{8AED}', None), ('\u{2F9D1}', '\u{8B8A}', None), ('\u{2F9D2}', '\u{8C55}', None),
+    ('\u{2F9D3}', '\u{27CA8}', None), ('\u{2F9D4}', '\u{8CAB}', None), ('\u{2F9D5}', '\u{8CC1}', None),
+    ('\u{2F9D6}', '\u{8D1B}', None), ('\u{2F9D7}', '\u{8D77}', None), ('\u{2F9D8}', '\u{27F2F}', None),
+    ('\u{2F9D9}', '\u{20804}', None), ('\u{2F9DA}', '\u{8DCB}', None), ('\u{2F9DB}', '\u{8DBC}', None),
+    ('\u{2F9DC}', '\u{8DF0}', None), ('\u{2F9DD}', '\u{208DE}', None), ('\u{2F9DE}', '\u{8ED4}', None),
+    ('\u{2F9DF}', '\u{8F38}', None), ('\u{2F9E0}', '\u{285D2}', None), ('\u{2F9E1}', '\u{285ED}', None),
+    ('\u{2F9E2}', '\u{9094}', None), ('\u{2F9E3}', '\u{90F1}', None), ('\u{2F9E4}', '\u{9111}', None),
+    ('\u{2F9E5}', '\u{2872E}', None), ('\u{2F9E6}', '\u{911B}', None), ('\u{2F9E7}', '\u{9238}', None),
+    ('\u{2F9E8}', '\u{92D7}', None), ('\u{2F9E9}', '\u{92D8}', None), ('\u{2F9EA}', '\u{927C}', None),
+    ('\u{2F9EB}', '\u{93F9}', None), ('\u{2F9EC}', '\u{9415}', None), ('\u{2F9ED}', '\u{28BFA}', None),
+    ('\u{2F9EE}', '\u{958B}', None), ('\u{2F9EF}', '\u{4995}', None), ('\u{2F9F0}', '\u{95B7}', None),
+    ('\u{2F9F1}', '\u{28D77}', None), ('\u{2F9F2}', '\u{49E6}', None), ('\u{2F9F3}', '\u{96C3}', None),
+    ('\u{2F9F4}', '\u{5DB2}', None), ('\u{2F9F5}', '\u{9723}', None), ('\u{2F9F6}', '\u{29145}', None),
+    ('\u{2F9F7}', '\u{2921A}', None), ('\u{2F9F8}', '\u{4A6E}', None), ('\u{2F9F9}', '\u{4A76}', None),
+    ('\u{2F9FA}', '\u{97E0}', None), ('\u{2F9FB}', '\u{2940A}', None), ('\u{2F9FC}', '\u{4AB2}', None),
+    ('\u{2F9FD}', '\u{29496}', None), ('\u{2F9FE}', '\u{980B}', None), ('\u{2F9FF}', '\u{980B}', None),
+    ('\u{2FA00}', '\u{9829}', None), ('\u{2FA01}', '\u{295B6}', None), ('\u{2FA02}', '\u{98E2}', None),
+    ('\u{2FA03}', '\u{4B33}', None), ('\u{2FA04}', '\u{9929}', None), ('\u{2FA05}', '\u{99A7}', None),
+    ('\u{2FA06}', '\u{99C2}', None), ('\u{2FA07}', '\u{99FE}', None), ('\u{2FA08}', '\u{4BCE}', None),
+    ('\u{2FA09}', '\u{29B30}', None), ('\u{2FA0A}', '\u{9B12}', None), ('\u{2FA0B}', '\u{9C40}', None),
+    ('\u{2FA0C}', '\u{9CFD}', None), ('\u{2FA0D}', '\u{4CCE}', None), ('\u{2FA0E}', '\u{4CED}', None),
+    ('\u{2FA0F}', '\u{9D67}', None), ('\u{2FA10}', '\u{2A0CE}', None), ('\u{2FA11}', '\u{4CF8}', None),
+    ('\u{2FA12}', '\u{2A105}', None), ('\u{2FA13}', '\u{2A20E}', None), ('\u{2FA14}', '\u{2A291}', None),
+    ('\u{2FA15}', '\u{9EBB}', None), ('\u{2FA16}', '\u{4D56}', None), ('\u{2FA17}', '\u{9EF9}', None),
+    ('\u{2FA18}', '\u{9EFE}', None), ('\u{2FA19}', '\u{9F05}', None), ('\u{2FA1A}', '\u{9F0F}', None),
+    ('\u{2FA1B}', '\u{9F16}', None), ('\u{2FA1C}', '\u{9F3B}', None), ('\u{2FA1D}', '\u{2A600}', None),
+];
+
+/// Primary composites, sorted by the pair they compose from.
+pub(crate) const COMPOSITIONS: &[(char, char, char)] = &[
+    ('\u{3C}', '\u{338}', '\u{226E}'), ('\u{3D}', '\u{338}', '\u{2260}'), ('\u{3E}', '\u{338}', '\u{226F}'), ('\u{41}', '\u{300}', '\u{C0}'),
+    ('\u{41}', '\u{301}', '\u{C1}'), ('\u{41}', '\u{302}', '\u{C2}'), ('\u{41}', '\u{303}', '\u{C3}'), ('\u{41}', '\u{304}', '\u{100}'),
+    ('\u{41}', '\u{306}', '\u{102}'), ('\u{41}', '\u{307}', '\u{226}'), ('\u{41}', '\u{308}', '\u{C4}'), ('\u{41}', '\u{309}', '\u{1EA2}'),
+    ('\u{41}', '\u{30A}', '\u{C5}'), ('\u{41}', '\u{30C}', '\u{1CD}'), ('\u{41}', '\u{30F}', '\u{200}'), ('\u{41}', '\u{311}', '\u{202}'),
+    ('\u{41}', '\u{323}', '\u{1EA0}'), ('\u{41}', '\u{325}', '\u{1E00}'), ('\u{41}', '\u{328}', '\u{104}'), ('\u{42}', '\u{307}', '\u{1E02}'),
+    ('\u{42}', '\u{323}', '\u{1E04}'), ('\u{42}', '\u{331}', '\u{1E06}'), ('\u{43}', '\u{301}', '\u{106}'), ('\u{43}', '\u{302}', '\u{108}'),
+    ('\u{43}', '\u{307}', '\u{10A}'), ('\u{43}', '\u{30C}', '\u{10C}'), ('\u{43}', '\u{327}', '\u{C7}'), ('\u{44}', '\u{307}', '\u{1E0A}'),
+    ('\u{44}', '\u{30C}', '\u{10E}'), ('\u{44}', '\u{323}', '\u{1E0C}'), ('\u{44}', '\u{327}', '\u{1E10}'), ('\u{44}', '\u{32D}', '\u{1E12}'),
+    ('\u{44}', '\u{331}', '\u{1E0E}'), ('\u{45}', '\u{300}', '\u{C8}'), ('\u{45}', '\u{301}', '\u{C9}'), ('\u{45}', '\u{302}', '\u{CA}'),
+    ('\u{45}', '\u{303}', '\u{1EBC}'), ('\u{45}', '\u{304}', '\u{112}'), ('\u{45}', '\u{306}', '\u{114}'), ('\u{45}', '\u{307}', '\u{116}'),
+    ('\u{45}', '\u{308}', '\u{CB}'), ('\u{45}', '\u{309}', '\u{1EBA}'), ('\u{45}', '\u{30C}', '\u{11A}'), ('\u{45}', '\u{30F}', '\u{204}'),
+    ('\u{45}', '\u{311}', '\u{206}'), ('\u{45}', '\u{323}', '\u{1EB8}'), ('\u{45}', '\u{327}', '\u{228}'), ('\u{45}', '\u{328}', '\u{118}'),
+    ('\u{45}', '\u{32D}', '\u{1E18}'), ('\u{45}', '\u{330}', '\u{1E1A}'), ('\u{46}', '\u{307}', '\u{1E1E}'), ('\u{47}', '\u{301}', '\u{1F4}'),
+    ('\u{47}', '\u{302}', '\u{11C}'), ('\u{47}', '\u{304}', '\u{1E20}'), ('\u{47}', '\u{306}', '\u{11E}'), ('\u{47}', '\u{307}', '\u{120}'),
+    ('\u{47}', '\u{30C}', '\u{1E6}'), ('\u{47}', '\u{327}', '\u{122}'), ('\u{48}', '\u{302}', '\u{124}'), ('\u{48}', '\u{307}', '\u{1E22}'),
+    ('\u{48}', '\u{308}', '\u{1E26}'), ('\u{48}', '\u{30C}', '\u{21E}'), ('\u{48}', '\u{323}', '\u{1E24}'), ('\u{48}', '\u{327}', '\u{1E28}'),
+    ('\u{48}', '\u{32E}', '\u{1E2A}'), ('\u{49}', '\u{300}', '\u{CC}'), ('\u{49}', '\u{301}', '\u{CD}'), ('\u{49}', '\u{302}', '\u{CE}'),
+    ('\u{49}', '\u{303}', '\u{128}'), ('\u{49}', '\u{304}', '\u{12A}'), ('\u{49}', '\u{306}', '\u{12C}'), ('\u{49}', '\u{307}', '\u{130}'),
+    ('\u{49}', '\u{308}', '\u{CF}'), ('\u{49}', '\u{309}', '\u{1EC8}'), ('\u{49}', '\u{30C}', '\u{1CF}'), ('\u{49}', '\u{30F}', '\u{208}'),
+    ('\u{49}', '\u{311}', '\u{20A}'), ('\u{49}', '\u{323}', '\u{1ECA}'), ('\u{49}', '\u{328}', '\u{12E}'), ('\u{49}', '\u{330}', '\u{1E2C}'),
+    ('\u{4A}', '\u{302}', '\u{134}'), ('\u{4B}', '\u{301}', '\u{1E30}'), ('\u{4B}', '\u{30C}', '\u{1E8}'), ('\u{4B}', '\u{323}', '\u{1E32}'),
+    ('\u{4B}', '\u{327}', '\u{136}'), ('\u{4B}', '\u{331}', '\u{1E34}'), ('\u{4C}', '\u{301}', '\u{139}'), ('\u{4C}', '\u{30C}', '\u{13D}'),
+    ('\u{4C}', '\u{323}', '\u{1E36}'), ('\u{4C}', '\u{327}', '\u{13B}'), ('\u{4C}', '\u{32D}', '\u{1E3C}'), ('\u{4C}', '\u{331}', '\u{1E3A}'),
+    ('\u{4D}', '\u{301}', '\u{1E3E}'), ('\u{4D}', '\u{307}', '\u{1E40}'), ('\u{4D}', '\u{323}', '\u{1E42}'), ('\u{4E}', '\u{300}', '\u{1F8}'),
+    ('\u{4E}', '\u{301}', '\u{143}'), ('\u{4E}', '\u{303}', '\u{D1}'), ('\u{4E}', '\u{307}', '\u{1E44}'), ('\u{4E}', '\u{30C}', '\u{147}'),
+    ('\u{4E}', '\u{323}', '\u{1E46}'), ('\u{4E}', '\u{327}', '\u{145}'), ('\u{4E}', '\u{32D}', '\u{1E4A}'), ('\u{4E}', '\u{331}', '\u{1E48}'),
+    ('\u{4F}', '\u{300}', '\u{D2}'), ('\u{4F}', '\u{301}', '\u{D3}'), ('\u{4F}', '\u{302}', '\u{D4}'), ('\u{4F}', '\u{303}', '\u{D5}'),
+    ('\u{4F}', '\u{304}', '\u{14C}'), ('\u{4F}', '\u{306}', '\u{14E}'), ('\u{4F}', '\u{307}', '\u{22E}'), ('\u{4F}', '\u{308}', '\u{D6}'),
+    ('\u{4F}', '\u{309}', '\u{1ECE}'), ('\u{4F}', '\u{30B}', '\u{150}'), ('\u{4F}', '\u{30C}', '\u{1D1}'), ('\u{4F}', '\u{30F}', '\u{20C}'),
+    ('\u{4F}', '\u{311}', '\u{20E}'), ('\u{4F}', '\u{31B}', '\u{1A0}'), ('\u{4F}', '\u{323}', '\u{1ECC}'), ('\u{4F}', '\u{328}', '\u{1EA}'),
+    ('\u{50}', '\u{301}', '\u{1E54}'), ('\u{50}', '\u{307}', '\u{1E56}'), ('\u{52}', '\u{301}', '\u{154}'), ('\u{52}', '\u{307}', '\u{1E58}'),
+    ('\u{52}', '\u{30C}', '\u{158}'), ('\u{52}', '\u{30F}', '\u{210}'), ('\u{52}', '\u{311}', '\u{212}'), ('\u{52}', '\u{323}', '\u{1E5A}'),
+    ('\u{52}', '\u{327}', '\u{156}'), ('\u{52}', '\u{331}', '\u{1E5E}'), ('\u{53}', '\u{301}', '\u{15A}'), ('\u{53}', '\u{302}', '\u{15C}'),
+    ('\u{53}', '\u{307}', '\u{1E60}'), ('\u{53}', '\u{30C}', '\u{160}'), ('\u{53}', '\u{323}', '\u{1E62}'), ('\u{53}', '\u{326}', '\u{218}'),
+    ('\u{53}', '\u{327}', '\u{15E}'), ('\u{54}', '\u{307}', '\u{1E6A}'), ('\u{54}', '\u{30C}', '\u{164}'), ('\u{54}', '\u{323}', '\u{1E6C}'),
+    ('\u{54}', '\u{326}', '\u{21A}'), ('\u{54}', '\u{327}', '\u{162}'), ('\u{54}', '\u{32D}', '\u{1E70}'), ('\u{54}', '\u{331}', '\u{1E6E}'),
+    ('\u{55}', '\u{300}', '\u{D9}'), ('\u{55}', '\u{301}', '\u{DA}'), ('\u{55}', '\u{302}', '\u{DB}'), ('\u{55}', '\u{303}', '\u{168}'),
+    ('\u{55}', '\u{304}', '\u{16A}'), ('\u{55}', '\u{306}', '\u{16C}'), ('\u{55}', '\u{308}', '\u{DC}'), ('\u{55}', '\u{309}', '\u{1EE6}'),
+    ('\u{55}', '\u{30A}', '\u{16E}'), ('\u{55}', '\u{30B}', '\u{170}'), ('\u{55}', '\u{30C}', '\u{1D3}'), ('\u{55}', '\u{30F}', '\u{214}'),
+    ('\u{55}', '\u{311}', '\u{216}'), ('\u{55}', '\u{31B}', '\u{1AF}'), ('\u{55}', '\u{323}', '\u{1EE4}'), ('\u{55}', '\u{324}', '\u{1E72}'),
+    ('\u{55}', '\u{328}', '\u{172}'), ('\u{55}', '\u{32D}', '\u{1E76}'), ('\u{55}', '\u{330}', '\u{1E74}'), ('\u{56}', '\u{303}', '\u{1E7C}'),
+    ('\u{56}', '\u{323}', '\u{1E7E}'), ('\u{57}', '\u{300}', '\u{1E80}'), ('\u{57}', '\u{301}', '\u{1E82}'), ('\u{57}', '\u{302}', '\u{174}'),
+    ('\u{57}', '\u{307}', '\u{1E86}'), ('\u{57}', '\u{308}', '\u{1E84}'), ('\u{57}', '\u{323}', '\u{1E88}'), ('\u{58}', '\u{307}', '\u{1E8A}'),
+    ('\u{58}', '\u{308}', '\u{1E8C}'), ('\u{59}', '\u{300}', '\u{1EF2}'), ('\u{59}', '\u{301}', '\u{DD}'), ('\u{59}', '\u{302}', '\u{176}'),
+    ('\u{59}', '\u{303}', '\u{1EF8}'), ('\u{59}', '\u{304}', '\u{232}'), ('\u{59}', '\u{307}', '\u{1E8E}'), ('\u{59}', '\u{308}', '\u{178}'),
+    ('\u{59}', '\u{309}', '\u{1EF6}'), ('\u{59}', '\u{323}', '\u{1EF4}'), ('\u{5A}', '\u{301}', '\u{179}'), ('\u{5A}', '\u{302}', '\u{1E90}'),
+    ('\u{5A}', '\u{307}', '\u{17B}'), ('\u{5A}', '\u{30C}', '\u{17D}'), ('\u{5A}', '\u{323}', '\u{1E92}'), ('\u{5A}', '\u{331}', '\u{1E94}'),
+    ('\u{61}', '\u{300}', '\u{E0}'), ('\u{61}', '\u{301}', '\u{E1}'), ('\u{61}', '\u{302}', '\u{E2}'), ('\u{61}', '\u{303}', '\u{E3}'),
+    ('\u{61}', '\u{304}', '\u{101}'), ('\u{61}', '\u{306}', '\u{103}'), ('\u{61}', '\u{307}', '\u{227}'), ('\u{61}', '\u{308}', '\u{E4}'),
+    ('\u{61}', '\u{309}', '\u{1EA3}'), ('\u{61}', '\u{30A}', '\u{E5}'), ('\u{61}', '\u{30C}', '\u{1CE}'), ('\u{61}', '\u{30F}', '\u{201}'),
+    ('\u{61}', '\u{311}', '\u{203}'), ('\u{61}', '\u{323}', '\u{1EA1}'), ('\u{61}', '\u{325}', '\u{1E01}'), ('\u{61}', '\u{328}', '\u{105}'),
+    ('\u{62}', '\u{307}', '\u{1E03}'), ('\u{62}', '\u{323}', '\u{1E05}'), ('\u{62}', '\u{331}', '\u{1E07}'), ('\u{63}', '\u{301}', '\u{107}'),
+    ('\u{63}', '\u{302}', '\u{109}'), ('\u{63}', '\u{307}', '\u{10B}'), ('\u{63}', '\u{30C}', '\u{10D}'), ('\u{63}', '\u{327}', '\u{E7}'),
+    ('\u{64}', '\u{307}', '\u{1E0B}'), ('\u{64}', '\u{30C}', '\u{10F}'), ('\u{64}', '\u{323}', '\u{1E0D}'), ('\u{64}', '\u{327}', '\u{1E11}'),
+    ('\u{64}', '\u{32D}', '\u{1E13}'), ('\u{64}', '\u{331}', '\u{1E0F}'), ('\u{65}', '\u{300}', '\u{E8}'), ('\u{65}', '\u{301}', '\u{E9}'),
+    ('\u{65}', '\u{302}', '\u{EA}'), ('\u{65}', '\u{303}', '\u{1EBD}'), ('\u{65}', '\u{304}', '\u{113}'), ('\u{65}', '\u{306}', '\u{115}'),
+    ('\u{65}', '\u{307}', '\u{117}'), ('\u{65}', '\u{308}', '\u{EB}'), ('\u{65}', '\u{309}', '\u{1EBB}'), ('\u{65}', '\u{30C}', '\u{11B}'),
+    ('\u{65}', '\u{30F}', '\u{205}'), ('\u{65}', '\u{311}', '\u{207}'), ('\u{65}', '\u{323}', '\u{1EB9}'), ('\u{65}', '\u{327}', '\u{229}'),
+    ('\u{65}', '\u{328}', '\u{119}'), ('\u{65}', '\u{32D}', '\u{1E19}'), ('\u{65}', '\u{330}', '\u{1E1B}'), ('\u{66}', '\u{307}', '\u{1E1F}'),
+    ('\u{67}', '\u{301}', '\u{1F5}'), ('\u{67}', '\u{302}', '\u{11D}'), ('\u{67}', '\u{304}', '\u{1E21}'), ('\u{67}', '\u{306}', '\u{11F}'),
+    ('\u{67}', '\u{307}', '\u{121}'), ('\u{67}', '\u{30C}', '\u{1E7}'), ('\u{67}', '\u{327}', '\u{123}'), ('\u{68}', '\u{302}', '\u{125}'),
+    ('\u{68}', '\u{307}', '\u{1E23}'), ('\u{68}', '\u{308}', '\u{1E27}'), ('\u{68}', '\u{30C}', '\u{21F}'), ('\u{68}', '\u{323}', '\u{1E25}'),
+    ('\u{68}', '\u{327}', '\u{1E29}'), ('\u{68}', '\u{32E}', '\u{1E2B}'), ('\u{68}', '\u{331}', '\u{1E96}'), ('\u{69}', '\u{300}', '\u{EC}'),
+    ('\u{69}', '\u{301}', '\u{ED}'), ('\u{69}', '\u{302}', '\u{EE}'), ('\u{69}', '\u{303}', '\u{129}'), ('\u{69}', '\u{304}', '\u{12B}'),
+    ('\u{69}', '\u{306}', '\u{12D}'), ('\u{69}', '\u{308}', '\u{EF}'), ('\u{69}', '\u{309}', '\u{1EC9}'), ('\u{69}', '\u{30C}', '\u{1D0}'),
+    ('\u{69}', '\u{30F}', '\u{209}'), ('\u{69}', '\u{311}', '\u{20B}'), ('\u{69}', '\u{323}', '\u{1ECB}'), ('\u{69}', '\u{328}', '\u{12F}'),
+    ('\u{69}', '\u{330}', '\u{1E2D}'), ('\u{6A}', '\u{302}', '\u{135}'), ('\u{6A}', '\u{30C}', '\u{1F0}'), ('\u{6B}', '\u{301}', '\u{1E31}'),
+    ('\u{6B}', '\u{30C}', '\u{1E9}'), ('\u{6B}', '\u{323}', '\u{1E33}'), ('\u{6B}', '\u{327}', '\u{137}'), ('\u{6B}', '\u{331}', '\u{1E35}'),
+    ('\u{6C}', '\u{301}', '\u{13A}'), ('\u{6C}', '\u{30C}', '\u{13E}'), ('\u{6C}', '\u{323}', '\u{1E37}'), ('\u{6C}', '\u{327}', '\u{13C}'),
+    ('\u{6C}', '\u{32D}', '\u{1E3D}'), ('\u{6C}', '\u{331}', '\u{1E3B}'), ('\u{6D}', '\u{301}', '\u{1E3F}'), ('\u{6D}', '\u{307}', '\u{1E41}'),
+    ('\u{6D}', '\u{323}', '\u{1E43}'), ('\u{6E}', '\u{300}', '\u{1F9}'), ('\u{6E}', '\u{301}', '\u{144}'), ('\u{6E}', '\u{303}', '\u{F1}'),
+    ('\u{6E}', '\u{307}', '\u{1E45}'), ('\u{6E}', '\u{30C}', '\u{148}'), ('\u{6E}', '\u{323}', '\u{1E47}'), ('\u{6E}', '\u{327}', '\u{146}'),
+    ('\u{6E}', '\u{32D}', '\u{1E4B}'), ('\u{6E}', '\u{331}', '\u{1E49}'), ('\u{6F}', '\u{300}', '\u{F2}'), ('\u{6F}', '\u{301}', '\u{F3}'),
+    ('\u{6F}', '\u{302}', '\u{F4}'), ('\u{6F}', '\u{303}', '\u{F5}'), ('\u{6F}', '\u{304}', '\u{14D}'), ('\u{6F}', '\u{306}', '\u{14F}'),
+    ('\u{6F}', '\u{307}', '\u{22F}'), ('\u{6F}', '\u{308}', '\u{F6}'), ('\u{6F}', '\u{309}', '\u{1ECF}'), ('\u{6F}', '\u{30B}', '\u{151}'),
+    ('\u{6F}', '\u{30C}', '\u{1D2}'), ('\u{6F}', '\u{30F}', '\u{20D}'), ('\u{6F}', '\u{311}', '\u{20F}'), ('\u{6F}', '\u{31B}', '\u{1A1}'),
+    ('\u{6F}', '\u{323}', '\u{1ECD}'), ('\u{6F}', '\u{328}', '\u{1EB}'), ('\u{70}', '\u{301}', '\u{1E55}'), ('\u{70}', '\u{307}', '\u{1E57}'),
+    ('\u{72}', '\u{301}', '\u{155}'), ('\u{72}', '\u{307}', '\u{1E59}'), ('\u{72}', '\u{30C}', '\u{159}'), ('\u{72}', '\u{30F}', '\u{211}'),
+    ('\u{72}', '\u{311}', '\u{213}'), ('\u{72}', '\u{323}', '\u{1E5B}'), ('\u{72}', '\u{327}', '\u{157}'), ('\u{72}', '\u{331}', '\u{1E5F}'),
+    ('\u{73}', '\u{301}', '\u{15B}'), ('\u{73}', '\u{302}', '\u{15D}'), ('\u{73}', '\u{307}', '\u{1E61}'), ('\u{73}', '\u{30C}', '\u{161}'),
+    ('\u{73}', '\u{323}', '\u{1E63}'), ('\u{73}', '\u{326}', '\u{219}'), ('\u{73}', '\u{327}', '\u{15F}'), ('\u{74}', '\u{307}', '\u{1E6B}'),
+    ('\u{74}', '\u{308}', '\u{1E97}'), ('\u{74}', '\u{30C}', '\u{165}'), ('\u{74}', '\u{323}', '\u{1E6D}'), ('\u{74}', '\u{326}', '\u{21B}'),
+    ('\u{74}', '\u{327}', '\u{163}'), ('\u{74}', '\u{32D}', '\u{1E71}'), ('\u{74}', '\u{331}', '\u{1E6F}'), ('\u{75}', '\u{300}', '\u{F9}'),
+    ('\u{75}', '\u{301}', '\u{FA}'), ('\u{75}', '\u{302}', '\u{FB}'), ('\u{75}', '\u{303}', '\u{169}'), ('\u{75}', '\u{304}', '\u{16B}'),
+    ('\u{75}', '\u{306}', '\u{16D}'), ('\u{75}', '\u{308}', '\u{FC}'), ('\u{75}', '\u{309}', '\u{1EE7}'), ('\u{75}', '\u{30A}', '\u{16F}'),
+    ('\u{75}', '\u{30B}', '\u{171}'), ('\u{75}', '\u{30C}', '\u{1D4}'), ('\u{75}', '\u{30F}', '\u{215}'), ('\u{75}', '\u{311}', '\u{217}'),
+    ('\u{75}', '\u{31B}', '\u{1B0}'), ('\u{75}', '\u{323}', '\u{1EE5}'), ('\u{75}', '\u{324}', '\u{1E73}'), ('\u{75}', '\u{328}', '\u{173}'),
+    ('\u{75}', '\u{32D}', '\u{1E77}'), ('\u{75}', '\u{330}', '\u{1E75}'), ('\u{76}', '\u{303}', '\u{1E7D}'), ('\u{76}', '\u{323}', '\u{1E7F}'),
+    ('\u{77}', '\u{300}', '\u{1E81}'), ('\u{77}', '\u{301}', '\u{1E83}'), ('\u{77}', '\u{302}', '\u{175}'), ('\u{77}', '\u{307}', '\u{1E87}'),
+    ('\u{77}', '\u{308}', '\u{1E85}'), ('\u{77}', '\u{30A}', '\u{1E98}'), ('\u{77}', '\u{323}', '\u{1E89}'), ('\u{78}', '\u{307}', '\u{1E8B}'),
+    ('\u{78}', '\u{308}', '\u{1E8D}'), ('\u{79}', '\u{300}', '\u{1EF3}'), ('\u{79}', '\u{301}', '\u{FD}'), ('\u{79}', '\u{302}', '\u{177}'),
+    ('\u{79}', '\u{303}', '\u{1EF9}'), ('\u{79}', '\u{304}', '\u{233}'), ('\u{79}', '\u{307}', '\u{1E8F}'), ('\u{79}', '\u{308}', '\u{FF}'),
+    ('\u{79}', '\u{309}', '\u{1EF7}'), ('\u{79}', '\u{30A}', '\u{1E99}'), ('\u{79}', '\u{323}', '\u{1EF5}'), ('\u{7A}', '\u{301}', '\u{17A}'),
+    ('\u{7A}', '\u{302}', '\u{1E91}'), ('\u{7A}', '\u{307}', '\u{17C}'), ('\u{7A}', '\u{30C}', '\u{17E}'), ('\u{7A}', '\u{323}', '\u{1E93}'),
+    ('\u{7A}', '\u{331}', '\u{1E95}'), ('\u{A8}', '\u{300}', '\u{1FED}'), ('\u{A8}', '\u{301}', '\u{385}'), ('\u{A8}', '\u{342}', '\u{1FC1}'),
+    ('\u{C2}', '\u{300}', '\u{1EA6}'), ('\u{C2}', '\u{301}', '\u{1EA4}'), ('\u{C2}', '\u{303}', '\u{1EAA}'), ('\u{C2}', '\u{309}', '\u{1EA8}'),
+    ('\u{C4}', '\u{304}', '\u{1DE}'), ('\u{C5}', '\u{301}', '\u{1FA}'), ('\u{C6}', '\u{301}', '\u{1FC}'), ('\u{C6}', '\u{304}', '\u{1E2}'),
+    ('\u{C7}', '\u{301}', '\u{1E08}'), ('\u{CA}', '\u{300}', '\u{1EC0}'), ('\u{CA}', '\u{301}', '\u{1EBE}'), ('\u{CA}', '\u{303}', '\u{1EC4}'),
+    ('\u{CA}', '\u{309}', '\u{1EC2}'), ('\u{CF}', '\u{301}', '\u{1E2E}'), ('\u{D4}', '\u{300}', '\u{1ED2}'), ('\u{D4}', '\u{301}', '\u{1ED0}'),
+    ('\u{D4}', '\u{303}', '\u{1ED6}'), ('\u{D4}', '\u{309}', '\u{1ED4}'), ('\u{D5}', '\u{301}', '\u{1E4C}'), ('\u{D5}', '\u{304}', '\u{22C}'),
+    ('\u{D5}', '\u{308}', '\u{1E4E}'), ('\u{D6}', '\u{304}', '\u{22A}'), ('\u{D8}', '\u{301}', '\u{1FE}'), ('\u{DC}', '\u{300}', '\u{1DB}'),
+    ('\u{DC}', '\u{301}', '\u{1D7}'), ('\u{DC}', '\u{304}', '\u{1D5}'), ('\u{DC}', '\u{30C}', '\u{1D9}'), ('\u{E2}', '\u{300}', '\u{1EA7}'),
+    ('\u{E2}', '\u{301}', '\u{1EA5}'), ('\u{E2}', '\u{303}', '\u{1EAB}'), ('\u{E2}', '\u{309}', '\u{1EA9}'), ('\u{E4}', '\u{304}', '\u{1DF}'),
+    ('\u{E5}', '\u{301}', '\u{1FB}'), ('\u{E6}', '\u{301}', '\u{1FD}'), ('\u{E6}', '\u{304}', '\u{1E3}'), ('\u{E7}', '\u{301}', '\u{1E09}'),
+    ('\u{EA}', '\u{300}', '\u{1EC1}'), ('\u{EA}', '\u{301}', '\u{1EBF}'), ('\u{EA}', '\u{303}', '\u{1EC5}'), ('\u{EA}', '\u{309}', '\u{1EC3}'),
+    ('\u{EF}', '\u{301}', '\u{1E2F}'), ('\u{F4}', '\u{300}', '\u{1ED3}'), ('\u{F4}', '\u{301}', '\u{1ED1}'), ('\u{F4}', '\u{303}', '\u{1ED7}'),
+    ('\u{F4}', '\u{309}', '\u{1ED5}'), ('\u{F5}', '\u{301}', '\u{1E4D}'), ('\u{F5}', '\u{304}', '\u{22D}'), ('\u{F5}', '\u{308}', '\u{1E4F}'),
+    ('\u{F6}', '\u{304}', '\u{22B}'), ('\u{F8}', '\u{301}', '\u{1FF}'), ('\u{FC}', '\u{300}', '\u{1DC}'), ('\u{FC}', '\u{301}', '\u{1D8}'),
+    ('\u{FC}', '\u{304}', '\u{1D6}'), ('\u{FC}', '\u{30C}', '\u{1DA}'), ('\u{102}', '\u{300}', '\u{1EB0}'), ('\u{102}', '\u{301}', '\u{1EAE}'),
+    ('\u{102}', '\u{303}', '\u{1EB4}'), ('\u{102}', '\u{309}', '\u{1EB2}'), ('\u{103}', '\u{300}', '\u{1EB1}'), ('\u{103}', '\u{301}', '\u{1EAF}'),
+    ('\u{103}', '\u{303}', '\u{1EB5}'), ('\u{103}', '\u{309}', '\u{1EB3}'), ('\u{112}', '\u{300}', '\u{1E14}'), ('\u{112}', '\u{301}', '\u{1E16}'),
+    ('\u{113}', '\u{300}', '\u{1E15}'), ('\u{113}', '\u{301}', '\u{1E17}'), ('\u{14C}', '\u{300}', '\u{1E50}'), ('\u{14C}', '\u{301}', '\u{1E52}'),
+    ('\u{14D}', '\u{300}', '\u{1E51}'), ('\u{14D}', '\u{301}', '\u{1E53}'), ('\u{15A}', '\u{307}', '\u{1E64}'), ('\u{15B}', '\u{307}', '\u{1E65}'),
+    ('\u{160}', '\u{307}', '\u{1E66}'), ('\u{161}', '\u{307}', '\u{1E67}'), ('\u{168}', '\u{301}', '\u{1E78}'), ('\u{169}', '\u{301}', '\u{1E79}'),
+    ('\u{16A}', '\u{308}', '\u{1E7A}'), ('\u{16B}', '\u{308}', '\u{1E7B}'), ('\u{17F}', '\u{307}', '\u{1E9B}'), ('\u{1A0}', '\u{300}', '\u{1EDC}'),
+    ('\u{1A0}', '\u{301}', '\u{1EDA}'), ('\u{1A0}', '\u{303}', '\u{1EE0}'), ('\u{1A0}', '\u{309}', '\u{1EDE}'), ('\u{1A0}', '\u{323}', '\u{1EE2}'),
+    ('\u{1A1}', '\u{300}', '\u{1EDD}'), ('\u{1A1}', '\u{301}', '\u{1EDB}'), ('\u{1A1}', '\u{303}', '\u{1EE1}'), ('\u{1A1}', '\u{309}', '\u{1EDF}'),
+    ('\u{1A1}', '\u{323}', '\u{1EE3}'), ('\u{1AF}', '\u{300}', '\u{1EEA}'), ('\u{1AF}', '\u{301}', '\u{1EE8}'), ('\u{1AF}', '\u{303}', '\u{1EEE}'),
+    ('\u{1AF}', '\u{309}', '\u{1EEC}'), ('\u{1AF}', '\u{323}', '\u{1EF0}'), ('\u{1B0}', '\u{300}', '\u{1EEB}'), ('\u{1B0}', '\u{301}', '\u{1EE9}'),
+    ('\u{1B0}', '\u{303}', '\u{1EEF}'), ('\u{1B0}', '\u{309}', '\u{1EED}'), ('\u{1B0}', '\u{323}', '\u{1EF1}'), ('\u{1B7}', '\u{30C}', '\u{1EE}'),
+    ('\u{1EA}', '\u{304}', '\u{1EC}'), ('\u{1EB}', '\u{304}', '\u{1ED}'), ('\u{226}', '\u{304}', '\u{1E0}'), ('\u{227}', '\u{304}', '\u{1E1}'),
+    ('\u{228}', '\u{306}', '\u{1E1C}'), ('\u{229}', '\u{306}', '\u{1E1D}'), ('\u{22E}', '\u{304}', '\u{230}'), ('\u{22F}', '\u{304}', '\u{231}'),
+    ('\u{292}', '\u{30C}', '\u{1EF}'), ('\u{391}', '\u{300}', '\u{1FBA}'), ('\u{391}', '\u{301}', '\u{386}'), ('\u{391}', '\u{304}', '\u{1FB9}'),
+    ('\u{391}', '\u{306}', '\u{1FB8}'), ('\u{391}', '\u{313}', '\u{1F08}'), ('\u{391}', '\u{314}', '\u{1F09}'), ('\u{391}', '\u{345}', '\u{1FBC}'),
+    ('\u{395}', '\u{300}', '\u{1FC8}'), ('\u{395}', '\u{301}', '\u{388}'), ('\u{395}', '\u{313}', '\u{1F18}'), ('\u{395}', '\u{314}', '\u{1F19}'),
+    ('\u{397}', '\u{300}', '\u{1FCA}'), ('\u{397}', '\u{301}', '\u{389}'), ('\u{397}', '\u{313}', '\u{1F28}'), ('\u{397}', '\u{314}', '\u{1F29}'),
+    ('\u{397}', '\u{345}', '\u{1FCC}'), ('\u{399}', '\u{300}', '\u{1FDA}'), ('\u{399}', '\u{301}', '\u{38A}'), ('\u{399}', '\u{304}', '\u{1FD9}'),
+    ('\u{399}', '\u{306}', '\u{1FD8}'), ('\u{399}', '\u{308}', '\u{3AA}'), ('\u{399}', '\u{313}', '\u{1F38}'), ('\u{399}', '\u{314}', '\u{1F39}'),
+    ('\u{39F}', '\u{300}', '\u{1FF8}'), ('\u{39F}', '\u{301}', '\u{38C}'), ('\u{39F}', '\u{313}', '\u{1F48}'), ('\u{39F}', '\u{314}', '\u{1F49}'),
+    ('\u{3A1}', '\u{314}', '\u{1FEC}'), ('\u{3A5}', '\u{300}', '\u{1FEA}'), ('\u{3A5}', '\u{301}', '\u{38E}'), ('\u{3A5}', '\u{304}', '\u{1FE9}'),
+    ('\u{3A5}', '\u{306}', '\u{1FE8}'), ('\u{3A5}', '\u{308}', '\u{3AB}'), ('\u{3A5}', '\u{314}', '\u{1F59}'), ('\u{3A9}', '\u{300}', '\u{1FFA}'),
+    ('\u{3A9}', '\u{301}', '\u{38F}'), ('\u{3A9}', '\u{313}', '\u{1F68}'), ('\u{3A9}', '\u{314}', '\u{1F69}'), ('\u{3A9}', '\u{345}', '\u{1FFC}'),
+    ('\u{3AC}', '\u{345}', '\u{1FB4}'), ('\u{3AE}', '\u{345}', '\u{1FC4}'), ('\u{3B1}', '\u{300}', '\u{1F70}'), ('\u{3B1}', '\u{301}', '\u{3AC}'),
+    ('\u{3B1}', '\u{304}', '\u{1FB1}'), ('\u{3B1}', '\u{306}', '\u{1FB0}'), ('\u{3B1}', '\u{313}', '\u{1F00}'), ('\u{3B1}', '\u{314}', '\u{1F01}'),
+    ('\u{3B1}', '\u{342}', '\u{1FB6}'), ('\u{3B1}', '\u{345}', '\u{1FB3}'), ('\u{3B5}', '\u{300}', '\u{1F72}'), ('\u{3B5}', '\u{301}', '\u{3AD}'),
+    ('\u{3B5}', '\u{313}', '\u{1F10}'), ('\u{3B5}', '\u{314}', '\u{1F11}'), ('\u{3B7}', '\u{300}', '\u{1F74}'), ('\u{3B7}', '\u{301}', '\u{3AE}'),
+    ('\u{3B7}', '\u{313}', '\u{1F20}'), ('\u{3B7}', '\u{314}', '\u{1F21}'), ('\u{3B7}', '\u{342}', '\u{1FC6}'), ('\u{3B7}', '\u{345}', '\u{1FC3}'),
+    ('\u{3B9}', '\u{300}', '\u{1F76}'), ('\u{3B9}', '\u{301}', '\u{3AF}'), ('\u{3B9}', '\u{304}', '\u{1FD1}'), ('\u{3B9}', '\u{306}', '\u{1FD0}'),
+    ('\u{3B9}', '\u{308}', '\u{3CA}'), ('\u{3B9}', '\u{313}', '\u{1F30}'), ('\u{3B9}', '\u{314}', '\u{1F31}'), ('\u{3B9}', '\u{342}', '\u{1FD6}'),
+    ('\u{3BF}', '\u{300}', '\u{1F78}'), ('\u{3BF}', '\u{301}', '\u{3CC}'), ('\u{3BF}', '\u{313}', '\u{1F40}'), ('\u{3BF}', '\u{314}', '\u{1F41}'),
+    ('\u{3C1}', '\u{313}', '\u{1FE4}'), ('\u{3C1}', '\u{314}', '\u{1FE5}'), ('\u{3C5}', '\u{300}', '\u{1F7A}'), ('\u{3C5}', '\u{301}', '\u{3CD}'),
+    ('\u{3C5}', '\u{304}', '\u{1FE1}'), ('\u{3C5}', '\u{306}', '\u{1FE0}'), ('\u{3C5}', '\u{308}', '\u{3CB}'), ('\u{3C5}', '\u{313}', '\u{1F50}'),
+    ('\u{3C5}', '\u{314}', '\u{1F51}'), ('\u{3C5}', '\u{342}', '\u{1FE6}'), ('\u{3C9}', '\u{300}', '\u{1F7C}'), ('\u{3C9}', '\u{301}', '\u{3CE}'),
+    ('\u{3C9}', '\u{313}', '\u{1F60}'), ('\u{3C9}', '\u{314}', '\u{1F61}'), ('\u{3C9}', '\u{342}', '\u{1FF6}'), ('\u{3C9}', '\u{345}', '\u{1FF3}'),
+    ('\u{3CA}', '\u{300}', '\u{1FD2}'), ('\u{3CA}', '\u{301}', '\u{390}'), ('\u{3CA}', '\u{342}', '\u{1FD7}'), ('\u{3CB}', '\u{300}', '\u{1FE2}'),
+    ('\u{3CB}', '\u{301}', '\u{3B0}'), ('\u{3CB}', '\u{342}', '\u{1FE7}'), ('\u{3CE}', '\u{345}', '\u{1FF4}'), ('\u{3D2}', '\u{301}', '\u{3D3}'),
+    ('\u{3D2}', '\u{308}', '\u{3D4}'), ('\u{406}', '\u{308}', '\u{407}'), ('\u{410}', '\u{306}', '\u{4D0}'), ('\u{410}', '\u{308}', '\u{4D2}'),
+    ('\u{413}', '\u{301}', '\u{403}'), ('\u{415}', '\u{300}', '\u{400}'), ('\u{415}', '\u{306}', '\u{4D6}'), ('\u{415}', '\u{308}', '\u{401}'),
+    ('\u{416}', '\u{306}', '\u{4C1}'), ('\u{416}', '\u{308}', '\u{4DC}'), ('\u{417}', '\u{308}', '\u{4DE}'), ('\u{418}', '\u{300}', '\u{40D}'),
+    ('\u{418}', '\u{304}', '\u{4E2}'), ('\u{418}', '\u{306}', '\u{419}'), ('\u{418}', '\u{308}', '\u{4E4}'), ('\u{41A}', '\u{301}', '\u{40C}'),
+    ('\u{41E}', '\u{308}', '\u{4E6}'), ('\u{423}', '\u{304}', '\u{4EE}'), ('\u{423}', '\u{306}', '\u{40E}'), ('\u{423}', '\u{308}', '\u{4F0}'),
+    ('\u{423}', '\u{30B}', '\u{4F2}'), ('\u{427}', '\u{308}', '\u{4F4}'), ('\u{42B}', '\u{308}', '\u{4F8}'), ('\u{42D}', '\u{308}', '\u{4EC}'),
+    ('\u{430}', '\u{306}', '\u{4D1}'), ('\u{430}', '\u{308}', '\u{4D3}'), ('\u{433}', '\u{301}', '\u{453}'), ('\u{435}', '\u{300}', '\u{450}'),
+    ('\u{435}', '\u{306}', '\u{4D7}'), ('\u{435}', '\u{308}', '\u{451}'), ('\u{436}', '\u{306}', '\u{4C2}'), ('\u{436}', '\u{308}', '\u{4DD}'),
+    ('\u{437}', '\u{308}', '\u{4DF}'), ('\u{438}', '\u{300}', '\u{45D}'), ('\u{438}', '\u{304}', '\u{4E3}'), ('\u{438}', '\u{306}', '\u{439}'),
+    ('\u{438}', '\u{308}', '\u{4E5}'), ('\u{43A}', '\u{301}', '\u{45C}'), ('\u{43E}', '\u{308}', '\u{4E7}'), ('\u{443}', '\u{304}', '\u{4EF}'),
+    ('\u{443}', '\u{306}', '\u{45E}'), ('\u{443}', '\u{308}', '\u{4F1}'), ('\u{443}', '\u{30B}', '\u{4F3}'), ('\u{447}', '\u{308}', '\u{4F5}'),
+    ('\u{44B}', '\u{308}', '\u{4F9}'), ('\u{44D}', '\u{308}', '\u{4ED}'), ('\u{456}', '\u{308}', '\u{457}'), ('\u{474}', '\u{30F}', '\u{476}'),
+    ('\u{475}', '\u{30F}', '\u{477}'), ('\u{4D8}', '\u{308}', '\u{4DA}'), ('\u{4D9}', '\u{308}', '\u{4DB}'), ('\u{4E8}', '\u{308}', '\u{4EA}'),
+    ('\u{4E9}', '\u{308}', '\u{4EB}'), ('\u{627}', '\u{653}', '\u{622}'), ('\u{627}', '\u{654}', '\u{623}'), ('\u{627}', '\u{655}', '\u{625}'),
+    ('\u{648}', '\u{654}', '\u{624}'), ('\u{64A}', '\u{654}', '\u{626}'), ('\u{6C1}', '\u{654}', '\u{6C2}'), ('\u{6D2}', '\u{654}', '\u{6D3}'),
+    ('\u{6D5}', '\u{654}', '\u{6C0}'), ('\u{928}', '\u{93C}', '\u{929}'), ('\u{930}', '\u{93C}', '\u{931}'), ('\u{933}', '\u{93C}', '\u{934}'),
+    ('\u{9C7}', '\u{9BE}', '\u{9CB}'), ('\u{9C7}', '\u{9D7}', '\u{9CC}'), ('\u{B47}', '\u{B3E}', '\u{B4B}'), ('\u{B47}', '\u{B56}', '\u{B48}'),
+    ('\u{B47}', '\u{B57}', '\u{B4C}'), ('\u{B92}', '\u{BD7}', '\u{B94}'), ('\u{BC6}', '\u{BBE}', '\u{BCA}'), ('\u{BC6}', '\u{BD7}', '\u{BCC}'),
+    ('\u{BC7}', '\u{BBE}', '\u{BCB}'), ('\u{C46}', '\u{C56}', '\u{C48}'), ('\u{CBF}', '\u{CD5}', '\u{CC0}'), ('\u{CC6}', '\u{CC2}', '\u{CCA}'),
+    ('\u{CC6}', '\u{CD5}', '\u{CC7}'), ('\u{CC6}', '\u{CD6}', '\u{CC8}'), ('\u{CCA}', '\u{CD5}', '\u{CCB}'), ('\u{D46}', '\u{D3E}', '\u{D4A}'),
+    ('\u{D46}', '\u{D57}', '\u{D4C}'), ('\u{D47}', '\u{D3E}', '\u{D4B}'), ('\u{DD9}', '\u{DCA}', '\u{DDA}'), ('\u{DD9}', '\u{DCF}', '\u{DDC}'),
+    ('\u{DD9}', '\u{DDF}', '\u{DDE}'), ('\u{DDC}', '\u{DCA}', '\u{DDD}'), ('\u{1025}', '\u{102E}', '\u{1026}'), ('\u{1B05}', '\u{1B35}', '\u{1B06}'),
+    ('\u{1B07}', '\u{1B35}', '\u{1B08}'), ('\u{1B09}', '\u{1B35}', '\u{1B0A}'), ('\u{1B0B}', '\u{1B35}', '\u{1B0C}'), ('\u{1B0D}', '\u{1B35}', '\u{1B0E}'),
+    ('\u{1B11}', '\u{1B35}', '\u{1B12}'), ('\u{1B3A}', '\u{1B35}', '\u{1B3B}'), ('\u{1B3C}', '\u{1B35}', '\u{1B3D}'), ('\u{1B3E}', '\u{1B35}', '\u{1B40}'),
+    ('\u{1B3F}', '\u{1B35}', '\u{1B41}'), ('\u{1B42}', '\u{1B35}', '\u{1B43}'), ('\u{1E36}', '\u{304}', '\u{1E38}'), ('\u{1E37}', '\u{304}', '\u{1E39}'),
+    ('\u{1E5A}', '\u{304}', '\u{1E5C}'), ('\u{1E5B}', '\u{304}', '\u{1E5D}'), ('\u{1E62}', '\u{307}', '\u{1E68}'), ('\u{1E63}', '\u{307}', '\u{1E69}'),
+    ('\u{1EA0}', '\u{302}', '\u{1EAC}'), ('\u{1EA0}', '\u{306}', '\u{1EB6}'), ('\u{1EA1}', '\u{302}', '\u{1EAD}'), ('\u{1EA1}', '\u{306}', '\u{1EB7}'),
+    ('\u{1EB8}', '\u{302}', '\u{1EC6}'), ('\u{1EB9}', '\u{302}', '\u{1EC7}'), ('\u{1ECC}', '\u{302}', '\u{1ED8}'), ('\u{1ECD}', '\u{302}', '\u{1ED9}'),
+    ('\u{1F00}', '\u{300}', '\u{1F02}'), ('\u{1F00}', '\u{301}', '\u{1F04}'), ('\u{1F00}', '\u{342}', '\u{1F06}'), ('\u{1F00}', '\u{345}', '\u{1F80}'),
+    ('\u{1F01}', '\u{300}', '\u{1F03}'), ('\u{1F01}', '\u{301}', '\u{1F05}'), ('\u{1F01}', '\u{342}', '\u{1F07}'), ('\u{1F01}', '\u{345}', '\u{1F81}'),
+    ('\u{1F02}', '\u{345}', '\u{1F82}'), ('\u{1F03}', '\u{345}', '\u{1F83}'), ('\u{1F04}', '\u{345}', '\u{1F84}'), ('\u{1F05}', '\u{345}', '\u{1F85}'),
+    ('\u{1F06}', '\u{345}', '\u{1F86}'), ('\u{1F07}', '\u{345}', '\u{1F87}'), ('\u{1F08}', '\u{300}', '\u{1F0A}'), ('\u{1F08}', '\u{301}', '\u{1F0C}'),
+    ('\u{1F08}', '\u{342}', '\u{1F0E}'), ('\u{1F08}', '\u{345}', '\u{1F88}'), ('\u{1F09}', '\u{300}', '\u{1F0B}'), ('\u{1F09}', '\u{301}', '\u{1F0D}'),
+    ('\u{1F09}', '\u{342}', '\u{1F0F}'), ('\u{1F09}', '\u{345}', '\u{1F89}'), ('\u{1F0A}', '\u{345}', '\u{1F8A}'), ('\u{1F0B}', '\u{345}', '\u{1F8B}'),
+    ('\u{1F0C}', '\u{345}', '\u{1F8C}'), ('\u{1F0D}', '\u{345}', '\u{1F8D}'), ('\u{1F0E}', '\u{345}', '\u{1F8E}'), ('\u{1F0F}', '\u{345}', '\u{1F8F}'),
+    ('\u{1F10}', '\u{300}', '\u{1F12}'), ('\u{1F10}', '\u{301}', '\u{1F14}'), ('\u{1F11}', '\u{300}', '\u{1F13}'), ('\u{1F11}', '\u{301}', '\u{1F15}'),
+    ('\u{1F18}', '\u{300}', '\u{1F1A}'), ('\u{1F18}', '\u{301}', '\u{1F1C}'), ('\u{1F19}', '\u{300}', '\u{1F1B}'), ('\u{1F19}', '\u{301}', '\u{1F1D}'),
+    ('\u{1F20}', '\u{300}', '\u{1F22}'), ('\u{1F20}', '\u{301}', '\u{1F24}'), ('\u{1F20}', '\u{342}', '\u{1F26}'), ('\u{1F20}', '\u{345}', '\u{1F90}'),
+    ('\u{1F21}', '\u{300}', '\u{1F23}'), ('\u{1F21}', '\u{301}', '\u{1F25}'), ('\u{1F21}', '\u{342}', '\u{1F27}'), ('\u{1F21}', '\u{345}', '\u{1F91}'),
+    ('\u{1F22}', '\u{345}', '\u{1F92}'), ('\u{1F23}', '\u{345}', '\u{1F93}'), ('\u{1F24}', '\u{345}', '\u{1F94}'), ('\u{1F25}', '\u{345}', '\u{1F95}'),
+    ('\u{1F26}', '\u{345}', '\u{1F96}'), ('\u{1F27}', '\u{345}', '\u{1F97}'), ('\u{1F28}', '\u{300}', '\u{1F2A}'), ('\u{1F28}', '\u{301}', '\u{1F2C}'),
+    ('\u{1F28}', '\u{342}', '\u{1F2E}'), ('\u{1F28}', '\u{345}', '\u{1F98}'), ('\u{1F29}', '\u{300}', '\u{1F2B}'), ('\u{1F29}', '\u{301}', '\u{1F2D}'),
+    ('\u{1F29}', '\u{342}', '\u{1F2F}'), ('\u{1F29}', '\u{345}', '\u{1F99}'), ('\u{1F2A}', '\u{345}', '\u{1F9A}'), ('\u{1F2B}', '\u{345}', '\u{1F9B}'),
+    ('\u{1F2C}', '\u{345}', '\u{1F9C}'), ('\u{1F2D}', '\u{345}', '\u{1F9D}'), ('\u{1F2E}', '\u{345}', '\u{1F9E}'), ('\u{1F2F}', '\u{345}', '\u{1F9F}'),
+    ('\u{1F30}', '\u{300}', '\u{1F32}'), ('\u{1F30}', '\u{301}', '\u{1F34}'), ('\u{1F30}', '\u{342}', '\u{1F36}'), ('\u{1F31}', '\u{300}', '\u{1F33}'),
+    ('\u{1F31}', '\u{301}', '\u{1F35}'), ('\u{1F31}', '\u{342}', '\u{1F37}'), ('\u{1F38}', '\u{300}', '\u{1F3A}'), ('\u{1F38}', '\u{301}', '\u{1F3C}'),
+    ('\u{1F38}', '\u{342}', '\u{1F3E}'), ('\u{1F39}', '\u{300}', '\u{1F3B}'), ('\u{1F39}', '\u{301}', '\u{1F3D}'), ('\u{1F39}', '\u{342}', '\u{1F3F}'),
+    ('\u{1F40}', '\u{300}', '\u{1F42}'), ('\u{1F40}', '\u{301}', '\u{1F44}'), ('\u{1F41}', '\u{300}', '\u{1F43}'), ('\u{1F41}', '\u{301}', '\u{1F45}'),
+    ('\u{1F48}', '\u{300}', '\u{1F4A}'), ('\u{1F48}', '\u{301}', '\u{1F4C}'), ('\u{1F49}', '\u{300}', '\u{1F4B}'), ('\u{1F49}', '\u{301}', '\u{1F4D}'),
+    ('\u{1F50}', '\u{300}', '\u{1F52}'), ('\u{1F50}', '\u{301}', '\u{1F54}'), ('\u{1F50}', '\u{342}', '\u{1F56}'), ('\u{1F51}', '\u{300}', '\u{1F53}'),
+    ('\u{1F51}', '\u{301}', '\u{1F55}'), ('\u{1F51}', '\u{342}', '\u{1F57}'), ('\u{1F59}', '\u{300}', '\u{1F5B}'), ('\u{1F59}', '\u{301}', '\u{1F5D}'),
+    ('\u{1F59}', '\u{342}', '\u{1F5F}'), ('\u{1F60}', '\u{300}', '\u{1F62}'), ('\u{1F60}', '\u{301}', '\u{1F64}'), ('\u{1F60}', '\u{342}', '\u{1F66}'),
+    ('\u{1F60}', '\u{345}', '\u{1FA0}'), ('\u{1F61}', '\u{300}', '\u{1F63}'), ('\u{1F61}', '\u{301}', '\u{1F65}'), ('\u{1F61}', '\u{342}', '\u{1F67}'),
+    ('\u{1F61}', '\u{345}', '\u{1FA1}'), ('\u{1F62}', '\u{345}', '\u{1FA2}'), ('\u{1F63}', '\u{345}', '\u{1FA3}'), ('\u{1F64}', '\u{345}', '\u{1FA4}'),
+    ('\u{1F65}', '\u{345}', '\u{1FA5}'), ('\u{1F66}', '\u{345}', '\u{1FA6}'), ('\u{1F67}', '\u{345}', '\u{1FA7}'), ('\u{1F68}', '\u{300}', '\u{1F6A}'),
+    ('\u{1F68}', '\u{301}', '\u{1F6C}'), ('\u{1F68}', '\u{342}', '\u{1F6E}'), ('\u{1F68}', '\u{345}', '\u{1FA8}'), ('\u{1F69}', '\u{300}', '\u{1F6B}'),
+    ('\u{1F69}', '\u{301}', '\u{1F6D}'), ('\u{1F69}', '\u{342}', '\u{1F6F}'), ('\u{1F69}', '\u{345}', '\u{1FA9}'), ('\u{1F6A}', '\u{345}', '\u{1FAA}'),
+    ('\u{1F6B}', '\u{345}', '\u{1FAB}'), ('\u{1F6C}', '\u{345}', '\u{1FAC}'), ('\u{1F6D}', '\u{345}', '\u{1FAD}'), ('\u{1F6E}', '\u{345}', '\u{1FAE}'),
+    ('\u{1F6F}', '\u{345}', '\u{1FAF}'), ('\u{1F70}', '\u{345}', '\u{1FB2}'), ('\u{1F74}', '\u{345}', '\u{1FC2}'), ('\u{1F7C}', '\u{345}', '\u{1FF2}'),
+    ('\u{1FB6}', '\u{345}', '\u{1FB7}'), ('\u{1FBF}', '\u{300}', '\u{1FCD}'), ('\u{1FBF}', '\u{301}', '\u{1FCE}'), ('\u{1FBF}', '\u{342}', '\u{1FCF}'),
+    ('\u{1FC6}', '\u{345}', '\u{1FC7}'), ('\u{1FF6}', '\u{345}', '\u{1FF7}'), ('\u{1FFE}', '\u{300}', '\u{1FDD}'), ('\u{1FFE}', '\u{301}', '\u{1FDE}'),
+    ('\u{1FFE}', '\u{342}', '\u{1FDF}'), ('\u{2190}', '\u{338}', '\u{219A}'), ('\u{2192}', '\u{338}', '\u{219B}'), ('\u{2194}', '\u{338}', '\u{21AE}'),
+    ('\u{21D0}', '\u{338}', '\u{21CD}'), ('\u{21D2}', '\u{338}', '\u{21CF}'), ('\u{21D4}', '\u{338}', '\u{21CE}'), ('\u{2203}', '\u{338}', '\u{2204}'),
+    ('\u{2208}', '\u{338}', '\u{2209}'), ('\u{220B}', '\u{338}', '\u{220C}'), ('\u{2223}', '\u{338}', '\u{2224}'), ('\u{2225}', '\u{338}', '\u{2226}'),
+    ('\u{223C}', '\u{338}', '\u{2241}'), ('\u{2243}', '\u{338}', '\u{2244}'), ('\u{2245}', '\u{338}', '\u{2247}'), ('\u{2248}', '\u{338}', '\u{2249}'),
+    ('\u{224D}', '\u{338}', '\u{226D}'), ('\u{2261}', '\u{338}', '\u{2262}'), ('\u{2264}', '\u{338}', '\u{2270}'), ('\u{2265}', '\u{338}', '\u{2271}'),
+    ('\u{2272}', '\u{338}', '\u{2274}'), ('\u{2273}', '\u{338}', '\u{2275}'), ('\u{2276}', '\u{338}', '\u{2278}'), ('\u{2277}', '\u{338}', '\u{2279}'),
+    ('\u{227A}', '\u{338}', '\u{2280}'), ('\u{227B}', '\u{338}', '\u{2281}'), ('\u{227C}', '\u{338}', '\u{22E0}'), ('\u{227D}', '\u{338}', '\u{22E1}'),
+    ('\u{2282}', '\u{338}', '\u{2284}'), ('\u{2283}', '\u{338}', '\u{2285}'), ('\u{2286}', '\u{338}', '\u{2288}'), ('\u{2287}', '\u{338}', '\u{2289}'),
+    ('\u{2291}', '\u{338}', '\u{22E2}'), ('\u{2292}', '\u{338}', '\u{22E3}'), ('\u{22A2}', '\u{338}', '\u{22AC}'), ('\u{22A8}', '\u{338}', '\u{22AD}'),
+    ('\u{22A9}', '\u{338}', '\u{22AE}'), ('\u{22AB}', '\u{338}', '\u{22AF}'), ('\u{22B2}', '\u{338}', '\u{22EA}'), ('\u{22B3}', '\u{338}', '\u{22EB}'),
+    ('\u{22B4}', '\u{338}', '\u{22EC}'), ('\u{22B5}', '\u{338}', '\u{22ED}'), ('\u{3046}', '\u{3099}', '\u{3094}'), ('\u{304B}', '\u{3099}', '\u{304C}'),
+    ('\u{304D}', '\u{3099}', '\u{304E}'), ('\u{304F}', '\u{3099}', '\u{3050}'), ('\u{3051}', '\u{3099}', '\u{3052}'), ('\u{3053}', '\u{3099}', '\u{3054}'),
+    ('\u{3055}', '\u{3099}', '\u{3056}'), ('\u{3057}', '\u{3099}', '\u{3058}'), ('\u{3059}', '\u{3099}', '\u{305A}'), ('\u{305B}', '\u{3099}', '\u{305C}'),
+    ('\u{305D}', '\u{3099}', '\u{305E}'), ('\u{305F}', '\u{3099}', '\u{3060}'), ('\u{3061}', '\u{3099}', '\u{3062}'), ('\u{3064}', '\u{3099}', '\u{3065}'),
+    ('\u{3066}', '\u{3099}', '\u{3067}'), ('\u{3068}', '\u{3099}', '\u{3069}'), ('\u{306F}', '\u{3099}', '\u{3070}'), ('\u{306F}', '\u{309A}', '\u{3071}'),
+    ('\u{3072}', '\u{3099}', '\u{3073}'), ('\u{3072}', '\u{309A}', '\u{3074}'), ('\u{3075}', '\u{3099}', '\u{3076}'), ('\u{3075}', '\u{309A}', '\u{3077}'),
+    ('\u{3078}', '\u{3099}', '\u{3079}'), ('\u{3078}', '\u{309A}', '\u{307A}'), ('\u{307B}', '\u{3099}', '\u{307C}'), ('\u{307B}', '\u{309A}', '\u{307D}'),
+    ('\u{309D}', '\u{3099}', '\u{309E}'), ('\u{30A6}', '\u{3099}', '\u{30F4}'), ('\u{30AB}', '\u{3099}', '\u{30AC}'), ('\u{30AD}', '\u{3099}', '\u{30AE}'),
+    ('\u{30AF}', '\u{3099}', '\u{30B0}'), ('\u{30B1}', '\u{3099}', '\u{30B2}'), ('\u{30B3}', '\u{3099}', '\u{30B4}'), ('\u{30B5}', '\u{3099}', '\u{30B6}'),
+    ('\u{30B7}', '\u{3099}', '\u{30B8}'), ('\u{30B9}', '\u{3099}', '\u{30BA}'), ('\u{30BB}', '\u{3099}', '\u{30BC}'), ('\u{30BD}', '\u{3099}', '\u{30BE}'),
+    ('\u{30BF}', '\u{3099}', '\u{30C0}'), ('\u{30C1}', '\u{3099}', '\u{30C2}'), ('\u{30C4}', '\u{3099}', '\u{30C5}'), ('\u{30C6}', '\u{3099}', '\u{30C7}'),
+    ('\u{30C8}', '\u{3099}', '\u{30C9}'), ('\u{30CF}', '\u{3099}', '\u{30D0}'), ('\u{30CF}', '\u{309A}', '\u{30D1}'), ('\u{30D2}', '\u{3099}', '\u{30D3}'),
+    ('\u{30D2}', '\u{309A}', '\u{30D4}'), ('\u{30D5}', '\u{3099}', '\u{30D6}'), ('\u{30D5}', '\u{309A}', '\u{30D7}'), ('\u{30D8}', '\u{3099}', '\u{30D9}'),
+    ('\u{30D8}', '\u{309A}', '\u{30DA}'), ('\u{30DB}', '\u{3099}', '\u{30DC}'), ('\u{30DB}', '\u{309A}', '\u{30DD}'), ('\u{30EF}', '\u{3099}', '\u{30F7}'),
+    ('\u{30F0}', '\u{3099}', '\u{30F8}'), ('\u{30F1}', '\u{3099}', '\u{30F9}'), ('\u{30F2}', '\u{3099}', '\u{30FA}'), ('\u{30FD}', '\u{3099}', '\u{30FE}'),
+    ('\u{11099}', '\u{110BA}', '\u{1109A}'), ('\u{1109B}', '\u{110BA}', '\u{1109C}'), ('\u{110A5}', '\u{110BA}', '\u{110AB}'), ('\u{11131}', '\u{11127}', '\u{1112E}'),
+    ('\u{11132}', '\u{11127}', '\u{1112F}'), ('\u{11347}', '\u{1133E}', '\u{1134B}'), ('\u{11347}', '\u{11357}', '\u{1134C}'), ('\u{114B9}', '\u{114B0}', '\u{114BC}'),
+    ('\u{114B9}', '\u{114BA}', '\u{114BB}'), ('\u{114B9}', '\u{114BD}', '\u{114BE}'), ('\u{115B8}', '\u{115AF}', '\u{115BA}'), ('\u{115B9}', '\u{115AF}', '\u{115BB}'),
+    ('\u{11935}', '\u{11930}', '\u{11938}'),
+];
+
+/// Ranges of characters with a nonzero canonical combining class, and that class.
+pub(crate) const COMBINING_CLASSES: &[(char, char, u8)] = &[
+    ('\u{300}', '\u{314}', 230), ('\u{315}', '\u{315}', 232), ('\u{316}', '\u{319}', 220), ('\u{31A}', '\u{31A}', 232),
+    ('\u{31B}', '\u{31B}', 216), ('\u{31C}', '\u{320}', 220), ('\u{321}', '\u{322}', 202), ('\u{323}', '\u{326}', 220),
+    ('\u{327}', '\u{328}', 202), ('\u{329}', '\u{333}', 220), ('\u{334}', '\u{338}', 1), ('\u{339}', '\u{33C}', 220),
+    ('\u{33D}', '\u{344}', 230), ('\u{345}', '\u{345}', 240), ('\u{346}', '\u{346}', 230), ('\u{347}', '\u{349}', 220),
+    ('\u{34A}', '\u{34C}', 230), ('\u{34D}', '\u{34E}', 220), ('\u{350}', '\u{352}', 230), ('\u{353}', '\u{356}', 220),
+    ('\u{357}', '\u{357}', 230), ('\u{358}', '\u{358}', 232), ('\u{359}', '\u{35A}', 220), ('\u{35B}', '\u{35B}', 230),
+    ('\u{35C}', '\u{35C}', 233), ('\u{35D}', '\u{35E}', 234), ('\u{35F}', '\u{35F}', 233), ('\u{360}', '\u{361}', 234),
+    ('\u{362}', '\u{362}', 233), ('\u{363}', '\u{36F}', 230), ('\u{483}', '\u{487}', 230), ('\u{591}', '\u{591}', 220),
+    ('\u{592}', '\u{595}', 230), ('\u{596}', '\u{596}', 220), ('\u{597}', '\u{599}', 230), ('\u{59A}', '\u{59A}', 222),
+    ('\u{59B}', '\u{59B}', 220), ('\u{59C}', '\u{5A1}', 230), ('\u{5A2}', '\u{5A7}', 220), ('\u{5A8}', '\u{5A9}', 230),
+    ('\u{5AA}', '\u{5AA}', 220), ('\u{5AB}', '\u{5AC}', 230), ('\u{5AD}', '\u{5AD}', 222), ('\u{5AE}', '\u{5AE}', 228),
+    ('\u{5AF}', '\u{5AF}', 230), ('\u{5B0}', '\u{5B0}', 10), ('\u{5B1}', '\u{5B1}', 11), ('\u{5B2}', '\u{5B2}', 12),
+    ('\u{5B3}', '\u{5B3}', 13), ('\u{5B4}', '\u{5B4}', 14), ('\u{5B5}', '\u{5B5}', 15), ('\u{5B6}', '\u{5B6}', 16),
+    ('\u{5B7}', '\u{5B7}', 17), ('\u{5B8}', '\u{5B8}', 18), ('\u{5B9}', '\u{5BA}', 19), ('\u{5BB}', '\u{5BB}', 20),
+    ('\u{5BC}', '\u{5BC}', 21), ('\u{5BD}', '\u{5BD}', 22), ('\u{5BF}', '\u{5BF}', 23), ('\u{5C1}', '\u{5C1}', 24),
+    ('\u{5C2}', '\u{5C2}', 25), ('\u{5C4}', '\u{5C4}', 230), ('\u{5C5}', '\u{5C5}', 220), ('\u{5C7}', '\u{5C7}', 18),
+    ('\u{610}', '\u{617}', 230), ('\u{618}', '\u{618}', 30), ('\u{619}', '\u{619}', 31), ('\u{61A}', '\u{61A}', 32),
+    ('\u{64B}', '\u{64B}', 27), ('\u{64C}', '\u{64C}', 28), ('\u{64D}', '\u{64D}', 29), ('\u{64E}', '\u{64E}', 30),
+    ('\u{64F}', '\u{64F}', 31), ('\u{650}', '\u{650}', 32), ('\u{651}', '\u{651}', 33), ('\u{652}', '\u{652}', 34),
+    ('\u{653}', '\u{654}', 230), ('\u{655}', '\u{656}', 220), ('\u{657}', '\u{65B}', 230), ('\u{65C}', '\u{65C}', 220),
+    ('\u{65D}', '\u{65E}', 230), ('\u{65F}', '\u{65F}', 220), ('\u{670}', '\u{670}', 35), ('\u{6D6}', '\u{6DC}', 230),
+    ('\u{6DF}', '\u{6E2}', 230), ('\u{6E3}', '\u{6E3}', 220), ('\u{6E4}', '\u{6E4}', 230), ('\u{6E7}', '\u{6E8}', 230),
+    ('\u{6EA}', '\u{6EA}', 220), ('\u{6EB}', '\u{6EC}', 230), ('\u{6ED}', '\u{6ED}', 220), ('\u{711}', '\u{711}', 36),
+    ('\u{730}', '\u{730}', 230), ('\u{731}', '\u{731}', 220), ('\u{732}', '\u{733}', 230), ('\u{734}', '\u{734}', 220),
+    ('\u{735}', '\u{736}', 230), ('\u{737}', '\u{739}', 220), ('\u{73A}', '\u{73A}', 230), ('\u{73B}', '\u{73C}', 220),
+    ('\u{73D}', '\u{73D}', 230), ('\u{73E}', '\u{73E}', 220), ('\u{73F}', '\u{741}', 230), ('\u{742}', '\u{742}', 220),
+    ('\u{743}', '\u{743}', 230), ('\u{744}', '\u{744}', 220), ('\u{745}', '\u{745}', 230), ('\u{746}', '\u{746}', 220),
+    ('\u{747}', '\u{747}', 230), ('\u{748}', '\u{748}', 220), ('\u{749}', '\u{74A}', 230), ('\u{7EB}', '\u{7F1}', 230),
+    ('\u{7F2}', '\u{7F2}', 220), ('\u{7F3}', '\u{7F3}', 230), ('\u{7FD}', '\u{7FD}', 220), ('\u{816}', '\u{819}', 230),
+    ('\u{81B}', '\u{823}', 230), ('\u{825}', '\u{827}', 230), ('\u{829}', '\u{82D}', 230), ('\u{859}', '\u{85B}', 220),
+    ('\u{898}', '\u{898}', 230), ('\u{899}', '\u{89B}', 220), ('\u{89C}', '\u{89F}', 230), ('\u{8CA}', '\u{8CE}', 230),
+    ('\u{8CF}', '\u{8D3}', 220), ('\u{8D4}', '\u{8E1}', 230), ('\u{8E3}', '\u{8E3}', 220), ('\u{8E4}', '\u{8E5}', 230),
+    ('\u{8E6}', '\u{8E6}', 220), ('\u{8E7}', '\u{8E8}', 230), ('\u{8E9}', '\u{8E9}', 220), ('\u{8EA}', '\u{8EC}', 230),
+    ('\u{8ED}', '\u{8EF}', 220), ('\u{8F0}', '\u{8F0}', 27), ('\u{8F1}', '\u{8F1}', 28), ('\u{8F2}', '\u{8F2}', 29),
+    ('\u{8F3}', '\u{8F5}', 230), ('\u{8F6}', '\u{8F6}', 220), ('\u{8F7}', '\u{8F8}', 230), ('\u{8F9}', '\u{8FA}', 220),
+    ('\u{8FB}', '\u{8FF}', 230), ('\u{93C}', '\u{93C}', 7), ('\u{94D}', '\u{94D}', 9), ('\u{951}', '\u{951}', 230),
+    ('\u{952}', '\u{952}', 220), ('\u{953}', '\u{954}', 230), ('\u{9BC}', '\u{9BC}', 7), ('\u{9CD}', '\u{9CD}', 9),
+    ('\u{9FE}', '\u{9FE}', 230), ('\u{A3C}', '\u{A3C}', 7), ('\u{A4D}', '\u{A4D}', 9), ('\u{ABC}', '\u{ABC}', 7),
+    ('\u{ACD}', '\u{ACD}', 9), ('\u{B3C}', '\u{B3C}', 7), ('\u{B4D}', '\u{B4D}', 9), ('\u{BCD}', '\u{BCD}', 9),
+    ('\u{C3C}', '\u{C3C}', 7), ('\u{C4D}', '\u{C4D}', 9), ('\u{C55}', '\u{C55}', 84), ('\u{C56}', '\u{C56}', 91),
+    ('\u{CBC}', '\u{CBC}', 7), ('\u{CCD}', '\u{CCD}', 9), ('\u{D3B}', '\u{D3C}', 9), ('\u{D4D}', '\u{D4D}', 9),
+    ('\u{DCA}', '\u{DCA}', 9), ('\u{E38}', '\u{E39}', 103), ('\u{E3A}', '\u{E3A}', 9), ('\u{E48}', '\u{E4B}', 107),
+    ('\u{EB8}', '\u{EB9}', 118), ('\u{EBA}', '\u{EBA}', 9), ('\u{EC8}', '\u{ECB}', 122), ('\u{F18}', '\u{F19}', 220),
+    ('\u{F35}', '\u{F35}', 220), ('\u{F37}', '\u{F37}', 220), ('\u{F39}', '\u{F39}', 216), ('\u{F71}', '\u{F71}', 129),
+    ('\u{F72}', '\u{F72}', 130), ('\u{F74}', '\u{F74}', 132), ('\u{F7A}', '\u{F7D}', 130), ('\u{F80}', '\u{F80}', 130),
+    ('\u{F82}', '\u{F83}', 230), ('\u{F84}', '\u{F84}', 9), ('\u{F86}', '\u{F87}', 230), ('\u{FC6}', '\u{FC6}', 220),
+    ('\u{1037}', '\u{1037}', 7), ('\u{1039}', '\u{103A}', 9), ('\u{108D}', '\u{108D}', 220), ('\u{135D}', '\u{135F}', 230),
+    ('\u{1714}', '\u{1715}', 9), ('\u{1734}', '\u{1734}', 9), ('\u{17D2}', '\u{17D2}', 9), ('\u{17DD}', '\u{17DD}', 230),
+    ('\u{18A9}', '\u{18A9}', 228), ('\u{1939}', '\u{1939}', 222), ('\u{193A}', '\u{193A}', 230), ('\u{193B}', '\u{193B}', 220),
+    ('\u{1A17}', '\u{1A17}', 230), ('\u{1A18}', '\u{1A18}', 220), ('\u{1A60}', '\u{1A60}', 9), ('\u{1A75}', '\u{1A7C}', 230),
+    ('\u{1A7F}', '\u{1A7F}', 220), ('\u{1AB0}', '\u{1AB4}', 230), ('\u{1AB5}', '\u{1ABA}', 220), ('\u{1ABB}', '\u{1ABC}', 230),
+    ('\u{1ABD}', '\u{1ABD}', 220), ('\u{1ABF}', '\u{1AC0}', 220), ('\u{1AC1}', '\u{1AC2}', 230), ('\u{1AC3}', '\u{1AC4}', 220),
+    ('\u{1AC5}', '\u{1AC9}', 230), ('\u{1ACA}', '\u{1ACA}', 220), ('\u{1ACB}', '\u{1ACE}', 230), ('\u{1B34}', '\u{1B34}', 7),
+    ('\u{1B44}', '\u{1B44}', 9), ('\u{1B6B}', '\u{1B6B}', 230), ('\u{1B6C}', '\u{1B6C}', 220), ('\u{1B6D}', '\u{1B73}', 230),
+    ('\u{1BAA}', '\u{1BAB}', 9), ('\u{1BE6}', '\u{1BE6}', 7), ('\u{1BF2}', '\u{1BF3}', 9), ('\u{1C37}', '\u{1C37}', 7),
+    ('\u{1CD0}', '\u{1CD2}', 230), ('\u{1CD4}', '\u{1CD4}', 1), ('\u{1CD5}', '\u{1CD9}', 220), ('\u{1CDA}', '\u{1CDB}', 230),
+    ('\u{1CDC}', '\u{1CDF}', 220), ('\u{1CE0}', '\u{1CE0}', 230), ('\u{1CE2}', '\u{1CE8}', 1), ('\u{1CED}', '\u{1CED}', 220),
+    ('\u{1CF4}', '\u{1CF4}', 230), ('\u{1CF8}', '\u{1CF9}', 230), ('\u{1DC0}', '\u{1DC1}', 230), ('\u{1DC2}', '\u{1DC2}', 220),
+    ('\u{1DC3}', '\u{1DC9}', 230), ('\u{1DCA}', '\u{1DCA}', 220), ('\u{1DCB}', '\u{1DCC}', 230), ('\u{1DCD}', '\u{1DCD}', 234),
+    ('\u{1DCE}', '\u{1DCE}', 214), ('\u{1DCF}', '\u{1DCF}', 220), ('\u{1DD0}', '\u{1DD0}', 202), ('\u{1DD1}', '\u{1DF5}', 230),
+    ('\u{1DF6}', '\u{1DF6}', 232), ('\u{1DF7}', '\u{1DF8}', 228), ('\u{1DF9}', '\u{1DF9}', 220), ('\u{1DFA}', '\u{1DFA}', 218),
+    ('\u{1DFB}', '\u{1DFB}', 230), ('\u{1DFC}', '\u{1DFC}', 233), ('\u{1DFD}', '\u{1DFD}', 220), ('\u{1DFE}', '\u{1DFE}', 230),
+    ('\u{1DFF}', '\u{1DFF}', 220), ('\u{20D0}', '\u{20D1}', 230), ('\u{20D2}', '\u{20D3}', 1), ('\u{20D4}', '\u{20D7}', 230),
+    ('\u{20D8}', '\u{20DA}', 1), ('\u{20DB}', '\u{20DC}', 230), ('\u{20E1}', '\u{20E1}', 230), ('\u{20E5}', '\u{20E6}', 1),
+    ('\u{20E7}', '\u{20E7}', 230), ('\u{20E8}', '\u{20E8}', 220), ('\u{20E9}', '\u{20E9}', 230), ('\u{20EA}', '\u{20EB}', 1),
+    ('\u{20EC}', '\u{20EF}', 220), ('\u{20F0}', '\u{20F0}', 230), ('\u{2CEF}', '\u{2CF1}', 230), ('\u{2D7F}', '\u{2D7F}', 9),
+    ('\u{2DE0}', '\u{2DFF}', 230), ('\u{302A}', '\u{302A}', 218), ('\u{302B}', '\u{302B}', 228), ('\u{302C}', '\u{302C}', 232),
+    ('\u{302D}', '\u{302D}', 222), ('\u{302E}', '\u{302F}', 224), ('\u{3099}', '\u{309A}', 8), ('\u{A66F}', '\u{A66F}', 230),
+    ('\u{A674}', '\u{A67D}', 230), ('\u{A69E}', '\u{A69F}', 230), ('\u{A6F0}', '\u{A6F1}', 230), ('\u{A806}', '\u{A806}', 9),
+    ('\u{A82C}', '\u{A82C}', 9), ('\u{A8C4}', '\u{A8C4}', 9), ('\u{A8E0}', '\u{A8F1}', 230), ('\u{A92B}', '\u{A92D}', 220),
+    ('\u{A953}', '\u{A953}', 9), ('\u{A9B3}', '\u{A9B3}', 7), ('\u{A9C0}', '\u{A9C0}', 9), ('\u{AAB0}', '\u{AAB0}', 230),
+    ('\u{AAB2}', '\u{AAB3}', 230), ('\u{AAB4}', '\u{AAB4}', 220), ('\u{AAB7}', '\u{AAB8}', 230), ('\u{AABE}', '\u{AABF}', 230),
+    ('\u{AAC1}', '\u{AAC1}', 230), ('\u{AAF6}', '\u{AAF6}', 9), ('\u{ABED}', '\u{ABED}', 9), ('\u{FB1E}', '\u{FB1E}', 26),
+    ('\u{FE20}', '\u{FE26}', 230), ('\u{FE27}', '\u{FE2D}', 220), ('\u{FE2E}', '\u{FE2F}', 230), ('\u{101FD}', '\u{101FD}', 220),
+    ('\u{102E0}', '\u{102E0}', 220), ('\u{10376}', '\u{1037A}', 230), ('\u{10A0D}', '\u{10A0D}', 220), ('\u{10A0F}', '\u{10A0F}', 230),
+    ('\u{10A38}', '\u{10A38}', 230), ('\u{10A39}', '\u{10A39}', 1), ('\u{10A3A}', '\u{10A3A}', 220), ('\u{10A3F}', '\u{10A3F}', 9),
+    ('\u{10AE5}', '\u{10AE5}', 230), ('\u{10AE6}', '\u{10AE6}', 220), ('\u{10D24}', '\u{10D27}', 230), ('\u{10EAB}', '\u{10EAC}', 230),
+    ('\u{10F46}', '\u{10F47}', 220), ('\u{10F48}', '\u{10F4A}', 230), ('\u{10F4B}', '\u{10F4B}', 220), ('\u{10F4C}', '\u{10F4C}', 230),
+    ('\u{10F4D}', '\u{10F50}', 220), ('\u{10F82}', '\u{10F82}', 230), ('\u{10F83}', '\u{10F83}', 220), ('\u{10F84}', '\u{10F84}', 230),
+    ('\u{10F85}', '\u{10F85}', 220), ('\u{11046}', '\u{11046}', 9), ('\u{11070}', '\u{11070}', 9), ('\u{1107F}', '\u{1107F}', 9),
+    ('\u{110B9}', '\u{110B9}', 9), ('\u{110BA}', '\u{110BA}', 7), ('\u{11100}', '\u{11102}', 230), ('\u{11133}', '\u{11134}', 9),
+    ('\u{11173}', '\u{11173}', 7), ('\u{111C0}', '\u{111C0}', 9), ('\u{111CA}', '\u{111CA}', 7), ('\u{11235}', '\u{11235}', 9),
+    ('\u{11236}', '\u{11236}', 7), ('\u{112E9}', '\u{112E9}', 7), ('\u{112EA}', '\u{112EA}', 9), ('\u{1133B}', '\u{1133C}', 7),
+    ('\u{1134D}', '\u{1134D}', 9), ('\u{11366}', '\u{1136C}', 230), ('\u{11370}', '\u{11374}', 230), ('\u{11442}', '\u{11442}', 9),
+    ('\u{11446}', '\u{11446}', 7), ('\u{1145E}', '\u{1145E}', 230), ('\u{114C2}', '\u{114C2}', 9), ('\u{114C3}', '\u{114C3}', 7),
+    ('\u{115BF}', '\u{115BF}', 9), ('\u{115C0}', '\u{115C0}', 7), ('\u{1163F}', '\u{1163F}', 9), ('\u{116B6}', '\u{116B6}', 9),
+    ('\u{116B7}', '\u{116B7}', 7), ('\u{1172B}', '\u{1172B}', 9), ('\u{11839}', '\u{11839}', 9), ('\u{1183A}', '\u{1183A}', 7),
+    ('\u{1193D}', '\u{1193E}', 9), ('\u{11943}', '\u{11943}', 7), ('\u{119E0}', '\u{119E0}', 9), ('\u{11A34}', '\u{11A34}', 9),
+    ('\u{11A47}', '\u{11A47}', 9), ('\u{11A99}', '\u{11A99}', 9), ('\u{11C3F}', '\u{11C3F}', 9), ('\u{11D42}', '\u{11D42}', 7),
+    ('\u{11D44}', '\u{11D45}', 9), ('\u{11D97}', '\u{11D97}', 9), ('\u{16AF0}', '\u{16AF4}', 1), ('\u{16B30}', '\u{16B36}', 230),
+    ('\u{16FF0}', '\u{16FF1}', 6), ('\u{1BC9E}', '\u{1BC9E}', 1), ('\u{1D165}', '\u{1D166}', 216), ('\u{1D167}', '\u{1D169}', 1),
+    ('\u{1D16D}', '\u{1D16D}', 226), ('\u{1D16E}', '\u{1D172}', 216), ('\u{1D17B}', '\u{1D182}', 220), ('\u{1D185}', '\u{1D189}', 230),
+    ('\u{1D18A}', '\u{1D18B}', 220), ('\u{1D1AA}', '\u{1D1AD}', 230), ('\u{1D242}', '\u{1D244}', 230), ('\u{1E000}', '\u{1E006}', 230),
+    ('\u{1E008}', '\u{1E018}', 230), ('\u{1E01B}', '\u{1E021}', 230), ('\u{1E023}', '\u{1E024}', 230), ('\u{1E026}', '\u{1E02A}', 230),
+    ('\u{1E130}', '\u{1E136}', 230), ('\u{1E2AE}', '\u{1E2AE}', 230), ('\u{1E2EC}', '\u{1E2EF}', 230), ('\u{1E8D0}', '\u{1E8D6}', 220),
+    ('\u{1E944}', '\u{1E949}', 230), ('\u{1E94A}', '\u{1E94A}', 7),
+];
@@ -0,0 +1,197 @@
+//! `--normalize`: Unicode NFC normalization of patterns and input, so that text written with
+//! precomposed characters (`é`) and with combining marks (`e` + U+0301) match each other.
+
+use crate::nfc_tables::{COMBINING_CLASSES, COMPOSITIONS, DECOMPOSITIONS};
+use std::borrow::Cow;
+use std::io::{self, BufRead, Read};
+use std::str;
+
+// Hangul syllables are built from leading consonants, vowels, and optional trailing consonants.
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// `text` in Normalization Form C, borrowed when it already is.
+pub(crate) fn nfc(text: &str) -> Cow<'_, str> {
+    // Nothing below U+0300 decomposes to or combines with anything before it.
+    if text.chars().all(|c| c < '\u{300}') {
+        return Cow::Borrowed(text);
+    }
+    let mut chars = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        decompose(c, &mut chars);
+    }
+    reorder(&mut chars);
+    compose(&mut chars);
+    let normalized: String = chars.into_iter().collect();
+    if normalized == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(normalized)
+    }
+}
+
+/// Pushes the full canonical decomposition of `c`.
+fn decompose(c: char, out: &mut Vec<char>) {
+    let code = u32::from(c);
+    if (S_BASE..S_BASE + S_COUNT).contains(&code) {
+        let index = code - S_BASE;
+        let jamo = |code| char::from_u32(code).expect("Hangul jamo are characters");
+        out.push(jamo(L_BASE + index / N_COUNT));
+        out.push(jamo(V_BASE + index % N_COUNT / T_COUNT));
+        if !index.is_multiple_of(T_COUNT) {
+            out.push(jamo(T_BASE + index % T_COUNT));
+        }
+        return;
+    }
+    match DECOMPOSITIONS.binary_search_by_key(&c, |&(from, _, _)| from) {
+        Ok(found) => {
+            let (_, first, second) = DECOMPOSITIONS[found];
+            decompose(first, out);
+            if let Some(second) = second {
+                decompose(second, out);
+            }
+        }
+        Err(_) => out.push(c),
+    }
+}
+
+fn combining_class(c: char) -> u8 {
+    let found = COMBINING_CLASSES.binary_search_by(|&(start, end, _)| {
+        if c < start {
+            std::cmp::Ordering::Greater
+        } else if c > end {
+            std::cmp::Ordering::Less
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    });
+    found.map_or(0, |index| COMBINING_CLASSES[index].2)
+}
+
+/// Puts each run of combining marks in canonical order, a stable sort by combining class.
+fn reorder(chars: &mut [char]) {
+    let mut start = 0;
+    while start < chars.len() {
+        if combining_class(chars[start]) == 0 {
+            start += 1;
+            continue;
+        }
+        let end = chars[start..]
+            .iter()
+            .position(|&c| combining_class(c) == 0)
+            .map_or(chars.len(), |offset| start + offset);
+        chars[start..end].sort_by_key(|&c| combining_class(c));
+        start = end;
+    }
+}
+
+/// The primary composite of `first` followed by `second`, if there is one.
+fn composite(first: char, second: char) -> Option<char> {
+    let (first_code, second_code) = (u32::from(first), u32::from(second));
+    if (L_BASE..L_BASE + L_COUNT).contains(&first_code)
+        && (V_BASE..V_BASE + V_COUNT).contains(&second_code)
+    {
+        let index = (first_code - L_BASE) * N_COUNT + (second_code - V_BASE) * T_COUNT;
+        return char::from_u32(S_BASE + index);
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&first_code)
+        && (first_code - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&second_code)
+    {
+        return char::from_u32(first_code + second_code - T_BASE);
+    }
+    COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(a, b, _)| (a, b))
+        .ok()
+        .map(|found| COMPOSITIONS[found].2)
+}
+
+/// Composes decomposed, reordered `chars` in place: each mark joins the last starter before
+/// it unless a mark of the same or a higher class comes between them.
+fn compose(chars: &mut Vec<char>) {
+    let Some(&first) = chars.first() else {
+        return;
+    };
+    let mut starter = 0;
+    // A leading mark has no starter, so nothing may compose with it.
+    let mut last_class = match combining_class(first) {
+        0 => 0,
+        _ => u16::MAX,
+    };
+    let mut kept = 1;
+    for index in 1..chars.len() {
+        let c = chars[index];
+        let class = u16::from(combining_class(c));
+        let joined = (last_class < class || last_class == 0)
+            .then(|| composite(chars[starter], c))
+            .flatten();
+        if let Some(joined) = joined {
+            chars[starter] = joined;
+            continue;
+        }
+        if class == 0 {
+            starter = kept;
+        }
+        last_class = class;
+        chars[kept] = c;
+        kept += 1;
+    }
+    chars.truncate(kept);
+}
+
+/// Normalizes its input line by line; lines that are not UTF-8 pass through unchanged.
+pub(crate) struct Normalizer<R> {
+    inner: R,
+    line: Vec<u8>,
+    normalized: Vec<u8>,
+    position: usize,
+}
+
+impl<R: BufRead> Normalizer<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Normalizer {
+            inner,
+            line: Vec::new(),
+            normalized: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Read for Normalizer<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Normalizer<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.normalized.len() {
+            // A line feed is never part of a composition, so lines normalize independently.
+            self.line.clear();
+            self.inner.read_until(b'\n', &mut self.line)?;
+            self.normalized.clear();
+            self.position = 0;
+            match str::from_utf8(&self.line) {
+                Ok(text) => self.normalized.extend_from_slice(nfc(text).as_bytes()),
+                Err(_) => self.normalized.extend_from_slice(&self.line),
+            }
+        }
+        Ok(&self.normalized[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.normalized.len());
+    }
+}
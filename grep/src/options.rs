@@ -0,0 +1,1131 @@
+//! The single table of command-line options.
+//!
+//! Parsing, `--help`, `--help-long`, the man page, and the generated shell completions all read
+//! from [`OPTIONS`], so adding a flag here is enough for it to show up everywhere.
+
+/// What an option does once parsed; [`crate::Config::parse`] matches on this exhaustively.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Flag {
+    IgnoreCase,
+    SmartCase,
+    CaseSensitive,
+    LineNumber,
+    ByteOffset,
+    Column,
+    ByteColumns,
+    InvertMatch,
+    Recursive,
+    Sort,
+    Include,
+    Exclude,
+    Type,
+    TypeNot,
+    TypeAdd,
+    TypeList,
+    FilesFrom,
+    Files,
+    Explain,
+    SearchNames,
+    NamesOnly,
+    Serve,
+    IndexBuild,
+    IndexQuery,
+    Hidden,
+    NoIgnore,
+    IgnoreFile,
+    Git,
+    Follow,
+    MaxDepth,
+    MaxFilesize,
+    NewerThan,
+    OlderThan,
+    Devices,
+    Verbose,
+    NoFollow,
+    NoDedupe,
+    WithFilename,
+    NoFilename,
+    Label,
+    Heading,
+    Table,
+    Color,
+    ColorWhen,
+    Colors,
+    Hyperlink,
+    HyperlinkFormat,
+    ContextDim,
+    MaxCount,
+    MaxTotal,
+    FilesWithMatches,
+    FilesWithoutMatch,
+    Count,
+    CountMatches,
+    Total,
+    Unique,
+    GroupByDir,
+    Summary,
+    Stats,
+    LineBuffered,
+    Watch,
+    Tail,
+    Tui,
+    Quiet,
+    NoMessages,
+    Null,
+    NullData,
+    AfterContext,
+    BeforeContext,
+    Context,
+    FieldSeparator,
+    ContextSeparator,
+    NoGroupSeparator,
+    MaxColumns,
+    MaxColumnsPreview,
+    OnlyMatching,
+    OContext,
+    Multiline,
+    MaxBuffer,
+    MaxMemory,
+    Pattern,
+    PatternFile,
+    PatternFrom,
+    Rules,
+    AllOf,
+    Within,
+    WithinEnd,
+    AnyOf,
+    NoneOf,
+    WordRegexp,
+    LineRegexp,
+    StartsWith,
+    EndsWith,
+    Timeout,
+    Deadline,
+    Regexp,
+    FixedStrings,
+    DebugPattern,
+    Pcre,
+    Binary,
+    Encoding,
+    Normalize,
+    SearchZip,
+    Archives,
+    Mmap,
+    NoMmap,
+    Engine,
+    Trace,
+    Cache,
+    CacheClear,
+    Threads,
+    Replace,
+    Write,
+    DryRun,
+    Interactive,
+    Diff,
+    Trim,
+    Json,
+    Vimgrep,
+    Format,
+    LineRange,
+    HeadLines,
+    HeadBytes,
+    NoConfig,
+    GenerateCompletions,
+    GenerateMan,
+    HelpLong,
+    Help,
+}
+
+pub(crate) struct OptionSpec {
+    pub(crate) flag: Flag,
+    pub(crate) short: Option<char>,
+    pub(crate) long: Option<&'static str>,
+    /// Placeholder for the option's value, such as `N`; `None` for plain switches.
+    pub(crate) value: Option<&'static str>,
+    /// The only accepted values, offered by shell completion.
+    pub(crate) choices: &'static [&'static str],
+    pub(crate) help: &'static str,
+    /// Left out of `--help`.
+    pub(crate) hidden: bool,
+}
+
+impl OptionSpec {
+    /// The option as written in usage text, e.g. `-s, --no-messages` or `--sort KEY`.
+    pub(crate) fn synopsis(&self) -> String {
+        let names: Vec<String> = self
+            .short
+            .map(|short| format!("-{}", short))
+            .into_iter()
+            .chain(self.long.map(|long| format!("--{}", long)))
+            .collect();
+        match self.value {
+            Some(value) => format!("{} {}", names.join(", "), value),
+            None => names.join(", "),
+        }
+    }
+}
+
+const fn switch(
+    flag: Flag,
+    short: Option<char>,
+    long: Option<&'static str>,
+    help: &'static str,
+) -> OptionSpec {
+    OptionSpec {
+        flag,
+        short,
+        long,
+        value: None,
+        choices: &[],
+        help,
+        hidden: false,
+    }
+}
+
+const fn valued(
+    flag: Flag,
+    short: Option<char>,
+    long: Option<&'static str>,
+    value: &'static str,
+    help: &'static str,
+) -> OptionSpec {
+    OptionSpec {
+        flag,
+        short,
+        long,
+        value: Some(value),
+        choices: &[],
+        help,
+        hidden: false,
+    }
+}
+
+pub(crate) const SORT_KEYS: &[&str] = &["path", "mtime", "modified", "size", "none"];
+pub(crate) const ENCODINGS: &[&str] = &["utf-8", "utf-16le", "utf-16be", "latin1"];
+pub(crate) const DEVICE_ACTIONS: &[&str] = &["skip", "read"];
+pub(crate) const COLOR_WHEN: &[&str] = &["auto", "always", "never"];
+pub(crate) const ENGINES: &[&str] = &["auto", "stream", "mmap"];
+pub(crate) const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+/// Every option, in the order `--help` lists them.
+pub(crate) const OPTIONS: &[OptionSpec] = &[
+    switch(
+        Flag::IgnoreCase,
+        Some('i'),
+        Some("ignore-case"),
+        "Case-insensitive search",
+    ),
+    switch(
+        Flag::SmartCase,
+        Some('S'),
+        Some("smart-case"),
+        "Case-insensitive unless the pattern contains an uppercase letter",
+    ),
+    switch(
+        Flag::CaseSensitive,
+        None,
+        Some("case-sensitive"),
+        "Case-sensitive search, overriding an earlier -i or -S (default)",
+    ),
+    switch(
+        Flag::LineNumber,
+        Some('n'),
+        Some("line-number"),
+        "Print line numbers",
+    ),
+    switch(
+        Flag::ByteOffset,
+        Some('b'),
+        Some("byte-offset"),
+        "Print the 0-based byte offset of each line",
+    ),
+    switch(
+        Flag::Column,
+        None,
+        Some("column"),
+        "Print the 1-based column, in characters, of each line's first match",
+    ),
+    switch(
+        Flag::ByteColumns,
+        None,
+        Some("byte-columns"),
+        "Like --column, but count the column in bytes",
+    ),
+    switch(
+        Flag::InvertMatch,
+        Some('v'),
+        Some("invert-match"),
+        "Invert match (exclude lines that match the pattern)",
+    ),
+    switch(
+        Flag::Recursive,
+        Some('r'),
+        Some("recursive"),
+        "Recursive directory search",
+    ),
+    OptionSpec {
+        choices: SORT_KEYS,
+        ..valued(
+            Flag::Sort,
+            None,
+            Some("sort"),
+            "KEY",
+            "Order recursive results by path, mtime (newest first), size (largest first), or none",
+        )
+    },
+    valued(
+        Flag::Include,
+        None,
+        Some("include"),
+        "GLOB",
+        "With -r, only search files matching GLOB (repeatable)",
+    ),
+    valued(
+        Flag::Exclude,
+        None,
+        Some("exclude"),
+        "GLOB",
+        "With -r, skip files matching GLOB (repeatable)",
+    ),
+    valued(
+        Flag::Type,
+        Some('t'),
+        Some("type"),
+        "TYPE",
+        "With -r, only search files of TYPE, such as rust or py (repeatable)",
+    ),
+    valued(
+        Flag::TypeNot,
+        Some('T'),
+        Some("type-not"),
+        "TYPE",
+        "With -r, skip files of TYPE (repeatable)",
+    ),
+    valued(
+        Flag::TypeAdd,
+        None,
+        Some("type-add"),
+        "NAME:GLOB",
+        "Add GLOB (or comma-separated globs) to file type NAME, creating it if needed",
+    ),
+    switch(
+        Flag::TypeList,
+        None,
+        Some("type-list"),
+        "List the file types and their globs",
+    ),
+    valued(
+        Flag::FilesFrom,
+        None,
+        Some("files-from"),
+        "FILE",
+        "Also search the files listed in FILE (- for stdin), filtered like walked files",
+    ),
+    switch(
+        Flag::Files,
+        None,
+        Some("files"),
+        "Print the files that would be searched, without searching; takes no pattern",
+    ),
+    valued(
+        Flag::Explain,
+        None,
+        Some("explain"),
+        "PATH",
+        "Say why PATH would or would not be searched (repeatable); takes no pattern",
+    ),
+    switch(
+        Flag::SearchNames,
+        None,
+        Some("search-names"),
+        "Also match the pattern against file paths, printing each matching path",
+    ),
+    switch(
+        Flag::NamesOnly,
+        None,
+        Some("names-only"),
+        "Match the pattern against file paths only, like find | grep",
+    ),
+    valued(
+        Flag::Serve,
+        None,
+        Some("serve"),
+        "ADDRESS",
+        "Answer HTTP GET /search?query=... requests on ADDRESS with JSON results",
+    ),
+    // What `grep index build` and `grep index query` are rewritten to.
+    OptionSpec {
+        hidden: true,
+        ..switch(
+            Flag::IndexBuild,
+            None,
+            Some("index-build"),
+            "Same as grep index build",
+        )
+    },
+    OptionSpec {
+        hidden: true,
+        ..switch(
+            Flag::IndexQuery,
+            None,
+            Some("index-query"),
+            "Same as grep index query",
+        )
+    },
+    switch(
+        Flag::Hidden,
+        None,
+        Some("hidden"),
+        "With -r, also search hidden files and directories",
+    ),
+    switch(
+        Flag::NoIgnore,
+        None,
+        Some("no-ignore"),
+        "With -r, also search files excluded by .gitignore, .ignore, or .rgignore",
+    ),
+    valued(
+        Flag::IgnoreFile,
+        None,
+        Some("ignore-file"),
+        "FILE",
+        "With -r, also skip paths matched by the gitignore-style rules in FILE",
+    ),
+    switch(
+        Flag::Git,
+        None,
+        Some("git"),
+        "Search only the files git tracks under each directory, instead of walking it",
+    ),
+    switch(
+        Flag::Follow,
+        None,
+        Some("follow"),
+        "With -r, descend into symlinked directories",
+    ),
+    switch(
+        Flag::NoFollow,
+        None,
+        Some("no-follow"),
+        "With -r, skip symlinked directories (default)",
+    ),
+    switch(
+        Flag::NoDedupe,
+        None,
+        Some("no-dedupe"),
+        "Search a file again each time it is named or reached by another path",
+    ),
+    valued(
+        Flag::MaxDepth,
+        None,
+        Some("max-depth"),
+        "N",
+        "With -r, descend at most N directory levels below each input",
+    ),
+    valued(
+        Flag::MaxFilesize,
+        None,
+        Some("max-filesize"),
+        "SIZE",
+        "Skip walked files larger than SIZE bytes (K, M, or G suffixes allowed)",
+    ),
+    valued(
+        Flag::NewerThan,
+        None,
+        Some("newer-than"),
+        "AGE",
+        "Only search walked files modified within AGE, such as 30m, 2d, or 1h30m",
+    ),
+    valued(
+        Flag::OlderThan,
+        None,
+        Some("older-than"),
+        "AGE",
+        "Only search walked files last modified more than AGE ago",
+    ),
+    OptionSpec {
+        choices: DEVICE_ACTIONS,
+        ..valued(
+            Flag::Devices,
+            Some('D'),
+            Some("devices"),
+            "ACTION",
+            "Skip (the default) or read devices and sockets named as inputs",
+        )
+    },
+    switch(
+        Flag::Verbose,
+        None,
+        Some("verbose"),
+        "Report files skipped for their size or age, or as duplicates, on stderr",
+    ),
+    switch(
+        Flag::WithFilename,
+        Some('H'),
+        Some("with-filename"),
+        "Print filenames, even for a single input",
+    ),
+    OptionSpec {
+        hidden: true,
+        ..switch(Flag::WithFilename, Some('f'), None, "Print filenames")
+    },
+    switch(
+        Flag::NoFilename,
+        None,
+        Some("no-filename"),
+        "Never print filenames, even for several inputs",
+    ),
+    valued(
+        Flag::Label,
+        None,
+        Some("label"),
+        "NAME",
+        "Name standard input NAME wherever a filename is shown, instead of (standard input)",
+    ),
+    switch(
+        Flag::Heading,
+        None,
+        Some("heading"),
+        "Print each file's name once above its numbered, indented matches",
+    ),
+    switch(
+        Flag::Table,
+        None,
+        Some("table"),
+        "Line results up in columns: file names padded, line numbers right-aligned",
+    ),
+    switch(
+        Flag::Color,
+        Some('c'),
+        None,
+        "Color output when printing to a terminal (same as --color auto)",
+    ),
+    OptionSpec {
+        choices: COLOR_WHEN,
+        ..valued(
+            Flag::ColorWhen,
+            None,
+            Some("color"),
+            "WHEN",
+            "Color output: auto, always, or never (default: never)",
+        )
+    },
+    valued(
+        Flag::Colors,
+        None,
+        Some("colors"),
+        "SPEC",
+        "Colors as GREP_COLORS-style SGR codes, e.g. ms=01;31:fn=35:ln=32",
+    ),
+    OptionSpec {
+        choices: COLOR_WHEN,
+        ..valued(
+            Flag::Hyperlink,
+            None,
+            Some("hyperlink"),
+            "WHEN",
+            "Make printed file paths clickable links: auto, always, or never (default: never)",
+        )
+    },
+    valued(
+        Flag::HyperlinkFormat,
+        None,
+        Some("hyperlink-format"),
+        "FORMAT",
+        "Where --hyperlink links point: file, vscode, cursor, idea, or a format with {path}",
+    ),
+    switch(
+        Flag::ContextDim,
+        None,
+        Some("context-dim"),
+        "With -c, dim the parts of matching lines outside the match",
+    ),
+    valued(
+        Flag::MaxCount,
+        Some('m'),
+        Some("max-count"),
+        "N",
+        "Stop reading a file after N selected lines",
+    ),
+    valued(
+        Flag::MaxTotal,
+        None,
+        Some("max-total"),
+        "N",
+        "Stop the whole search after N selected lines across all files",
+    ),
+    switch(
+        Flag::FilesWithMatches,
+        Some('l'),
+        Some("files-with-matches"),
+        "Only print the names of files that contain a match",
+    ),
+    switch(
+        Flag::FilesWithoutMatch,
+        Some('L'),
+        Some("files-without-match"),
+        "Only print the names of files that contain no match",
+    ),
+    switch(
+        Flag::Count,
+        None,
+        Some("count"),
+        "Print the number of matching lines per file instead of the lines",
+    ),
+    switch(
+        Flag::CountMatches,
+        None,
+        Some("count-matches"),
+        "Like --count, but count every match, not just matching lines",
+    ),
+    switch(
+        Flag::Total,
+        None,
+        Some("total"),
+        "With --count, finish with a total:N line summing every file",
+    ),
+    switch(
+        Flag::Unique,
+        None,
+        Some("unique"),
+        "Report each distinct matching line once per file (also for --count)",
+    ),
+    switch(
+        Flag::GroupByDir,
+        None,
+        Some("group-by-dir"),
+        "Instead of lines, print a tree of directories with their matching files and matches",
+    ),
+    switch(
+        Flag::Summary,
+        None,
+        Some("summary"),
+        "Print how many files matched out of those searched to stderr",
+    ),
+    switch(
+        Flag::Stats,
+        None,
+        Some("stats"),
+        "Print files, matched lines, bytes, and time spent to stderr at the end",
+    ),
+    switch(
+        Flag::LineBuffered,
+        None,
+        Some("line-buffered"),
+        "Flush output after every line, even when stdout is not a terminal",
+    ),
+    switch(
+        Flag::Watch,
+        None,
+        Some("watch"),
+        "Keep running, and search again whenever an input file changes",
+    ),
+    switch(
+        Flag::Tail,
+        None,
+        Some("tail"),
+        "Keep running, and print matching lines as they are appended to the files",
+    ),
+    switch(
+        Flag::Tui,
+        None,
+        Some("tui"),
+        "Browse the results interactively, filtering and opening them in $EDITOR",
+    ),
+    switch(
+        Flag::Quiet,
+        Some('q'),
+        Some("quiet"),
+        "Print nothing; exit 0 on a match, 1 on none, 2 on errors",
+    ),
+    switch(
+        Flag::NoMessages,
+        Some('s'),
+        Some("no-messages"),
+        "Suppress error messages about unreadable files",
+    ),
+    switch(
+        Flag::Null,
+        Some('Z'),
+        Some("null"),
+        "Follow every printed filename with a NUL byte instead of a separator",
+    ),
+    switch(
+        Flag::NullData,
+        None,
+        Some("null-data"),
+        "Treat input and output records as NUL-terminated instead of lines",
+    ),
+    valued(
+        Flag::AfterContext,
+        Some('A'),
+        Some("after-context"),
+        "N",
+        "Print N lines of context after each matching line",
+    ),
+    valued(
+        Flag::BeforeContext,
+        Some('B'),
+        Some("before-context"),
+        "N",
+        "Print N lines of context before each matching line",
+    ),
+    valued(
+        Flag::Context,
+        Some('C'),
+        Some("context"),
+        "N",
+        "Print N lines of context around each matching line",
+    ),
+    valued(
+        Flag::FieldSeparator,
+        None,
+        Some("field-separator"),
+        "STR",
+        "Separator between prefix fields and the line (default ':')",
+    ),
+    valued(
+        Flag::ContextSeparator,
+        None,
+        Some("context-separator"),
+        "STR",
+        "Line between non-contiguous context groups (default '--')",
+    ),
+    switch(
+        Flag::NoGroupSeparator,
+        None,
+        Some("no-group-separator"),
+        "Print nothing between context groups",
+    ),
+    valued(
+        Flag::MaxColumns,
+        None,
+        Some("max-columns"),
+        "N",
+        "Truncate printed lines to N characters around the first match (0 = no limit)",
+    ),
+    switch(
+        Flag::MaxColumnsPreview,
+        None,
+        Some("max-columns-preview"),
+        "With --max-columns, keep the start of each long line instead of centring on the match",
+    ),
+    switch(
+        Flag::OnlyMatching,
+        Some('o'),
+        Some("only-matching"),
+        "Print each match on its own line instead of the whole line",
+    ),
+    valued(
+        Flag::OContext,
+        None,
+        Some("o-context"),
+        "N",
+        "With -o, widen each printed match by N characters on both sides",
+    ),
+    switch(
+        Flag::Multiline,
+        Some('U'),
+        Some("multiline"),
+        "Allow matches to span line boundaries",
+    ),
+    valued(
+        Flag::MaxBuffer,
+        None,
+        Some("max-buffer"),
+        "N",
+        "Largest file, in bytes, buffered by --multiline",
+    ),
+    valued(
+        Flag::MaxMemory,
+        None,
+        Some("max-memory"),
+        "SIZE",
+        "Stream files over SIZE rather than map them, and skip any that would be held whole",
+    ),
+    valued(
+        Flag::Pattern,
+        Some('e'),
+        Some("pattern"),
+        "PATTERN",
+        "Search for PATTERN; repeat to match lines containing any of them",
+    ),
+    valued(
+        Flag::PatternFile,
+        None,
+        Some("file"),
+        "FILE",
+        "Read patterns from FILE, one per line",
+    ),
+    valued(
+        Flag::PatternFrom,
+        None,
+        Some("pattern-from"),
+        "FILE",
+        "Read one pattern, exactly as written, from FILE (- for stdin)",
+    ),
+    valued(
+        Flag::Rules,
+        None,
+        Some("rules"),
+        "FILE",
+        "Search for every named rule in the TOML file FILE, reporting which matched",
+    ),
+    valued(
+        Flag::AllOf,
+        None,
+        Some("all-of"),
+        "PATTERN",
+        "Only select lines that also match PATTERN (repeatable)",
+    ),
+    valued(
+        Flag::Within,
+        None,
+        Some("within"),
+        "REGEX",
+        "Only search lines between a line matching REGEX and the next, or --within-end's",
+    ),
+    valued(
+        Flag::WithinEnd,
+        None,
+        Some("within-end"),
+        "REGEX",
+        "End --within regions at lines matching REGEX, letting them nest",
+    ),
+    valued(
+        Flag::AnyOf,
+        None,
+        Some("any-of"),
+        "PATTERN",
+        "Only select lines that also match at least one --any-of PATTERN (repeatable)",
+    ),
+    valued(
+        Flag::NoneOf,
+        None,
+        Some("none-of"),
+        "PATTERN",
+        "Only select lines that do not match PATTERN (repeatable)",
+    ),
+    switch(
+        Flag::WordRegexp,
+        Some('w'),
+        Some("word-regexp"),
+        "Only match the pattern as a whole word",
+    ),
+    switch(
+        Flag::LineRegexp,
+        Some('x'),
+        Some("line-regexp"),
+        "Only match the pattern against the whole line",
+    ),
+    switch(
+        Flag::StartsWith,
+        None,
+        Some("starts-with"),
+        "Only match the pattern at the start of a line",
+    ),
+    switch(
+        Flag::EndsWith,
+        None,
+        Some("ends-with"),
+        "Only match the pattern at the end of a line",
+    ),
+    valued(
+        Flag::Timeout,
+        None,
+        Some("timeout"),
+        "MS",
+        "Skip a file once searching it takes longer than MS milliseconds (0 = off)",
+    ),
+    valued(
+        Flag::Deadline,
+        None,
+        Some("deadline"),
+        "MS",
+        "Stop the whole run after MS milliseconds, leaving the files not yet searched (0 = off)",
+    ),
+    switch(
+        Flag::Regexp,
+        Some('E'),
+        Some("regexp"),
+        "Treat the pattern as a regular expression",
+    ),
+    switch(
+        Flag::FixedStrings,
+        Some('F'),
+        Some("fixed-strings"),
+        "Treat the pattern as a literal string (default)",
+    ),
+    switch(
+        Flag::Pcre,
+        Some('P'),
+        Some("pcre"),
+        "Treat the pattern as a regex with Perl-style look-around",
+    ),
+    // The spelling ripgrep and GNU grep users may reach for.
+    OptionSpec {
+        hidden: true,
+        ..switch(Flag::Pcre, None, Some("pcre2"), "Same as -P")
+    },
+    switch(
+        Flag::DebugPattern,
+        None,
+        Some("debug-pattern"),
+        "Print the compiled pattern, its flags, and the matching engine to stderr",
+    ),
+    switch(
+        Flag::Binary,
+        Some('a'),
+        Some("binary"),
+        "Search binary files as text instead of just noting that they match",
+    ),
+    OptionSpec {
+        choices: ENCODINGS,
+        ..valued(
+            Flag::Encoding,
+            None,
+            Some("encoding"),
+            "NAME",
+            "Read inputs as utf-8, utf-16le, utf-16be, or latin1 (default: detect a BOM)",
+        )
+    },
+    switch(
+        Flag::Normalize,
+        None,
+        Some("normalize"),
+        "Apply Unicode NFC normalization to patterns and input, so composed and decomposed forms match",
+    ),
+    switch(
+        Flag::SearchZip,
+        Some('z'),
+        Some("search-zip"),
+        "Search the contents of gzip, bzip2, and xz compressed files",
+    ),
+    switch(
+        Flag::Archives,
+        None,
+        Some("archives"),
+        "Search each file inside zip and tar archives",
+    ),
+    switch(
+        Flag::Mmap,
+        None,
+        Some("mmap"),
+        "Memory-map files instead of reading them; the same as --engine mmap",
+    ),
+    switch(
+        Flag::NoMmap,
+        None,
+        Some("no-mmap"),
+        "Never memory-map files; the same as --engine stream",
+    ),
+    OptionSpec {
+        choices: ENGINES,
+        ..valued(
+            Flag::Engine,
+            None,
+            Some("engine"),
+            "ENGINE",
+            "Read files through a buffer (stream), memory-mapped (mmap), or as suits each (auto)",
+        )
+    },
+    switch(
+        Flag::Trace,
+        None,
+        Some("trace"),
+        "Report on stderr how each input is read, and why",
+    ),
+    switch(
+        Flag::Cache,
+        None,
+        Some("cache"),
+        "Keep each file's results, and reuse them while the file is unchanged",
+    ),
+    switch(
+        Flag::CacheClear,
+        None,
+        Some("cache-clear"),
+        "Remove everything --cache has kept",
+    ),
+    valued(
+        Flag::Threads,
+        None,
+        Some("threads"),
+        "N",
+        "Search up to N files at once (default: one per CPU); output order is unchanged",
+    ),
+    valued(
+        Flag::Replace,
+        None,
+        Some("replace"),
+        "TEMPLATE",
+        "Print matching lines with each match replaced by TEMPLATE ($1 for groups)",
+    ),
+    switch(
+        Flag::Write,
+        None,
+        Some("write"),
+        "Apply --replace to the files themselves instead of printing",
+    ),
+    switch(
+        Flag::DryRun,
+        None,
+        Some("dry-run"),
+        "With --write, print a diff of the changes without touching the files",
+    ),
+    switch(
+        Flag::Interactive,
+        None,
+        Some("interactive"),
+        "With --write, show each change and ask whether to make it (y, n, a for all, q to stop)",
+    ),
+    switch(
+        Flag::Diff,
+        None,
+        Some("diff"),
+        "Show what --replace would change as a unified diff",
+    ),
+    switch(
+        Flag::Trim,
+        None,
+        Some("trim"),
+        "Strip leading and trailing whitespace from printed lines",
+    ),
+    switch(
+        Flag::Json,
+        None,
+        Some("json"),
+        "Print each matching and context line as a JSON object",
+    ),
+    switch(
+        Flag::Vimgrep,
+        None,
+        Some("vimgrep"),
+        "Print file:line:column:line once per match, for editors' quickfix lists",
+    ),
+    valued(
+        Flag::Format,
+        None,
+        Some("format"),
+        "TEMPLATE",
+        "Print TEMPLATE once per match, filling in {file}, {line}, {column}, and groups like {1}; or csv, tsv, or sarif",
+    ),
+    valued(
+        Flag::LineRange,
+        None,
+        Some("line-range"),
+        "START:END",
+        "Only search lines START through END; either end may be left open",
+    ),
+    valued(
+        Flag::HeadLines,
+        None,
+        Some("head-lines"),
+        "N",
+        "Only read the first N lines of each input",
+    ),
+    valued(
+        Flag::HeadBytes,
+        None,
+        Some("head-bytes"),
+        "SIZE",
+        "Only read the first SIZE bytes of each input (K, M, or G suffixes allowed)",
+    ),
+    switch(
+        Flag::NoConfig,
+        None,
+        Some("no-config"),
+        "Ignore the configuration file of default flags",
+    ),
+    OptionSpec {
+        choices: SHELLS,
+        hidden: true,
+        ..valued(
+            Flag::GenerateCompletions,
+            None,
+            Some("generate-completions"),
+            "SHELL",
+            "Print a completion script for bash, zsh, fish, or powershell",
+        )
+    },
+    OptionSpec {
+        hidden: true,
+        ..switch(
+            Flag::GenerateMan,
+            None,
+            Some("generate-man"),
+            "Print a roff man page of every option, with examples",
+        )
+    },
+    switch(
+        Flag::HelpLong,
+        None,
+        Some("help-long"),
+        "Show every option with its accepted values, examples, and exit statuses",
+    ),
+    switch(Flag::Help, Some('h'), Some("help"), "Show help information"),
+];
+
+/// A command line shown in `--help-long` and the man page.
+pub(crate) struct Example {
+    pub(crate) command: &'static str,
+    pub(crate) description: &'static str,
+}
+
+/// Options used together, in the order `--help-long` lists them.
+pub(crate) const EXAMPLES: &[Example] = &[
+    Example {
+        command: "grep -rn TODO src",
+        description: "Every TODO under src, with its file and line number",
+    },
+    Example {
+        command: "grep -ri --type rust -w unsafe .",
+        description: "Whole-word, case-insensitive uses of unsafe in Rust files only",
+    },
+    Example {
+        command: "grep -rl --exclude '*.lock' serde .",
+        description: "Just the names of the files that mention serde, skipping lock files",
+    },
+    Example {
+        command: "grep -C 2 -n panic src/main.rs",
+        description: "Each panic with two lines of context either side",
+    },
+    Example {
+        command: "grep -E -o '[0-9]+[.][0-9]+[.][0-9]+' CHANGELOG.md",
+        description: "Only the version numbers, one per line",
+    },
+    Example {
+        command: "grep -E --count -v '^(#|$)' .gitignore",
+        description: "How many lines are neither comments nor blank",
+    },
+    Example {
+        command: "grep -r -E --replace '${1}_v2' --diff 'fn (\\w+)_v1' src",
+        description: "Preview renaming every _v1 function as a diff; add --write to apply it",
+    },
+    Example {
+        command: "journalctl -b | grep -i --color=auto error",
+        description: "Search standard input, highlighting matches on a terminal",
+    },
+    Example {
+        command: "grep -r --json -e main --sort path src",
+        description: "Machine-readable results, in path order",
+    },
+];
+
+/// Finds the option spelled exactly `arg`, such as `-i` or `--sort`. `--sort=size` is split
+/// by the parser before it gets here.
+pub(crate) fn lookup(arg: &str) -> Option<&'static OptionSpec> {
+    if let Some(long) = arg.strip_prefix("--") {
+        return OPTIONS.iter().find(|spec| spec.long == Some(long));
+    }
+    let mut chars = arg.strip_prefix('-')?.chars();
+    let short = chars.next()?;
+    if chars.next().is_some() {
+        return None;
+    }
+    lookup_short(short)
+}
+
+pub(crate) fn lookup_short(short: char) -> Option<&'static OptionSpec> {
+    OPTIONS.iter().find(|spec| spec.short == Some(short))
+}
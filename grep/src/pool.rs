@@ -0,0 +1,56 @@
+//! A small worker pool that keeps results in input order.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+
+/// Runs `work` on every item across `threads` threads and hands each item and its result to
+/// `emit` in the order of `items`, as soon as it and every earlier result are ready. Items are
+/// taken as workers are free for them, so `items` may still be producing them.
+pub(crate) fn for_each_ordered<T, R, W, E>(
+    items: impl Iterator<Item = T> + Send,
+    threads: usize,
+    work: W,
+    mut emit: E,
+) where
+    T: Send,
+    R: Send,
+    W: Fn(&T) -> R + Sync,
+    E: FnMut(T, R),
+{
+    let items = Mutex::new(items.enumerate());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (items, work) = (&items, &work);
+            scope.spawn(move || {
+                loop {
+                    // Numbered under the lock, so the numbers follow the order of `items`.
+                    let next = items.lock().unwrap().next();
+                    let Some((index, item)) = next else {
+                        break;
+                    };
+                    let result = work(&item);
+                    if sender.send((index, item, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Hold results that finish early until everything before them has been emitted.
+        let mut pending = BTreeMap::new();
+        let mut expected = 0;
+        for (index, item, result) in receiver {
+            pending.insert(index, (item, result));
+            while let Some((item, result)) = pending.remove(&expected) {
+                emit(item, result);
+                expected += 1;
+            }
+        }
+    });
+}
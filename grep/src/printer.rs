@@ -0,0 +1,786 @@
+use crate::config::Config;
+use crate::format::Template;
+use crate::json;
+use crate::matcher::Matcher;
+use crate::sarif;
+use crate::search::{LongText, MatchSink, SinkMatch};
+use crate::table;
+use crate::theme::{self, Theme};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// Formats selected lines for the terminal, the way the CLI prints them, into `out`.
+pub(crate) struct Printer<'a, W> {
+    config: &'a Config,
+    out: W,
+    count: usize,
+    /// Matches on the selected lines, for `--count-matches` and `--stats`.
+    matches: usize,
+    /// Lines already reported for this file, kept only when `--unique` is set.
+    seen: HashSet<String>,
+    /// Whether this file's `--heading` header has been written.
+    heading_written: bool,
+}
+
+impl<'a, W: Write> Printer<'a, W> {
+    pub(crate) fn new(config: &'a Config, out: W) -> Self {
+        Printer {
+            config,
+            out,
+            count: 0,
+            matches: 0,
+            seen: HashSet::new(),
+            heading_written: false,
+        }
+    }
+
+    /// Number of lines selected so far.
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Number of matches on those lines; a line selected by `-v` counts once.
+    pub(crate) fn matches(&self) -> usize {
+        self.matches
+    }
+}
+
+impl<W: Write> MatchSink for Printer<'_, W> {
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+        let config = self.config;
+        if config.unique && !self.seen.insert(found.line.to_string()) {
+            return Ok(true);
+        }
+
+        self.count += 1;
+        self.matches += found.spans.len().max(1);
+        if config.quiet {
+            // Nothing is printed, and one selected line already decides the exit status.
+            return Ok(false);
+        }
+        if config.files_without_match {
+            // One hit rules the file out; `run` lists it only if nothing was selected.
+            return Ok(false);
+        }
+        if config.list_files {
+            // One hit is enough to list the file, so skip the rest of it.
+            if let Some(path) = found.path {
+                print_filename(&mut self.out, path, config)?;
+            }
+            return Ok(false);
+        }
+
+        if config.count || config.group_by_dir {
+            return Ok(true);
+        }
+        if config.json {
+            json::write_line(&mut self.out, "match", found)?;
+            return Ok(true);
+        }
+        if config.sarif {
+            sarif::write_results(&mut self.out, found, config)?;
+            return Ok(true);
+        }
+        if config.vimgrep {
+            self.print_vimgrep(found)?;
+            return Ok(true);
+        }
+        if let Some(template) = &config.format {
+            self.print_format(found, template)?;
+            return Ok(true);
+        }
+        self.write_heading(found.path)?;
+        if config.only_matching {
+            self.print_only_matching(found)?;
+            return Ok(true);
+        }
+
+        let display_line = match config.replace.as_deref() {
+            Some(template) if !found.spans.is_empty() => {
+                let replaced = config.matcher.replace_template(found.line, template);
+                let replaced = if config.trim {
+                    replaced.trim()
+                } else {
+                    &replaced
+                };
+                // Match positions no longer line up with the rewritten text, so nothing is coloured.
+                render_line(replaced, &[], false, config)
+            }
+            _ => {
+                let highlight = config.colored && !found.spans.is_empty();
+                let (text, spans) = displayed(found.line, &found.spans, config);
+                render_line(text, &spans, highlight, config)
+            }
+        };
+        print_line(
+            &mut self.out,
+            found,
+            &display_line,
+            &config.field_separator,
+            config,
+        )?;
+        Ok(true)
+    }
+
+    fn context(&mut self, line: &SinkMatch<'_>) -> io::Result<bool> {
+        let config = self.config;
+        if !prints_context(config) {
+            // -o and --vimgrep print matches only, so context lines have nothing to show.
+        } else if config.json {
+            json::write_line(&mut self.out, "context", line)?;
+        } else {
+            self.write_heading(line.path)?;
+            // With -v the context lines are the matching ones, so their matches are coloured.
+            let highlight = config.colored && config.invert_match;
+            let spans: Vec<_> = if highlight {
+                config.matcher.find_iter(line.line).collect()
+            } else {
+                Vec::new()
+            };
+            let (text, spans) = displayed(line.line, &spans, config);
+            let display_line = render_line(text, &spans, highlight, config);
+            print_line(
+                &mut self.out,
+                line,
+                &display_line,
+                CONTEXT_SEPARATOR,
+                config,
+            )?;
+        }
+        Ok(true)
+    }
+
+    fn binary_matched(&mut self, path: Option<&Path>) -> io::Result<()> {
+        let config = self.config;
+        self.count += 1;
+        self.matches += 1;
+        match path {
+            _ if config.quiet => Ok(()),
+            Some(path) if config.list_files => print_filename(&mut self.out, path, config),
+            _ if !prints_lines(config) => Ok(()),
+            path if config.json => json::write_binary(&mut self.out, path),
+            path if config.sarif => sarif::write_binary(&mut self.out, path),
+            Some(path) if uses_heading(config) => {
+                self.write_heading(Some(path))?;
+                writeln!(self.out, "{}binary file matches", HEADING_INDENT)
+            }
+            Some(path) => writeln!(self.out, "{}: binary file matches", path_label(path)),
+            None => writeln!(self.out, "binary file matches"),
+        }
+    }
+
+    /// A long line is printed a piece at a time where it is printed as it stands, and read whole
+    /// for anything that needs all of it at once: `--json`, `--replace`, `--trim`, and the
+    /// like.
+    fn long_line(&mut self, found: &SinkMatch<'_>, mut text: LongText<'_>) -> io::Result<bool> {
+        let config = self.config;
+        let selected = text.selected();
+        if selected && (config.count || config.group_by_dir) {
+            // Only the matches are counted.
+            let mut matches = 0;
+            while let Some((_, spans)) = text.next_piece()? {
+                matches += spans.len();
+            }
+            self.count += 1;
+            self.matches += matches.max(1);
+            return Ok(true);
+        }
+        let highlight = config.colored && (selected != config.invert_match);
+        if !prints_context(config)
+            || config.unique
+            || config.json
+            || config.replace.is_some()
+            || config.trim
+            || config.max_columns > 0
+            || (highlight && !selected)
+        {
+            return text.hand_on(found, self);
+        }
+
+        self.write_heading(found.path)?;
+        let separator = match selected {
+            true => &config.field_separator,
+            false => CONTEXT_SEPARATOR,
+        };
+        let column = text.column(config.byte_columns);
+        write!(
+            self.out,
+            "{}",
+            build_prefix(found, column, separator, config)
+        )?;
+        let mut matches = 0;
+        while let Some((piece, spans)) = text.next_piece()? {
+            matches += spans.len();
+            if highlight {
+                let highlighted = highlight_line(
+                    &piece,
+                    &spans,
+                    &config.matcher,
+                    &config.theme,
+                    config.context_dim,
+                );
+                self.out.write_all(highlighted.as_bytes())?;
+            } else {
+                self.out.write_all(piece.as_bytes())?;
+            }
+        }
+        if selected {
+            self.count += 1;
+            self.matches += matches.max(1);
+        }
+        let carriage_return = if found.crlf { "\r" } else { "" };
+        write!(
+            self.out,
+            "{}{}",
+            carriage_return,
+            char::from(config.record_terminator())
+        )?;
+        Ok(true)
+    }
+
+    fn context_break(&mut self) -> io::Result<bool> {
+        let config = self.config;
+        if let Some(separator) = config.group_separator.as_ref()
+            && prints_context(config)
+            && !config.json
+        {
+            writeln!(self.out, "{}", separator)?;
+        }
+        Ok(true)
+    }
+}
+
+impl<W: Write> Printer<'_, W> {
+    /// `--heading`: prints the file's name before its first line. `run` separates files with
+    /// a blank line, since one printer sees only its own file.
+    fn write_heading(&mut self, path: Option<&Path>) -> io::Result<()> {
+        let config = self.config;
+        if self.heading_written || !uses_heading(config) {
+            return Ok(());
+        }
+        self.heading_written = true;
+        if let Some(path) = path {
+            let name = file_name(path, None, None, config);
+            let terminator = if config.null_terminated { '\0' } else { '\n' };
+            write!(self.out, "{}{}", name, terminator)?;
+        }
+        Ok(())
+    }
+
+    /// `--vimgrep`: one `file:line:column:text` record per match, the column counted in bytes
+    /// from 1 as Vim's quickfix list expects. A line selected by `-v` has one record at column 1.
+    fn print_vimgrep(&mut self, found: &SinkMatch<'_>) -> io::Result<()> {
+        let config = self.config;
+        let theme = &config.theme;
+        let column = found.spans.first().map(|span| span.start + 1);
+        let name = found.path.map_or_else(String::new, |path| {
+            file_name(path, Some(found.line_number), column, config)
+        });
+        let prefix = [
+            name,
+            colour(&found.line_number.to_string(), &theme.line_number, config),
+        ];
+        let separator = colour(":", &theme.separator, config);
+        let whole_line = 0..0;
+        let spans = if found.spans.is_empty() {
+            std::slice::from_ref(&whole_line)
+        } else {
+            &found.spans[..]
+        };
+        let terminator = char::from(config.record_terminator());
+        for span in spans {
+            let column = colour(&(span.start + 1).to_string(), &theme.line_number, config);
+            let text = if config.only_matching && !span.is_empty() {
+                if config.colored {
+                    paint_match(found.line, span.clone(), &config.matcher, theme)
+                } else {
+                    found.line[span.clone()].to_string()
+                }
+            } else {
+                let highlight = config.colored && !found.spans.is_empty();
+                let (text, spans) = displayed(found.line, &found.spans, config);
+                render_line(text, &spans, highlight, config)
+            };
+            write!(
+                self.out,
+                "{}{sep}{}{sep}{}{sep}{}{}",
+                prefix[0],
+                prefix[1],
+                column,
+                text,
+                terminator,
+                sep = separator
+            )?;
+        }
+        Ok(())
+    }
+
+    /// `--format`: the template once per match, or once for a line selected by `-v`.
+    fn print_format(&mut self, found: &SinkMatch<'_>, template: &Template) -> io::Result<()> {
+        let config = self.config;
+        let file = found
+            .path
+            .map_or_else(String::new, |path| path_label(path).into_owned());
+        let terminator = char::from(config.record_terminator());
+        if found.spans.is_empty() {
+            let record = template.render(&file, found.line_number, 1, found.line, None, config);
+            return write!(self.out, "{}{}", record, terminator);
+        }
+        for span in &found.spans {
+            // In multiline mode a match may start on a later line of the block.
+            let line_number =
+                found.line_number + found.line[..span.start].matches(terminator).count();
+            let record = template.render(
+                &file,
+                line_number,
+                column(found.line, span.start, config),
+                found.line,
+                Some(span.clone()),
+                config,
+            );
+            write!(self.out, "{}{}", record, terminator)?;
+        }
+        Ok(())
+    }
+
+    /// `-o`: prints each match on its own line, widened by `--o-context` characters on both
+    /// sides. The prefix carries the match's own line number and byte offset.
+    fn print_only_matching(&mut self, found: &SinkMatch<'_>) -> io::Result<()> {
+        let config = self.config;
+        let line = found.line;
+        for span in found.spans.iter().filter(|span| !span.is_empty()) {
+            let start = step_back(line, span.start, config.o_context);
+            let end = step_forward(line, span.end, config.o_context);
+            let display = match config.replace.as_deref() {
+                Some(template) => {
+                    let expanded = config.matcher.expand_at(line, span.clone(), template);
+                    let snippet = format!(
+                        "{}{}{}",
+                        &line[start..span.start],
+                        expanded,
+                        &line[span.end..end]
+                    );
+                    render_line(&snippet, &[], false, config)
+                }
+                None => {
+                    let span = span.start - start..span.end - start;
+                    render_line(
+                        &line[start..end],
+                        std::slice::from_ref(&span),
+                        config.colored,
+                        config,
+                    )
+                }
+            };
+            let position = SinkMatch {
+                path: found.path,
+                // In multiline mode a match may start on a later line of the block.
+                line_number: found.line_number
+                    + line[..span.start]
+                        .matches(char::from(config.record_terminator()))
+                        .count(),
+                byte_offset: found.byte_offset + span.start,
+                line,
+                spans: vec![span.clone()],
+                // Only the match is printed, not the end of the line.
+                crlf: false,
+            };
+            print_line(
+                &mut self.out,
+                &position,
+                &display,
+                &config.field_separator,
+                config,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The byte index `chars` characters before `from` in `line`, stopping at the start.
+fn step_back(line: &str, from: usize, chars: usize) -> usize {
+    if chars == 0 {
+        return from;
+    }
+    line[..from]
+        .char_indices()
+        .rev()
+        .nth(chars - 1)
+        .map_or(0, |(at, _)| at)
+}
+
+/// The byte index `chars` characters after `from` in `line`, stopping at the end.
+fn step_forward(line: &str, from: usize, chars: usize) -> usize {
+    line[from..]
+        .char_indices()
+        .nth(chars)
+        .map_or(line.len(), |(at, _)| from + at)
+}
+
+/// Whether selected lines are printed at all, rather than just names or counts.
+fn prints_lines(config: &Config) -> bool {
+    !config.quiet
+        && !config.list_files
+        && !config.files_without_match
+        && !config.count
+        && !config.group_by_dir
+}
+
+/// `--vimgrep` and `--format` print matches alone: no context, group separators, or headings.
+fn prints_context(config: &Config) -> bool {
+    prints_lines(config)
+        && !config.only_matching
+        && !config.vimgrep
+        && config.format.is_none()
+        && !config.sarif
+}
+
+/// `--format csv` and `tsv`: the row of column names, ahead of any match.
+pub(crate) fn print_header(mut out: impl Write, config: &Config) -> io::Result<()> {
+    match config.format.as_ref().and_then(Template::header) {
+        Some(header) if prints_lines(config) => {
+            write!(out, "{}{}", header, char::from(config.record_terminator()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether lines are grouped under a per-file `--heading` rather than prefixed with the name.
+pub(crate) fn uses_heading(config: &Config) -> bool {
+    config.heading
+        && config.show_filenames
+        && prints_lines(config)
+        && !config.json
+        && !config.vimgrep
+        && config.format.is_none()
+        && !config.sarif
+}
+
+/// Puts lines under a `--heading` header.
+const HEADING_INDENT: &str = "  ";
+
+/// Separates the prefix of a context line from the line, where a match would use `:`.
+const CONTEXT_SEPARATOR: &str = "-";
+
+/// The text of `line` that gets printed, trimmed if `--trim` asks for it, and its `spans`
+/// moved to match.
+fn displayed<'l>(
+    line: &'l str,
+    spans: &[Range<usize>],
+    config: &Config,
+) -> (&'l str, Vec<Range<usize>>) {
+    if !config.trim {
+        return (line, spans.to_vec());
+    }
+    let start = line.len() - line.trim_start().len();
+    let end = line.trim_end().len().max(start);
+    (&line[start..end], within(spans, start, end))
+}
+
+/// The parts of `spans` between `start` and `end`, counted from `start`.
+fn within(spans: &[Range<usize>], start: usize, end: usize) -> Vec<Range<usize>> {
+    spans
+        .iter()
+        .filter(|span| span.end > start && span.start < end)
+        .map(|span| span.start.max(start) - start..span.end.min(end) - start)
+        .collect()
+}
+
+/// Prints a `--count` result, labelled like `label:count` when a label is given.
+pub(crate) fn print_count(
+    out: &mut (impl Write + ?Sized),
+    label: Option<&str>,
+    count: usize,
+    config: &Config,
+) -> io::Result<()> {
+    match label {
+        Some(label) if config.null_terminated => writeln!(out, "{}\0{}", label, count),
+        Some(label) => writeln!(out, "{}{}{}", label, config.field_separator, count),
+        None => writeln!(out, "{}", count),
+    }
+}
+
+/// `--search-names`: prints `name` as a record of its own if the pattern matches it, with the
+/// matches highlighted. Returns whether it matched.
+pub(crate) fn print_name(
+    out: &mut (impl Write + ?Sized),
+    name: &str,
+    config: &Config,
+) -> io::Result<bool> {
+    let spans: Vec<_> = config.matcher.find_iter(name).collect();
+    if spans.is_empty() || config.quiet {
+        return Ok(!spans.is_empty());
+    }
+    if config.json {
+        json::write_name(&mut *out, name, &spans)?;
+        return Ok(true);
+    }
+    let name = if config.colored {
+        highlight_line(name, &spans, &config.matcher, &config.theme, false)
+    } else {
+        name.to_string()
+    };
+    let terminator = if config.null_terminated { '\0' } else { '\n' };
+    write!(out, "{}{}", name, terminator)?;
+    Ok(true)
+}
+
+/// `path` as printed. On Windows a walk joins names with `\`, so a `/` typed in an input
+/// (`src/lib.rs`, or `src/` then walked) is printed as `\` too, keeping every prefix alike.
+pub(crate) fn path_label(path: &Path) -> Cow<'_, str> {
+    let label = path.to_string_lossy();
+    if cfg!(windows) && label.contains('/') {
+        Cow::Owned(label.replace('/', "\\"))
+    } else {
+        label
+    }
+}
+
+pub(crate) fn print_filename(
+    out: &mut (impl Write + ?Sized),
+    path: &Path,
+    config: &Config,
+) -> io::Result<()> {
+    let terminator = if config.null_terminated { '\0' } else { '\n' };
+    write!(out, "{}{}", file_name(path, None, None, config), terminator)
+}
+
+/// `path` as printed, coloured and, with `--hyperlink`, linked to the file at `line` and
+/// `column`.
+fn file_name(path: &Path, line: Option<usize>, column: Option<usize>, config: &Config) -> String {
+    let name = colour(&path_label(path), &config.theme.filename, config);
+    match &config.hyperlink {
+        Some(format) if path != Path::new(&config.label) => format.link(&name, path, line, column),
+        _ => name,
+    }
+}
+
+fn print_line(
+    out: &mut impl Write,
+    found: &SinkMatch<'_>,
+    display_line: &str,
+    separator: &str,
+    config: &Config,
+) -> io::Result<()> {
+    let column = found
+        .spans
+        .first()
+        .map(|span| column(found.line, span.start, config));
+    let prefix = build_prefix(found, column, separator, config);
+    let terminator = char::from(config.record_terminator());
+    let carriage_return = if found.crlf { "\r" } else { "" };
+    write!(
+        out,
+        "{}{}{}{}",
+        prefix, display_line, carriage_return, terminator
+    )
+}
+
+/// The 1-based column of byte `start` within its line of `text`, in characters unless
+/// `--byte-columns`. In multiline mode `text` can hold several lines.
+pub(crate) fn column(text: &str, start: usize, config: &Config) -> usize {
+    let terminator = char::from(config.record_terminator());
+    let line = &text[text[..start].rfind(terminator).map_or(0, |at| at + 1)..start];
+    if config.byte_columns {
+        line.len() + 1
+    } else {
+        line.chars().count() + 1
+    }
+}
+
+/// Marker shown wherever `--max-columns` cut text from a line.
+const OMITTED_MARKER: &str = "[... omitted]";
+
+/// `line` as printed, cut to `--max-columns` around its first match and, with `highlight`, with
+/// its matches at `spans` coloured.
+fn render_line(line: &str, spans: &[Range<usize>], highlight: bool, config: &Config) -> String {
+    let (start, end) = visible_window(line, spans.first().cloned(), config);
+    let visible = &line[start..end];
+    // Highlight only the visible window so truncation never splits an escape sequence.
+    let mut rendered = if highlight {
+        let spans = within(spans, start, end);
+        highlight_line(
+            visible,
+            &spans,
+            &config.matcher,
+            &config.theme,
+            config.context_dim,
+        )
+    } else {
+        visible.to_string()
+    };
+
+    if start > 0 {
+        rendered = format!("{} {}", OMITTED_MARKER, rendered);
+    }
+    if end < line.len() {
+        rendered.push(' ');
+        rendered.push_str(OMITTED_MARKER);
+    }
+    rendered
+}
+
+/// Byte range of `line` to display: all of it, or `max_columns` characters centred on the first
+/// match (or from the start, with `--max-columns-preview`). The match is `first` if known.
+fn visible_window(line: &str, first: Option<Range<usize>>, config: &Config) -> (usize, usize) {
+    let max_columns = config.max_columns;
+    let total = line.chars().count();
+    if max_columns == 0 || total <= max_columns {
+        return (0, line.len());
+    }
+
+    let char_index = |byte: usize| line[..byte].chars().count();
+    let byte_index = |chars: usize| {
+        line.char_indices()
+            .nth(chars)
+            .map_or(line.len(), |(byte, _)| byte)
+    };
+
+    if config.max_columns_preview {
+        return (0, byte_index(max_columns));
+    }
+    let centre = first
+        .or_else(|| config.matcher.find(line))
+        .map_or(0, |found| {
+            (char_index(found.start) + char_index(found.end)) / 2
+        });
+    let start = centre
+        .saturating_sub(max_columns / 2)
+        .min(total - max_columns);
+    (byte_index(start), byte_index(start + max_columns))
+}
+
+/// `line` with the matches at `spans` coloured, and the text between them dimmed if `dim_rest`.
+fn highlight_line(
+    line: &str,
+    spans: &[Range<usize>],
+    matcher: &Matcher,
+    theme: &Theme,
+    dim_rest: bool,
+) -> String {
+    let rest = |text: &str| {
+        if dim_rest {
+            theme::paint(text, theme::DIM)
+        } else {
+            text.to_string()
+        }
+    };
+    let mut highlighted = String::with_capacity(line.len());
+    let mut last = 0;
+    for span in spans.iter().filter(|span| !span.is_empty()) {
+        highlighted.push_str(&rest(&line[last..span.start]));
+        highlighted.push_str(&paint_match(line, span.clone(), matcher, theme));
+        last = span.end;
+    }
+    highlighted.push_str(&rest(&line[last..]));
+    highlighted
+}
+
+/// The match at `span` of `line` in the match colour, with each capture group that has a
+/// colour of its own painted in it. A group inside another shares the outer one's colour.
+fn paint_match(line: &str, span: Range<usize>, matcher: &Matcher, theme: &Theme) -> String {
+    if theme.groups.is_empty() {
+        return theme::paint(&line[span], &theme.matched);
+    }
+    let mut painted = String::new();
+    let mut at = span.start;
+    for (group, sgr) in matcher
+        .group_spans(line, span.clone())
+        .into_iter()
+        .zip(&theme.groups)
+    {
+        match group {
+            Some(group) if !sgr.is_empty() && !group.is_empty() && group.start >= at => {
+                painted.push_str(&theme::paint(&line[at..group.start], &theme.matched));
+                painted.push_str(&theme::paint(&line[group.clone()], sgr));
+                at = group.end;
+            }
+            _ => {}
+        }
+    }
+    painted.push_str(&theme::paint(&line[at..span.end], &theme.matched));
+    painted
+}
+
+/// `text` in the theme colour `sgr` when output is coloured.
+fn colour(text: &str, sgr: &str, config: &Config) -> String {
+    if config.colored {
+        theme::paint(text, sgr)
+    } else {
+        text.to_string()
+    }
+}
+
+/// The file name, line number, and the rest that go before a printed line, `column` being that
+/// of the line's first match.
+fn build_prefix(
+    found: &SinkMatch<'_>,
+    column: Option<usize>,
+    separator: &str,
+    config: &Config,
+) -> String {
+    let theme = &config.theme;
+    let heading = uses_heading(config);
+    let mut prefix = String::new();
+    let mut parts = Vec::new();
+
+    if heading {
+        prefix.push_str(HEADING_INDENT);
+    }
+    if let Some(path) = found.path.filter(|_| config.show_filenames && !heading) {
+        let name = file_name(path, Some(found.line_number), column, config);
+        if config.null_terminated {
+            // -Z: a NUL replaces the separator, so names containing it stay unambiguous.
+            prefix.push_str(&name);
+            prefix.push('\0');
+        } else {
+            parts.push(name);
+        }
+    }
+
+    if config.show_line_numbers {
+        parts.push(colour(
+            &found.line_number.to_string(),
+            &theme.line_number,
+            config,
+        ));
+    }
+
+    // Context lines and lines selected by -v have no match to give a column, though a table
+    // keeps the field.
+    if config.column {
+        match column {
+            Some(column) => parts.push(colour(&column.to_string(), &theme.line_number, config)),
+            None if config.table => parts.push(String::new()),
+            None => {}
+        }
+    }
+
+    if config.byte_offset {
+        parts.push(colour(
+            &found.byte_offset.to_string(),
+            &theme.byte_offset,
+            config,
+        ));
+    }
+
+    if !parts.is_empty() {
+        let separator = colour(separator, &theme.separator, config);
+        if config.table {
+            // Each field is a cell of its own, for the table to pad.
+            for part in parts {
+                prefix.push_str(&part);
+                prefix.push_str(&separator);
+                prefix.push(table::CELL);
+            }
+        } else {
+            prefix.push_str(&parts.join(&separator));
+            prefix.push_str(&separator);
+        }
+        prefix.push(' ');
+    }
+    prefix
+}
@@ -0,0 +1,100 @@
+//! Default arguments read from a configuration file and the environment at startup.
+//!
+//! The file holds one argument per line, exactly as it would be typed on the command line (so
+//! `--threads` and `4` go on separate lines, or as `-m` then `5`). Blank lines and lines starting
+//! with `#` are skipped. `SEARCH_UTILITY_OPTS` (or failing that `GREP_OPTIONS`) holds more,
+//! split at whitespace as a shell would. The file's arguments come first, then the variable's,
+//! then those on the command line, so later flags take precedence.
+
+use crate::error::SearchError;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Turns off the configuration file; see [`default_args`].
+pub const NO_CONFIG: &str = "--no-config";
+
+/// Environment variables holding default arguments, the first that is set being used.
+const OPTIONS_VARS: [&str; 2] = ["SEARCH_UTILITY_OPTS", "GREP_OPTIONS"];
+
+/// The first configuration file that exists: `$XDG_CONFIG_HOME/grep/config` (with
+/// `~/.config` standing in for an unset `XDG_CONFIG_HOME`), then `~/.greprc`.
+pub fn config_path() -> Option<PathBuf> {
+    let home = env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from);
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".config")));
+    let candidates = [
+        xdg.map(|dir| dir.join("grep").join("config")),
+        home.map(|home| home.join(".greprc")),
+    ];
+    candidates.into_iter().flatten().find(|path| path.is_file())
+}
+
+/// The default arguments to put before `cli`: those of the configuration file, unless `cli`
+/// contains `--no-config` (before any `--`) or there is none, then those of the environment.
+pub fn default_args(cli: &[String]) -> Result<Vec<String>, SearchError> {
+    let disabled = cli
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == NO_CONFIG);
+    let mut args = match config_path() {
+        Some(path) if !disabled => {
+            read_args(&path).map_err(|err| SearchError::io("config file", path, err))?
+        }
+        _ => Vec::new(),
+    };
+    if let Some((name, value)) = OPTIONS_VARS
+        .iter()
+        .find_map(|name| Some((name, env::var(name).ok()?)))
+    {
+        args.extend(split_words(&value).map_err(|problem| format!("{}: {}", name, problem))?);
+    }
+    Ok(args)
+}
+
+/// `text` split into words at whitespace, with `'…'` and `"…"` quoting and `\` escaping the
+/// next character (except inside single quotes), as a shell would.
+pub(crate) fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    // `None` between words, so that `''` still makes an (empty) word.
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('\''), c) => word.get_or_insert_default().push(c),
+            (_, '\\') => {
+                let escaped = chars.next().ok_or("trailing backslash")?;
+                word.get_or_insert_default().push(escaped);
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    if let Some(open) = quote {
+        return Err(format!("unterminated {} quote", open));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn read_args(path: &Path) -> io::Result<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        // Only whole-line comments, so that an argument may itself contain `#` or spaces.
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
@@ -0,0 +1,221 @@
+//! `--write`: applying `--replace` to files in place, change by change with `--interactive`,
+//! or previewing it with `--diff` or `--dry-run`.
+
+use crate::config::Config;
+use crate::diff;
+use crate::matcher::Within;
+use crate::search::{self, STDIN_PATH};
+use crate::tui::RawMode;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Rewrites every matching line of `path`, up to `--max-count` of them, with the `--replace`
+/// template and returns how many lines changed. With `--diff` a unified diff of the changes goes
+/// to `out` as well, and with `--dry-run` (or `--diff` alone) the file is left alone; only a
+/// preview can read stdin. With `--interactive` only the changes the user accepts are made, and
+/// counted.
+///
+/// The new contents are written to a temporary file next to the original (or the file a symlink
+/// names) and renamed over it, so the file is never left half-written.
+pub(crate) fn rewrite_file(
+    path: &Path,
+    config: &Config,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let Some(template) = config.replace.as_deref() else {
+        return Ok(0);
+    };
+    let bytes = if path == Path::new(STDIN_PATH) {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        bytes
+    } else {
+        if let Some(cap) = config.max_memory
+            && fs::metadata(path)?.len() > cap
+        {
+            return Err(search::over_memory("the file", cap));
+        }
+        fs::read(path)?
+    };
+    if bytes.contains(&0) && !config.binary {
+        // Leave binary files untouched, as searching them would.
+        return Ok(0);
+    }
+    let contents =
+        String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let mut raws = Vec::new();
+    let mut lines = Vec::new();
+    let mut changes = Vec::new();
+    let mut regions = config.within.as_ref().map(Within::regions);
+    let mut selected = 0;
+    for (index, raw) in contents.split_inclusive('\n').enumerate() {
+        let line_number = index + 1;
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        raws.push(raw);
+        lines.push(line);
+
+        let inside = regions.as_mut().is_none_or(|regions| regions.inside(line));
+        let in_range = inside
+            && config
+                .line_range
+                .as_ref()
+                .is_none_or(|range| range.contains(&line_number));
+        // Lines past `--max-count` are kept as they are, as a search would not select them.
+        let wanted = in_range && config.max_count.is_none_or(|max| selected < max);
+        if wanted && config.matcher.is_match(line) && config.filter.accepts(line) {
+            selected += 1;
+            let replaced = config.matcher.replace_template(line, template);
+            if replaced != line {
+                changes.push((index, replaced));
+            }
+        }
+    }
+
+    let name = if path == Path::new(STDIN_PATH) {
+        config.label.as_str().into()
+    } else {
+        path.to_string_lossy()
+    };
+    if config.interactive {
+        changes = confirm(&name, &lines, changes, config)?;
+    }
+    if changes.is_empty() {
+        return Ok(0);
+    }
+    if config.diff || config.dry_run {
+        diff::write_unified(out, &name, &lines, &changes, config)?;
+    }
+    if config.write && !config.dry_run {
+        let mut rewritten = String::with_capacity(contents.len());
+        let mut pending = changes.iter().peekable();
+        for (index, raw) in raws.into_iter().enumerate() {
+            match pending.next_if(|&&(changed, _)| changed == index) {
+                Some((_, replaced)) => {
+                    rewritten.push_str(replaced);
+                    rewritten.push_str(&raw[lines[index].len()..]);
+                }
+                None => rewritten.push_str(raw),
+            }
+        }
+        replace_atomically(path, rewritten.as_bytes())?;
+    }
+    Ok(changes.len())
+}
+
+/// With `--interactive`, the answer that `a` or `q` gave for every later change of the run.
+static STANDING: AtomicU8 = AtomicU8::new(ASK);
+const ASK: u8 = 0;
+const ALL: u8 = 1;
+const NONE: u8 = 2;
+
+/// `--interactive`: shows each of `changes` as a hunk of its own on stderr and keeps those the
+/// user says to make.
+fn confirm(
+    name: &str,
+    lines: &[&str],
+    changes: Vec<(usize, String)>,
+    config: &Config,
+) -> io::Result<Vec<(usize, String)>> {
+    let mut stderr = io::stderr().lock();
+    let mut accepted = Vec::new();
+    let mut shown = false;
+    for change in changes {
+        match STANDING.load(Ordering::Relaxed) {
+            ALL => {
+                accepted.push(change);
+                continue;
+            }
+            NONE => break,
+            _ => {}
+        }
+        if !shown {
+            diff::write_header(&mut stderr, name, config)?;
+            shown = true;
+        }
+        diff::write_hunk(&mut stderr, lines, std::slice::from_ref(&change), config)?;
+        match ask(&mut stderr)? {
+            'y' => accepted.push(change),
+            'a' => {
+                STANDING.store(ALL, Ordering::Relaxed);
+                accepted.push(change);
+            }
+            'q' => STANDING.store(NONE, Ordering::Relaxed),
+            _ => {}
+        }
+    }
+    Ok(accepted)
+}
+
+/// Asks whether to make the change just shown until one of `y`, `n`, `a`, or `q` is given.
+/// From a terminal a single key is read; otherwise a whole line, and the end of the
+/// input counts as `q`.
+fn ask(stderr: &mut impl Write) -> io::Result<char> {
+    loop {
+        write!(stderr, "Apply this change [y,n,a,q,?]? ")?;
+        stderr.flush()?;
+        let answer = if io::stdin().is_terminal() {
+            let _raw = RawMode::enter()?;
+            let byte = loop {
+                let mut byte = [0];
+                if io::stdin().lock().read(&mut byte)? == 1 {
+                    break byte[0];
+                }
+            };
+            // Ctrl-C and Ctrl-D quit, since raw mode keeps them from stopping the process.
+            let answer = match byte {
+                0x03 | 0x04 => 'q',
+                byte => char::from(byte).to_ascii_lowercase(),
+            };
+            writeln!(stderr, "{}", answer.escape_default())?;
+            answer
+        } else {
+            let mut line = String::new();
+            if io::stdin().lock().read_line(&mut line)? == 0 {
+                'q'
+            } else {
+                line.trim()
+                    .chars()
+                    .next()
+                    .map_or(' ', |c| c.to_ascii_lowercase())
+            }
+        };
+        if matches!(answer, 'y' | 'n' | 'a' | 'q') {
+            return Ok(answer);
+        }
+        writeln!(
+            stderr,
+            "y - make this change\n\
+             n - leave this line as it is\n\
+             a - make this change and every later one\n\
+             q - leave this line and every later one as they are"
+        )?;
+    }
+}
+
+fn replace_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    // Renaming over a symlink would replace the link and leave the file it names alone.
+    let path = &fs::canonicalize(path)?;
+    let temp = temp_path(path);
+    let result = fs::write(&temp, contents)
+        .and_then(|()| {
+            let permissions = fs::metadata(path)?.permissions();
+            fs::set_permissions(&temp, permissions)
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// A sibling of `path`, so the final rename never crosses filesystems.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    path.with_file_name(format!(".{}.grep-write-{}", name, std::process::id()))
+}
@@ -0,0 +1,398 @@
+//! `--rules FILE`: named rules read from a TOML file, all searched for in one pass.
+//!
+//! ```toml
+//! [[rule]]
+//! name = "no-unwrap"
+//! pattern = '\.unwrap\(\)'
+//! severity = "error"
+//! message = "handle the error instead"
+//! include = ["*.rs"]
+//! ```
+//!
+//! Every rule's pattern goes into the search's own matcher, so a file is read once however many
+//! rules there are; each line it selects is then checked against the rules one by one to say
+//! which matched. Only what the example shows is read: `[[rule]]` tables of one-line keys with
+//! basic or literal strings and arrays of them.
+
+use crate::config::Config;
+use crate::glob::GlobSet;
+use crate::matcher::{MatchOptions, Matcher, PatternSpec, Syntax};
+use crate::printer;
+use crate::search::{MatchSink, SinkMatch};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// How serious a rule's matches are; the names are SARIF's levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    #[default]
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+/// A rule as read from the file, before its pattern is compiled.
+pub(crate) struct RuleSpec {
+    pub(crate) name: String,
+    pub(crate) pattern: String,
+    severity: Severity,
+    message: Option<String>,
+    include: Vec<String>,
+}
+
+impl RuleSpec {
+    /// Compiles the rule, with `pattern` standing in for its own once `--normalize` has had it.
+    pub(crate) fn compile(
+        self,
+        pattern: String,
+        syntax: Syntax,
+        options: MatchOptions,
+    ) -> Result<Rule, String> {
+        let spec = PatternSpec {
+            patterns: vec![pattern],
+            syntax,
+            options,
+        };
+        let matcher = spec
+            .compile()
+            .map_err(|error| format!("rule {:?}: {}", self.name, error))?;
+        let include = GlobSet::new(&self.include)
+            .map_err(|error| format!("rule {:?}: {}", self.name, error))?;
+        Ok(Rule {
+            message: self
+                .message
+                .unwrap_or_else(|| format!("matches {}", self.pattern)),
+            name: self.name,
+            severity: self.severity,
+            include,
+            matcher,
+        })
+    }
+}
+
+/// A named pattern, reported with its severity and message wherever it matches.
+pub struct Rule {
+    pub name: String,
+    pub severity: Severity,
+    pub message: String,
+    /// Globs limiting the files the rule applies to; empty for every file.
+    include: GlobSet,
+    matcher: Matcher,
+}
+
+impl Rule {
+    fn applies_to(&self, path: Option<&Path>) -> bool {
+        if self.include.is_empty() {
+            return true;
+        }
+        path.is_some_and(|path| {
+            let path = path.to_string_lossy().replace('\\', "/");
+            self.include
+                .is_match(path.strip_prefix("./").unwrap_or(&path))
+        })
+    }
+}
+
+/// Reads the rules in `text`, in the order they are given.
+pub(crate) fn parse(text: &str) -> Result<Vec<RuleSpec>, String> {
+    let mut rules = Vec::new();
+    let mut current: Option<(usize, Vec<(String, Value)>)> = None;
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let at_line = |problem: String| format!("line {}: {}", number, problem);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("[[rule]]") {
+            end_of_line(rest).map_err(at_line)?;
+            if let Some((start, keys)) = current.take() {
+                rules.push(rule(keys).map_err(|problem| format!("line {}: {}", start, problem))?);
+            }
+            current = Some((number, Vec::new()));
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(at_line(format!(
+                "unknown table {}; rules go in [[rule]]",
+                line
+            )));
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(at_line(format!("expected key = value, found {}", line)));
+        };
+        let key = key.trim();
+        let (value, rest) = Value::parse(value.trim_start()).map_err(at_line)?;
+        end_of_line(rest).map_err(at_line)?;
+        let Some((_, keys)) = current.as_mut() else {
+            return Err(at_line(format!("{} is outside any [[rule]]", key)));
+        };
+        if keys.iter().any(|(seen, _)| seen == key) {
+            return Err(at_line(format!("{} is given twice", key)));
+        }
+        keys.push((key.to_string(), value));
+    }
+    if let Some((start, keys)) = current {
+        rules.push(rule(keys).map_err(|problem| format!("line {}: {}", start, problem))?);
+    }
+    if rules.is_empty() {
+        return Err("no [[rule]] tables".to_string());
+    }
+    Ok(rules)
+}
+
+fn rule(keys: Vec<(String, Value)>) -> Result<RuleSpec, String> {
+    let mut name = None;
+    let mut pattern = None;
+    let mut severity = Severity::default();
+    let mut message = None;
+    let mut include = Vec::new();
+    for (key, value) in keys {
+        match key.as_str() {
+            "name" => name = Some(value.string(&key)?),
+            "pattern" => pattern = Some(value.string(&key)?),
+            "message" => message = Some(value.string(&key)?),
+            "severity" => {
+                severity = match value.string(&key)?.as_str() {
+                    "error" => Severity::Error,
+                    "warning" => Severity::Warning,
+                    "note" => Severity::Note,
+                    other => {
+                        return Err(format!(
+                            "severity {:?} is not error, warning, or note",
+                            other
+                        ));
+                    }
+                }
+            }
+            "include" => include = value.strings(&key)?,
+            other => {
+                return Err(format!(
+                    "unknown key {}; a rule has name, pattern, severity, message, and include",
+                    other
+                ));
+            }
+        }
+    }
+    let name = name.ok_or("a rule needs a name")?;
+    let pattern = pattern.ok_or_else(|| format!("rule {:?} needs a pattern", name))?;
+    Ok(RuleSpec {
+        name,
+        pattern,
+        severity,
+        message,
+        include,
+    })
+}
+
+/// Allows only a comment after a value or table header.
+fn end_of_line(rest: &str) -> Result<(), String> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("unexpected {}", rest))
+    }
+}
+
+enum Value {
+    String(String),
+    Array(Vec<String>),
+}
+
+impl Value {
+    /// The value at the start of `text`, and what follows it.
+    fn parse(text: &str) -> Result<(Value, &str), String> {
+        let Some(mut rest) = text.strip_prefix('[') else {
+            let (string, rest) = parse_string(text)?;
+            return Ok((Value::String(string), rest));
+        };
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_string(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected , or ] in an array".to_string());
+            }
+        }
+    }
+
+    fn string(self, key: &str) -> Result<String, String> {
+        match self {
+            Value::String(string) => Ok(string),
+            Value::Array(_) => Err(format!("{} should be a string, not an array", key)),
+        }
+    }
+
+    fn strings(self, key: &str) -> Result<Vec<String>, String> {
+        match self {
+            Value::Array(items) => Ok(items),
+            Value::String(_) => Err(format!("{} should be an array of strings", key)),
+        }
+    }
+}
+
+/// A `"basic"` or `'literal'` string at the start of `text`, and what follows it.
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    if text.starts_with("\"\"\"") || text.starts_with("'''") {
+        return Err("multi-line strings are not supported".to_string());
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or("unterminated ' string")?;
+        return Ok((rest[..end].to_string(), &rest[end + 1..]));
+    }
+    let Some(rest) = text.strip_prefix('"') else {
+        return Err(format!("expected a quoted string, found {}", text));
+    };
+    let mut string = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((at, c)) = chars.next() {
+        match c {
+            '"' => return Ok((string, &rest[at + 1..])),
+            '\\' => {
+                let (_, escape) = chars.next().ok_or("unterminated \" string")?;
+                match escape {
+                    'b' => string.push('\u{8}'),
+                    't' => string.push('\t'),
+                    'n' => string.push('\n'),
+                    'f' => string.push('\u{c}'),
+                    'r' => string.push('\r'),
+                    '"' => string.push('"'),
+                    '\\' => string.push('\\'),
+                    'u' | 'U' => {
+                        let digits = if escape == 'u' { 4 } else { 8 };
+                        let hex: String = chars.by_ref().take(digits).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == digits)
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\{}{}", escape, hex))?;
+                        string.push(c);
+                    }
+                    other => {
+                        return Err(format!(
+                            "invalid escape \\{} (in a pattern, '...' needs no escaping)",
+                            other
+                        ));
+                    }
+                }
+            }
+            c => string.push(c),
+        }
+    }
+    Err("unterminated \" string".to_string())
+}
+
+/// Prints each match of a rule on the lines the search selects, as
+/// `path:line:column: severity[name]: message`.
+pub(crate) struct RulePrinter<'a, W: Write> {
+    config: &'a Config,
+    out: W,
+    count: usize,
+    matches: usize,
+}
+
+impl<'a, W: Write> RulePrinter<'a, W> {
+    pub(crate) fn new(config: &'a Config, out: W) -> Self {
+        RulePrinter {
+            config,
+            out,
+            count: 0,
+            matches: 0,
+        }
+    }
+
+    /// Lines on which some rule matched.
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Matches of every rule on those lines.
+    pub(crate) fn matches(&self) -> usize {
+        self.matches
+    }
+}
+
+impl<W: Write> MatchSink for RulePrinter<'_, W> {
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+        let config = self.config;
+        let terminator = char::from(config.record_terminator());
+        let file = found
+            .path
+            .map_or_else(String::new, |path| printer::path_label(path).into_owned());
+        let mut hits: Vec<(usize, &Rule)> = config
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(found.path))
+            .flat_map(|rule| {
+                rule.matcher
+                    .find_iter(found.line)
+                    .map(move |span| (span.start, rule))
+            })
+            .collect();
+        if !config.quiet {
+            // By position on the line; rules matching at the same place keep the file's order.
+            hits.sort_by_key(|&(start, _)| start);
+            for &(start, rule) in &hits {
+                // In multiline mode a match may start on a later line of the block.
+                let line_number =
+                    found.line_number + found.line[..start].matches(terminator).count();
+                if !file.is_empty() {
+                    write!(self.out, "{}:", file)?;
+                }
+                write!(
+                    self.out,
+                    "{}:{}: {}[{}]: {}{}",
+                    line_number,
+                    printer::column(found.line, start, config),
+                    rule.severity,
+                    rule.name,
+                    rule.message,
+                    terminator
+                )?;
+            }
+        }
+        let hits = hits.len();
+        if hits > 0 {
+            self.count += 1;
+            self.matches += hits;
+        }
+        // For -q one hit settles the exit status.
+        Ok(!(config.quiet && hits > 0))
+    }
+
+    fn binary_matched(&mut self, path: Option<&Path>) -> io::Result<()> {
+        // Which rule matched cannot be told without the lines to show.
+        self.count += 1;
+        self.matches += 1;
+        if self.config.quiet {
+            return Ok(());
+        }
+        match path {
+            Some(path) => writeln!(
+                self.out,
+                "{}: binary file matches",
+                printer::path_label(path)
+            ),
+            None => writeln!(self.out, "binary file matches"),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! `--format sarif`: a SARIF 2.1.0 log with one result per match, for uploading to code
+//! scanning services such as GitHub's.
+//!
+//! Results are written as they are found, one per line, and [`Log`] joins them into the
+//! document's `results` array, so a search of many files holds none of them in memory.
+
+use crate::config::Config;
+use crate::json::string;
+use crate::printer;
+use crate::search::SinkMatch;
+use std::io::{self, Write};
+use std::path::Path;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+/// Every result is reported under this one rule, described by the patterns searched for.
+const RULE_ID: &str = "match";
+
+/// Writes a result for each match in `found`, or one for the whole line if it was selected
+/// by `-v`.
+pub(crate) fn write_results(
+    out: &mut impl Write,
+    found: &SinkMatch<'_>,
+    config: &Config,
+) -> io::Result<()> {
+    let terminator = char::from(config.record_terminator());
+    if found.spans.is_empty() {
+        let region = format!(
+            "{{\"startLine\":{},\"snippet\":{{\"text\":{}}}}}",
+            found.line_number,
+            string(found.line)
+        );
+        let message = format!("Line selected: {}", found.line.trim());
+        return write_result(out, found.path, Some(&region), &message);
+    }
+    for span in &found.spans {
+        // A multiline match may start and end on later lines of the block.
+        let position = |at: usize| {
+            let before = &found.line[..at];
+            let line = found.line_number + before.matches(terminator).count();
+            let start = before.rfind(terminator).map_or(0, |at| at + 1);
+            (line, before[start..].chars().count() + 1)
+        };
+        let (start_line, start_column) = position(span.start);
+        let (end_line, end_column) = position(span.end);
+        let text = &found.line[span.clone()];
+        let region = format!(
+            "{{\"startLine\":{},\"startColumn\":{},\"endLine\":{},\"endColumn\":{},\
+             \"snippet\":{{\"text\":{}}}}}",
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            string(text)
+        );
+        write_result(
+            out,
+            found.path,
+            Some(&region),
+            &format!("Matched: {}", text),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the result for a binary input that matched, which has no line to point at.
+pub(crate) fn write_binary(out: &mut impl Write, path: Option<&Path>) -> io::Result<()> {
+    write_result(out, path, None, "Binary file matches")
+}
+
+fn write_result(
+    out: &mut impl Write,
+    path: Option<&Path>,
+    region: Option<&str>,
+    message: &str,
+) -> io::Result<()> {
+    let mut result = format!(
+        "{{\"ruleId\":{},\"ruleIndex\":0,\"level\":\"warning\",\"message\":{{\"text\":{}}}",
+        string(RULE_ID),
+        string(message)
+    );
+    if let Some(path) = path {
+        result.push_str(&format!(
+            ",\"locations\":[{{\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{}}}",
+            string(&uri(path))
+        ));
+        if let Some(region) = region {
+            result.push_str(&format!(",\"region\":{}", region));
+        }
+        result.push_str("}}]");
+    }
+    result.push('}');
+    writeln!(out, "{}", result)
+}
+
+/// `path` as the URI SARIF wants: relative paths stay relative (to where the search ran), and
+/// absolute ones become `file:` URIs.
+fn uri(path: &Path) -> String {
+    let label = printer::path_label(path).replace('\\', "/");
+    let relative = label.strip_prefix("./").unwrap_or(&label);
+    let mut uri = String::new();
+    if path.is_absolute() {
+        uri.push_str(if relative.starts_with('/') {
+            "file://"
+        } else {
+            "file:///"
+        });
+    }
+    for byte in relative.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => uri.push(char::from(byte)),
+            b'-' | b'.' | b'_' | b'~' | b'/' | b':' | b'@' | b'!' | b'$' | b'&' | b'\'' => {
+                uri.push(char::from(byte))
+            }
+            b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => uri.push(char::from(byte)),
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Wraps stdout to turn the result lines written through it into a SARIF document: the log's
+/// opening is written at once, results are separated by commas, and the closing is written
+/// when the log is dropped.
+pub(crate) struct Log<W: Write> {
+    out: W,
+    /// Whether a result's line ended, so that a comma is owed before the next.
+    pending: bool,
+}
+
+impl<W: Write> Log<W> {
+    pub(crate) fn new(mut out: W, config: &Config) -> Log<W> {
+        let description = format!("Matches {}", config.pattern.patterns.join(" or "));
+        // A log that cannot be started fails again, and is reported, at the first result.
+        let _ = writeln!(
+            out,
+            "{{\"version\":\"2.1.0\",\"$schema\":{},\"runs\":[{{\"tool\":{{\"driver\":{{\
+             \"name\":\"grep\",\"version\":{},\"rules\":[{{\"id\":{},\
+             \"shortDescription\":{{\"text\":{}}}}}]}}}},\
+             \"columnKind\":\"unicodeCodePoints\",\"results\":[",
+            string(SCHEMA),
+            string(env!("CARGO_PKG_VERSION")),
+            string(RULE_ID),
+            string(&description)
+        );
+        Log {
+            out,
+            pending: false,
+        }
+    }
+}
+
+impl<W: Write> Write for Log<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for piece in buf.split_inclusive(|&byte| byte == b'\n') {
+            if self.pending {
+                self.out.write_all(b",\n")?;
+                self.pending = false;
+            }
+            match piece.strip_suffix(b"\n") {
+                Some(line) => {
+                    self.out.write_all(line)?;
+                    self.pending = true;
+                }
+                None => self.out.write_all(piece)?,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Drop for Log<W> {
+    fn drop(&mut self) {
+        let end: &[u8] = if self.pending { b"\n]}]}\n" } else { b"]}]}\n" };
+        // Nothing is left to report a failure to.
+        let _ = self.out.write_all(end).and_then(|_| self.out.flush());
+    }
+}
@@ -0,0 +1,1547 @@
+use crate::config::Config;
+use crate::decompress::{self, Decoder};
+use crate::encoding::{Encoding, Transcoder};
+use crate::matcher::{Matcher, Within};
+use crate::mmap::Mmap;
+use crate::normalize::Normalizer;
+use crate::printer;
+use crate::strategy::{self, Plan, Strategy};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The input name that reads standard input instead of a file.
+pub(crate) const STDIN_PATH: &str = "-";
+
+/// How standard input is named in output, like a filename.
+pub const STDIN_LABEL: &str = "(standard input)";
+
+/// How much of an input is checked for NUL bytes; the default `BufReader` capacity.
+pub(crate) const BINARY_PROBE: usize = 8 * 1024;
+
+/// Lines longer than this are searched a window at a time rather than read whole.
+const LINE_CHUNK: usize = 1024 * 1024;
+
+/// How much of each window is searched again at the start of the next, so that a match
+/// shorter than this is found whole even where it crosses from one window into the next.
+const LINE_OVERLAP: usize = 64 * 1024;
+
+/// Passes a reader through, adding every byte consumed to its searcher's tally.
+struct Counted<'s, 'a, R> {
+    inner: R,
+    searcher: &'s Searcher<'a>,
+}
+
+impl<R: BufRead> Read for Counted<'_, '_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.searcher.add_bytes(read);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for Counted<'_, '_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.searcher.add_bytes(amount);
+        self.inner.consume(amount);
+    }
+}
+
+/// `--head-lines`: passes a reader through up to and including its `left`th terminator, then
+/// reports the end of input.
+struct HeadLines<R> {
+    inner: R,
+    /// Lines still to be read; `None` for no limit.
+    left: Option<usize>,
+    terminator: u8,
+}
+
+impl<R: BufRead> Read for HeadLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for HeadLines<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let buf = self.inner.fill_buf()?;
+        Ok(match self.left {
+            None => buf,
+            Some(0) => &[],
+            Some(left) => {
+                let end = memchr::memchr_iter(self.terminator, buf)
+                    .nth(left - 1)
+                    .map_or(buf.len(), |at| at + 1);
+                &buf[..end]
+            }
+        })
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if let Some(left) = self.left {
+            // The bytes being consumed are still buffered, so this reads nothing.
+            let ended = self.inner.fill_buf().map_or(0, |buf| {
+                memchr::memchr_iter(self.terminator, &buf[..amount.min(buf.len())]).count()
+            });
+            self.left = Some(left.saturating_sub(ended));
+        }
+        self.inner.consume(amount);
+    }
+}
+
+/// A line (or, in multiline mode, a block of lines) selected by a [`Searcher`].
+pub struct SinkMatch<'a> {
+    /// File the line came from, or `None` when searching an arbitrary reader.
+    pub path: Option<&'a Path>,
+    /// 1-based number of the first line in `line`.
+    pub line_number: usize,
+    /// 0-based byte offset of the start of `line` within the input.
+    pub byte_offset: usize,
+    /// The selected text without its trailing line terminator.
+    pub line: &'a str,
+    /// Byte ranges of every match within `line`; empty for lines selected by `invert_match`.
+    pub spans: Vec<Range<usize>>,
+    /// Whether `line` ended in `\r\n`. The `\r` is left out of `line`, so that `$` and `-x`
+    /// match before it, and put back when the line is printed.
+    pub crlf: bool,
+}
+
+/// An owned copy of a [`SinkMatch`], for callers that would rather collect results than
+/// handle them one at a time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchResult {
+    pub path: Option<PathBuf>,
+    pub line_number: usize,
+    pub byte_offset: usize,
+    pub line: String,
+    /// Byte ranges of every match within `line`, as in [`SinkMatch::spans`].
+    pub spans: Vec<Range<usize>>,
+    /// The same matches as ranges of `char`s, for editors that place highlights by character.
+    pub char_spans: Vec<Range<usize>>,
+}
+
+impl From<&SinkMatch<'_>> for SearchResult {
+    fn from(found: &SinkMatch<'_>) -> Self {
+        SearchResult {
+            path: found.path.map(Path::to_path_buf),
+            line_number: found.line_number,
+            byte_offset: found.byte_offset,
+            line: found.line.to_string(),
+            spans: found.spans.clone(),
+            char_spans: char_spans(found.line, &found.spans),
+        }
+    }
+}
+
+/// Collects every selected line; context lines and binary notices are dropped.
+impl MatchSink for Vec<SearchResult> {
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+        self.push(found.into());
+        Ok(true)
+    }
+}
+
+/// Receives the lines a [`Searcher`] selects.
+///
+/// Every search of a path or reader is framed by [`MatchSink::begin`] and then either
+/// [`MatchSink::end`] or, if it fails, [`MatchSink::error`].
+pub trait MatchSink {
+    /// Called before anything is read from an input; `path` is as it will appear in its lines.
+    fn begin(&mut self, _path: Option<&Path>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called once an input has been searched, including when the sink stopped it early.
+    fn end(&mut self, _path: Option<&Path>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called in place of [`MatchSink::end`] when searching an input fails, such as when it
+    /// cannot be opened; the error is still returned to the caller afterwards.
+    fn error(&mut self, _path: Option<&Path>, _error: &io::Error) {}
+
+    /// Handles one selected line. Returning `Ok(false)` stops searching the current input.
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool>;
+
+    /// Handles a line of surrounding context requested by `-A`/`-B`/`-C`; its `spans` are empty.
+    fn context(&mut self, _line: &SinkMatch<'_>) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// Called between two groups of selected and context lines that are not contiguous.
+    fn context_break(&mut self) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// Called instead of [`MatchSink::matched`], once, when an input that looks binary has a
+    /// selected line; the search of that input then stops.
+    fn binary_matched(&mut self, _path: Option<&Path>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Handles a selected line, or a line of context, too long to have been held whole, in
+    /// place of [`MatchSink::matched`] or [`MatchSink::context`]. `found` says where the line is,
+    /// with its `line` and `spans` left empty, and `text` reads the line itself. By default the
+    /// line is read whole and handed on as a shorter one would be.
+    fn long_line(&mut self, found: &SinkMatch<'_>, text: LongText<'_>) -> io::Result<bool> {
+        text.hand_on(found, self)
+    }
+}
+
+/// Turns the first selected line of a binary input into a single
+/// [`MatchSink::binary_matched`] call, hiding its lines and context.
+struct BinaryNotice<'s, S> {
+    sink: &'s mut S,
+}
+
+impl<S: MatchSink> MatchSink for BinaryNotice<'_, S> {
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+        self.sink.binary_matched(found.path)?;
+        Ok(false)
+    }
+
+    fn context(&mut self, _line: &SinkMatch<'_>) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    fn long_line(&mut self, found: &SinkMatch<'_>, text: LongText<'_>) -> io::Result<bool> {
+        if text.selected() {
+            self.sink.binary_matched(found.path)?;
+        }
+        Ok(!text.selected())
+    }
+}
+
+/// How much a search must find for its sink, worked out once from the mode flags so the
+/// line loop does no work whose result would not be printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MatchStrategy {
+    /// Lines are printed: every match on them, and any context around them.
+    Lines,
+    /// Lines are only tallied by `--count`, so no context is kept.
+    Count,
+    /// The first selected line settles the input, as for `-q`, `-l`, and `-L` (with `-v`, the
+    /// first line that does not match), so the search stops there.
+    FirstHit,
+}
+
+impl MatchStrategy {
+    pub(crate) fn of(config: &Config) -> Self {
+        if config.quiet || config.list_files || config.files_without_match {
+            MatchStrategy::FirstHit
+        } else if config.count || config.group_by_dir {
+            MatchStrategy::Count
+        } else {
+            MatchStrategy::Lines
+        }
+    }
+}
+
+/// Applies the matching rules of a [`Config`] to files or arbitrary readers.
+pub struct Searcher<'a> {
+    config: &'a Config,
+    strategy: MatchStrategy,
+    /// Whether selected lines need their match spans: to print or highlight them, or for
+    /// `--count-matches` and `--stats` to count them.
+    spans: bool,
+    bytes_searched: Cell<u64>,
+    /// Lines each search may select: `--max-count`, or less when `--max-total` is nearly spent.
+    max_count: Option<usize>,
+    /// Set from another thread to stop a search part way, as if it had timed out.
+    cancel: Option<&'a AtomicBool>,
+    /// `--deadline`: when the whole run must stop, however far this search has got.
+    until: Option<Instant>,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        let strategy = MatchStrategy::of(config);
+        Searcher {
+            config,
+            strategy,
+            spans: strategy == MatchStrategy::Lines
+                || config.count_matches
+                || config.group_by_dir
+                || config.stats,
+            bytes_searched: Cell::new(0),
+            max_count: match strategy {
+                MatchStrategy::FirstHit => Some(config.max_count.map_or(1, |max| max.min(1))),
+                _ => config.max_count,
+            },
+            cancel: None,
+            until: None,
+        }
+    }
+
+    /// Selects at most `max` lines per search, on top of `--max-count`.
+    pub(crate) fn limit(mut self, max: Option<usize>) -> Self {
+        self.max_count = match (self.max_count, max) {
+            (Some(count), Some(max)) => Some(count.min(max)),
+            (count, max) => count.or(max),
+        };
+        self
+    }
+
+    /// Makes searches stop with an [`io::ErrorKind::Interrupted`] error once `cancel` is set.
+    pub(crate) fn cancellable(mut self, cancel: Option<&'a AtomicBool>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Gives up at `until`, with [`past_deadline`]'s error.
+    pub(crate) fn until(mut self, until: Option<Instant>) -> Self {
+        self.until = until;
+        self
+    }
+
+    fn context(&self) -> Context {
+        let mut context = Context::new(self.config, self.max_count);
+        if self.strategy != MatchStrategy::Lines {
+            // Nothing would print the context, so unselected lines are never kept for it.
+            (context.before, context.after) = (0, 0);
+        }
+        context
+    }
+
+    fn deadline(&self) -> Deadline<'a> {
+        Deadline {
+            started: Instant::now(),
+            budget: self.config.timeout,
+            cancel: self.cancel,
+            until: self.until,
+        }
+    }
+
+    /// Bytes of input read so far by this searcher, across every search it has run. A search
+    /// that stops early (say at `--max-count`) counts only what it read.
+    pub fn bytes_searched(&self) -> u64 {
+        self.bytes_searched.get()
+    }
+
+    fn add_bytes(&self, bytes: usize) {
+        self.bytes_searched
+            .set(self.bytes_searched.get() + bytes as u64);
+    }
+
+    /// `--trace`: reports on stderr how the input at `path` is searched, and why.
+    fn trace(&self, path: Option<&Path>, plan: &Plan) {
+        if self.config.trace {
+            let name = path.map_or_else(Default::default, |path| printer::path_label(path));
+            eprintln!("Trace: {}: {}", name, plan);
+        }
+    }
+
+    /// Opens `path` and searches it, tagging every event with the path. With `search_zip`, a
+    /// compressed file is searched as its decompressed contents.
+    pub fn search_path(&self, path: &Path, sink: &mut impl MatchSink) -> io::Result<()> {
+        self.framed(Some(path), sink, |sink| self.open_and_search(path, sink))
+    }
+
+    fn open_and_search(&self, path: &Path, sink: &mut impl MatchSink) -> io::Result<()> {
+        if self.config.search_zip
+            && let Some(program) = decompress::program_for(path)?
+        {
+            self.trace(
+                Some(path),
+                &Plan::new(Strategy::Stream, format!("decompressed by {}", program)),
+            );
+            let mut decoder = Decoder::spawn(program, path)?;
+            self.search(&mut decoder.output, Some(path), sink)?;
+            return decoder.finish();
+        }
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        // Multiline mode would read the whole file, so there is no point starting.
+        if let Some(cap) = self.config.max_memory.filter(|_| metadata.is_file())
+            && metadata.len() > cap
+            && self.config.multiline
+        {
+            return Err(over_memory("the file", cap));
+        }
+        let plan = strategy::choose(self.config, &metadata);
+        self.trace(Some(path), &plan);
+        if plan.strategy == Strategy::Mmap {
+            // A file that cannot be mapped is simply read instead.
+            match Mmap::map(&file) {
+                Ok(mapped) => return self.search_mapped(&mapped, path, sink),
+                Err(error) => self.trace(
+                    Some(path),
+                    &Plan::new(Strategy::Stream, format!("could not be mapped: {}", error)),
+                ),
+            }
+        }
+        self.search(BufReader::new(file), Some(path), sink)
+    }
+
+    /// Searches a mapped file. Plain searches run the pattern over the whole buffer and only
+    /// split out the lines around each match; anything else goes through the line loop.
+    fn search_mapped(
+        &self,
+        bytes: &[u8],
+        path: &Path,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<()> {
+        let config = self.config;
+        let plain = !config.multiline
+            && strategy::scannable(config)
+            && Encoding::sniff(bytes).is_none()
+            && (config.binary || config.null_data || !probe_binary(bytes));
+        let finder = config.matcher.buffer_matcher().filter(|_| plain);
+        let (Some(finder), Ok(contents)) = (finder, str::from_utf8(bytes)) else {
+            return self.search(bytes, Some(path), sink);
+        };
+
+        let deadline = self.deadline();
+        let terminator = config.record_terminator();
+        let mut context = self.context();
+        let mut line_number = 1;
+        let mut counted = 0;
+        let mut at = 0;
+        while at < contents.len() && !context.finished() && !context.limit_reached() {
+            deadline.check()?;
+            let Some(candidate) = finder.find_at(contents, at) else {
+                at = contents.len();
+                break;
+            };
+            let start = memchr::memrchr(terminator, &bytes[at..candidate.start])
+                .map_or(at, |offset| at + offset + 1);
+            // An empty match after the final terminator is not on any line.
+            if start == bytes.len() {
+                at = start;
+                break;
+            }
+            let end = memchr::memchr(terminator, &bytes[candidate.start..])
+                .map_or(bytes.len(), |offset| candidate.start + offset);
+            line_number += memchr::memchr_iter(terminator, &bytes[counted..start]).count();
+            counted = start;
+            at = end + 1;
+
+            // The candidate only proves the line worth checking; the line itself must match.
+            let line = &contents[start..end];
+            let trimmed = match terminator {
+                b'\n' => line.strip_suffix('\r'),
+                _ => None,
+            };
+            let crlf = trimmed.is_some();
+            let line = trimmed.unwrap_or(line);
+            if !config.matcher.is_match(line) || !config.filter.accepts(line) {
+                continue;
+            }
+            let found = SinkMatch {
+                path: Some(path),
+                line_number,
+                byte_offset: start,
+                line,
+                spans: if self.spans {
+                    config.matcher.find_iter(line).collect()
+                } else {
+                    Vec::new()
+                },
+                crlf,
+            };
+            if !context.select(&found, sink)? {
+                break;
+            }
+        }
+        self.add_bytes(at.min(bytes.len()));
+        Ok(())
+    }
+
+    /// Searches `path` and returns every selected line.
+    pub fn collect_path(&self, path: &Path) -> io::Result<Vec<SearchResult>> {
+        let mut results = Vec::new();
+        self.search_path(path, &mut results)?;
+        Ok(results)
+    }
+
+    /// Searches standard input, tagging every event with the config's `label` as its path,
+    /// [`STDIN_LABEL`] unless `--label` gave another.
+    pub fn search_stdin(&self, sink: &mut impl MatchSink) -> io::Result<()> {
+        let label = Some(Path::new(&self.config.label));
+        self.trace(label, &Plan::new(Strategy::Stream, "standard input"));
+        self.framed(label, sink, |sink| {
+            self.search(io::stdin().lock(), label, sink)
+        })
+    }
+
+    /// Searches any buffered reader; events carry no path.
+    pub fn search_reader<R: BufRead>(
+        &self,
+        reader: R,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<()> {
+        self.framed(None, sink, |sink| self.search(reader, None, sink))
+    }
+
+    /// Searches one entry of an archive, tagging every event with `name` (such as
+    /// `bundle.zip!docs/readme.md`), which does not exist on disk.
+    pub(crate) fn search_entry<R: BufRead>(
+        &self,
+        reader: R,
+        name: &Path,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<()> {
+        self.framed(Some(name), sink, |sink| {
+            self.search(reader, Some(name), sink)
+        })
+    }
+
+    /// Runs `search` between the sink's [`MatchSink::begin`] and [`MatchSink::end`] calls.
+    fn framed<S: MatchSink>(
+        &self,
+        path: Option<&Path>,
+        sink: &mut S,
+        search: impl FnOnce(&mut S) -> io::Result<()>,
+    ) -> io::Result<()> {
+        sink.begin(path)?;
+        match search(sink) {
+            Ok(()) => sink.end(path),
+            Err(error) => {
+                sink.error(path, &error);
+                Err(error)
+            }
+        }
+    }
+
+    fn search<R: BufRead>(
+        &self,
+        reader: R,
+        path: Option<&Path>,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<()> {
+        let mut reader = Counted {
+            inner: reader.take(self.config.head_bytes.unwrap_or(u64::MAX)),
+            searcher: self,
+        };
+        // Without --encoding, a byte-order mark decides; the mark itself is never searched.
+        let encoding = match Encoding::sniff(reader.fill_buf()?) {
+            Some((sniffed, mark))
+                if self.config.encoding.is_none_or(|chosen| chosen == sniffed) =>
+            {
+                reader.consume(mark);
+                sniffed
+            }
+            _ => self.config.encoding.unwrap_or(Encoding::Utf8),
+        };
+        match (encoding, self.config.normalize) {
+            (Encoding::Utf8, false) => self.search_decoded(reader, path, sink),
+            (Encoding::Utf8, true) => self.search_decoded(Normalizer::new(reader), path, sink),
+            (other, false) => self.search_decoded(Transcoder::new(reader, other), path, sink),
+            (other, true) => {
+                let decoded = Normalizer::new(Transcoder::new(reader, other));
+                self.search_decoded(decoded, path, sink)
+            }
+        }
+    }
+
+    /// Searches `reader`, whose bytes are already UTF-8 unless the input is binary.
+    fn search_decoded<R: BufRead>(
+        &self,
+        reader: R,
+        path: Option<&Path>,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<()> {
+        // Lines are counted once decoded, so that UTF-16 terminators are seen as such.
+        let mut reader = HeadLines {
+            inner: reader,
+            left: self.config.head_lines,
+            terminator: self.config.record_terminator(),
+        };
+        // Like GNU grep, a NUL byte in the first block marks the input as binary, unless NUL
+        // is what separates the records.
+        let binary =
+            !self.config.binary && !self.config.null_data && probe_binary(reader.fill_buf()?);
+        let lossy = binary || self.config.binary;
+        if binary {
+            let mut notice = BinaryNotice { sink };
+            return self.search_text(reader, path, lossy, &mut notice);
+        }
+        self.search_text(reader, path, lossy, sink)
+    }
+
+    /// Searches `reader` as text. With `lossy`, invalid UTF-8 is replaced rather than rejected.
+    fn search_text<R: BufRead>(
+        &self,
+        reader: R,
+        path: Option<&Path>,
+        lossy: bool,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<()> {
+        if self.config.multiline {
+            return self.search_multiline(reader, path, lossy, sink);
+        }
+
+        let deadline = self.deadline();
+        let matcher = &self.config.matcher;
+        let line_range = self.config.line_range.as_ref();
+        let mut regions = self.config.within.as_ref().map(Within::regions);
+        let mut reader = reader;
+        let mut buffer = Vec::new();
+        let mut byte_offset = 0;
+        let mut line_number = 0;
+        let mut context = self.context();
+        // Unselected lines that may still be printed as before-context.
+        let mut before: VecDeque<Held<'_>> = VecDeque::new();
+        let terminator = self.config.record_terminator();
+        let chunk = self.config.max_memory.map_or(LINE_CHUNK, |cap| {
+            usize::try_from(cap).map_or(LINE_CHUNK, |cap| cap.clamp(1, LINE_CHUNK))
+        });
+
+        loop {
+            deadline.check()?;
+            if context.finished() {
+                break;
+            }
+            buffer.clear();
+            // Read raw bytes so the offset accounts for every terminator, including `\r\n`.
+            let mut read = reader
+                .by_ref()
+                .take(chunk as u64)
+                .read_until(terminator, &mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            // Every line moves the regions along, searched or not. Of a long line the start, read
+            // before the rest of it, is enough to know whether it opens or closes one.
+            let inside = regions.as_mut().is_none_or(|regions| {
+                regions.inside(&String::from_utf8_lossy(trim_terminator(
+                    &buffer, terminator,
+                )))
+            });
+            let mut long = if read == chunk && buffer.last() != Some(&terminator) {
+                let long =
+                    self.search_long_line(&mut reader, &mut buffer, chunk, lossy, &deadline)?;
+                read += long.read;
+                Some(long)
+            } else {
+                None
+            };
+            let line_start = byte_offset;
+            byte_offset += read;
+            line_number += 1;
+
+            if let Some(range) = line_range {
+                if line_number > *range.end() {
+                    break;
+                }
+                if line_number < *range.start() {
+                    continue;
+                }
+            }
+            if !inside {
+                continue;
+            }
+
+            // A long line that is printed, selected or as context, is handed on to be read back
+            // a piece at a time from where it was spilled; otherwise only the excerpt kept by
+            // `search_long_line` is.
+            let mut text = match &mut long {
+                Some(long)
+                    if long.is_match != self.config.invert_match
+                        || context.before > 0
+                        || context.after_left > 0 =>
+                {
+                    match long.spill.take() {
+                        Some(spill) => Some(self.long_text(spill, long, line_number, lossy)?),
+                        None => None,
+                    }
+                }
+                _ => None,
+            };
+            let decoded;
+            let (line, line_start, crlf, is_match) = match &long {
+                Some(long) if text.is_some() => ("", line_start, long.crlf, long.is_match),
+                Some(long) => (
+                    long.text.as_str(),
+                    line_start + long.start,
+                    long.crlf,
+                    long.is_match,
+                ),
+                None => {
+                    decoded = decode(trim_terminator(&buffer, terminator), lossy)?;
+                    let line = &*decoded;
+                    (
+                        line,
+                        line_start,
+                        terminator == b'\n' && buffer.ends_with(b"\r\n"),
+                        matcher.is_match(line) && self.config.filter.accepts(line),
+                    )
+                }
+            };
+            if is_match == self.config.invert_match {
+                let found = SinkMatch {
+                    path,
+                    line_number,
+                    byte_offset: line_start,
+                    line,
+                    spans: Vec::new(),
+                    crlf,
+                };
+                if context.after_left > 0 {
+                    let emitted = match text {
+                        Some(text) => context.emit_long(&found, text, sink)?,
+                        None => context.emit(&found, sink)?,
+                    };
+                    if !emitted {
+                        break;
+                    }
+                } else if context.before > 0 {
+                    if before.len() == context.before {
+                        before.pop_front();
+                    }
+                    before.push_back(Held {
+                        line_number,
+                        byte_offset: line_start,
+                        line: line.to_string(),
+                        crlf,
+                        long: text,
+                    });
+                }
+                continue;
+            }
+
+            if context.limit_reached() {
+                break;
+            }
+            let mut stopped = false;
+            for held in before.drain(..) {
+                let found = SinkMatch {
+                    path,
+                    line_number: held.line_number,
+                    byte_offset: held.byte_offset,
+                    line: &held.line,
+                    spans: Vec::new(),
+                    crlf: held.crlf,
+                };
+                let emitted = match held.long {
+                    Some(text) => context.emit_long(&found, text, sink)?,
+                    None => context.emit(&found, sink)?,
+                };
+                if !emitted {
+                    stopped = true;
+                    break;
+                }
+            }
+            if stopped {
+                break;
+            }
+
+            let spans = match &long {
+                _ if !is_match || !self.spans || text.is_some() => Vec::new(),
+                Some(long) => long.spans.clone(),
+                None => matcher.find_iter(line).collect(),
+            };
+            let found = SinkMatch {
+                path,
+                line_number,
+                byte_offset: line_start,
+                line,
+                spans,
+                crlf,
+            };
+            let selected = match text.take() {
+                Some(text) => context.select_long(&found, text, sink)?,
+                None => context.select(&found, sink)?,
+            };
+            if !selected {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Readies the spilled long line `long`, which is about to be printed.
+    fn long_text(
+        &self,
+        mut spill: Spill,
+        long: &LongLine,
+        line_number: usize,
+        lossy: bool,
+    ) -> io::Result<LongText<'a>> {
+        let selected = long.is_match != self.config.invert_match;
+        let find = selected && long.is_match && self.spans;
+        let first = long.spans.first().filter(|_| find).map(|span| {
+            let chars = long.chars + long.text[..span.start].chars().count();
+            (long.start + span.start, chars)
+        });
+        spill.file.rewind()?;
+        // The terminator, and a `\r` before it, are not part of the line.
+        let left = spill.len - u64::from(long.terminated) - u64::from(long.crlf);
+        Ok(LongText {
+            spill,
+            left,
+            matcher: &self.config.matcher,
+            find,
+            lossy,
+            selected,
+            line_number,
+            max_memory: self.config.max_memory,
+            first,
+            pending: Vec::new(),
+            window: String::new(),
+            lead: 0,
+        })
+    }
+
+    /// Reads and searches the rest of a line too long to hold whole, whose first `chunk` bytes
+    /// are in `buffer`. The line is searched in windows of about `chunk` bytes, each starting
+    /// with the last [`LINE_OVERLAP`] bytes of the one before; a match starting in that overlap
+    /// is left for the next window, which sees how it ends. Only the window holding the first
+    /// match (or the first window, if nothing matched) is kept; where the whole line may be
+    /// wanted, its bytes are spilled to a temporary file as they are read.
+    fn search_long_line<R: BufRead>(
+        &self,
+        reader: &mut R,
+        buffer: &mut Vec<u8>,
+        chunk: usize,
+        lossy: bool,
+        deadline: &Deadline<'_>,
+    ) -> io::Result<LongLine> {
+        let matcher = &self.config.matcher;
+        let filter = &self.config.filter;
+        let terminator = self.config.record_terminator();
+        let overlap = LINE_OVERLAP.min(chunk / 2);
+        let mut long = LongLine {
+            read: 0,
+            start: 0,
+            text: String::new(),
+            spans: Vec::new(),
+            is_match: false,
+            crlf: false,
+            terminated: false,
+            chars: 0,
+            spill: None,
+        };
+        if self.needs_whole_lines() {
+            let mut spill = Spill::new()?;
+            spill.write(buffer)?;
+            long.spill = Some(spill);
+        }
+        let mut all_seen = vec![false; filter.all.len()];
+        let mut any_seen = filter.any.is_empty();
+        let mut none_seen = false;
+        let mut kept = false;
+        let mut window = String::new();
+        // Where `window` starts in the line, and where in it the next search starts.
+        let mut offset = 0;
+        let mut from = 0;
+        // Characters before `offset`, until the first match settles where `long.text` starts.
+        let mut chars = 0;
+        // One character before the overlap is carried over too, for `\b` and look-behind to
+        // see; `^` cannot match after it.
+        let mut lead = 0;
+        let mut last = false;
+        loop {
+            deadline.check()?;
+            if last && buffer.pop_if(|byte| *byte == terminator).is_some() {
+                long.terminated = true;
+                long.crlf = terminator == b'\n'
+                    && (buffer.pop_if(|byte| *byte == b'\r').is_some()
+                        || (buffer.is_empty() && window.ends_with('\r') && window.pop().is_some()));
+            }
+            // A character split between reads waits for the rest of its bytes.
+            let whole = if last {
+                buffer.len()
+            } else {
+                complete_utf8(buffer)
+            };
+            window.push_str(&decode(&buffer[..whole], lossy)?);
+            buffer.drain(..whole);
+            from = from.min(window.len());
+
+            let settled = if last {
+                window.len()
+            } else {
+                window.floor_char_boundary(window.len() - overlap)
+            };
+            let deferred = |start: usize| !last && start >= settled;
+            if !long.is_match {
+                let mut spans = Vec::new();
+                let mut at = from;
+                while at <= window.len() {
+                    let Some(found) = matcher.find_at(&window, at) else {
+                        break;
+                    };
+                    if deferred(found.start) {
+                        break;
+                    }
+                    at = if found.is_empty() {
+                        // Step over an empty match so the search moves on.
+                        window[found.end..]
+                            .chars()
+                            .next()
+                            .map_or(window.len() + 1, |c| found.end + c.len_utf8())
+                    } else {
+                        found.end
+                    };
+                    spans.push(found);
+                }
+                from = if last { at } else { at.max(settled) };
+                if let Some(end) = spans.last().map(|span| span.end) {
+                    long.is_match = true;
+                    long.start = offset;
+                    long.chars = chars;
+                    long.text = window[..end.max(settled)].to_string();
+                    long.spans = spans;
+                    kept = true;
+                }
+            }
+            let begin = if offset == 0 { 0 } else { lead };
+            let hit = |matcher: &Matcher| {
+                matcher
+                    .find_at(&window, begin)
+                    .is_some_and(|found| !deferred(found.start))
+            };
+            for (seen, matcher) in all_seen.iter_mut().zip(&filter.all) {
+                *seen = *seen || hit(matcher);
+            }
+            any_seen = any_seen || filter.any.iter().any(hit);
+            none_seen = none_seen || filter.none.iter().any(hit);
+            if !kept {
+                long.text = window[..settled].to_string();
+                kept = true;
+            }
+            if last {
+                break;
+            }
+
+            let lead_start = window[..settled]
+                .char_indices()
+                .next_back()
+                .map_or(0, |(at, _)| at);
+            if !long.is_match {
+                chars += window[..lead_start].chars().count();
+            }
+            window.drain(..lead_start);
+            offset += lead_start;
+            from = from.saturating_sub(lead_start);
+            lead = settled - lead_start;
+
+            let kept = buffer.len();
+            let read = reader
+                .by_ref()
+                .take(chunk as u64)
+                .read_until(terminator, buffer)?;
+            if let Some(spill) = &mut long.spill {
+                spill.write(&buffer[kept..])?;
+            }
+            long.read += read;
+            last = read < chunk || buffer.last() == Some(&terminator);
+        }
+        long.is_match =
+            long.is_match && all_seen.iter().all(|&seen| seen) && any_seen && !none_seen;
+        if !long.is_match {
+            long.spans.clear();
+        }
+        Ok(long)
+    }
+
+    /// Whether a selected long line has to be handed on whole: for a count of lines, `-l`,
+    /// `-L`, and `-q` it is enough to know that it matched.
+    fn needs_whole_lines(&self) -> bool {
+        let config = self.config;
+        !(config.quiet
+            || config.list_files
+            || config.files_without_match
+            || (config.count && !config.count_matches))
+    }
+
+    fn search_multiline<R: BufRead>(
+        &self,
+        reader: R,
+        path: Option<&Path>,
+        lossy: bool,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<()> {
+        let deadline = self.deadline();
+        let max_buffer = self.config.max_buffer;
+        let cap = self
+            .config
+            .max_memory
+            .map_or(max_buffer as u64, |cap| cap.min(max_buffer as u64));
+        // Read one byte past the cap so oversized inputs are detected without buffering them whole.
+        let mut raw = Vec::new();
+        reader.take(cap + 1).read_to_end(&mut raw)?;
+        if raw.len() as u64 > cap && cap < max_buffer as u64 {
+            return Err(over_memory("the input", cap));
+        }
+        let contents = decode(&raw, lossy)?;
+        if contents.len() > max_buffer {
+            // The caller names the input.
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the input exceeds --max-buffer of {} bytes", max_buffer),
+            ));
+        }
+
+        let lines = line_spans(&contents, char::from(self.config.record_terminator()));
+        let inside: Option<Vec<bool>> = self.config.within.as_ref().map(|within| {
+            let mut regions = within.regions();
+            lines
+                .iter()
+                .map(|&(start, end)| regions.inside(&contents[start..end]))
+                .collect()
+        });
+        let in_range = |index: usize| {
+            self.config
+                .line_range
+                .as_ref()
+                .is_none_or(|range| range.contains(&(index + 1)))
+                && inside.as_ref().is_none_or(|inside| inside[index])
+        };
+        let line_of = |offset: usize| lines.partition_point(|&(start, _)| start <= offset) - 1;
+        // `line_spans` leaves a line's `\r` just past its end.
+        let crlf_at = |end: usize| contents[end..].starts_with('\r');
+
+        // Group matches by the block of lines they touch, merging blocks that overlap.
+        let mut blocks: Vec<(usize, usize, Vec<Range<usize>>)> = Vec::new();
+        for found in self.config.matcher.find_iter(&contents) {
+            let first = line_of(found.start);
+            // A multiline match belongs to the range when the line it starts on does.
+            if !in_range(first) {
+                continue;
+            }
+            let last = line_of(found.end.saturating_sub(1).max(found.start));
+            match blocks.last_mut() {
+                Some(block) if first <= block.1 => {
+                    block.1 = block.1.max(last);
+                    block.2.push(found);
+                }
+                _ => blocks.push((first, last, vec![found])),
+            }
+        }
+
+        let mut context = self.context();
+        // Emits the unselected lines between the last emitted line and line index `upto` that
+        // are owed as after-context or, when `upto` is selected, fall within before-context.
+        let emit_context = |context: &mut Context,
+                            upto: usize,
+                            selected: bool,
+                            sink: &mut _|
+         -> io::Result<bool> {
+            let next = context.last_emitted.unwrap_or(0);
+            let before_from = if selected {
+                upto.saturating_sub(context.before)
+            } else {
+                upto
+            };
+            for (index, &(start, end)) in lines.iter().enumerate().take(upto).skip(next) {
+                if !in_range(index) || (index < before_from && context.after_left == 0) {
+                    continue;
+                }
+                let line = SinkMatch {
+                    path,
+                    line_number: index + 1,
+                    byte_offset: start,
+                    line: &contents[start..end],
+                    spans: Vec::new(),
+                    crlf: crlf_at(end),
+                };
+                if !context.emit(&line, sink)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        };
+
+        if self.config.invert_match {
+            let mut blocks = blocks.iter().peekable();
+            for (index, &(start, end)) in lines.iter().enumerate() {
+                deadline.check()?;
+                while blocks.next_if(|block| block.1 < index).is_some() {}
+                if !in_range(index) || blocks.peek().is_some_and(|block| block.0 <= index) {
+                    continue;
+                }
+                if context.limit_reached() {
+                    break;
+                }
+                let found = SinkMatch {
+                    path,
+                    line_number: index + 1,
+                    byte_offset: start,
+                    line: &contents[start..end],
+                    spans: Vec::new(),
+                    crlf: crlf_at(end),
+                };
+                if !emit_context(&mut context, index, true, sink)?
+                    || !context.select(&found, sink)?
+                {
+                    return Ok(());
+                }
+            }
+            emit_context(&mut context, lines.len(), false, sink)?;
+            return Ok(());
+        }
+
+        for (first, last, spans) in blocks {
+            deadline.check()?;
+            if context.limit_reached() {
+                break;
+            }
+            let block_start = lines[first].0;
+            let found = SinkMatch {
+                path,
+                // Multiline matches report the line on which the match starts.
+                line_number: first + 1,
+                byte_offset: block_start,
+                line: &contents[block_start..lines[last].1],
+                spans: spans
+                    .into_iter()
+                    .map(|span| span.start - block_start..span.end - block_start)
+                    .collect(),
+                crlf: crlf_at(lines[last].1),
+            };
+            if !emit_context(&mut context, first, true, sink)? || !context.select(&found, sink)? {
+                return Ok(());
+            }
+            // The block covers several lines; context resumes after the last of them.
+            context.last_emitted = Some(last + 1);
+        }
+        emit_context(&mut context, lines.len(), false, sink)?;
+
+        Ok(())
+    }
+}
+
+/// What [`Searcher::search_long_line`] found in a line too long to hold.
+struct LongLine {
+    /// Bytes read past the line's first chunk, terminator included.
+    read: usize,
+    /// Where `text` starts in the line.
+    start: usize,
+    /// The window holding the line's first match, or its first window if it has none.
+    text: String,
+    /// The matches within `text`.
+    spans: Vec<Range<usize>>,
+    /// Whether the line matched the pattern and passed `--all-of` and the other filters.
+    is_match: bool,
+    crlf: bool,
+    /// Whether the line ended in its terminator rather than at the end of the input.
+    terminated: bool,
+    /// Characters in the line before `text`.
+    chars: usize,
+    /// The whole line, terminator included, where it may be handed on whole.
+    spill: Option<Spill>,
+}
+
+/// A temporary file holding one long line while it is searched, removed once it is dropped.
+struct Spill {
+    file: File,
+    path: PathBuf,
+    len: u64,
+}
+
+impl Spill {
+    fn new() -> io::Result<Spill> {
+        static SPILLED: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "grep-line-{}-{}",
+            std::process::id(),
+            SPILLED.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Spill { file, path, len: 0 })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.file.write_all(bytes)?;
+        self.len += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// A line too long to have been held whole, about to be printed, selected or as context. It is
+/// read back from where it was spilled a piece of about 1 MiB at a time, with the matches in
+/// each, so that printing it holds no more of it than searching it did.
+pub struct LongText<'a> {
+    spill: Spill,
+    /// Bytes of the line still to be read, leaving out its terminator.
+    left: u64,
+    matcher: &'a Matcher,
+    /// Whether the matches in each piece are wanted.
+    find: bool,
+    lossy: bool,
+    selected: bool,
+    line_number: usize,
+    max_memory: Option<u64>,
+    /// The byte and character offsets of the line's first match.
+    first: Option<(usize, usize)>,
+    /// The start of a character split between reads.
+    pending: Vec<u8>,
+    /// What has been read and not yet handed out, after the `lead` bytes that were: the last
+    /// character of the piece before, for `\b` and look-behind to see.
+    window: String,
+    lead: usize,
+}
+
+impl LongText<'_> {
+    /// Whether the line is selected, rather than context.
+    pub fn selected(&self) -> bool {
+        self.selected
+    }
+
+    /// The 1-based column of the line's first match, counted in bytes or in characters; `None`
+    /// for a line selected by `-v` or printed as context.
+    pub fn column(&self, bytes: bool) -> Option<usize> {
+        self.first
+            .map(|(byte, chars)| if bytes { byte } else { chars } + 1)
+    }
+
+    /// The next piece of the line, in order, with the byte ranges of the matches within it. A
+    /// match that crosses into the next piece (one shorter than the 64 KiB that pieces are
+    /// searched past their end) is kept whole, in the piece it starts in.
+    pub fn next_piece(&mut self) -> io::Result<Option<(String, Vec<Range<usize>>)>> {
+        if self.left == 0 && self.pending.is_empty() && self.window.len() == self.lead {
+            return Ok(None);
+        }
+        let read = self.left.min(LINE_CHUNK as u64);
+        let kept = self.pending.len();
+        self.pending.resize(kept + read as usize, 0);
+        self.spill.file.read_exact(&mut self.pending[kept..])?;
+        self.left -= read;
+        let last = self.left == 0;
+        let whole = if last {
+            self.pending.len()
+        } else {
+            complete_utf8(&self.pending)
+        };
+        self.window
+            .push_str(&decode(&self.pending[..whole], self.lossy)?);
+        self.pending.drain(..whole);
+
+        let window = &self.window;
+        let settled = if last {
+            window.len()
+        } else {
+            window
+                .floor_char_boundary(window.len().saturating_sub(LINE_OVERLAP))
+                .max(self.lead)
+        };
+        let mut spans = Vec::new();
+        let mut at = self.lead;
+        while self.find && at <= window.len() {
+            let Some(found) = self.matcher.find_at(window, at) else {
+                break;
+            };
+            if !last && found.start >= settled {
+                break;
+            }
+            at = if found.is_empty() {
+                // Step over an empty match so the search moves on.
+                window[found.end..]
+                    .chars()
+                    .next()
+                    .map_or(window.len() + 1, |c| found.end + c.len_utf8())
+            } else {
+                found.end
+            };
+            spans.push(found);
+        }
+        let end = spans.last().map_or(settled, |span| span.end.max(settled));
+        let lead = self.lead;
+        let piece = window[lead..end].to_string();
+        let spans = spans
+            .into_iter()
+            .map(|span| span.start - lead..span.end - lead)
+            .collect();
+        let lead_start = window[..end]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(at, _)| at);
+        self.window.drain(..lead_start);
+        self.lead = end - lead_start;
+        Ok(Some((piece, spans)))
+    }
+
+    /// The whole line and the byte ranges of its matches, for a sink that cannot take it a
+    /// piece at a time. A line over `--max-memory` skips its input with a warning instead.
+    pub fn read_whole(self) -> io::Result<(String, Vec<Range<usize>>)> {
+        if let Some(cap) = self.max_memory
+            && self.left > cap
+        {
+            return Err(over_memory(&format!("line {}", self.line_number), cap));
+        }
+        let mut bytes = Vec::new();
+        (&self.spill.file).take(self.left).read_to_end(&mut bytes)?;
+        let line = decode(&bytes, self.lossy)?.into_owned();
+        let spans = match self.find {
+            true => self.matcher.find_iter(&line).collect(),
+            false => Vec::new(),
+        };
+        Ok((line, spans))
+    }
+
+    /// Reads the line whole and hands it to `sink` the way a line short enough to hold is.
+    pub(crate) fn hand_on(
+        self,
+        found: &SinkMatch<'_>,
+        sink: &mut (impl MatchSink + ?Sized),
+    ) -> io::Result<bool> {
+        let selected = self.selected;
+        let (line, spans) = self.read_whole()?;
+        let found = SinkMatch {
+            path: found.path,
+            line_number: found.line_number,
+            byte_offset: found.byte_offset,
+            line: &line,
+            spans,
+            crlf: found.crlf,
+        };
+        if selected {
+            sink.matched(&found)
+        } else {
+            sink.context(&found)
+        }
+    }
+}
+
+/// An unselected line kept in case it is printed as before-context.
+struct Held<'a> {
+    line_number: usize,
+    byte_offset: usize,
+    line: String,
+    crlf: bool,
+    /// The whole line, where it was too long for `line` to hold.
+    long: Option<LongText<'a>>,
+}
+
+/// Tracks `-A`/`-B`/`-C` state while lines are handed to a sink.
+struct Context {
+    before: usize,
+    after: usize,
+    /// After-context lines still owed to the most recent selected line.
+    after_left: usize,
+    /// Line number of the last line handed to the sink, selected or context.
+    last_emitted: Option<usize>,
+    /// `--max-count`: how many lines may be selected.
+    max_count: Option<usize>,
+    selected: usize,
+}
+
+impl Context {
+    fn new(config: &Config, max_count: Option<usize>) -> Self {
+        Context {
+            before: config.before_context,
+            after: config.after_context,
+            after_left: 0,
+            last_emitted: None,
+            max_count,
+            selected: 0,
+        }
+    }
+
+    /// Whether `--max-count` lines have been selected, so no more may be.
+    fn limit_reached(&self) -> bool {
+        self.max_count.is_some_and(|max| self.selected >= max)
+    }
+
+    /// Whether nothing else can be emitted: the limit is reached and no after-context is owed.
+    fn finished(&self) -> bool {
+        self.limit_reached() && self.after_left == 0
+    }
+
+    /// Reports a break first if `line_number` does not follow straight on from the last
+    /// emitted line. Breaks only exist when context was asked for.
+    fn separate(&mut self, line_number: usize, sink: &mut impl MatchSink) -> io::Result<bool> {
+        let gap = self.last_emitted.is_some_and(|last| line_number > last + 1);
+        self.last_emitted = Some(line_number);
+        if gap && (self.before > 0 || self.after > 0) {
+            sink.context_break()
+        } else {
+            Ok(true)
+        }
+    }
+
+    fn emit(&mut self, line: &SinkMatch<'_>, sink: &mut impl MatchSink) -> io::Result<bool> {
+        self.after_left = self.after_left.saturating_sub(1);
+        Ok(self.separate(line.line_number, sink)? && sink.context(line)?)
+    }
+
+    fn select(&mut self, found: &SinkMatch<'_>, sink: &mut impl MatchSink) -> io::Result<bool> {
+        self.after_left = self.after;
+        self.selected += 1;
+        Ok(self.separate(found.line_number, sink)? && sink.matched(found)?)
+    }
+
+    /// [`Context::emit`] for a line too long to hold, which `text` reads.
+    fn emit_long(
+        &mut self,
+        line: &SinkMatch<'_>,
+        text: LongText<'_>,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<bool> {
+        self.after_left = self.after_left.saturating_sub(1);
+        Ok(self.separate(line.line_number, sink)? && sink.long_line(line, text)?)
+    }
+
+    /// [`Context::select`] for a line too long to hold, which `text` reads.
+    fn select_long(
+        &mut self,
+        found: &SinkMatch<'_>,
+        text: LongText<'_>,
+        sink: &mut impl MatchSink,
+    ) -> io::Result<bool> {
+        self.after_left = self.after;
+        self.selected += 1;
+        Ok(self.separate(found.line_number, sink)? && sink.long_line(found, text)?)
+    }
+}
+
+/// Wall-clock budget for searching a single input, set by `--timeout`, and the flag that
+/// cancels it early.
+struct Deadline<'a> {
+    started: Instant,
+    budget: Option<Duration>,
+    cancel: Option<&'a AtomicBool>,
+    until: Option<Instant>,
+}
+
+impl Deadline<'_> {
+    fn check(&self) -> io::Result<()> {
+        if self
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "search cancelled",
+            ));
+        }
+        if self.until.is_some_and(|until| Instant::now() >= until) {
+            return Err(past_deadline());
+        }
+        match self.budget {
+            Some(budget) if self.started.elapsed() > budget => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("search timed out after {} ms", budget.as_millis()),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The error for a file that would take more than `--max-memory` to search, which is skipped
+/// with a warning rather than failing the run.
+pub(crate) fn over_memory(what: &str, cap: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::OutOfMemory,
+        format!("skipped: {} is over --max-memory of {} bytes", what, cap),
+    )
+}
+
+/// The error for a search stopped, or never started, because the run reached its `--deadline`.
+pub(crate) fn past_deadline() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "the --deadline was reached")
+}
+
+/// Whether the first block of an input contains a NUL byte. Only the first `BINARY_PROBE` bytes
+/// count, so a mapped file is judged on the same block a buffered read would see first.
+pub(crate) fn probe_binary(head: &[u8]) -> bool {
+    head[..head.len().min(BINARY_PROBE)].contains(&0)
+}
+
+/// `spans`, byte ranges of `line` in order and not overlapping, as ranges of `char`s.
+fn char_spans(line: &str, spans: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut chars = 0;
+    let mut counted = 0;
+    spans
+        .iter()
+        .map(|span| {
+            chars += line[counted..span.start].chars().count();
+            let start = chars;
+            chars += line[span.clone()].chars().count();
+            counted = span.end;
+            start..chars
+        })
+        .collect()
+}
+
+/// Decodes a line (or a whole input) as UTF-8.
+fn decode(bytes: &[u8], lossy: bool) -> io::Result<Cow<'_, str>> {
+    if lossy {
+        return Ok(String::from_utf8_lossy(bytes));
+    }
+    str::from_utf8(bytes)
+        .map(Cow::Borrowed)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// How many bytes of `bytes` make up whole UTF-8 characters, leaving out a character cut
+/// short at the end. Invalid bytes count as whole, for [`decode`] to reject or replace.
+fn complete_utf8(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(4) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xC0 != 0x80 {
+            let width = match byte {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            return if width > back {
+                bytes.len() - back
+            } else {
+                bytes.len()
+            };
+        }
+    }
+    bytes.len()
+}
+
+/// Strips a trailing `terminator` from a raw line, along with a `\r` before a `\n`.
+fn trim_terminator(line: &[u8], terminator: u8) -> &[u8] {
+    let Some(line) = line.strip_suffix(&[terminator]) else {
+        return line;
+    };
+    if terminator == b'\n' {
+        line.strip_suffix(b"\r").unwrap_or(line)
+    } else {
+        line
+    }
+}
+
+/// Byte ranges of each line in `contents`, excluding the `terminator` (and a `\r` before `\n`).
+fn line_spans(contents: &str, terminator: char) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    while start < contents.len() {
+        let end = contents[start..]
+            .find(terminator)
+            .map_or(contents.len(), |offset| start + offset);
+        let trimmed = if terminator == '\n' && contents[start..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        spans.push((start, trimmed));
+        start = end + 1;
+    }
+    spans
+}
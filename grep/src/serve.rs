@@ -0,0 +1,330 @@
+//! `--serve`: a small HTTP server answering `GET /search` with `--json` results.
+//!
+//! A request names the pattern, where to look, and any flags in its query string, as in
+//! `/search?query=fn+main&path=src&flags=-i+-w`. Each one is parsed into its own [`Config`] and
+//! searched recursively; the response is the JSON lines `--json` would print, plus an
+//! `"error"` object for each input that could not be read.
+//!
+//! A request only ever searches under the directories the server was started on, and its flags
+//! may only shape the search: those that read or write other files, print help, or do anything
+//! but search are refused before they are parsed.
+
+use crate::config::{self, Config, ParseOutcome};
+use crate::json;
+use crate::options::{self, Flag};
+use crate::rcfile::split_words;
+use crate::search::{MatchSink, STDIN_PATH, Searcher, SinkMatch};
+use crate::walk;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The most a request line and its headers may take up.
+const MAX_HEAD: usize = 64 * 1024;
+
+/// How many connections are answered at once; more wait to be accepted.
+const MAX_CONNECTIONS: usize = 16;
+
+/// How long a client may take to send its request, or to read a part of the response, before
+/// its connection is dropped.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Flags a request may not give, since they do something other than shape a search: read or
+/// write files beyond the ones searched, print to the server's own output, or run some other
+/// mode.
+const REFUSED: &[Flag] = &[
+    Flag::Help,
+    Flag::HelpLong,
+    Flag::GenerateMan,
+    Flag::GenerateCompletions,
+    Flag::TypeList,
+    Flag::Cache,
+    Flag::CacheClear,
+    Flag::PatternFile,
+    Flag::PatternFrom,
+    Flag::Rules,
+    Flag::IgnoreFile,
+    Flag::FilesFrom,
+    Flag::Follow,
+    Flag::Files,
+    Flag::Explain,
+    Flag::IndexBuild,
+    Flag::IndexQuery,
+    Flag::Serve,
+    Flag::Watch,
+    Flag::Tail,
+    Flag::Tui,
+    Flag::Write,
+    Flag::Interactive,
+    Flag::DebugPattern,
+    Flag::Trace,
+    Flag::Verbose,
+];
+
+/// Answers requests on `address` until the process is stopped; searches go under `roots`, and a
+/// request that gives its own `path` may only narrow them. Only failing to listen returns.
+pub(crate) fn serve(address: SocketAddr, roots: &[String]) -> io::Result<()> {
+    let served = roots
+        .iter()
+        .map(fs::canonicalize)
+        .collect::<io::Result<Vec<_>>>()?;
+    let listener = TcpListener::bind(address)?;
+    eprintln!(
+        "Serving searches on http://{}/search",
+        listener.local_addr()?
+    );
+    let busy = Mutex::new(0);
+    let freed = Condvar::new();
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            // A connection that fails before it is accepted concerns only its client.
+            let Ok(stream) = stream else { continue };
+            {
+                let mut busy = freed
+                    .wait_while(busy.lock().unwrap(), |busy| *busy >= MAX_CONNECTIONS)
+                    .unwrap();
+                *busy += 1;
+            }
+            let (busy, freed, served) = (&busy, &freed, &served);
+            scope.spawn(move || {
+                // Nor does a client that goes away part way, or stalls, concern anyone else.
+                let _ = answer(stream, roots, served);
+                *busy.lock().unwrap() -= 1;
+                freed.notify_one();
+            });
+        }
+    });
+    Ok(())
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: &'static str, message: &str) -> Response {
+        let mut body = Vec::new();
+        // Writing to a Vec cannot fail.
+        let _ = json::write_error(&mut body, None, message);
+        Response {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+}
+
+fn answer(mut stream: TcpStream, roots: &[String], served: &[PathBuf]) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let response = match read_target(&stream)? {
+        Some(target) => respond(&target, roots, served),
+        None => Response::error("400 Bad Request", "malformed request"),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+/// The target of a `GET` request, such as `/search?query=x`, with its headers read past.
+/// `None` for anything else, or a head too long to be a real request.
+fn read_target(stream: &TcpStream) -> io::Result<Option<String>> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader
+        .by_ref()
+        .take(MAX_HEAD as u64)
+        .read_line(&mut request_line)?;
+    let mut read = request_line.len();
+    // The headers matter to no search, but a client may wait until they are read.
+    loop {
+        let mut header = String::new();
+        let len = reader
+            .by_ref()
+            .take((MAX_HEAD - read) as u64)
+            .read_line(&mut header)?;
+        read += len;
+        if len == 0 || header.trim_end().is_empty() || read >= MAX_HEAD {
+            break;
+        }
+    }
+    let mut words = request_line.split_whitespace();
+    Ok(match (words.next(), words.next()) {
+        (Some("GET"), Some(target)) if read < MAX_HEAD => Some(target.to_string()),
+        _ => None,
+    })
+}
+
+fn respond(target: &str, roots: &[String], served: &[PathBuf]) -> Response {
+    let (route, query) = target.split_once('?').unwrap_or((target, ""));
+    if route != "/search" {
+        return Response::error("404 Not Found", "the only endpoint is /search");
+    }
+    let mut pattern = None;
+    let mut paths = Vec::new();
+    let mut flags = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let Some(value) = percent_decode(value) else {
+            return Response::error("400 Bad Request", "malformed query string");
+        };
+        match key {
+            "query" => pattern = Some(value),
+            "path" => paths.push(value),
+            "flags" => match split_words(&value) {
+                Ok(words) => flags.extend(words),
+                Err(problem) => return Response::error("400 Bad Request", &problem),
+            },
+            other => {
+                let message = format!("unknown parameter {:?}", other);
+                return Response::error("400 Bad Request", &message);
+            }
+        }
+    }
+    let Some(pattern) = pattern else {
+        return Response::error("400 Bad Request", "missing the query parameter");
+    };
+    if paths.is_empty() {
+        paths = roots.to_vec();
+    }
+    if paths.iter().any(|path| path == STDIN_PATH) {
+        return Response::error("400 Bad Request", "the server cannot search standard input");
+    }
+    // Whether a path exists outside the served directories is not the client's business either.
+    let within = |path: &Path| {
+        fs::canonicalize(path).is_ok_and(|path| served.iter().any(|root| path.starts_with(root)))
+    };
+    if let Some(path) = paths.iter().find(|path| !within(Path::new(path))) {
+        let message = format!("{} is not under a directory this server searches", path);
+        return Response::error("403 Forbidden", &message);
+    }
+    if let Err(problem) = check_flags(&flags) {
+        return Response::error("400 Bad Request", &problem);
+    }
+
+    let mut args = vec!["-r".to_string()];
+    args.extend(flags);
+    args.extend(["-e".to_string(), pattern, "--".to_string()]);
+    args.extend(paths);
+    let config = match Config::parse(args) {
+        Ok(ParseOutcome::Run(config)) => config,
+        Ok(ParseOutcome::HelpPrinted) => {
+            return Response::error("400 Bad Request", "flags asked for help, not a search");
+        }
+        Err(error) => return Response::error("400 Bad Request", &error.to_string()),
+    };
+
+    let mut results = Results { body: Vec::new() };
+    let (mut targets, walk_errors) = walk::collect_targets(&config);
+    for (path, error) in walk_errors {
+        results.error(Some(&path), &error);
+    }
+    // A symlink under a served directory may still lead out of it.
+    targets.retain(|path| within(path));
+    let searcher = Searcher::new(&config);
+    for path in &targets {
+        // A failed input has already been reported through the sink's `error`.
+        let _ = searcher.search_path(path, &mut results);
+    }
+    Response {
+        status: "200 OK",
+        content_type: "application/x-ndjson",
+        body: results.body,
+    }
+}
+
+/// Checks a request's flags, read the way [`Config::parse`] reads them, for options in
+/// [`REFUSED`] and for anything that is not an option at all, such as a path slipped in among
+/// them.
+fn check_flags(flags: &[String]) -> Result<(), String> {
+    let mut words: VecDeque<String> = flags.iter().cloned().collect();
+    while let Some(word) = words.pop_front() {
+        if config::is_short_bundle(&word) {
+            for flag in config::expand_short_bundle(&word)?.into_iter().rev() {
+                words.push_front(flag);
+            }
+            continue;
+        }
+        let name = match word.split_once('=') {
+            Some((name, _)) if word.starts_with("--") => name,
+            _ => &word,
+        };
+        let Some(spec) = options::lookup(name) else {
+            return Err(match word.starts_with('-') {
+                true => format!("unknown option {}", word),
+                false => format!(
+                    "flags hold only options, not {:?}; name paths with path=",
+                    word
+                ),
+            });
+        };
+        if REFUSED.contains(&spec.flag) {
+            return Err(format!("{} cannot be used in a request", name));
+        }
+        if spec.value.is_some() && name.len() == word.len() {
+            words.pop_front();
+        }
+    }
+    Ok(())
+}
+
+/// Gathers a response body of JSON lines. Writing to a Vec cannot fail, so neither can it.
+struct Results {
+    body: Vec<u8>,
+}
+
+impl MatchSink for Results {
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+        json::write_line(&mut self.body, "match", found)?;
+        Ok(true)
+    }
+
+    fn context(&mut self, line: &SinkMatch<'_>) -> io::Result<bool> {
+        json::write_line(&mut self.body, "context", line)?;
+        Ok(true)
+    }
+
+    fn binary_matched(&mut self, path: Option<&Path>) -> io::Result<()> {
+        json::write_binary(&mut self.body, path)
+    }
+
+    fn error(&mut self, path: Option<&Path>, error: &io::Error) {
+        let _ = json::write_error(&mut self.body, path, &error.to_string());
+    }
+}
+
+/// A query-string value with `+` as a space and `%XX` escapes decoded; `None` if an escape is
+/// malformed or the result is not UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = after
+                    .get(..2)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+                bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+                rest = &after[2..];
+                continue;
+            }
+            byte => bytes.push(byte),
+        }
+        rest = after;
+    }
+    String::from_utf8(bytes).ok()
+}
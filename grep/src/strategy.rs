@@ -0,0 +1,174 @@
+//! How each file is read: streamed through a buffer, memory-mapped, or read whole for a
+//! `--multiline` pattern.
+//!
+//! Mapping saves copying a file through a buffer, and for a plain search lets the pattern run
+//! over the whole file at once, so that only the lines around its matches are split out. That
+//! pays for the cost of the map only on a file of some size, and sooner for fixed strings than
+//! for a regex, whose buffer form is compiled again for each file. `--engine` overrides the
+//! choice and `--trace` reports it for every input.
+
+use crate::config::Config;
+use crate::encoding::Encoding;
+use crate::matcher::Syntax;
+use std::fmt;
+use std::fs::Metadata;
+
+/// Files at least this large are mapped whatever the search.
+const MMAP_THRESHOLD: u64 = 64 * 1024 * 1024;
+/// From this size fixed strings are scanned for across a mapped file.
+const LITERAL_SCAN_THRESHOLD: u64 = 1024 * 1024;
+/// From this size a regex is run across a mapped file.
+const REGEX_SCAN_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// `--engine`: how files are read, or `Auto` to choose for each file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    #[default]
+    Auto,
+    /// Read through a buffer, a line at a time.
+    Stream,
+    /// Memory-mapped, where the file allows it.
+    Mmap,
+}
+
+impl Engine {
+    pub fn parse(value: &str) -> Option<Engine> {
+        match value {
+            "auto" => Some(Engine::Auto),
+            "stream" => Some(Engine::Stream),
+            "mmap" => Some(Engine::Mmap),
+            _ => None,
+        }
+    }
+}
+
+/// The way one input is searched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Strategy {
+    Stream,
+    Mmap,
+    /// Read whole, so that a match can span lines.
+    Multiline,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Strategy::Stream => "stream",
+            Strategy::Mmap => "mmap",
+            Strategy::Multiline => "multiline",
+        })
+    }
+}
+
+/// A strategy and why it was chosen, as `--trace` prints it.
+pub(crate) struct Plan {
+    pub(crate) strategy: Strategy,
+    reason: String,
+}
+
+impl Plan {
+    pub(crate) fn new(strategy: Strategy, reason: impl Into<String>) -> Plan {
+        Plan {
+            strategy,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.strategy, self.reason)
+    }
+}
+
+/// Chooses how to search the file `metadata` describes.
+pub(crate) fn choose(config: &Config, metadata: &Metadata) -> Plan {
+    if config.multiline {
+        return Plan::new(Strategy::Multiline, "--multiline matches may span lines");
+    }
+    // Only a regular file has a size worth believing; a pipe or device is streamed.
+    if !metadata.is_file() {
+        return Plan::new(Strategy::Stream, "not a regular file");
+    }
+    let len = metadata.len();
+    if config.max_memory.is_some_and(|cap| len > cap) {
+        return Plan::new(
+            Strategy::Stream,
+            format!("{}, over --max-memory", size(len)),
+        );
+    }
+    if config.head_lines.is_some() || config.head_bytes.is_some() {
+        return Plan::new(Strategy::Stream, "only the start is read");
+    }
+    match config.engine {
+        Engine::Stream => return Plan::new(Strategy::Stream, "--engine stream"),
+        Engine::Mmap => return Plan::new(Strategy::Mmap, "--engine mmap"),
+        Engine::Auto => {}
+    }
+    if len >= MMAP_THRESHOLD {
+        return Plan::new(
+            Strategy::Mmap,
+            format!("{}, at least {}", size(len), size(MMAP_THRESHOLD)),
+        );
+    }
+    let scan = match config.pattern.syntax {
+        _ if !scannable(config) => None,
+        Syntax::Literal => Some(("fixed strings", LITERAL_SCAN_THRESHOLD)),
+        Syntax::Regex => Some(("a regex", REGEX_SCAN_THRESHOLD)),
+        Syntax::Pcre => None,
+    };
+    match scan {
+        Some((pattern, threshold)) if len >= threshold => Plan::new(
+            Strategy::Mmap,
+            format!(
+                "{}, at least {} for {} scanned across the file",
+                size(len),
+                size(threshold),
+                pattern
+            ),
+        ),
+        Some((pattern, threshold)) => Plan::new(
+            Strategy::Stream,
+            format!(
+                "{}, under {} for {} scanned across the file",
+                size(len),
+                size(threshold),
+                pattern
+            ),
+        ),
+        None => Plan::new(
+            Strategy::Stream,
+            format!(
+                "{}, searched line by line and so under {}",
+                size(len),
+                size(MMAP_THRESHOLD)
+            ),
+        ),
+    }
+}
+
+/// Whether the search can run over a whole mapped file, rather than line by line over it, so
+/// far as the options tell; the file's own bytes can still rule it out.
+pub(crate) fn scannable(config: &Config) -> bool {
+    !config.invert_match
+        && config.before_context == 0
+        && config.after_context == 0
+        && config.line_range.is_none()
+        && config.within.is_none()
+        && !config.normalize
+        && config
+            .encoding
+            .is_none_or(|encoding| encoding == Encoding::Utf8)
+}
+
+/// `bytes` in the largest unit it reaches, rounded down, as in `3 MiB`.
+fn size(bytes: u64) -> String {
+    match bytes {
+        1 => "1 byte".to_string(),
+        _ if bytes >= 1 << 30 => format!("{} GiB", bytes >> 30),
+        _ if bytes >= 1 << 20 => format!("{} MiB", bytes >> 20),
+        _ if bytes >= 1 << 10 => format!("{} KiB", bytes >> 10),
+        _ => format!("{} bytes", bytes),
+    }
+}
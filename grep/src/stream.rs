@@ -0,0 +1,168 @@
+//! With the `async` feature: searches that async code can await without blocking its runtime.
+//!
+//! Each search runs on its own thread and hands results over through a small bounded queue, so
+//! a slow consumer holds the reader back rather than piling results up in memory. Nothing here
+//! depends on a particular runtime; [`ResultStream::poll_next`] fits `futures::Stream`, and a
+//! tokio `AsyncRead` can be passed in through `tokio_util::io::SyncIoBridge`.
+
+use crate::config::Config;
+use crate::search::{MatchSink, SearchResult, Searcher, SinkMatch};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{self, BufReader, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// Results queued ahead of the consumer before the search waits for it.
+const QUEUE_LIMIT: usize = 256;
+
+/// Searches `reader` on a background thread, yielding each selected line as it is found.
+pub fn search_async<R: Read + Send + 'static>(config: Arc<Config>, reader: R) -> ResultStream {
+    spawn(move |sink| Searcher::new(&config).search_reader(BufReader::new(reader), sink))
+}
+
+/// Searches the file at `path` on a background thread, as [`Searcher::search_path`] would.
+pub fn search_path_async(config: Arc<Config>, path: PathBuf) -> ResultStream {
+    spawn(move |sink| Searcher::new(&config).search_path(&path, sink))
+}
+
+fn spawn(search: impl FnOnce(&mut Sender) -> io::Result<()> + Send + 'static) -> ResultStream {
+    let shared = Arc::new(Shared::default());
+    let mut sender = Sender {
+        shared: Arc::clone(&shared),
+    };
+    thread::spawn(move || {
+        // A panic ends the stream with an error rather than leaving the consumer waiting.
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| search(&mut sender))).unwrap_or_else(
+            |payload| {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(io::Error::other(format!(
+                    "the search panicked: {}",
+                    message
+                )))
+            },
+        );
+        let mut state = sender.shared.lock();
+        state.finished = Some(outcome);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+    ResultStream { shared }
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when the consumer takes a result or goes away.
+    space: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panic on either side leaves nothing worth protecting, so the queue is used as is.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<SearchResult>,
+    /// How the search ended, once it has; taken when reported.
+    finished: Option<io::Result<()>>,
+    /// The consumer waiting for the next result.
+    waker: Option<Waker>,
+    /// Whether the [`ResultStream`] was dropped, which stops the search.
+    abandoned: bool,
+}
+
+/// The search thread's end of the queue.
+struct Sender {
+    shared: Arc<Shared>,
+}
+
+impl MatchSink for Sender {
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+        let mut state = self.shared.lock();
+        while state.queue.len() >= QUEUE_LIMIT && !state.abandoned {
+            state = self
+                .shared
+                .space
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        if state.abandoned {
+            return Ok(false);
+        }
+        state.queue.push_back(found.into());
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        Ok(true)
+    }
+}
+
+/// The selected lines of a search running in the background, in input order.
+///
+/// The stream ends after the last line, or with one `Err` if the search failed (or panicked)
+/// part way.
+/// Dropping it stops the search.
+pub struct ResultStream {
+    shared: Arc<Shared>,
+}
+
+impl ResultStream {
+    /// The next result, or `None` once the search is over.
+    pub fn recv(&mut self) -> Recv<'_> {
+        Recv { stream: self }
+    }
+
+    /// Polls for the next result, in the shape of `futures::Stream::poll_next`.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<SearchResult>>> {
+        let mut state = self.shared.lock();
+        if let Some(result) = state.queue.pop_front() {
+            self.shared.space.notify_one();
+            return Poll::Ready(Some(Ok(result)));
+        }
+        let Some(outcome) = state.finished.take() else {
+            state.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        };
+        // Once the outcome is reported, every later poll reports the end.
+        state.finished = Some(Ok(()));
+        match outcome {
+            Ok(()) => Poll::Ready(None),
+            Err(error) => Poll::Ready(Some(Err(error))),
+        }
+    }
+}
+
+impl Drop for ResultStream {
+    fn drop(&mut self) {
+        self.shared.lock().abandoned = true;
+        self.shared.space.notify_one();
+    }
+}
+
+/// The future returned by [`ResultStream::recv`].
+pub struct Recv<'s> {
+    stream: &'s mut ResultStream,
+}
+
+impl Future for Recv<'_> {
+    type Output = Option<io::Result<SearchResult>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.stream.poll_next(cx)
+    }
+}
@@ -0,0 +1,153 @@
+//! `--table`: results lined up in columns, with file names padded and line numbers, columns,
+//! and byte offsets right-aligned.
+//!
+//! The printer ends each field of a line's prefix with [`CELL`], and [`Table`], wrapped around
+//! stdout, holds rows back a batch at a time to measure them before padding each field to the
+//! widest seen so far. Widths only ever grow, so a later batch never undoes the alignment of an
+//! earlier one, and a run holds at most one batch in memory however much it prints.
+
+use crate::config::Config;
+use crate::printer;
+use std::io::{self, Write};
+
+/// Ends each field of a prefix in table mode. It is the ASCII unit separator, which text
+/// almost never holds; in a matched line itself it is just text, since only the prefix's
+/// fields are split off.
+pub(crate) const CELL: char = '\x1f';
+
+/// Rows held back before they are padded and printed.
+const BATCH: usize = 1024;
+
+/// Wraps stdout to pad the prefix fields of the lines written through it.
+pub(crate) struct Table<W: Write> {
+    out: W,
+    terminator: u8,
+    /// Fields in a row's prefix; a line with fewer is printed as it is.
+    fields: usize,
+    /// Whether the first field is the file name.
+    named: bool,
+    /// The widest each field has been, in characters on screen.
+    widths: Vec<usize>,
+    /// Lines of the current batch, each with its terminator.
+    rows: Vec<Vec<u8>>,
+    /// The start of a line whose end has not been written yet.
+    partial: Vec<u8>,
+}
+
+impl<W: Write> Table<W> {
+    pub(crate) fn new(out: W, config: &Config) -> Table<W> {
+        let named =
+            config.show_filenames && !printer::uses_heading(config) && !config.null_terminated;
+        let fields = usize::from(named)
+            + usize::from(config.show_line_numbers)
+            + usize::from(config.column)
+            + usize::from(config.byte_offset);
+        Table {
+            out,
+            terminator: config.record_terminator(),
+            fields,
+            named,
+            widths: vec![0; fields],
+            rows: Vec::new(),
+            partial: Vec::new(),
+        }
+    }
+
+    /// Pads and prints the rows held back.
+    fn write_batch(&mut self) -> io::Result<()> {
+        let rows: Vec<String> = self
+            .rows
+            .drain(..)
+            .map(|row| String::from_utf8_lossy(&row).into_owned())
+            .collect();
+        for cells in rows.iter().filter_map(|row| cells(row, self.fields)) {
+            for (width, cell) in self.widths.iter_mut().zip(&cells) {
+                *width = (*width).max(visible_width(cell));
+            }
+        }
+        for row in &rows {
+            let Some(cells) = cells(row, self.fields) else {
+                self.out.write_all(row.as_bytes())?;
+                continue;
+            };
+            for (field, (&width, cell)) in self.widths.iter().zip(&cells).enumerate() {
+                let padding = " ".repeat(width - visible_width(cell));
+                // Only the file name is read from the left.
+                if field == 0 && self.named {
+                    write!(self.out, "{}{}", cell, padding)?;
+                } else {
+                    write!(self.out, "{}{}", padding, cell)?;
+                }
+            }
+            self.out.write_all(cells[self.fields].as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Table<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.fields == 0 {
+            return self.out.write(buf);
+        }
+        let terminator = self.terminator;
+        for piece in buf.split_inclusive(|&byte| byte == terminator) {
+            self.partial.extend_from_slice(piece);
+            if piece.ends_with(&[terminator]) {
+                self.rows.push(std::mem::take(&mut self.partial));
+                if self.rows.len() >= BATCH {
+                    self.write_batch()?;
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_batch()?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Drop for Table<W> {
+    fn drop(&mut self) {
+        if !self.partial.is_empty() {
+            self.rows.push(std::mem::take(&mut self.partial));
+        }
+        // Nothing is left to report a failure to.
+        let _ = self.flush();
+    }
+}
+
+/// The prefix fields of `row` followed by the rest of it, or `None` for a line with fewer
+/// fields, such as a `--heading` name or a `--` between context groups.
+fn cells(row: &str, fields: usize) -> Option<Vec<&str>> {
+    let cells: Vec<&str> = row.splitn(fields + 1, CELL).collect();
+    (cells.len() == fields + 1).then_some(cells)
+}
+
+/// Characters in `text` that take up room on screen, leaving out colour and hyperlink escapes.
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // CSI, as in the colours' `ESC [ 1 ; 31 m`.
+            '\x1b' if chars.as_str().starts_with('[') => {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            // OSC, as in a hyperlink's `ESC ] 8 ; ; url ESC \`.
+            '\x1b' if chars.as_str().starts_with(']') => {
+                let rest = chars.as_str();
+                let end = rest.find("\x1b\\").map_or(rest.len(), |at| at + 2);
+                chars = rest[end..].chars();
+            }
+            _ => width += 1,
+        }
+    }
+    width
+}
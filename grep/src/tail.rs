@@ -0,0 +1,185 @@
+//! `--tail`: after the search, prints the matching lines appended to each file, like
+//! `tail -f | grep`.
+//!
+//! Each file stays open and its length is polled. Only complete lines are searched, so a line
+//! still being written waits for its terminator; context and multiline matches do not reach
+//! back into what was searched before.
+
+use crate::config::Config;
+use crate::printer::Printer;
+use crate::search::{LongText, MatchSink, Searcher, SinkMatch};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait between looks at the files.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Opens `targets` to be followed from where each ends now. Like `--watch`'s snapshot this is
+/// taken before the first search, so a line appended while it runs is still seen.
+pub(crate) fn follow(config: &Config, targets: &[PathBuf]) -> Vec<Followed> {
+    targets
+        .iter()
+        .filter_map(|path| match Followed::open(path, config) {
+            Ok(followed) => Some(followed),
+            Err(error) => {
+                report(config, path, &error);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Prints the matching lines appended to the `followed` files, forever.
+pub(crate) fn tail(config: &Config, mut followed: Vec<Followed>) -> ! {
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        for file in &mut followed {
+            if let Err(error) = file.search_new(config) {
+                report(config, &file.path, &error);
+            }
+        }
+    }
+}
+
+fn report(config: &Config, path: &Path, error: &io::Error) {
+    if !config.no_messages {
+        eprintln!("Error: {}: {}", path.display(), error);
+    }
+}
+
+/// A file being followed and how far into it the search has got.
+pub(crate) struct Followed {
+    path: PathBuf,
+    file: File,
+    /// Bytes read so far.
+    read: u64,
+    /// The end of the last complete line read, and the lines before it.
+    searched: usize,
+    lines: usize,
+    /// Bytes read after `searched`: the start of a line that has not been finished yet.
+    partial: Vec<u8>,
+}
+
+impl Followed {
+    fn open(path: &Path, config: &Config) -> io::Result<Followed> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut followed = Followed {
+            path: path.to_path_buf(),
+            file,
+            read: 0,
+            searched: 0,
+            lines: 0,
+            partial: Vec::new(),
+        };
+        // Line numbers carry on from the lines already there.
+        let terminator = config.record_terminator();
+        let mut reader = BufReader::new((&followed.file).take(len));
+        loop {
+            let block = reader.fill_buf()?;
+            if block.is_empty() {
+                break;
+            }
+            if let Some(end) = memchr::memrchr(terminator, block) {
+                followed.lines += memchr::memchr_iter(terminator, &block[..=end]).count();
+                followed.searched = followed.read as usize + end + 1;
+                followed.partial.clear();
+                followed.partial.extend_from_slice(&block[end + 1..]);
+            } else {
+                followed.partial.extend_from_slice(block);
+            }
+            let read = block.len();
+            followed.read += read as u64;
+            reader.consume(read);
+        }
+        Ok(followed)
+    }
+
+    /// Searches whatever complete lines have been appended since the last call.
+    fn search_new(&mut self, config: &Config) -> io::Result<()> {
+        let len = self.file.metadata()?.len();
+        if len < self.read {
+            // Truncated, as a rotated log is: follow it again from the start.
+            if !config.no_messages {
+                eprintln!("{}: file truncated", self.path.display());
+            }
+            self.read = 0;
+            self.searched = 0;
+            self.lines = 0;
+            self.partial.clear();
+        }
+        if len == self.read {
+            return Ok(());
+        }
+        self.file.seek(SeekFrom::Start(self.read))?;
+        let appended = (&mut self.file)
+            .take(len - self.read)
+            .read_to_end(&mut self.partial)?;
+        self.read += appended as u64;
+
+        let terminator = config.record_terminator();
+        let Some(end) = memchr::memrchr(terminator, &self.partial) else {
+            return Ok(());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let mut sink = Shifted {
+            sink: Printer::new(config, &mut out),
+            lines: self.lines,
+            bytes: self.searched,
+        };
+        Searcher::new(config).search_entry(&complete[..], &self.path, &mut sink)?;
+        self.lines += memchr::memchr_iter(terminator, &complete).count();
+        self.searched += complete.len();
+        out.flush()
+    }
+}
+
+/// Passes events on with their line numbers and offsets moved past what was searched before.
+struct Shifted<S> {
+    sink: S,
+    lines: usize,
+    bytes: usize,
+}
+
+impl<S> Shifted<S> {
+    fn shift<'a>(&self, found: &SinkMatch<'a>) -> SinkMatch<'a> {
+        SinkMatch {
+            path: found.path,
+            line_number: found.line_number + self.lines,
+            byte_offset: found.byte_offset + self.bytes,
+            line: found.line,
+            spans: found.spans.clone(),
+            crlf: found.crlf,
+        }
+    }
+}
+
+impl<S: MatchSink> MatchSink for Shifted<S> {
+    fn matched(&mut self, found: &SinkMatch<'_>) -> io::Result<bool> {
+        let found = self.shift(found);
+        self.sink.matched(&found)
+    }
+
+    fn context(&mut self, line: &SinkMatch<'_>) -> io::Result<bool> {
+        let line = self.shift(line);
+        self.sink.context(&line)
+    }
+
+    fn context_break(&mut self) -> io::Result<bool> {
+        self.sink.context_break()
+    }
+
+    fn binary_matched(&mut self, path: Option<&Path>) -> io::Result<()> {
+        self.sink.binary_matched(path)
+    }
+
+    fn long_line(&mut self, found: &SinkMatch<'_>, text: LongText<'_>) -> io::Result<bool> {
+        let found = self.shift(found);
+        self.sink.long_line(&found, text)
+    }
+}
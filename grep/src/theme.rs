@@ -0,0 +1,173 @@
+//! Colours for terminal output, customisable with `GREP_COLORS` or `--colors`.
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// When to colour output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal, honouring `NO_COLOR`, `CLICOLOR`, and `CLICOLOR_FORCE`.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(value: &str) -> Option<ColorChoice> {
+        match value {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    /// Whether output should be coloured right now. On Windows this also switches the console
+    /// to interpreting escape sequences, and `Auto` stays plain where it cannot.
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => {
+                enable_escapes();
+                true
+            }
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let set = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+                if set("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+                    true
+                } else if set("NO_COLOR").is_some() || set("CLICOLOR").is_some_and(|v| v == "0") {
+                    false
+                } else {
+                    io::stdout().is_terminal() && enable_escapes()
+                }
+            }
+        }
+    }
+}
+
+/// Turns on virtual-terminal processing for a Windows console on stdout, which cmd and
+/// PowerShell leave off; false if the console is too old to have it. Anything that is not a
+/// console, such as a mintty pipe or a file, is left alone and passes escapes through.
+#[cfg(windows)]
+fn enable_escapes() -> bool {
+    use std::ffi::c_void;
+    use std::os::windows::io::AsRawHandle;
+
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+    }
+
+    let handle = io::stdout().as_raw_handle();
+    let mut mode = 0;
+    // SAFETY: both calls only read or set the mode of stdout's handle, which lives as long as
+    // the process, and fail harmlessly when it is not a console.
+    unsafe {
+        GetConsoleMode(handle, &mut mode) == 0
+            || mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+            || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
+
+#[cfg(not(windows))]
+fn enable_escapes() -> bool {
+    true
+}
+
+/// SGR parameters (such as `01;31`) for each part of the output; an empty one is left plain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// `ms`: matched text.
+    pub matched: String,
+    /// `fn`: file names.
+    pub filename: String,
+    /// `ln`: line numbers.
+    pub line_number: String,
+    /// `bn`: byte offsets.
+    pub byte_offset: String,
+    /// `se`: separators between prefix fields.
+    pub separator: String,
+    /// `g1`, `g2`, ...: capture groups within a match, from group 1; a group with no colour
+    /// here (or an empty one) keeps the match colour.
+    pub groups: Vec<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            matched: "31".to_string(),
+            // GNU grep's colours, so prefixes stand out even on lines with no match (as with -v).
+            filename: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+            groups: Vec::new(),
+        }
+    }
+}
+
+/// Colour names `--colors` accepts, with their foreground SGR codes.
+const COLOUR_NAMES: &[(&str, &str)] = &[
+    ("black", "30"),
+    ("red", "31"),
+    ("green", "32"),
+    ("yellow", "33"),
+    ("blue", "34"),
+    ("magenta", "35"),
+    ("cyan", "36"),
+    ("white", "37"),
+];
+
+/// SGR parameters for `--context-dim`.
+pub(crate) const DIM: &str = "2";
+
+impl Theme {
+    /// Applies a `GREP_COLORS`-style spec such as `ms=01;31:fn=35:ln=32` on top of this theme.
+    /// `mt` sets the match colour like `ms`, and `gN` (or `groupN`) the colour of capture
+    /// group N; other keys GNU grep knows are ignored. Entries may also be separated by commas,
+    /// and a colour may be one of the eight basic names, as in `g1=yellow,g2=cyan`.
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        for entry in spec.split([':', ',']).filter(|entry| !entry.is_empty()) {
+            let (key, value) = entry.split_once('=').unwrap_or((entry, ""));
+            let value = match COLOUR_NAMES.iter().find(|(name, _)| *name == value) {
+                Some((_, sgr)) => sgr,
+                None if value.chars().all(|c| c.is_ascii_digit() || c == ';') => value,
+                None => return Err(format!("Invalid colour for {}: {}", key, value)),
+            };
+            let group = key
+                .strip_prefix("group")
+                .or_else(|| key.strip_prefix('g'))
+                .and_then(|number| number.parse::<usize>().ok())
+                .filter(|&number| number > 0);
+            let slot = match key {
+                "ms" | "mt" => &mut self.matched,
+                "fn" => &mut self.filename,
+                "ln" => &mut self.line_number,
+                "bn" => &mut self.byte_offset,
+                "se" => &mut self.separator,
+                _ => match group {
+                    Some(number) => {
+                        if self.groups.len() < number {
+                            self.groups.resize(number, String::new());
+                        }
+                        &mut self.groups[number - 1]
+                    }
+                    None => continue,
+                },
+            };
+            *slot = value.to_string();
+        }
+        Ok(())
+    }
+}
+
+/// `text` wrapped in the escape codes for `sgr`, or unchanged if `sgr` is empty.
+pub(crate) fn paint(text: &str, sgr: &str) -> String {
+    if sgr.is_empty() || text.is_empty() {
+        text.to_string()
+    } else {
+        format!("\x1b[{}m{}\x1b[0m", sgr, text)
+    }
+}
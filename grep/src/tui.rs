@@ -0,0 +1,386 @@
+//! `--tui`: an interactive browser over a search's results.
+//!
+//! The terminal is driven directly with termios and ANSI escapes: the results fill a scrollable
+//! list, typing narrows it further, and Enter opens the selected line in `$VISUAL` or `$EDITOR`.
+
+use crate::config::Config;
+use crate::search::SearchResult;
+use std::env;
+use std::io::{self, Read, Write};
+use std::process::Command;
+
+/// Shown on the last row.
+const HELP: &str = "Up/Down move  PgUp/PgDn page  Enter open in $EDITOR  type to filter  Esc quit";
+
+/// Lets the user browse `results` until they quit.
+pub(crate) fn browse(config: &Config, results: &[SearchResult]) -> io::Result<()> {
+    let mut browser = Browser {
+        config,
+        results,
+        filter: String::new(),
+        visible: (0..results.len()).collect(),
+        selected: 0,
+        top: 0,
+    };
+    let mut terminal = Terminal::enter()?;
+    loop {
+        browser.draw(&mut terminal)?;
+        match terminal.read_key()? {
+            Key::Quit => return Ok(()),
+            Key::Up => browser.move_by(-1),
+            Key::Down => browser.move_by(1),
+            Key::PageUp => browser.move_by(-(terminal.rows().saturating_sub(2) as isize)),
+            Key::PageDown => browser.move_by(terminal.rows().saturating_sub(2) as isize),
+            Key::Home => browser.move_by(isize::MIN),
+            Key::End => browser.move_by(isize::MAX),
+            Key::Backspace => {
+                browser.filter.pop();
+                browser.refilter();
+            }
+            Key::Char(c) => {
+                browser.filter.push(c);
+                browser.refilter();
+            }
+            Key::Enter => {
+                if let Some(&index) = browser.visible.get(browser.selected) {
+                    // The editor needs the terminal back as it normally is.
+                    drop(terminal);
+                    let opened = open_in_editor(&results[index]);
+                    terminal = Terminal::enter()?;
+                    terminal.status = opened.err().map(|error| error.to_string());
+                }
+            }
+            Key::Other => {}
+        }
+    }
+}
+
+struct Browser<'a> {
+    config: &'a Config,
+    results: &'a [SearchResult],
+    filter: String,
+    /// Indices into `results` of the lines the filter keeps, in order.
+    visible: Vec<usize>,
+    /// Position of the highlighted line within `visible`.
+    selected: usize,
+    /// Position within `visible` of the first line on screen.
+    top: usize,
+}
+
+impl Browser<'_> {
+    fn label(&self, result: &SearchResult) -> String {
+        let path = result
+            .path
+            .as_deref()
+            .map_or_else(String::new, |path| path.to_string_lossy().into_owned());
+        let sep = &self.config.field_separator;
+        format!(
+            "{}{}{}{} {}",
+            path,
+            sep,
+            result.line_number,
+            sep,
+            result.line.trim()
+        )
+    }
+
+    /// Keeps the results whose label contains the filter, ignoring case.
+    fn refilter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.visible = (0..self.results.len())
+            .filter(|&index| {
+                self.label(&self.results[index])
+                    .to_lowercase()
+                    .contains(&filter)
+            })
+            .collect();
+        self.selected = 0;
+        self.top = 0;
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.visible.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn draw(&mut self, terminal: &mut Terminal) -> io::Result<()> {
+        let (rows, columns) = (terminal.rows(), terminal.columns());
+        let list_rows = rows.saturating_sub(2).max(1);
+        // Scroll just enough to keep the selection on screen.
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + list_rows {
+            self.top = self.selected + 1 - list_rows;
+        }
+
+        // Each line clears what is left of the last frame, rather than the whole screen flashing.
+        let mut screen = String::from("\x1b[H");
+        let header = format!(
+            "{}/{} matches  filter: {}",
+            self.visible.len(),
+            self.results.len(),
+            self.filter
+        );
+        screen.push_str(&format!(
+            "\x1b[1m{}\x1b[0m\x1b[K\r\n",
+            fit(&header, columns)
+        ));
+        for (row, &index) in self
+            .visible
+            .iter()
+            .enumerate()
+            .skip(self.top)
+            .take(list_rows)
+        {
+            let line = fit(&self.label(&self.results[index]), columns);
+            if row == self.selected {
+                screen.push_str(&format!("\x1b[7m{}\x1b[0m\x1b[K\r\n", line));
+            } else {
+                screen.push_str(&format!("{}\x1b[K\r\n", line));
+            }
+        }
+        screen.push_str("\x1b[J");
+        let footer = terminal.status.as_deref().unwrap_or(HELP);
+        screen.push_str(&format!(
+            "\x1b[{};1H\x1b[2m{}\x1b[0m",
+            rows,
+            fit(footer, columns)
+        ));
+        terminal.out.write_all(screen.as_bytes())?;
+        terminal.out.flush()
+    }
+}
+
+/// `text` cut to `columns` characters, so no line wraps.
+fn fit(text: &str, columns: usize) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(columns)
+        .collect()
+}
+
+/// Runs `$VISUAL` or `$EDITOR` (else `vi`) on the result's file at its line, as `+LINE PATH`.
+fn open_in_editor(result: &SearchResult) -> io::Result<()> {
+    let Some(path) = result.path.as_deref() else {
+        return Ok(());
+    };
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // An editor such as `code --wait` carries its own arguments.
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(format!("+{}", result.line_number))
+        .arg(path)
+        .status()
+        .map_err(|error| {
+            io::Error::new(error.kind(), format!("cannot run {}: {}", program, error))
+        })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} exited with {}",
+            program, status
+        )))
+    }
+}
+
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Backspace,
+    Char(char),
+    Quit,
+    Other,
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped.
+struct Terminal {
+    out: io::Stdout,
+    /// A message for the footer in place of the help, such as a failure to open the editor.
+    status: Option<String>,
+    // Dropped after the screen is restored.
+    _raw: RawMode,
+}
+
+impl Terminal {
+    #[cfg(unix)]
+    fn enter() -> io::Result<Terminal> {
+        let mut terminal = Terminal {
+            _raw: RawMode::enter()?,
+            out: io::stdout(),
+            status: None,
+        };
+        // Alternate screen, cursor hidden.
+        terminal.out.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        Ok(terminal)
+    }
+
+    #[cfg(not(unix))]
+    fn enter() -> io::Result<Terminal> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--tui is not supported on this platform",
+        ))
+    }
+
+    /// The terminal's size as `(rows, columns)`, or 24 by 80 if it cannot be told.
+    fn size(&self) -> (usize, usize) {
+        #[cfg(unix)]
+        {
+            // SAFETY: winsize is plain data, and TIOCGWINSZ only writes to it.
+            let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+            let found = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+            if found == 0 && size.ws_row > 0 && size.ws_col > 0 {
+                return (usize::from(size.ws_row), usize::from(size.ws_col));
+            }
+        }
+        (24, 80)
+    }
+
+    fn rows(&self) -> usize {
+        self.size().0
+    }
+
+    fn columns(&self) -> usize {
+        self.size().1
+    }
+
+    /// The next byte typed, or `None` if nothing came within the read timeout.
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        Ok(match io::stdin().lock().read(&mut byte)? {
+            0 => None,
+            _ => Some(byte[0]),
+        })
+    }
+
+    fn read_key(&mut self) -> io::Result<Key> {
+        let first = loop {
+            if let Some(byte) = self.read_byte()? {
+                break byte;
+            }
+        };
+        self.status = None;
+        Ok(match first {
+            b'\r' | b'\n' => Key::Enter,
+            0x7F | 0x08 => Key::Backspace,
+            // Ctrl-C and Ctrl-D quit; Ctrl-P and Ctrl-N move, as in many pickers.
+            0x03 | 0x04 => Key::Quit,
+            0x10 => Key::Up,
+            0x0E => Key::Down,
+            0x1B => match self.read_byte()? {
+                None => Key::Quit,
+                Some(b'[' | b'O') => self.read_sequence()?,
+                Some(_) => Key::Other,
+            },
+            byte if byte < 0x20 => Key::Other,
+            byte => {
+                // Gather the rest of a UTF-8 character.
+                let len = match byte {
+                    0xF0.. => 4,
+                    0xE0.. => 3,
+                    0xC0.. => 2,
+                    _ => 1,
+                };
+                let mut bytes = vec![byte];
+                while bytes.len() < len {
+                    match self.read_byte()? {
+                        Some(next) => bytes.push(next),
+                        None => break,
+                    }
+                }
+                match std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|text| text.chars().next())
+                {
+                    Some(c) => Key::Char(c),
+                    None => Key::Other,
+                }
+            }
+        })
+    }
+
+    /// The key of an escape sequence after its `ESC [`, such as `A` for Up or `5~` for PgUp.
+    fn read_sequence(&mut self) -> io::Result<Key> {
+        let mut sequence = Vec::new();
+        while let Some(byte) = self.read_byte()? {
+            sequence.push(byte);
+            if byte.is_ascii_alphabetic() || byte == b'~' {
+                break;
+            }
+        }
+        Ok(match sequence.as_slice() {
+            b"A" => Key::Up,
+            b"B" => Key::Down,
+            b"H" | b"1~" => Key::Home,
+            b"F" | b"4~" => Key::End,
+            b"5~" => Key::PageUp,
+            b"6~" => Key::PageDown,
+            _ => Key::Other,
+        })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // Back to the main screen with the cursor shown.
+        let _ = self.out.write_all(b"\x1b[?25h\x1b[?1049l");
+        let _ = self.out.flush();
+    }
+}
+
+/// The terminal on stdin reading keys one at a time, unechoed, until dropped. A read waits a
+/// tenth of a second at most, so a lone Esc can be told from the start of a sequence.
+pub(crate) struct RawMode {
+    #[cfg(unix)]
+    saved: libc::termios,
+}
+
+impl RawMode {
+    #[cfg(unix)]
+    pub(crate) fn enter() -> io::Result<RawMode> {
+        // SAFETY: termios is plain data, filled in by tcgetattr before it is read.
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: stdin is a valid descriptor and `saved` a valid termios to write to.
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 1;
+        // SAFETY: applies a termios derived from the current one to stdin.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { saved })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn enter() -> io::Result<RawMode> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "keys cannot be read one at a time on this platform",
+        ))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: restores the settings read from stdin when raw mode was entered.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.saved);
+        }
+    }
+}
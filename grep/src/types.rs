@@ -0,0 +1,95 @@
+//! `--type`: named sets of globs for common kinds of file, like ripgrep's and ag's.
+
+use std::collections::BTreeMap;
+
+/// File types known without any `--type-add`.
+const BUILTIN: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+    ),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rb", &["*.rb"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// The names of the built-in types, offered by shell completion for `--type`.
+pub(crate) fn builtin_names() -> Vec<&'static str> {
+    BUILTIN.iter().map(|(name, _)| *name).collect()
+}
+
+/// Every file type of one run: the built-ins plus `--type-add` definitions, by name.
+pub(crate) struct TypeRegistry {
+    types: BTreeMap<String, Vec<String>>,
+}
+
+impl TypeRegistry {
+    pub(crate) fn new() -> Self {
+        let types = BUILTIN
+            .iter()
+            .map(|(name, globs)| {
+                let globs = globs.iter().map(|glob| glob.to_string()).collect();
+                (name.to_string(), globs)
+            })
+            .collect();
+        TypeRegistry { types }
+    }
+
+    /// `--type-add NAME:GLOB[,GLOB...]`: adds globs to a type, creating it if it is new.
+    pub(crate) fn add(&mut self, definition: &str) -> Result<(), String> {
+        let invalid = || {
+            format!(
+                "Invalid value for --type-add: {} (expected NAME:GLOB)",
+                definition
+            )
+        };
+        let (name, globs) = definition.split_once(':').ok_or_else(invalid)?;
+        let globs: Vec<String> = globs
+            .split(',')
+            .filter(|glob| !glob.is_empty())
+            .map(str::to_string)
+            .collect();
+        if name.is_empty() || globs.is_empty() {
+            return Err(invalid());
+        }
+        self.types
+            .entry(name.to_string())
+            .or_default()
+            .extend(globs);
+        Ok(())
+    }
+
+    /// The globs of every type in `names`.
+    pub(crate) fn globs(&self, names: &[String]) -> Result<Vec<String>, String> {
+        let mut globs = Vec::new();
+        for name in names {
+            let found = self
+                .types
+                .get(name)
+                .ok_or_else(|| format!("Unknown file type: {} (see --type-list)", name))?;
+            globs.extend(found.iter().cloned());
+        }
+        Ok(globs)
+    }
+
+    /// `--type-list`: one `name: glob, glob` line per type, sorted by name.
+    pub(crate) fn list(&self) -> String {
+        self.types
+            .iter()
+            .map(|(name, globs)| format!("{}: {}\n", name, globs.join(", ")))
+            .collect()
+    }
+}
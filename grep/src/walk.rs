@@ -0,0 +1,889 @@
+use crate::config::Config;
+use crate::ignore::Gitignore;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex, mpsc};
+use std::thread;
+use std::time::SystemTime;
+
+/// Order in which files found by a recursive walk are searched. Without one, the walk is
+/// depth-first with each directory's entries in name order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// Lexicographic by path.
+    Path,
+    /// Most recently modified first.
+    Mtime,
+    /// Largest first.
+    Size,
+    /// Whatever order the file system lists directories in, which varies between platforms
+    /// but saves sorting each directory.
+    Unsorted,
+}
+
+impl SortKey {
+    pub fn parse(value: &str) -> Option<SortKey> {
+        match value {
+            "path" => Some(SortKey::Path),
+            "mtime" | "modified" => Some(SortKey::Mtime),
+            "size" => Some(SortKey::Size),
+            "none" => Some(SortKey::Unsorted),
+            _ => None,
+        }
+    }
+}
+
+/// A file to search, or an entry a walk could not read, which is reported and skipped.
+pub(crate) type Target = Result<PathBuf, (PathBuf, io::Error)>;
+
+/// Targets found but not yet taken by the search, beyond which the walk waits for it.
+const IN_FLIGHT: usize = 4096;
+
+/// Every file to search, plus the entries a recursive walk could not read (which are skipped).
+pub(crate) fn collect_targets(config: &Config) -> (Vec<PathBuf>, Vec<(PathBuf, io::Error)>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for_each_target(config, &mut |target| {
+        match target {
+            Ok(path) => files.push(path),
+            Err(failed) => errors.push(failed),
+        }
+        true
+    });
+    (files, errors)
+}
+
+/// Runs `search` over the targets as they are found, so that the first results are printed
+/// while the walk is still going on, on other threads. Once `search` returns, the walk stops.
+pub(crate) fn stream_targets<R>(config: &Config, search: impl FnOnce(Targets) -> R) -> R {
+    let (sender, receiver) = mpsc::sync_channel(IN_FLIGHT);
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            for_each_target(config, &mut |target| sender.send(target).is_ok());
+        });
+        search(Targets {
+            receiver,
+            bound: bound(config),
+        })
+    })
+}
+
+/// The targets of a [`stream_targets`] run, in the order they are searched.
+pub(crate) struct Targets {
+    receiver: mpsc::Receiver<Target>,
+    bound: Option<usize>,
+}
+
+impl Iterator for Targets {
+    type Item = Target;
+
+    fn next(&mut self) -> Option<Target> {
+        self.receiver.recv().ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.bound)
+    }
+}
+
+/// At most how many targets a run has, when it walks no directory.
+fn bound(config: &Config) -> Option<usize> {
+    let mut named = config.inputs.iter().chain(&config.listed_files);
+    let walks = config.recursive || config.git;
+    let walked = walks && named.any(|input| Path::new(input).is_dir());
+    (!walked).then(|| config.inputs.len() + config.listed_files.len())
+}
+
+/// Hands every file to search to `emit`, along with the entries a recursive walk could not
+/// read, in the order they are searched, until `emit` returns false.
+fn for_each_target(config: &Config, emit: &mut dyn FnMut(Target) -> bool) {
+    let mut seen = HashMap::new();
+    let mut emit = |target: Target| match &target {
+        Ok(path) if !config.no_dedupe && !first_seen(path, &mut seen, config) => true,
+        _ => emit(target),
+    };
+
+    for input in &config.inputs {
+        let path = PathBuf::from(input);
+        let going = if path.is_dir() {
+            if config.git {
+                git_files(&path, config, &mut emit)
+            } else {
+                !config.recursive || walk_dir(&path, config, &mut emit)
+            }
+        } else {
+            // Keep the original path even if it does not exist; processing will raise an error.
+            !keep_special(&path, config) || emit(Ok(path))
+        };
+        if !going {
+            return;
+        }
+    }
+
+    // Listed files are filtered like walked ones, since nobody picked them out by hand.
+    for listed in &config.listed_files {
+        let path = PathBuf::from(listed);
+        let going = if path.is_dir() {
+            if config.git {
+                git_files(&path, config, &mut emit)
+            } else {
+                !config.recursive || walk_dir(&path, config, &mut emit)
+            }
+        } else {
+            let kept = keep_special(&path, config)
+                && passes_globs(&path, Path::new(""), config)
+                && passes_metadata(&path, config);
+            !kept || emit(Ok(path))
+        };
+        if !going {
+            return;
+        }
+    }
+}
+
+/// Whether `path` is the first path to a file, rather than one already queued under another,
+/// such as one named twice, or reached both directly and through a symlink, so that each is
+/// searched and printed once. Paths that cannot be inspected are all kept, so that searching
+/// them reports why.
+fn first_seen(path: &Path, seen: &mut HashMap<FileId, PathBuf>, config: &Config) -> bool {
+    let Some(id) = file_id(path) else {
+        return true;
+    };
+    match seen.get(&id) {
+        Some(first) => {
+            if config.verbose {
+                eprintln!(
+                    "Skipped {}: same file as {}",
+                    path.display(),
+                    first.display()
+                );
+            }
+            false
+        }
+        None => {
+            seen.insert(id, path.to_path_buf());
+            true
+        }
+    }
+}
+
+/// Walks nested directories under `root` for -r, queuing every file that passes the filters.
+///
+/// Directories are read by a pool of workers, each listing one directory at a time and queuing
+/// its subdirectories for whichever worker is free, so on a slow or network file system many
+/// reads are in flight at once. The listings are put back together here depth first, each
+/// directory's entries in name order, so files are queued in the same order however the reads
+/// happen to finish. They are handed to `emit` as they come, unless `--sort` has to see them
+/// all first. Returns false if `emit` stopped the walk.
+fn walk_dir(root: &Path, config: &Config, emit: &mut dyn FnMut(Target) -> bool) -> bool {
+    // A walk no deeper than the root has nothing to list.
+    if config.max_depth == Some(0) {
+        return true;
+    }
+    let sorted = config.sort.filter(|&key| key != SortKey::Unsorted);
+    let mut found = Vec::new();
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    };
+    let follow_id = |path: &Path| file_id(path).filter(|_| config.follow);
+    let (sender, receiver) = mpsc::channel();
+    let queue = Queue::new(Task {
+        dir: root.to_path_buf(),
+        depth: 0,
+        rules: Vec::new(),
+        ancestors: follow_id(root)
+            .map(|id| (id, root.to_path_buf()))
+            .into_iter()
+            .collect(),
+        listing: sender,
+    });
+    let going = thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while let Some(task) = queue.pop() {
+                    read_dir(task, root, config, &queue);
+                    queue.finished();
+                }
+            });
+        }
+
+        // With --follow, a directory reachable through several links is walked once, under
+        // the first path to it in walk order.
+        let mut visited: HashSet<FileId> = follow_id(root).into_iter().collect();
+        let mut stack = vec![receiver.recv().unwrap_or_default().into_iter()];
+        while let Some(listing) = stack.last_mut() {
+            let Some(item) = listing.next() else {
+                stack.pop();
+                continue;
+            };
+            let going = match item {
+                Item::File(path) if sorted.is_some() => {
+                    found.push(path);
+                    true
+                }
+                Item::File(path) => emit(Ok(path)),
+                Item::Dir { id, listing } => {
+                    if id.is_none_or(|id| visited.insert(id)) {
+                        stack.push(listing.recv().unwrap_or_default().into_iter());
+                    }
+                    true
+                }
+                Item::Error(path, error) => emit(Err((path, error))),
+                Item::Loop(path, ancestor) => {
+                    if !config.no_messages {
+                        eprintln!(
+                            "Warning: {}: symlink loop back to {}, not followed",
+                            path.display(),
+                            ancestor.display()
+                        );
+                    }
+                    true
+                }
+                Item::Skipped(path, reason) => {
+                    if config.verbose {
+                        eprintln!("Skipped {}: {}", path.display(), reason);
+                    }
+                    true
+                }
+            };
+            if !going {
+                queue.stop();
+                return false;
+            }
+        }
+        true
+    });
+    if let Some(key) = sorted {
+        sort_files(&mut found, key);
+    }
+    going && found.into_iter().all(|path| emit(Ok(path)))
+}
+
+/// A directory waiting to be read by a walk worker.
+struct Task {
+    dir: PathBuf,
+    /// How far below the walk root `dir` is.
+    depth: usize,
+    /// The ignore rules of the directories from the root down to `dir`'s parent.
+    rules: Vec<(PathBuf, Arc<Gitignore>)>,
+    /// With `--follow`, the directories from the root down to `dir`, so that a link back to
+    /// one of them is not followed round and round.
+    ancestors: Vec<(FileId, PathBuf)>,
+    /// Where `dir`'s listing goes once it is read.
+    listing: mpsc::Sender<Vec<Item>>,
+}
+
+/// An entry of a directory listing, as the walk yields them.
+enum Item {
+    File(PathBuf),
+    /// A subdirectory, with its identity under `--follow`, whose own listing arrives once a
+    /// worker has read it.
+    Dir {
+        id: Option<FileId>,
+        listing: mpsc::Receiver<Vec<Item>>,
+    },
+    Error(PathBuf, io::Error),
+    /// A followed symlink leading back to the directory named second.
+    Loop(PathBuf, PathBuf),
+    /// A file that failed `--max-filesize`, `--newer-than`, or `--older-than`, and why.
+    Skipped(PathBuf, String),
+}
+
+/// The directories a walk has still to read, newest first, so that workers tend to read them
+/// in the order the walk yields them.
+struct Queue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+struct QueueState {
+    waiting: Vec<Task>,
+    /// How many directories are being read.
+    reading: usize,
+    /// Whether the walk was stopped before the end.
+    stopped: bool,
+}
+
+impl Queue {
+    fn new(root: Task) -> Queue {
+        Queue {
+            state: Mutex::new(QueueState {
+                waiting: vec![root],
+                reading: 0,
+                stopped: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// The next directory to read, waiting for one if others are still being read; `None`
+    /// once the walk is over.
+    fn pop(&self) -> Option<Task> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.stopped {
+                return None;
+            }
+            if let Some(task) = state.waiting.pop() {
+                state.reading += 1;
+                return Some(task);
+            }
+            if state.reading == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Ends the walk early: directories still waiting are never read.
+    fn stop(&self) {
+        self.state.lock().unwrap().stopped = true;
+        self.changed.notify_all();
+    }
+
+    /// Queues the subdirectories of one directory, given in walk order.
+    fn push(&self, tasks: Vec<Task>) {
+        if tasks.is_empty() {
+            return;
+        }
+        self.state
+            .lock()
+            .unwrap()
+            .waiting
+            .extend(tasks.into_iter().rev());
+        self.changed.notify_all();
+    }
+
+    /// Notes that a directory popped earlier has been read and its subdirectories queued.
+    fn finished(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.reading -= 1;
+        if state.reading == 0 && state.waiting.is_empty() {
+            self.changed.notify_all();
+        }
+    }
+}
+
+/// Lists `task`'s directory, filtered and in walk order, queuing the subdirectories to descend
+/// into.
+fn read_dir(task: Task, root: &Path, config: &Config, queue: &Queue) {
+    let Task {
+        dir,
+        depth,
+        mut rules,
+        ancestors,
+        listing,
+    } = task;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            let _ = listing.send(vec![Item::Error(dir, error)]);
+            return;
+        }
+    };
+    if !config.no_ignore
+        && let Some(own) = Gitignore::from_dir(&dir)
+    {
+        rules.push((dir.clone(), Arc::new(own)));
+    }
+    let mut items = Vec::new();
+    let mut entries: Vec<_> = entries
+        .filter_map(|entry| {
+            entry
+                .map_err(|error| items.push(Item::Error(dir.clone(), error)))
+                .ok()
+        })
+        .collect();
+    if config.sort != Some(SortKey::Unsorted) {
+        entries.sort_by_key(|entry| entry.file_name());
+    }
+    let depth = depth + 1;
+    let mut subdirs = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        // A followed link is whatever it points at, so a broken one cannot be walked.
+        let file_type = match entry.file_type() {
+            Ok(file_type) if config.follow && file_type.is_symlink() => {
+                fs::metadata(&path).map(|metadata| metadata.file_type())
+            }
+            Ok(file_type) => Ok(file_type),
+            Err(error) => Err(error),
+        };
+        let is_dir = match file_type {
+            Ok(file_type) => file_type.is_dir(),
+            Err(error) => {
+                items.push(Item::Error(path, error));
+                continue;
+            }
+        };
+        if !keep_entry(&path, is_dir, root, &rules, config) {
+            continue;
+        }
+        if is_dir {
+            let id = if config.follow { file_id(&path) } else { None };
+            if let Some(id) = &id
+                && let Some((_, ancestor)) = ancestors.iter().find(|(seen, _)| seen == id)
+            {
+                items.push(Item::Loop(path, ancestor.clone()));
+                continue;
+            }
+            // A directory at the deepest level walked is not listed.
+            if config.max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            let (sender, receiver) = mpsc::channel();
+            let mut below = ancestors.clone();
+            // Copied on Unix, cloned where an id is a path.
+            below.extend(id.iter().map(|id| (id.to_owned(), path.clone())));
+            subdirs.push(Task {
+                dir: path,
+                depth,
+                rules: rules.clone(),
+                ancestors: below,
+                listing: sender,
+            });
+            items.push(Item::Dir {
+                id,
+                listing: receiver,
+            });
+        } else if path.is_file() && passes_globs(&path, root, config) {
+            match skip_reason(&path, config) {
+                Some(reason) => items.push(Item::Skipped(path, reason)),
+                None => items.push(Item::File(path)),
+            }
+        }
+    }
+    queue.push(subdirs);
+    // The walk may have stopped reading; then there is nobody to tell.
+    let _ = listing.send(items);
+}
+
+/// `--git`: queues the files git tracks under `root`, new ones already staged included, that
+/// pass the filters a walk would apply other than ignore files. A tracked file deleted from the
+/// work tree is left out, as is a submodule. Outside a repository, or without git to ask,
+/// `root` is walked as for -r instead.
+fn git_files(root: &Path, config: &Config, emit: &mut dyn FnMut(Target) -> bool) -> bool {
+    let listed = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-files", "-z"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    let listed = match listed {
+        Ok(output) if output.status.success() => output.stdout,
+        failed => {
+            if !config.no_messages {
+                let reason = match failed {
+                    Ok(_) => "not in a git repository".to_string(),
+                    Err(error) => format!("cannot run git ({})", error),
+                };
+                eprintln!(
+                    "Warning: {}: {}, so searching everything under it",
+                    root.display(),
+                    reason
+                );
+            }
+            return walk_dir(root, config, emit);
+        }
+    };
+    let mut found = Vec::new();
+    for name in listed
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+    {
+        let relative = path_from_bytes(name);
+        let hidden = || {
+            relative
+                .components()
+                .any(|part| part.as_os_str().to_string_lossy().starts_with('.'))
+        };
+        if config
+            .max_depth
+            .is_some_and(|depth| relative.components().count() > depth)
+            || (!config.hidden && hidden())
+        {
+            continue;
+        }
+        let path = root.join(&relative);
+        if path.is_file() && passes_globs(&path, root, config) && passes_metadata(&path, config) {
+            found.push(path);
+        }
+    }
+    if let Some(key) = config.sort {
+        sort_files(&mut found, key);
+    }
+    found.into_iter().all(|path| emit(Ok(path)))
+}
+
+/// `--explain`: why a search would leave `path` out, if it would, going through the checks a
+/// walk (or the handling of a named input) makes, in the same order. Fails if `path` cannot be
+/// inspected at all.
+pub(crate) fn why_skipped(path: &Path, config: &Config) -> io::Result<Option<String>> {
+    let metadata = fs::metadata(path)?;
+    let id = file_id(path);
+    let same =
+        |other: &str| Path::new(other) == path || id.is_some() && file_id(Path::new(other)) == id;
+    if metadata.is_dir() {
+        let reason = if config.recursive {
+            "a directory, whose files are searched in turn"
+        } else {
+            "a directory, searched only with -r"
+        };
+        return Ok(Some(reason.to_string()));
+    }
+    let special = || {
+        special_kind(path)
+            .filter(|_| !config.read_devices)
+            .map(|kind| format!("a {}, searched only with --devices read", kind))
+    };
+    if config.inputs.iter().any(|input| same(input)) {
+        return Ok(special());
+    }
+    if config.listed_files.iter().any(|listed| same(listed)) {
+        return Ok(special()
+            .or_else(|| glob_reason(path, Path::new(""), config))
+            .or_else(|| skip_reason(path, config)));
+    }
+
+    // Otherwise the file has to be under a directory that is walked: the first one, in the
+    // order they are searched.
+    let canonical = |path: &Path| {
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty());
+        let name = path.file_name().map(Path::new).unwrap_or(Path::new(""));
+        fs::canonicalize(parent.unwrap_or(Path::new("."))).map(|dir| dir.join(name))
+    };
+    let file = canonical(path)?;
+    let roots = config.inputs.iter().chain(&config.listed_files);
+    let found = roots
+        .map(Path::new)
+        .filter(|root| root.is_dir())
+        .find_map(|root| {
+            let relative = file.strip_prefix(fs::canonicalize(root).ok()?).ok()?;
+            Some((root, relative.to_path_buf()))
+        });
+    let Some((root, relative)) = found else {
+        return Ok(Some(
+            "not among the inputs, or under any of them".to_string(),
+        ));
+    };
+    if !config.recursive {
+        return Ok(Some(format!(
+            "inside {}, which is searched only with -r",
+            root.display()
+        )));
+    }
+    let depth = relative.components().count();
+    if let Some(max) = config.max_depth.filter(|&max| depth > max) {
+        return Ok(Some(format!(
+            "{} levels below {}, deeper than --max-depth {}",
+            depth,
+            root.display(),
+            max
+        )));
+    }
+    let walked = root.join(&relative);
+    let reason = match config.git.then(|| tracked(root, &relative)).flatten() {
+        Some(false) => Some("not tracked by git".to_string()),
+        // --git skips hidden files, but not those ignore files match.
+        Some(true) => {
+            let hidden = relative
+                .components()
+                .any(|part| part.as_os_str().to_string_lossy().starts_with('.'));
+            (hidden && !config.hidden).then(|| "hidden, searched only with --hidden".to_string())
+        }
+        None => walk_reason(root, &relative, config)?,
+    };
+    if reason.is_some() {
+        return Ok(reason);
+    }
+    if !metadata.is_file() {
+        return Ok(Some(
+            "not a regular file, which a walk does not search".to_string(),
+        ));
+    }
+    Ok(glob_reason(&walked, root, config).or_else(|| skip_reason(&walked, config)))
+}
+
+/// Why a walk of `root` never reaches the file at `relative` below it, if it does not: the
+/// hidden name, ignore rule, or unfollowed symlink that leaves it or a directory above it out.
+fn walk_reason(root: &Path, relative: &Path, config: &Config) -> io::Result<Option<String>> {
+    let mut rules = Vec::new();
+    let mut dir = root.to_path_buf();
+    let parts: Vec<_> = relative.components().collect();
+    for (index, part) in parts.iter().enumerate() {
+        if !config.no_ignore
+            && let Some(own) = Gitignore::from_dir(&dir)
+        {
+            rules.push((dir.clone(), Arc::new(own)));
+        }
+        let entry = dir.join(part);
+        let is_dir = index + 1 < parts.len();
+        if is_dir && !config.follow && fs::symlink_metadata(&entry)?.file_type().is_symlink() {
+            let reason = format!(
+                "inside {}, a symlink followed only with --follow",
+                entry.display()
+            );
+            return Ok(Some(reason));
+        }
+        if let Some(reason) = entry_reason(&entry, is_dir, root, &rules, config) {
+            return Ok(Some(match is_dir {
+                true => format!("inside {}, which is {}", entry.display(), reason),
+                false => reason,
+            }));
+        }
+        dir = entry;
+    }
+    Ok(None)
+}
+
+/// Whether git tracks `relative` below `root`, or `None` where git cannot say, in which case
+/// `--git` walks `root` as -r would.
+fn tracked(root: &Path, relative: &Path) -> Option<bool> {
+    let listed = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-files", "-z", "--"])
+        .arg(relative)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(!listed.stdout.is_empty())
+}
+
+/// A path as git prints it, which is bytes on Unix and UTF-8 elsewhere.
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Applies `--include`, `--exclude`, `--type`, and `--type-not` to a walked file, relative to
+/// the walk root.
+fn passes_globs(file: &Path, root: &Path, config: &Config) -> bool {
+    glob_reason(file, root, config).is_none()
+}
+
+/// Why `--include`, `--exclude`, `--type`, or `--type-not` leaves `file` out, if one does.
+fn glob_reason(file: &Path, root: &Path, config: &Config) -> Option<String> {
+    let sets = [
+        &config.include,
+        &config.exclude,
+        &config.types,
+        &config.types_not,
+    ];
+    if sets.iter().all(|set| set.is_empty()) {
+        return None;
+    }
+    let relative = slash_path(file.strip_prefix(root).unwrap_or(file));
+    if !config.include.is_empty() && !config.include.is_match(&relative) {
+        return Some("matches no --include glob".to_string());
+    }
+    if let Some(glob) = config.exclude.matching(&relative) {
+        return Some(format!("matches the --exclude glob {}", glob));
+    }
+    if !config.types.is_empty() && !config.types.is_match(&relative) {
+        return Some("matches none of the --type globs".to_string());
+    }
+    let glob = config.types_not.matching(&relative)?;
+    Some(format!("matches the --type-not glob {}", glob))
+}
+
+/// Applies `--max-filesize`, `--newer-than`, and `--older-than`, noting each skipped file under
+/// `--verbose`.
+fn passes_metadata(file: &Path, config: &Config) -> bool {
+    let Some(reason) = skip_reason(file, config) else {
+        return true;
+    };
+    if config.verbose {
+        eprintln!("Skipped {}: {}", file.display(), reason);
+    }
+    false
+}
+
+/// Why `--max-filesize`, `--newer-than`, or `--older-than` leaves `file` out, if one does. A
+/// file that cannot be inspected is kept, so that searching it reports why.
+fn skip_reason(file: &Path, config: &Config) -> Option<String> {
+    if config.max_filesize.is_none() && config.newer_than.is_none() && config.older_than.is_none() {
+        return None;
+    }
+    let metadata = fs::metadata(file).ok()?;
+    if let Some(max) = config.max_filesize
+        && metadata.len() > max
+    {
+        return Some(format!("{} bytes is over --max-filesize", metadata.len()));
+    }
+    // A modification time in the future counts as just now.
+    let age = metadata.modified().ok().map(|modified| {
+        SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default()
+    });
+    if let (Some(newer), Some(age)) = (config.newer_than, age)
+        && age > newer
+    {
+        return Some("modified before --newer-than".to_string());
+    }
+    if let (Some(older), Some(age)) = (config.older_than, age)
+        && age < older
+    {
+        return Some("modified after --older-than".to_string());
+    }
+    None
+}
+
+/// Decides whether a named input that is not a directory is searched: devices and sockets are
+/// skipped with a warning unless `--devices read` asks for them, since reading one can block
+/// forever or never end. Named pipes, such as those from process substitution, are streamed.
+/// A walk only ever yields regular files, so it never gets here.
+fn keep_special(path: &Path, config: &Config) -> bool {
+    if config.read_devices {
+        return true;
+    }
+    let Some(kind) = special_kind(path) else {
+        return true;
+    };
+    if !config.no_messages {
+        eprintln!(
+            "Warning: {}: skipping {} (use --devices read to search it)",
+            path.display(),
+            kind
+        );
+    }
+    false
+}
+
+/// What `path` is, if it is a device or socket, following symlinks such as `/dev/stdin`.
+#[cfg(unix)]
+fn special_kind(path: &Path) -> Option<&'static str> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let file_type = fs::metadata(path).ok()?.file_type();
+    if file_type.is_char_device() {
+        Some("character device")
+    } else if file_type.is_block_device() {
+        Some("block device")
+    } else if file_type.is_socket() {
+        Some("socket")
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_kind(_path: &Path) -> Option<&'static str> {
+    None
+}
+
+/// `path` with its components joined by `/`, as globs expect.
+fn slash_path(path: &Path) -> String {
+    let parts: Vec<_> = path
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+/// Decides whether a walked file or directory is searched (or descended into): hidden entries
+/// and those matched by an ignore file in any directory between the walk root and the entry
+/// are skipped, unless `--hidden` or `--no-ignore` say otherwise, as are those `--ignore-file`
+/// matches. `rules` are the ignore files' rules from the root down to the entry's directory.
+fn keep_entry(
+    path: &Path,
+    is_dir: bool,
+    root: &Path,
+    rules: &[(PathBuf, Arc<Gitignore>)],
+    config: &Config,
+) -> bool {
+    entry_reason(path, is_dir, root, rules, config).is_none()
+}
+
+/// Why [`keep_entry`] skips `path`, if it does: its hidden name, or the ignore rule that
+/// decided it.
+fn entry_reason(
+    path: &Path,
+    is_dir: bool,
+    root: &Path,
+    rules: &[(PathBuf, Arc<Gitignore>)],
+    config: &Config,
+) -> Option<String> {
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    if !config.hidden && hidden {
+        return Some("hidden, searched only with --hidden".to_string());
+    }
+    let mut deciding = path
+        .strip_prefix(root)
+        .ok()
+        .and_then(|relative| config.ignore_file.deciding(&slash_path(relative), is_dir));
+    // Rules in deeper directories take precedence, so the root's come first.
+    for (dir, rules) in rules.iter().filter(|_| !config.no_ignore) {
+        let Ok(relative) = path.strip_prefix(dir) else {
+            continue;
+        };
+        if let Some(rule) = rules.deciding(&slash_path(relative), is_dir) {
+            deciding = Some(rule);
+        }
+    }
+    let rule = deciding.filter(|rule| rule.ignores())?;
+    Some(format!("ignored by {}", rule))
+}
+
+/// What identifies a file however it is reached: its device and inode on Unix, and its
+/// canonical path elsewhere.
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = PathBuf;
+
+/// The identity of the file at `path`, following symlinks.
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|meta| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> Option<FileId> {
+    fs::canonicalize(path).ok()
+}
+
+/// Sorts walked files by `key`; files that cannot be stat'ed go last, in path order.
+fn sort_files(files: &mut [PathBuf], key: SortKey) {
+    match key {
+        SortKey::Path => files.sort(),
+        SortKey::Unsorted => {}
+        SortKey::Mtime => {
+            files.sort_by_cached_key(|path| {
+                let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+                (
+                    modified.is_none(),
+                    Reverse(modified.unwrap_or(SystemTime::UNIX_EPOCH)),
+                    path.clone(),
+                )
+            });
+        }
+        SortKey::Size => {
+            files.sort_by_cached_key(|path| {
+                let size = fs::metadata(path).map(|meta| meta.len()).ok();
+                (size.is_none(), Reverse(size.unwrap_or(0)), path.clone())
+            });
+        }
+    }
+}
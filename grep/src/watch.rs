@@ -0,0 +1,85 @@
+//! `--watch`: searches again whenever an input changes, found by polling file metadata.
+
+use crate::config::Config;
+use crate::walk;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How long to wait between looks at the inputs.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Clears a terminal and moves the cursor to its top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// What a file looked like when last seen; a change to either means it was written.
+#[derive(PartialEq, Eq)]
+pub(crate) struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// Every file a search would cover, with how each looked.
+pub(crate) type Snapshot = BTreeMap<PathBuf, Stamp>;
+
+pub(crate) fn snapshot(targets: &[PathBuf]) -> Snapshot {
+    targets
+        .iter()
+        .filter_map(|path| {
+            let metadata = path.metadata().ok()?;
+            let stamp = Stamp {
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+            };
+            Some((path.clone(), stamp))
+        })
+        .collect()
+}
+
+/// Watches the inputs of `config` forever. `last` is how they looked before the first search,
+/// so a change made while it ran is still seen.
+///
+/// The walk is redone on every poll, so new files under a watched directory are picked up. On a
+/// terminal each change clears the screen and searches everything again, giving a live view;
+/// otherwise only the files that were added or changed are searched, and their results are
+/// appended to what was already printed.
+pub(crate) fn watch(
+    config: &Config,
+    mut last: Snapshot,
+    mut search: impl FnMut(&[PathBuf], Vec<(PathBuf, io::Error)>),
+) -> ! {
+    let refresh = io::stdout().is_terminal();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let (targets, walk_errors) = walk::collect_targets(config);
+        let current = snapshot(&targets);
+        if current == last {
+            continue;
+        }
+        if refresh {
+            let mut stdout = io::stdout();
+            // A failed write to stdout has nowhere better to be reported.
+            let _ = stdout
+                .write_all(CLEAR_SCREEN.as_bytes())
+                .and_then(|_| stdout.flush());
+            search(&targets, walk_errors);
+        } else {
+            // Walk errors were reported by the searches before; only new content is printed.
+            let changed: Vec<PathBuf> = targets
+                .iter()
+                .filter(|path| {
+                    current
+                        .get(*path)
+                        .is_some_and(|stamp| last.get(*path) != Some(stamp))
+                })
+                .cloned()
+                .collect();
+            if !changed.is_empty() {
+                search(&changed, Vec::new());
+            }
+        }
+        last = current;
+    }
+}